@@ -0,0 +1,327 @@
+use crate::progress::{self, ProgressEvent, ProgressReporter};
+use crate::verifier::{FileEntry, FileManifest};
+use crate::{paths, Error, Result};
+use beam_formats::path::{name_key, sanitize_entry_path};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const EMPTY_ZIP_MAGIC: &[u8] = b"PK\x05\x06";
+const SEVEN_ZIP_MAGIC: &[u8] = b"7z\xbc\xaf\x27\x1c";
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+// Within a single large entry (a multi-GB data.grf), progress is reported every this many bytes
+const PROGRESS_STEP: u64 = 16 * 1024 * 1024;
+// How many manifest problems are named in the error before the rest are only counted
+const MAX_LISTED_PROBLEMS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    SevenZip,
+}
+
+impl ArchiveFormat {
+    // Goes by content rather than extension, since split parts are named `.001`, `.002`, ...
+    pub fn detect<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 6];
+        let mut read = 0;
+        while read < magic.len() {
+            match reader.read(&mut magic[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        reader.seek(SeekFrom::Start(0))?;
+        
+        let magic = &magic[..read];
+        if magic.starts_with(ZIP_MAGIC) || magic.starts_with(EMPTY_ZIP_MAGIC) {
+            Ok(ArchiveFormat::Zip)
+        } else if magic.starts_with(SEVEN_ZIP_MAGIC) {
+            Ok(ArchiveFormat::SevenZip)
+        } else {
+            Err(Error::PatchFailed("Client package is neither a zip nor a 7z archive".to_string()))
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct ExtractOptions {
+    // Every file it lists must come out of the archive with that size and SHA-256
+    pub manifest: Option<FileManifest>,
+    pub reporter: Option<ProgressReporter>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractReport {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+pub fn extract(package: &Path, destination: &Path, options: &ExtractOptions) -> Result<ExtractReport> {
+    extract_from(BufReader::new(File::open(package)?), destination, options)
+}
+
+// The volumes of a split package, in order
+pub fn extract_parts(parts: &[PathBuf], destination: &Path, options: &ExtractOptions) -> Result<ExtractReport> {
+    extract_from(BufReader::new(MultiPartReader::open(parts)?), destination, options)
+}
+
+// Blocking; callers on the async runtime run it through `spawn_blocking`
+pub fn extract_from<R: Read + Seek>(mut reader: R, destination: &Path, options: &ExtractOptions) -> Result<ExtractReport> {
+    let format = ArchiveFormat::detect(&mut reader)?;
+    fs::create_dir_all(destination)?;
+    let mut unpacker = Unpacker::new(destination, options);
+    
+    match format {
+        ArchiveFormat::Zip => extract_zip(reader, &mut unpacker)?,
+        ArchiveFormat::SevenZip => extract_7z(reader, &mut unpacker)?,
+    }
+    
+    let report = unpacker.finish()?;
+    info!("Extracted {} files ({} bytes) into {:?}", report.files, report.bytes, destination);
+    Ok(report)
+}
+
+fn extract_zip<R: Read + Seek>(reader: R, unpacker: &mut Unpacker) -> Result<()> {
+    let invalid = |e: zip::result::ZipError| Error::PatchFailed(format!("Invalid client package: {}", e));
+    let mut archive = zip::ZipArchive::new(reader).map_err(invalid)?;
+    
+    unpacker.total_bytes = (0..archive.len())
+        .map(|index| archive.by_index_raw(index).map(|entry| entry.size()))
+        .sum::<std::result::Result<u64, _>>()
+        .map_err(invalid)?;
+    
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(invalid)?;
+        let name = entry.name().to_string();
+        if entry.is_dir() {
+            unpacker.directory(&name)?;
+        } else {
+            unpacker.file(&name, &mut entry)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sevenz")]
+fn extract_7z<R: Read + Seek>(mut reader: R, unpacker: &mut Unpacker) -> Result<()> {
+    let invalid = |e: sevenz_rust::Error| Error::PatchFailed(format!("Invalid client package: {}", e));
+    let length = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut archive = sevenz_rust::SevenZReader::new(reader, length, sevenz_rust::Password::empty()).map_err(invalid)?;
+    unpacker.total_bytes = archive.archive().files.iter().map(|entry| entry.size()).sum();
+    
+    // The callback can only return the library's error type, so ours is carried out beside it
+    let mut failure = None;
+    archive
+        .for_each_entries(|entry, data| {
+            let result = if entry.is_directory() {
+                unpacker.directory(entry.name())
+            } else {
+                unpacker.file(entry.name(), data)
+            };
+            match result {
+                Ok(()) => Ok(true),
+                Err(e) => {
+                    failure = Some(e);
+                    Ok(false)
+                }
+            }
+        })
+        .map_err(invalid)?;
+    failure.map_or(Ok(()), Err)
+}
+
+#[cfg(not(feature = "sevenz"))]
+fn extract_7z<R: Read + Seek>(_reader: R, _unpacker: &mut Unpacker) -> Result<()> {
+    Err(Error::PatchFailed(
+        "This launcher was built without 7z support; distribute the client as a zip".to_string(),
+    ))
+}
+
+// Reads the volumes of a split package as the single file they were cut from, so the package is extracted
+// without first being joined on disk
+pub struct MultiPartReader {
+    parts: Vec<File>,
+    // Where each volume starts in the joined file
+    offsets: Vec<u64>,
+    length: u64,
+    position: u64,
+}
+
+impl MultiPartReader {
+    pub fn open(parts: &[PathBuf]) -> Result<Self> {
+        let mut files = Vec::with_capacity(parts.len());
+        let mut offsets = Vec::with_capacity(parts.len());
+        let mut length = 0;
+        for part in parts {
+            let file = File::open(part)?;
+            offsets.push(length);
+            length += file.metadata()?.len();
+            files.push(file);
+        }
+        Ok(MultiPartReader {
+            parts: files,
+            offsets,
+            length,
+            position: 0,
+        })
+    }
+}
+
+impl Read for MultiPartReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.length || buf.is_empty() {
+            return Ok(0);
+        }
+        // The last volume starting at or before the position; empty volumes are skipped over
+        let index = self.offsets.partition_point(|&offset| offset <= self.position) - 1;
+        let within = self.position - self.offsets[index];
+        let end = self.offsets.get(index + 1).copied().unwrap_or(self.length);
+        let wanted = buf.len().min((end - self.position) as usize);
+        
+        let part = &mut self.parts[index];
+        part.seek(SeekFrom::Start(within))?;
+        let read = part.read(&mut buf[..wanted])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for MultiPartReader {
+    fn seek(&mut self, from: SeekFrom) -> std::io::Result<u64> {
+        let position = match from {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.length.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before the start of the package")
+        })?;
+        Ok(self.position)
+    }
+}
+
+// Writes entries below the destination and checks each against the manifest as it goes
+struct Unpacker<'a> {
+    destination: PathBuf,
+    expected: Option<HashMap<String, &'a FileEntry>>,
+    seen: HashSet<String>,
+    problems: Vec<String>,
+    reporter: Option<ProgressReporter>,
+    buffer: Vec<u8>,
+    files: usize,
+    extracted_bytes: u64,
+    total_bytes: u64,
+}
+
+impl<'a> Unpacker<'a> {
+    fn new(destination: &Path, options: &'a ExtractOptions) -> Self {
+        Unpacker {
+            destination: paths::long_path(destination),
+            expected: options
+                .manifest
+                .as_ref()
+                .map(|manifest| manifest.files.iter().map(|entry| (name_key(&entry.path), entry)).collect()),
+            seen: HashSet::new(),
+            problems: Vec::new(),
+            reporter: options.reporter.clone(),
+            buffer: vec![0u8; COPY_BUFFER_SIZE],
+            files: 0,
+            extracted_bytes: 0,
+            total_bytes: 0,
+        }
+    }
+    
+    fn directory(&mut self, name: &str) -> Result<()> {
+        // Archives made from a folder may list that folder itself, with an empty name
+        if name.trim_matches(|c| c == '/' || c == '\\').is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(self.destination.join(sanitize_entry_path(name)?))?;
+        Ok(())
+    }
+    
+    fn file(&mut self, name: &str, data: &mut dyn Read) -> Result<()> {
+        let relative = sanitize_entry_path(name)?;
+        let target = self.destination.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let mut output = File::create(&target)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        let mut next_report = self.extracted_bytes + PROGRESS_STEP;
+        loop {
+            let read = data.read(&mut self.buffer)?;
+            if read == 0 {
+                break;
+            }
+            output.write_all(&self.buffer[..read])?;
+            hasher.update(&self.buffer[..read]);
+            size += read as u64;
+            self.extracted_bytes += read as u64;
+            if self.extracted_bytes >= next_report {
+                self.report(name);
+                next_report = self.extracted_bytes + PROGRESS_STEP;
+            }
+        }
+        self.files += 1;
+        self.report(name);
+        
+        if let Some(expected) = &self.expected {
+            let key = name_key(&paths::relative_name(&relative));
+            match expected.get(&key) {
+                Some(entry) if entry.size != size => {
+                    self.problems.push(format!("{}: {} bytes instead of {}", name, size, entry.size))
+                }
+                Some(entry) if !entry.checksum.eq_ignore_ascii_case(&format!("{:x}", hasher.finalize())) => {
+                    self.problems.push(format!("{}: checksum mismatch", name))
+                }
+                Some(_) => {}
+                None => warn!("{} is in the client package but not in its manifest", name),
+            }
+            self.seen.insert(key);
+        }
+        Ok(())
+    }
+    
+    fn report(&self, name: &str) {
+        progress::report(&self.reporter, ProgressEvent::Extracting {
+            file: name.to_string(),
+            extracted_bytes: self.extracted_bytes,
+            total_bytes: self.total_bytes,
+        });
+    }
+    
+    fn finish(mut self) -> Result<ExtractReport> {
+        if let Some(expected) = &self.expected {
+            let mut missing: Vec<&str> = expected
+                .iter()
+                .filter(|(key, _)| !self.seen.contains(*key))
+                .map(|(_, entry)| entry.path.as_str())
+                .collect();
+            missing.sort_unstable();
+            self.problems.extend(missing.into_iter().map(|path| format!("{}: missing from the package", path)));
+        }
+        
+        if !self.problems.is_empty() {
+            let mut message = self.problems.iter().take(MAX_LISTED_PROBLEMS).cloned().collect::<Vec<_>>().join("; ");
+            if self.problems.len() > MAX_LISTED_PROBLEMS {
+                message.push_str(&format!(" and {} more", self.problems.len() - MAX_LISTED_PROBLEMS));
+            }
+            return Err(Error::PatchFailed(format!("Client package does not match its manifest: {}", message)));
+        }
+        
+        Ok(ExtractReport {
+            files: self.files,
+            bytes: self.extracted_bytes,
+        })
+    }
+}
@@ -0,0 +1,110 @@
+// Patch lists and manifests as mirrors serve them, plain or gzip-compressed. Compression is recognized by its
+// magic bytes, so `.gz` files and `Content-Encoding: gzip` responses both work however the server labels them.
+// The decompressed size is capped so a broken or hostile mirror cannot fill memory.
+use crate::{Error, Result};
+use flate2::read::GzDecoder;
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+use std::io::{BufRead, BufReader, Read};
+use tokio::sync::mpsc;
+
+// A 10,000 line patch list is about 1 MB, a 100,000 entry manifest about 15 MB
+pub const MAX_PATCH_LIST_SIZE: u64 = 32 * 1024 * 1024;
+pub const MAX_MANIFEST_SIZE: u64 = 256 * 1024 * 1024;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+pub async fn read_text(response: Response, limit: u64, what: &str) -> Result<String> {
+    read_with(response, limit, what, |reader| {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        Ok(String::from_utf8_lossy(&content).into_owned())
+    })
+    .await
+}
+
+// Parses the body while it downloads, so a large manifest is never held as text
+pub async fn read_json<T: DeserializeOwned + Send + 'static>(response: Response, limit: u64, what: &str) -> Result<T> {
+    read_with(response, limit, what, |reader| Ok(serde_json::from_reader(BufReader::new(reader))?)).await
+}
+
+async fn read_with<T: Send + 'static>(
+    mut response: Response,
+    limit: u64,
+    what: &str,
+    parse: impl FnOnce(&mut dyn Read) -> std::io::Result<T> + Send + 'static,
+) -> Result<T> {
+    let (sender, receiver) = mpsc::channel(16);
+    let parser = tokio::task::spawn_blocking(move || {
+        let mut body = BufReader::new(ChunkReader { receiver, chunk: Vec::new(), position: 0 });
+        let decoded: Box<dyn Read> = if body.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            Box::new(GzDecoder::new(body))
+        } else {
+            Box::new(body)
+        };
+        parse(&mut Limited { inner: decoded, remaining: limit, limit })
+    });
+    
+    let mut received = 0u64;
+    let fed: Result<()> = async {
+        while let Some(chunk) = response.chunk().await? {
+            received += chunk.len() as u64;
+            if received > limit {
+                return Err(Error::DownloadFailed(format!("The {} is larger than {} MB", what, limit / (1024 * 1024))));
+            }
+            // The parser stops reading at the first error, which is reported below
+            if sender.send(chunk.to_vec()).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    drop(sender);
+    
+    let parsed = parser.await.map_err(|e| Error::DownloadFailed(e.to_string()))?;
+    fed?;
+    parsed.map_err(|e| Error::DownloadFailed(format!("Invalid {}: {}", what, e)))
+}
+
+// Blocking reader over the chunks the download task sends
+struct ChunkReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.position);
+        buf[..len].copy_from_slice(&self.chunk[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+// Fails once more than `limit` bytes were read, where `Read::take` would end the data silently
+struct Limited<R> {
+    inner: R,
+    remaining: u64,
+    limit: u64,
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.remaining = self.remaining.checked_sub(len as u64).ok_or_else(|| {
+            std::io::Error::other(format!("decompresses to more than {} MB", self.limit / (1024 * 1024)))
+        })?;
+        Ok(len)
+    }
+}
@@ -0,0 +1,164 @@
+use crate::config::{HttpConfig, LaunchConfig};
+use crate::{multi_user, Config, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const LAST_CHARACTER_FILE: &str = ".beam-last-character.json";
+const PLACEHOLDERS: &[&str] = &["character", "server", "slot"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Character {
+    pub name: String,
+    // Char server the character lives on, as the client expects it on the command line
+    #[serde(default)]
+    pub server: Option<String>,
+    #[serde(default)]
+    pub slot: Option<u32>,
+    #[serde(default)]
+    pub level: Option<u32>,
+    #[serde(default)]
+    pub job: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CharacterList {
+    Wrapped { characters: Vec<Character> },
+    Bare(Vec<Character>),
+}
+
+// What "Continue as <character>" launches into; kept per user so shared installs do not mix accounts up
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharacterSelection {
+    #[serde(default)]
+    pub character: Option<String>,
+    #[serde(default)]
+    pub server: Option<String>,
+    #[serde(default)]
+    pub slot: Option<u32>,
+}
+
+impl CharacterSelection {
+    pub fn from_character(character: &Character) -> Self {
+        CharacterSelection {
+            character: Some(character.name.clone()),
+            server: character.server.clone(),
+            slot: character.slot,
+        }
+    }
+    
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(selection) => Some(selection),
+            Err(e) => {
+                warn!("Ignoring corrupt character selection {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+    
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::InvalidConfig(format!("Failed to serialize character selection: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+    
+    fn value(&self, placeholder: &str) -> Option<String> {
+        match placeholder {
+            "character" => self.character.clone(),
+            "server" => self.server.clone(),
+            "slot" => self.slot.map(|slot| slot.to_string()),
+            _ => None,
+        }
+    }
+}
+
+// Next to the per-user DATA.INI when multi-user mode is on, otherwise in the game directory
+pub fn selection_path(config: &Config) -> Result<PathBuf> {
+    let dir = match config.multi_user() {
+        Some(multi_user) => {
+            let server_name = config.app.server_name.as_deref().unwrap_or(&config.app.name);
+            multi_user::user_settings_dir(multi_user, server_name)?
+        }
+        None => config
+            .app
+            .game_directory
+            .as_ref()
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?,
+    };
+    Ok(dir.join(LAST_CHARACTER_FILE))
+}
+
+// Fills `{character}`, `{server}` and `{slot}` in the configured templates. A template naming a value the
+// selection does not have is left out, so `-char:{slot}` never reaches the client as `-char:`.
+pub fn launch_args(config: &LaunchConfig, selection: &CharacterSelection) -> Vec<String> {
+    config
+        .args
+        .iter()
+        .filter_map(|template| {
+            let mut arg = template.clone();
+            for placeholder in PLACEHOLDERS {
+                let pattern = format!("{{{}}}", placeholder);
+                if arg.contains(&pattern) {
+                    arg = arg.replace(&pattern, &selection.value(placeholder)?);
+                }
+            }
+            Some(arg)
+        })
+        .collect()
+}
+
+pub fn validate(config: &LaunchConfig) -> Result<()> {
+    for template in &config.args {
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| Error::InvalidConfig(format!("Unclosed placeholder in launch argument `{}`", template)))?;
+            let name = &rest[start + 1..start + end];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(Error::InvalidConfig(format!(
+                    "Unknown placeholder {{{}}} in launch argument `{}`; use {{character}}, {{server}} or {{slot}}",
+                    name, template
+                )));
+            }
+            rest = &rest[start + end + 1..];
+        }
+    }
+    Ok(())
+}
+
+// Asks the server which characters the logged-in account has
+pub async fn fetch_characters(http: &HttpConfig, config: &LaunchConfig, token: &str) -> Result<Vec<Character>> {
+    let url = config
+        .characters_url
+        .as_ref()
+        .ok_or_else(|| Error::InvalidConfig("launch.characters_url is not configured".to_string()))?;
+    
+    info!("Fetching character list from {}", url);
+    let response = crate::http::client(http)?
+        .get(url)
+        .bearer_auth(token)
+        .send()
+        .await?;
+    
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED || response.status() == reqwest::StatusCode::FORBIDDEN {
+        return Err(Error::AuthFailed(format!("Character list was refused: HTTP {}", response.status())));
+    }
+    if !response.status().is_success() {
+        return Err(Error::DownloadFailed(format!("Failed to fetch character list: HTTP {}", response.status())));
+    }
+    
+    let list: CharacterList = response
+        .json()
+        .await
+        .map_err(|e| Error::DownloadFailed(format!("Invalid character list: {}", e)))?;
+    Ok(match list {
+        CharacterList::Wrapped { characters } | CharacterList::Bare(characters) => characters,
+    })
+}
@@ -0,0 +1,110 @@
+use crate::body;
+use crate::config::HttpConfig;
+use crate::paths;
+use crate::verifier::{FileEntry, FileManifest};
+use crate::{Error, Result, Verifier};
+use beam_formats::path::name_key;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOrigin {
+    Stock,
+    ServerPatched,
+    UnknownModification,
+    Missing,
+}
+
+// SHA-256 hashes keyed by normalized relative path (lowercase, forward slashes)
+#[derive(Debug, Clone, Default)]
+pub struct ChecksumDatabase {
+    files: HashMap<String, String>,
+}
+
+impl ChecksumDatabase {
+    pub fn from_manifest(manifest: FileManifest) -> Self {
+        let files = manifest
+            .files
+            .into_iter()
+            .map(|entry| (name_key(&entry.path), entry.checksum.to_lowercase()))
+            .collect();
+        ChecksumDatabase { files }
+    }
+    
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let manifest: FileManifest = serde_json::from_str(&content)
+            .map_err(|e| Error::InvalidConfig(format!("Invalid checksum database: {}", e)))?;
+        Ok(Self::from_manifest(manifest))
+    }
+    
+    pub async fn fetch(http: &HttpConfig, url: &str) -> Result<Self> {
+        info!("Downloading checksum database from: {}", url);
+        
+        let response = crate::http::client(http)?.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed(format!(
+                "Failed to download checksum database: HTTP {}",
+                response.status()
+            )));
+        }
+        
+        let manifest: FileManifest = body::read_json(response, body::MAX_MANIFEST_SIZE, "checksum database").await?;
+        Ok(Self::from_manifest(manifest))
+    }
+    
+    // Hashes every file under `dir`; run against a clean official install to build the stock dataset
+    pub async fn generate<P: AsRef<Path>>(dir: P) -> Result<FileManifest> {
+        let dir = dir.as_ref();
+        let mut relative_paths = Vec::new();
+        collect_files(dir, dir, &mut relative_paths)?;
+        relative_paths.sort();
+        
+        let mut files = Vec::with_capacity(relative_paths.len());
+        for (relative, relative_path) in relative_paths {
+            let full_path = dir.join(relative_path);
+            debug!("Hashing {}", relative);
+            files.push(FileEntry {
+                checksum: Verifier::compute_file_checksum(&full_path).await?,
+                size: std::fs::metadata(&full_path)?.len(),
+                path: relative,
+            });
+        }
+        
+        info!("Generated checksum database with {} files", files.len());
+        Ok(FileManifest { files })
+    }
+    
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.files.get(&name_key(path)).map(|s| s.as_str())
+    }
+    
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(|s| s.as_str())
+    }
+    
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, out)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            out.push((paths::relative_name(relative), relative.to_path_buf()));
+        }
+    }
+    Ok(())
+}
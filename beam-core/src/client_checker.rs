@@ -1,9 +1,13 @@
-use crate::{Config, Result};
+use crate::checksum_db::{ChecksumDatabase, FileOrigin};
+use crate::client_info::{self, ClientInfo};
+use crate::url_template::{self, UrlVariables};
+use crate::{Config, Result, Verifier};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
 use std::fs;
 use std::path::Path;
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientStatusResult {
@@ -13,6 +17,15 @@ pub struct ClientStatusResult {
     pub corrupted_files: usize,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientDiagnostics {
+    pub stock: usize,
+    pub server_patched: usize,
+    pub unknown_modification: Vec<String>,
+    pub missing: Vec<String>,
+    pub client: Option<ClientInfo>,
+}
+
 pub struct ClientChecker {
     config: Config,
 }
@@ -75,6 +88,89 @@ impl ClientChecker {
         Ok((files_checked, corrupted_files))
     }
     
+    pub async fn diagnose_client(&self) -> Result<ClientDiagnostics> {
+        let stock = self.load_stock_database().await?;
+        let server = self.load_server_database().await?;
+        self.diagnose_files(&stock, &server).await
+    }
+    
+    // Classifies every file known to either database as stock, patched by this server, or modified by something else
+    pub async fn diagnose_files(&self, stock: &ChecksumDatabase, server: &ChecksumDatabase) -> Result<ClientDiagnostics> {
+        let game_dir = self.config.app.game_directory.as_ref()
+            .ok_or_else(|| crate::Error::InvalidConfig("Game directory not set".to_string()))?;
+        
+        let paths: BTreeSet<&str> = stock.paths().chain(server.paths()).collect();
+        let mut diagnostics = ClientDiagnostics::default();
+        match client_info::inspect(&self.config) {
+            Ok(info) => diagnostics.client = Some(info),
+            Err(e) => warn!("Could not read client executable: {}", e),
+        }
+        
+        for path in paths {
+            let file_path = Path::new(game_dir).join(path);
+            
+            let origin = if !file_path.exists() {
+                FileOrigin::Missing
+            } else {
+                let checksum = Verifier::compute_file_checksum(&file_path).await?;
+                if server.get(path) == Some(checksum.as_str()) {
+                    FileOrigin::ServerPatched
+                } else if stock.get(path) == Some(checksum.as_str()) {
+                    FileOrigin::Stock
+                } else {
+                    FileOrigin::UnknownModification
+                }
+            };
+            
+            debug!("{}: {:?}", path, origin);
+            match origin {
+                FileOrigin::Stock => diagnostics.stock += 1,
+                FileOrigin::ServerPatched => diagnostics.server_patched += 1,
+                FileOrigin::UnknownModification => diagnostics.unknown_modification.push(path.to_string()),
+                FileOrigin::Missing => diagnostics.missing.push(path.to_string()),
+            }
+        }
+        
+        info!(
+            "Client diagnostics: {} stock, {} patched by server, {} unknown modifications, {} missing",
+            diagnostics.stock,
+            diagnostics.server_patched,
+            diagnostics.unknown_modification.len(),
+            diagnostics.missing.len()
+        );
+        
+        Ok(diagnostics)
+    }
+    
+    async fn load_stock_database(&self) -> Result<ChecksumDatabase> {
+        let client_check = self.config.client_check.as_ref();
+        
+        if let Some(file) = client_check.and_then(|c| c.stock_hashes_file.as_ref()) {
+            return ChecksumDatabase::load(file);
+        }
+        
+        match client_check.and_then(|c| c.stock_hashes_url.as_ref()) {
+            Some(url) => ChecksumDatabase::fetch(&self.config.http, url).await,
+            None => Err(crate::Error::InvalidConfig(
+                "No stock checksum database configured (client_check.stock_hashes_file or stock_hashes_url)".to_string(),
+            )),
+        }
+    }
+    
+    async fn load_server_database(&self) -> Result<ChecksumDatabase> {
+        let url = self.config.client_check.as_ref()
+            .and_then(|c| c.server_manifest_url.clone())
+            .or_else(|| {
+                let variables = UrlVariables::from_config(&self.config);
+                self.config.patcher.mirrors.first().map(|m| url_template::mirror_file_url(m, &variables, "manifest.json"))
+            });
+        
+        match url {
+            Some(url) => ChecksumDatabase::fetch(&self.config.http, &url).await,
+            None => Ok(ChecksumDatabase::default()),
+        }
+    }
+    
     #[allow(dead_code)]
     fn calculate_file_hash(&self, path: &Path) -> Result<String> {
         let content = fs::read(path)?;
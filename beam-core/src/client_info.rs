@@ -0,0 +1,133 @@
+use crate::game_settings::{self, SettingsBackend};
+use crate::{Config, Error, Result};
+use beam_formats::pe::PeImage;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use tracing::info;
+
+// Enough for the DOS stub, PE headers and section table of any client
+const HEADER_READ_SIZE: u64 = 4096;
+// Section names protectors leave behind; a packed client cannot be matched by its code hash
+const PACKER_SECTIONS: &[(&str, &str)] = &[
+    ("UPX", "UPX"),
+    (".themida", "Themida"),
+    (".winlice", "WinLicense"),
+    (".vmp", "VMProtect"),
+    (".enigma", "Enigma Protector"),
+    ("MPRESS", "MPRESS"),
+    (".aspack", "ASPack"),
+    (".petite", "Petite"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionInfo {
+    pub name: String,
+    pub size: u32,
+    pub sha256: String,
+    pub code: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub exe: String,
+    pub architecture: String,
+    // Link time from the PE header; the date RO clients are known by, e.g. 2013-08-07 for 2013-08-07aRagexe
+    pub build_date: Option<DateTime<Utc>>,
+    pub size: u64,
+    pub sha256: String,
+    // Covers the code sections only, so diffed or hexed data (server IP, strings) leaves it unchanged
+    pub code_hash: String,
+    pub sections: Vec<SectionInfo>,
+    pub packer: Option<String>,
+    // Name of the matching `client_check.known_builds` entry
+    pub variant: Option<String>,
+    pub settings_backend: SettingsBackend,
+}
+
+impl ClientInfo {
+    pub fn client_date(&self) -> Option<NaiveDate> {
+        self.build_date.map(|date| date.date_naive())
+    }
+}
+
+pub fn inspect(config: &Config) -> Result<ClientInfo> {
+    let game_dir = config
+        .app
+        .game_directory
+        .as_ref()
+        .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?;
+    let path = Path::new(game_dir).join(&config.app.client_exe);
+    let data = fs::read(&path)?;
+    let image = PeImage::parse_headers(&data)?;
+    
+    let sections: Vec<SectionInfo> = image
+        .sections
+        .iter()
+        .map(|section| SectionInfo {
+            name: section.name.clone(),
+            size: section.raw_size,
+            sha256: format!("{:x}", Sha256::digest(section.data(&data).unwrap_or_default())),
+            code: section.is_code(),
+        })
+        .collect();
+    
+    let mut code = Sha256::new();
+    for section in image.sections.iter().filter(|section| section.is_code()) {
+        code.update(section.data(&data).unwrap_or_default());
+    }
+    let code_hash = format!("{:x}", code.finalize());
+    
+    let packer = image.sections.iter().find_map(|section| {
+        PACKER_SECTIONS
+            .iter()
+            .find(|(prefix, _)| section.name.to_ascii_lowercase().starts_with(&prefix.to_ascii_lowercase()))
+            .map(|(_, name)| name.to_string())
+    });
+    let variant = config
+        .client_check
+        .as_ref()
+        .and_then(|check| check.known_builds.iter().find(|build| build.code_hash.eq_ignore_ascii_case(&code_hash)))
+        .map(|build| build.name.clone());
+    
+    let build_date = build_date(image.timestamp);
+    let info = ClientInfo {
+        exe: config.app.client_exe.clone(),
+        architecture: image.machine.to_string(),
+        build_date,
+        size: data.len() as u64,
+        sha256: format!("{:x}", Sha256::digest(&data)),
+        code_hash,
+        sections,
+        packer,
+        variant,
+        settings_backend: game_settings::select_backend(config, build_date),
+    };
+    info!(
+        "Client {}: {} build from {}{}",
+        info.exe,
+        info.architecture,
+        info.client_date().map(|date| date.to_string()).unwrap_or_else(|| "an unknown date".to_string()),
+        info.variant.as_ref().map(|variant| format!(" ({})", variant)).unwrap_or_default()
+    );
+    Ok(info)
+}
+
+// Only the headers are read, for callers that need the date before every launch
+pub fn client_build_date(game_dir: &Path, client_exe: &str) -> Option<DateTime<Utc>> {
+    let mut header = Vec::new();
+    fs::File::open(game_dir.join(client_exe))
+        .ok()?
+        .take(HEADER_READ_SIZE)
+        .read_to_end(&mut header)
+        .ok()?;
+    build_date(PeImage::parse_headers(&header).ok()?.timestamp)
+}
+
+// Zero and future values are not dates: stripped headers and reproducible builds put other data there
+fn build_date(timestamp: u32) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(timestamp as i64, 0).filter(|date| timestamp != 0 && *date <= Utc::now())
+}
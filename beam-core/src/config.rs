@@ -1,5 +1,6 @@
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,6 +11,26 @@ pub struct Config {
     pub sso: Option<SsoConfig>,
     pub updater: Option<UpdaterConfig>,
     pub server: Option<ServerConfig>,
+    #[serde(default)]
+    pub client_check: Option<ClientCheckConfig>,
+    #[serde(default)]
+    pub multi_user: Option<MultiUserConfig>,
+    #[serde(default)]
+    pub packaging: Option<PackagingConfig>,
+    #[serde(default)]
+    pub launch: Option<LaunchConfig>,
+    #[serde(default)]
+    pub play_time: Option<PlayTimeConfig>,
+    #[serde(default)]
+    pub wrapper: Option<WrapperConfig>,
+    #[serde(default)]
+    pub dependencies: Option<DependencyConfig>,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub installer: Option<InstallerConfig>,
+    #[serde(default)]
+    pub integrity_check: Option<IntegrityCheckConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,15 +46,262 @@ pub struct AppConfig {
     pub server_name: Option<String>,
     pub video_background_enabled: Option<bool>,
     pub video_background_file: Option<String>,
+    // Internet café / kiosk installs: players cannot change settings, the game directory or apply manual patches
+    #[serde(default)]
+    pub kiosk: bool,
+    // Where game settings are stored; detected from the client when unset
+    #[serde(default)]
+    pub settings_backend: Option<crate::game_settings::SettingsBackend>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PatcherConfig {
     pub mirrors: Vec<MirrorConfig>,
     pub patch_list_url: String,
+    #[serde(default)]
+    pub patch_list_format: PatchListFormat,
+    // GRF every patch goes into unless the patch list, the patch itself or a `grf_targets` rule names another
     pub target_grf: String,
+    // Further GRFs for servers that split their content, e.g. rdata.grf and a custom server.grf
+    #[serde(default)]
+    pub grf_targets: Vec<GrfTargetConfig>,
     pub allow_manual_patch: bool,
     pub verify_checksums: bool,
+    #[serde(default)]
+    pub decompression_limits: Option<DecompressionLimitsConfig>,
+    #[serde(default)]
+    pub grf_alignment: Option<u32>,
+    // Flags byte for patched GRF entries, for shields and tools that check it
+    #[serde(default)]
+    pub grf_entry_flags: beam_formats::grf::GrfFlagPolicy,
+    // Keep parsed GRF file tables in the cache folder so unchanged archives open without re-reading them
+    #[serde(default = "default_true")]
+    pub grf_table_cache: bool,
+    // Write every patch as loose files below the game directory instead of merging it into a GRF
+    #[serde(default)]
+    pub extract_to_game_dir: bool,
+    #[serde(default)]
+    pub run_retry: Option<RunRetryConfig>,
+    #[serde(default)]
+    pub new_grf: Option<NewGrfConfig>,
+    // Consent to append to the GRF in place when there is no room for a full rebuild
+    #[serde(default)]
+    pub allow_in_place_apply: bool,
+    // Always append updates to the GRF and rewrite only its file table, instead of rebuilding it on every run
+    #[serde(default)]
+    pub append_patches: bool,
+    // Percentage of the GRF that may be dead space left by appended updates before it is compacted after a run
+    #[serde(default)]
+    pub defragment_threshold: Option<u8>,
+    #[serde(default)]
+    pub background_priority: Option<BackgroundPriorityConfig>,
+    // Seconds without a single received byte before a download is aborted and retried (default 30)
+    #[serde(default)]
+    pub download_stall_timeout: Option<u64>,
+    // Probe every mirror and start with the fastest healthy one instead of going strictly by priority
+    #[serde(default)]
+    pub mirror_selection: Option<MirrorSelectionConfig>,
+    // Patches downloaded at once while earlier ones are applied (default 1: download, then apply, one by one)
+    #[serde(default)]
+    pub concurrent_downloads: Option<usize>,
+    // Release channel for JSON manifests that tag patches with one (default "stable")
+    #[serde(default)]
+    pub channel: Option<String>,
+    // Further channels whose patches are downloaded as optional, skippable content
+    #[serde(default)]
+    pub optional_channels: Vec<String>,
+    // What happens when a patch would replace a file changed on this computer since a patch last wrote it
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    // Refuse every patch that is not a BEAM archive signed with one of `patch_signing_keys`
+    #[serde(default)]
+    pub require_signed_patches: bool,
+    // Ed25519 public keys (64 hex characters) patches are signed with; signed patches must match one of them
+    #[serde(default)]
+    pub patch_signing_keys: Vec<String>,
+}
+
+impl PatcherConfig {
+    pub fn signing_keys(&self) -> Result<Vec<[u8; 32]>> {
+        self.patch_signing_keys
+            .iter()
+            .map(|key| {
+                beam_formats::beam::decode_key(key)
+                    .map_err(|e| Error::InvalidConfig(format!("patch_signing_keys: {}", e)))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    // Stop the patch before anything is written and let the player choose per file
+    #[default]
+    Ask,
+    KeepMine,
+    TakeTheirs,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchListFormat {
+    // patchlist.txt or a JSON manifest, told apart by the response
+    #[default]
+    Auto,
+    // Thor/NeoCube plist.txt with `<index> <file>` lines, so migrated servers keep their existing list
+    Plist,
+}
+
+// Routes patches or files into a GRF other than `target_grf`; the first matching rule wins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrfTargetConfig {
+    // Relative to the game directory
+    pub grf: String,
+    // Patch ids or file names merged into this GRF as a whole
+    #[serde(default)]
+    pub patches: Vec<String>,
+    // Files whose name starts with one of these (`data\texture\`), in any case and with either separator
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+}
+
+impl GrfTargetConfig {
+    pub fn matches_patch(&self, id: &str, filename: &str) -> bool {
+        self.patches.iter().any(|patch| patch == id || patch.eq_ignore_ascii_case(filename))
+    }
+    
+    pub fn matches_file(&self, name: &str) -> bool {
+        let name = beam_formats::path::name_key(name);
+        self.prefixes.iter().any(|prefix| name.starts_with(&beam_formats::path::name_key(prefix)))
+    }
+}
+
+// Used only when the target GRF does not exist yet and has to be created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewGrfConfig {
+    #[serde(default = "default_new_grf_version")]
+    pub version: u32,
+    // 28 hex characters for the 14-byte header key
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub seed: u32,
+}
+
+fn default_new_grf_version() -> u32 {
+    0x200
+}
+
+impl NewGrfConfig {
+    pub fn to_create_options(&self) -> Result<beam_formats::grf::GrfCreateOptions> {
+        let mut options = beam_formats::grf::GrfCreateOptions {
+            version: self.version,
+            seed: self.seed,
+            ..Default::default()
+        };
+        
+        if let Some(key) = &self.key {
+            let key = key.trim();
+            if key.len() != 28 || !key.is_ascii() {
+                return Err(Error::InvalidConfig("GRF key must be 28 hex characters".to_string()));
+            }
+            for (i, byte) in options.key.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&key[i * 2..i * 2 + 2], 16)
+                    .map_err(|_| Error::InvalidConfig(format!("Invalid hex in GRF key: {}", key)))?;
+            }
+        }
+        
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRetryConfig {
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_retry_delay_seconds")]
+    pub delay_seconds: u64,
+}
+
+impl Default for RunRetryConfig {
+    fn default() -> Self {
+        RunRetryConfig {
+            max_retries: default_max_retries(),
+            delay_seconds: default_retry_delay_seconds(),
+        }
+    }
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_delay_seconds() -> u64 {
+    5
+}
+
+// Keeps auto-patching from competing with a running game or stream for CPU and disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundPriorityConfig {
+    #[serde(default = "default_true")]
+    pub cpu: bool,
+    #[serde(default = "default_true")]
+    pub io: bool,
+}
+
+impl Default for BackgroundPriorityConfig {
+    fn default() -> Self {
+        BackgroundPriorityConfig { cpu: true, io: true }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorSelectionConfig {
+    // A mirror that has not answered within this many milliseconds counts as down
+    #[serde(default = "default_probe_timeout_ms")]
+    pub probe_timeout_ms: u64,
+    // How long probe results are reused before the mirrors are measured again
+    #[serde(default = "default_probe_cache_seconds")]
+    pub cache_seconds: u64,
+}
+
+impl Default for MirrorSelectionConfig {
+    fn default() -> Self {
+        MirrorSelectionConfig {
+            probe_timeout_ms: default_probe_timeout_ms(),
+            cache_seconds: default_probe_cache_seconds(),
+        }
+    }
+}
+
+fn default_probe_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_probe_cache_seconds() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecompressionLimitsConfig {
+    pub max_entry_size_mb: u64,
+    pub max_total_size_mb: u64,
+    pub max_ratio: u64,
+}
+
+impl DecompressionLimitsConfig {
+    pub fn to_limits(&self) -> beam_formats::limits::DecompressionLimits {
+        beam_formats::limits::DecompressionLimits {
+            max_entry_size: self.max_entry_size_mb.saturating_mul(1024 * 1024),
+            max_total_size: self.max_total_size_mb.saturating_mul(1024 * 1024),
+            max_ratio: self.max_ratio,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +309,52 @@ pub struct MirrorConfig {
     pub name: String,
     pub url: String,
     pub priority: u32,
+    // Sent with every request to this mirror, e.g. a CDN access key
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub bearer_token: Option<String>,
+    // POST endpoint that trades the player's SSO token for a time-limited download URL per file
+    #[serde(default)]
+    pub signed_url_endpoint: Option<String>,
+    // Talk to an S3-compatible bucket directly; `url` is ignored when set
+    #[serde(default)]
+    pub s3: Option<S3MirrorConfig>,
+    // Number of hosts or folders `{shard}` in `url` spreads files over (default 16)
+    #[serde(default)]
+    pub shard_count: Option<u32>,
+    // SHA-256 of the certificate or public key the mirror must present, in hex or base64; see `pinning`
+    #[serde(default)]
+    pub pin_sha256: Vec<String>,
+}
+
+// Amazon S3, or any S3-compatible store through `endpoint` (GCS interoperability, R2, MinIO, Wasabi)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3MirrorConfig {
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    // Key prefix the patches live under, e.g. "patches/"
+    #[serde(default)]
+    pub prefix: String,
+    // Without credentials requests are sent unsigned, which works for public buckets
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    #[serde(default)]
+    pub session_token: Option<String>,
+    #[serde(default)]
+    pub path_style: bool,
+    // Build the patch list from the objects under `prefix` instead of downloading patch_list_url
+    #[serde(default)]
+    pub list_patches: bool,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +367,10 @@ pub struct UiConfig {
     pub show_file_list: bool,
     pub news_feed_url: Option<String>,
     pub server_status_url: Option<String>,
+    #[serde(default)]
+    pub news_click_tracking_url: Option<String>,
+    #[serde(default)]
+    pub events_url: Option<String>,
     pub custom_buttons: Vec<CustomButton>,
     pub layout: LayoutConfig,
 }
@@ -76,6 +394,19 @@ pub struct LayoutConfig {
     pub width: u32,
     pub height: u32,
     pub use_custom_layout: bool,
+    // Pixel box for an HTML notice page, as legacy patcher skins had
+    #[serde(default)]
+    pub notice_box: Option<NoticeBox>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoticeBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,6 +416,9 @@ pub struct SsoConfig {
     pub token_url: String,
     pub client_id: String,
     pub redirect_uri: String,
+    // Grant type for one-time codes when the token endpoint answers `mfa_required`
+    #[serde(default)]
+    pub mfa_grant_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,12 +437,188 @@ pub struct ServerConfig {
     pub char_server_port: u16,
     pub map_server_ip: String,
     pub map_server_port: u16,
+    // Seconds between background status checks while a server is up (default 30)
+    #[serde(default)]
+    pub status_poll_interval: Option<u64>,
+    // Longest wait between checks once every server is down; the wait doubles up to it (default 300)
+    #[serde(default)]
+    pub status_poll_max_interval: Option<u64>,
+    // Current player count as a bare number or `{"online": <number>}`, sampled into the population history
+    #[serde(default)]
+    pub player_count_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientCheckConfig {
+    #[serde(default)]
+    pub stock_hashes_file: Option<String>,
+    #[serde(default)]
+    pub stock_hashes_url: Option<String>,
+    #[serde(default)]
+    pub server_manifest_url: Option<String>,
+    // Client builds recognised by the hash of their code sections, for diagnostics
+    #[serde(default)]
+    pub known_builds: Vec<KnownClientBuild>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownClientBuild {
+    pub name: String,
+    pub code_hash: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MultiUserConfig {
+    pub enabled: bool,
+    #[serde(default)]
+    pub settings_dir: Option<String>,
+    #[serde(default)]
+    pub settings_launch_arg: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackagingConfig {
+    #[serde(default)]
+    pub trackers: Vec<String>,
+    #[serde(default)]
+    pub web_seeds: Vec<String>,
+    #[serde(default)]
+    pub piece_size_kb: Option<u64>,
+    #[serde(default)]
+    pub private: bool,
+}
+
+// For clients that can be started straight into a char server or character
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaunchConfig {
+    // Appended to the client command line, e.g. `-charserver:{server}` or `-char:{slot}`
+    #[serde(default)]
+    pub args: Vec<String>,
+    // Lists the account's characters for the SSO access token; without it the player types the name
+    #[serde(default)]
+    pub characters_url: Option<String>,
+}
+
+// Play-time rules some regions require; launching is refused outside the allowed times
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayTimeConfig {
+    #[serde(flatten)]
+    pub policy: crate::play_time::PlayTimePolicy,
+    // Per-account policy in the same format, requested with the player's SSO token
+    #[serde(default)]
+    pub policy_url: Option<String>,
+}
+
+// When `launch_game` runs a quick check of the game files first; see `integrity::IntegrityState::due`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityCheckConfig {
+    #[serde(default)]
+    pub every_launches: Option<u32>,
+    // Also due when the game files were never checked
+    #[serde(default)]
+    pub every_days: Option<u32>,
+    // Check before the next launch when the client exited with an error or crashed
+    #[serde(default = "default_true")]
+    pub after_abnormal_exit: bool,
+}
+
+// Direct3D wrapper such as dgVoodoo or DXVK, installed next to the client from a zip package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrapperConfig {
+    pub name: String,
+    pub package_url: String,
+    // Changing it (or the URL, without a checksum) makes launchers update their copy
+    #[serde(default)]
+    pub sha256: Option<String>,
+    // Package entries to install, e.g. `MS/x86/DDraw.dll` or `dgVoodoo.conf`; empty installs every top-level file
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub enabled_by_default: bool,
+}
+
+// Runtimes the client needs, offered to players whose system is missing them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DependencyConfig {
+    #[serde(default)]
+    pub redistributables: Vec<RedistributableConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedistributableConfig {
+    pub name: String,
+    // An installer, run with `args`, or a `.zip` whose DLLs are copied next to the client
+    pub url: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    // DLL names it provides; one `*` is allowed, e.g. `d3dx9_*.dll`
+    pub provides: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// Full client download for players who do not have the game yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallerConfig {
+    // File name on the mirrors, e.g. the archive `beam-patcher package-client` writes
+    pub package: String,
+    // Single-file packages only; split ones are checked part by part through `checksums`
+    #[serde(default)]
+    pub sha256: Option<String>,
+    // Number of volumes for a package split into `<package>.001`, `<package>.002`, ...
+    #[serde(default)]
+    pub parts: Option<u32>,
+    // The `<package>.sha256` file `package-client` writes, with one checksum per volume
+    #[serde(default)]
+    pub checksums: Option<String>,
+    // The `manifest.json` written alongside the package; when set, every extracted file is checked against it
+    #[serde(default)]
+    pub manifest: Option<String>,
+}
+
+// Applies to every request the launcher makes: patches, manifests, news, login and self-update checks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpConfig {
+    // Defaults to `Beam-Patcher/1.0`
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    // e.g. `CF-Access-Client-Id` and `CF-Access-Client-Secret` for a patch server behind Cloudflare Access
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl WrapperConfig {
+    pub fn revision(&self) -> String {
+        self.sha256.clone().unwrap_or_else(|| self.package_url.clone())
+    }
+}
+
+impl PackagingConfig {
+    pub fn to_torrent_options(&self) -> crate::torrent::TorrentOptions {
+        crate::torrent::TorrentOptions {
+            trackers: self.trackers.clone(),
+            web_seeds: self.web_seeds.clone(),
+            piece_size: self.piece_size_kb.map(|kb| kb * 1024),
+            private: self.private,
+            comment: None,
+        }
+    }
 }
 
 impl Config {
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&content)?;
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+        // Editors on non-English Windows often save in the ANSI code page, which would corrupt non-ASCII paths
+        let content = std::str::from_utf8(bytes).map_err(|e| {
+            Error::InvalidConfig(format!(
+                "{} is not valid UTF-8 (byte {}); save it with UTF-8 encoding",
+                path.display(),
+                e.valid_up_to()
+            ))
+        })?;
+        let config: Config = serde_yaml::from_str(content)?;
         config.validate()?;
         Ok(config)
     }
@@ -124,17 +634,166 @@ impl Config {
             return Err(Error::InvalidConfig("At least one mirror must be configured".to_string()));
         }
         
-        if self.patcher.patch_list_url.is_empty() {
+        let lists_patches = self.patcher.mirrors.iter().any(|m| m.s3.as_ref().map(|s3| s3.list_patches).unwrap_or(false));
+        if self.patcher.patch_list_url.is_empty() && !lists_patches {
             return Err(Error::InvalidConfig("Patch list URL cannot be empty".to_string()));
         }
         
         if self.patcher.target_grf.is_empty() {
             return Err(Error::InvalidConfig("Target GRF cannot be empty".to_string()));
         }
+        for target in &self.patcher.grf_targets {
+            if target.grf.is_empty() || beam_formats::path::sanitize_entry_path(&target.grf).is_err() {
+                return Err(Error::InvalidConfig(format!(
+                    "GRF target `{}` must be a path inside the game directory",
+                    target.grf
+                )));
+            }
+            if target.patches.is_empty() && target.prefixes.is_empty() {
+                return Err(Error::InvalidConfig(format!("GRF target {} needs `patches` or `prefixes`", target.grf)));
+            }
+            if target.prefixes.iter().any(String::is_empty) {
+                return Err(Error::InvalidConfig(format!("GRF target {} has an empty prefix", target.grf)));
+            }
+        }
+        
+        crate::http::validate(&self.http)?;
+        
+        crate::url_template::validate(&self.patcher.patch_list_url, crate::url_template::PATCH_LIST_VARIABLES)?;
+        
+        for mirror in &self.patcher.mirrors {
+            crate::mirror_auth::mirror_headers(mirror)?;
+            crate::url_template::validate(&mirror.url, crate::url_template::MIRROR_VARIABLES)?;
+            if mirror.shard_count == Some(0) {
+                return Err(Error::InvalidConfig(format!("Mirror {} needs a shard_count of at least 1", mirror.name)));
+            }
+            crate::pinning::parse_pins(&mirror.pin_sha256)?;
+            if !mirror.pin_sha256.is_empty() && mirror.s3.is_none() && !mirror.url.starts_with("https://") {
+                return Err(Error::InvalidConfig(format!("Mirror {} has pin_sha256 but is not served over HTTPS", mirror.name)));
+            }
+            if let Some(s3) = &mirror.s3 {
+                if mirror.signed_url_endpoint.is_some() {
+                    return Err(Error::InvalidConfig(format!(
+                        "Mirror {} cannot use both s3 and signed_url_endpoint",
+                        mirror.name
+                    )));
+                }
+                crate::s3::validate(s3)?;
+            }
+        }
+        
+        if let Some(server) = &self.server {
+            if server.status_poll_interval == Some(0) {
+                return Err(Error::InvalidConfig("status_poll_interval must be at least 1 second".to_string()));
+            }
+            if let (Some(interval), Some(max)) = (server.status_poll_interval, server.status_poll_max_interval) {
+                if max < interval {
+                    return Err(Error::InvalidConfig("status_poll_max_interval must not be below status_poll_interval".to_string()));
+                }
+            }
+        }
+        
+        if self.patcher.download_stall_timeout == Some(0) {
+            return Err(Error::InvalidConfig("download_stall_timeout must be at least 1 second".to_string()));
+        }
+        
+        if self.patcher.require_signed_patches && self.patcher.patch_signing_keys.is_empty() {
+            return Err(Error::InvalidConfig("require_signed_patches needs at least one patch_signing_keys entry".to_string()));
+        }
+        self.patcher.signing_keys()?;
         
+        if self.patcher.concurrent_downloads == Some(0) {
+            return Err(Error::InvalidConfig("concurrent_downloads must be at least 1".to_string()));
+        }
+        
+        if let Some(alignment) = self.patcher.grf_alignment {
+            if !alignment.is_power_of_two() {
+                return Err(Error::InvalidConfig("GRF alignment must be a power of two".to_string()));
+            }
+        }
+        
+        if let beam_formats::grf::GrfFlagPolicy::Fixed(flags) = self.patcher.grf_entry_flags {
+            if flags & 0x06 != 0 {
+                return Err(Error::InvalidConfig("grf_entry_flags must not set the DES bits 0x02 and 0x04".to_string()));
+            }
+        }
+        
+        if self.patcher.defragment_threshold.is_some_and(|threshold| !(1..=100).contains(&threshold)) {
+            return Err(Error::InvalidConfig("defragment_threshold must be a percentage from 1 to 100".to_string()));
+        }
+        
+        if let Some(new_grf) = &self.patcher.new_grf {
+            if new_grf.version != 0x200 && new_grf.version != 0x300 {
+                return Err(Error::InvalidConfig("New GRF version must be 0x200 or 0x300".to_string()));
+            }
+            new_grf.to_create_options()?;
+        }
+        
+        if let Some(limits) = &self.patcher.decompression_limits {
+            if limits.max_entry_size_mb == 0 || limits.max_total_size_mb == 0 || limits.max_ratio == 0 {
+                return Err(Error::InvalidConfig("Decompression limits must be greater than zero".to_string()));
+            }
+        }
+        
+        if let Some(launch) = &self.launch {
+            crate::characters::validate(launch)?;
+        }
+        
+        if let Some(integrity_check) = &self.integrity_check {
+            if integrity_check.every_launches == Some(0) || integrity_check.every_days == Some(0) {
+                return Err(Error::InvalidConfig("integrity_check intervals must be at least 1".to_string()));
+            }
+        }
+        
+        if let Some(play_time) = &self.play_time {
+            play_time.policy.validate()?;
+        }
+        
+        if let Some(wrapper) = &self.wrapper {
+            for file in &wrapper.files {
+                beam_formats::path::sanitize_entry_path(file)
+                    .map_err(|e| Error::InvalidConfig(format!("Invalid wrapper file {}: {}", file, e)))?;
+            }
+        }
+        
+        if let Some(installer) = &self.installer {
+            if installer.parts == Some(0) {
+                return Err(Error::InvalidConfig("installer.parts must be at least 1".to_string()));
+            }
+            if installer.parts.unwrap_or(1) > 1 && installer.sha256.is_some() {
+                return Err(Error::InvalidConfig(
+                    "installer.sha256 cannot check a split package; use installer.checksums".to_string(),
+                ));
+            }
+        }
+        
+        if let Some(piece_size_kb) = self.packaging.as_ref().and_then(|p| p.piece_size_kb) {
+            if piece_size_kb < 16 || !piece_size_kb.is_power_of_two() {
+                return Err(Error::InvalidConfig("Torrent piece size must be a power of two of at least 16 KB".to_string()));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Guards every operation a kiosk install must not allow, whichever frontend asks for it
+    pub fn ensure_unlocked(&self, action: &str) -> Result<()> {
+        if self.app.kiosk {
+            return Err(Error::KioskLocked(format!("{} is disabled on this installation", action)));
+        }
+        Ok(())
+    }
+    
+    pub fn set_game_directory(&mut self, directory: &str) -> Result<()> {
+        self.ensure_unlocked("Changing the game folder")?;
+        self.app.game_directory = Some(directory.to_string());
         Ok(())
     }
     
+    pub fn multi_user(&self) -> Option<&MultiUserConfig> {
+        self.multi_user.as_ref().filter(|m| m.enabled)
+    }
+    
     pub fn default() -> Self {
         Config {
             app: AppConfig {
@@ -149,6 +808,8 @@ impl Config {
                 server_name: Some("MyRO".to_string()),
                 video_background_enabled: Some(false),
                 video_background_file: None,
+                kiosk: false,
+                settings_backend: None,
             },
             patcher: PatcherConfig {
                 mirrors: vec![
@@ -156,12 +817,39 @@ impl Config {
                         name: "Primary Mirror".to_string(),
                         url: "https://patch.example.com".to_string(),
                         priority: 1,
+                        headers: BTreeMap::new(),
+                        bearer_token: None,
+                        signed_url_endpoint: None,
+                        s3: None,
+                        shard_count: None,
+                        pin_sha256: Vec::new(),
                     },
                 ],
                 patch_list_url: "https://patch.example.com/patchlist.txt".to_string(),
+                patch_list_format: PatchListFormat::Auto,
                 target_grf: "data.grf".to_string(),
+                grf_targets: Vec::new(),
                 allow_manual_patch: true,
                 verify_checksums: true,
+                decompression_limits: None,
+                grf_alignment: None,
+                grf_entry_flags: Default::default(),
+                grf_table_cache: true,
+                extract_to_game_dir: false,
+                run_retry: None,
+                new_grf: None,
+                allow_in_place_apply: false,
+                append_patches: false,
+                defragment_threshold: None,
+                background_priority: None,
+                download_stall_timeout: None,
+                mirror_selection: None,
+                concurrent_downloads: None,
+                channel: None,
+                optional_channels: vec![],
+                conflict_policy: ConflictPolicy::Ask,
+                require_signed_patches: false,
+                patch_signing_keys: Vec::new(),
             },
             ui: UiConfig {
                 theme: "default".to_string(),
@@ -172,11 +860,14 @@ impl Config {
                 show_file_list: true,
                 news_feed_url: None,
                 server_status_url: None,
+                news_click_tracking_url: None,
+                events_url: None,
                 custom_buttons: vec![],
                 layout: LayoutConfig {
                     width: 800,
                     height: 600,
                     use_custom_layout: false,
+                    notice_box: None,
                 },
             },
             sso: Some(SsoConfig {
@@ -185,6 +876,7 @@ impl Config {
                 token_url: "https://auth.example.com/token".to_string(),
                 client_id: "beam-patcher".to_string(),
                 redirect_uri: "http://localhost:8080/callback".to_string(),
+                mfa_grant_type: None,
             }),
             updater: Some(UpdaterConfig {
                 enabled: true,
@@ -199,7 +891,51 @@ impl Config {
                 char_server_port: 6121,
                 map_server_ip: "127.0.0.1".to_string(),
                 map_server_port: 5121,
+                status_poll_interval: None,
+                status_poll_max_interval: None,
+                player_count_url: None,
             }),
+            client_check: None,
+            multi_user: None,
+            packaging: None,
+            launch: None,
+            play_time: None,
+            wrapper: None,
+            dependencies: None,
+            http: HttpConfig::default(),
+            installer: None,
+            integrity_check: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn loads_config_with_bom() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yml");
+        let mut config = Config::default();
+        config.app.game_directory = Some("C:/Игры/라그나로크".to_string());
+        let mut content = b"\xEF\xBB\xBF".to_vec();
+        content.extend_from_slice(serde_yaml::to_string(&config).unwrap().as_bytes());
+        std::fs::write(&path, content).unwrap();
+        
+        let loaded = Config::load(&path).unwrap();
+        assert_eq!(loaded.app.game_directory.as_deref(), Some("C:/Игры/라그나로크"));
+    }
+    
+    #[test]
+    fn refuses_non_utf8_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yml");
+        // A CP949 comment, as an editor saving in the ANSI code page would write it
+        let mut content = serde_yaml::to_string(&Config::default()).unwrap().into_bytes();
+        content.extend_from_slice(b"# \xb6\xf3\xb1\xd7\n");
+        std::fs::write(&path, content).unwrap();
+        
+        assert!(matches!(Config::load(&path), Err(Error::InvalidConfig(_))));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::config::ConflictPolicy;
+use crate::{Error, Result};
+use beam_formats::content_index::ContentHash;
+use beam_formats::path;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const CONFLICTS_FILE: &str = ".beam-patch-conflicts.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictChoice {
+    KeepMine,
+    TakeTheirs,
+}
+
+// A file changed on this computer that a patch would replace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileConflict {
+    pub patch: String,
+    // The GRF holding the file, or None for a loose file in the game directory
+    pub grf: Option<String>,
+    pub file: String,
+    // Content hash of the patch's version; a choice holds as long as patches bring that same content
+    pub incoming: String,
+    #[serde(default)]
+    pub choice: Option<ConflictChoice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConflictResolution {
+    #[serde(default)]
+    pub grf: Option<String>,
+    pub file: String,
+    pub choice: ConflictChoice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Write,
+    // The player's version stays
+    Keep,
+    // Waiting for the player's choice; the patch must not be saved
+    Unresolved,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ConflictFile {
+    // Content hash of every file as a patch last left it, by `file_key`
+    #[serde(default)]
+    patched: BTreeMap<String, String>,
+    #[serde(default)]
+    conflicts: Vec<FileConflict>,
+}
+
+// Tells files the player changed apart from files patches changed. Every file a patch writes is recorded with its
+// content hash; a later patch that would replace a file whose content matches neither that record nor the
+// incoming data has found a local change. Files no patch has written yet are never conflicts.
+pub struct ConflictLog {
+    path: PathBuf,
+    file: ConflictFile,
+    patch: String,
+    policy: ConflictPolicy,
+    // Written earlier by the patch being applied, so what is stored is not what the player has yet
+    written: HashSet<String>,
+}
+
+fn file_key(grf: Option<&str>, file: &str) -> String {
+    format!("{}|{}", grf.map(path::name_key).unwrap_or_default(), path::name_key(file))
+}
+
+impl ConflictLog {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let file = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt conflict log {:?}: {}", path, e);
+                ConflictFile::default()
+            }),
+            Err(_) => ConflictFile::default(),
+        };
+        ConflictLog {
+            path,
+            file,
+            patch: String::new(),
+            policy: ConflictPolicy::default(),
+            written: HashSet::new(),
+        }
+    }
+    
+    pub fn save(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.file)
+            .map_err(|e| Error::PatchFailed(format!("Failed to serialize conflict log: {}", e)))?;
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+    
+    // Starts checking `patch`; conflicts still waiting for a choice are found again if they remain
+    pub fn begin(&mut self, patch: &str, policy: ConflictPolicy) {
+        self.patch = patch.to_string();
+        self.policy = policy;
+        self.written.clear();
+        self.file.conflicts.retain(|conflict| conflict.choice.is_some());
+    }
+    
+    // Whether `check` needs the current content of `file`; untracked files are written without reading them
+    pub fn is_tracked(&self, grf: Option<&str>, file: &str) -> bool {
+        let key = file_key(grf, file);
+        self.file.patched.contains_key(&key) && !self.written.contains(&key)
+    }
+    
+    // Whether `incoming` may replace `file`, whose content is `current` (None when missing or untracked)
+    pub fn check(&mut self, grf: Option<&str>, file: &str, current: Option<ContentHash>, incoming: ContentHash) -> Decision {
+        let key = file_key(grf, file);
+        let incoming = incoming.to_string();
+        let modified = !self.written.contains(&key)
+            && match (self.file.patched.get(&key), current) {
+                (Some(recorded), Some(current)) => *recorded != current.to_string() && current.to_string() != incoming,
+                _ => false,
+            };
+        // Choices made for other versions of the file have nothing left to decide
+        self.file.conflicts.retain(|conflict| {
+            file_key(conflict.grf.as_deref(), &conflict.file) != key || (modified && conflict.incoming == incoming)
+        });
+        if !modified {
+            self.file.patched.insert(key.clone(), incoming);
+            self.written.insert(key);
+            return Decision::Write;
+        }
+        
+        let existing = self.file.conflicts.iter().position(|conflict| {
+            file_key(conflict.grf.as_deref(), &conflict.file) == key && conflict.incoming == incoming
+        });
+        let choice = existing.and_then(|index| self.file.conflicts[index].choice);
+        let decision = match (choice, self.policy) {
+            (Some(ConflictChoice::KeepMine), _) | (None, ConflictPolicy::KeepMine) => {
+                info!("Keeping {}, which was changed on this computer", file);
+                Decision::Keep
+            }
+            (Some(ConflictChoice::TakeTheirs), _) | (None, ConflictPolicy::TakeTheirs) => {
+                warn!("Replacing {}, which was changed on this computer", file);
+                Decision::Write
+            }
+            (None, ConflictPolicy::Ask) => {
+                if existing.is_none() {
+                    self.file.conflicts.push(FileConflict {
+                        patch: self.patch.clone(),
+                        grf: grf.map(str::to_string),
+                        file: file.to_string(),
+                        incoming,
+                        choice: None,
+                    });
+                }
+                return Decision::Unresolved;
+            }
+        };
+        self.file.patched.insert(key.clone(), incoming);
+        self.written.insert(key);
+        decision
+    }
+    
+    pub fn unresolved(&self) -> Vec<FileConflict> {
+        self.file.conflicts.iter().filter(|conflict| conflict.choice.is_none()).cloned().collect()
+    }
+    
+    pub fn resolve(&mut self, resolution: &ConflictResolution) -> Result<()> {
+        let key = file_key(resolution.grf.as_deref(), &resolution.file);
+        let mut found = false;
+        for conflict in &mut self.file.conflicts {
+            if conflict.choice.is_none() && file_key(conflict.grf.as_deref(), &conflict.file) == key {
+                conflict.choice = Some(resolution.choice);
+                found = true;
+            }
+        }
+        if !found {
+            return Err(Error::PatchFailed(format!("{} has no open conflict", resolution.file)));
+        }
+        Ok(())
+    }
+    
+    // Fails while files wait for a choice. Only the conflicts are saved: records of files this patch would have
+    // written stay as they were, since the patch is not applied.
+    pub fn ensure_resolved(&self) -> Result<()> {
+        let unresolved = self.unresolved();
+        if unresolved.is_empty() {
+            return Ok(());
+        }
+        let mut saved = ConflictLog::load(&self.path);
+        saved.file.conflicts = self.file.conflicts.clone();
+        saved.save()?;
+        
+        let mut names: Vec<&str> = unresolved.iter().take(5).map(|conflict| conflict.file.as_str()).collect();
+        let more = format!("and {} more", unresolved.len().saturating_sub(names.len()));
+        if unresolved.len() > names.len() {
+            names.push(&more);
+        }
+        Err(Error::Conflicts(format!(
+            "{} would replace {} file(s) changed on this computer: {}",
+            self.patch,
+            unresolved.len(),
+            names.join(", ")
+        )))
+    }
+}
@@ -0,0 +1,324 @@
+use crate::config::MirrorConfig;
+use crate::url_template::{self, UrlVariables};
+use crate::{install_id, mirror_auth, s3, Config, Result};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RANGE};
+use reqwest::{Client, Url};
+use serde::Serialize;
+use std::fmt::{self, Write as _};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout;
+use tokio_native_tls::{native_tls, TlsConnector};
+use tracing::info;
+
+const STEP_TIMEOUT: Duration = Duration::from_secs(10);
+// Enough of the response to prove the server answers, without downloading a patch
+const PROBE_RANGE: &str = "bytes=0-1023";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStage {
+    Dns,
+    Tcp,
+    Tls,
+    Http,
+}
+
+impl fmt::Display for ProbeStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            ProbeStage::Dns => "DNS",
+            ProbeStage::Tcp => "TCP",
+            ProbeStage::Tls => "TLS",
+            ProbeStage::Http => "HTTP",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeStep {
+    pub stage: ProbeStage,
+    pub ok: bool,
+    pub duration_ms: u64,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointKind {
+    PatchList,
+    Mirror,
+    GameServer,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointReport {
+    pub kind: EndpointKind,
+    pub name: String,
+    // host:port for game servers; URLs lose their query string, which may carry signed credentials
+    pub target: String,
+    pub ok: bool,
+    // Stops at the first failing stage, since the later ones cannot run without it
+    pub steps: Vec<ProbeStep>,
+}
+
+// What the player pastes into the support channel when the launcher cannot connect
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionReport {
+    pub launcher_version: String,
+    pub server_name: Option<String>,
+    // Lets support match reports from the same install without knowing who sent them
+    pub install_id: Option<String>,
+    pub generated_at: DateTime<Utc>,
+    pub endpoints: Vec<EndpointReport>,
+    // The same report as plain text, ready to paste
+    pub text: String,
+}
+
+impl ConnectionReport {
+    pub fn ok(&self) -> bool {
+        self.endpoints.iter().all(|endpoint| endpoint.ok)
+    }
+}
+
+// Every mirror, the patch list and the login/char/map servers are probed one after another, so the
+// timings are not skewed by the probes competing with each other
+pub async fn diagnose(config: &Config) -> Result<ConnectionReport> {
+    let client = crate::http::client_builder(&config.http)?
+        .timeout(STEP_TIMEOUT)
+        .build()?;
+    let mut endpoints = Vec::new();
+    
+    let variables = UrlVariables::from_config(config);
+    let patch_list = url_template::expand(&config.patcher.patch_list_url, &variables, None, url_template::DEFAULT_SHARD_COUNT);
+    if !patch_list.is_empty() {
+        let mirror = url_template::mirror_for_url(&config.patcher.mirrors, &patch_list);
+        let headers = mirror.map(mirror_auth::mirror_headers).transpose()?.unwrap_or_default();
+        endpoints.push(probe_url(&client, EndpointKind::PatchList, "Patch list", &patch_list, headers).await);
+    }
+    
+    for mirror in &config.patcher.mirrors {
+        endpoints.push(probe_mirror(&client, mirror, &variables).await?);
+    }
+    
+    if let Some(server) = &config.server {
+        let servers = [
+            ("Login server", &server.login_server_ip, server.login_server_port),
+            ("Char server", &server.char_server_ip, server.char_server_port),
+            ("Map server", &server.map_server_ip, server.map_server_port),
+        ];
+        for (name, host, port) in servers {
+            endpoints.push(probe_server(name, host, port).await);
+        }
+    }
+    
+    let mut report = ConnectionReport {
+        launcher_version: config.app.version.clone(),
+        server_name: config.app.server_name.clone(),
+        install_id: install_id::load_or_create(config).ok(),
+        generated_at: Utc::now(),
+        endpoints,
+        text: String::new(),
+    };
+    report.text = render(&report);
+    info!(
+        "Connection check: {} of {} endpoints reachable",
+        report.endpoints.iter().filter(|endpoint| endpoint.ok).count(),
+        report.endpoints.len()
+    );
+    Ok(report)
+}
+
+async fn probe_mirror(client: &Client, mirror: &MirrorConfig, variables: &UrlVariables) -> Result<EndpointReport> {
+    let (url, headers) = mirror_probe_target(mirror, variables)?;
+    Ok(probe_url(client, EndpointKind::Mirror, &mirror.name, &url, headers).await)
+}
+
+// Where a mirror is asked whether it is up, and with which headers
+pub(crate) fn mirror_probe_target(mirror: &MirrorConfig, variables: &UrlVariables) -> Result<(String, HeaderMap)> {
+    let mut headers = mirror_auth::mirror_headers(mirror)?;
+    // An unsigned request to a private bucket still proves the bucket host answers; the 403 says the rest
+    let url = match &mirror.s3 {
+        Some(config) => {
+            headers.clear();
+            s3::object_url(config, "")?
+        }
+        // Templated mirrors are probed at the folder a file would go to, on one of the shards
+        None if url_template::is_template(&mirror.url) => {
+            let url = url_template::mirror_file_url(mirror, variables, "");
+            format!("{}/", url.trim_end_matches('/'))
+        }
+        None => format!("{}/", mirror.url.trim_end_matches('/')),
+    };
+    Ok((url, headers))
+}
+
+async fn probe_url(client: &Client, kind: EndpointKind, name: &str, url: &str, headers: HeaderMap) -> EndpointReport {
+    let target = url.split('?').next().unwrap_or(url).to_string();
+    let mut steps = Vec::new();
+    
+    let parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            steps.push(step(ProbeStage::Dns, Instant::now(), Err(format!("Invalid URL: {}", e))));
+            return endpoint(kind, name, target, steps);
+        }
+    };
+    let host = parsed.host_str().unwrap_or_default().trim_matches(|c| c == '[' || c == ']').to_string();
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    
+    let Some(address) = resolve(&host, port, &mut steps).await else {
+        return endpoint(kind, name, target, steps);
+    };
+    let Some(stream) = connect(address, &mut steps).await else {
+        return endpoint(kind, name, target, steps);
+    };
+    if parsed.scheme() == "https" && !handshake(&host, stream, &mut steps).await {
+        return endpoint(kind, name, target, steps);
+    }
+    
+    // A fresh connection through the normal client, so proxies and the configured headers apply as they do when patching
+    let started = Instant::now();
+    let result = client
+        .get(url)
+        .headers(headers)
+        .header(RANGE, PROBE_RANGE)
+        .send()
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|response| {
+            let status = response.status();
+            let detail = format!("HTTP {}", status);
+            // Any answer short of a server error means the host is reachable; 401/403/404 are config problems
+            if status.is_server_error() {
+                Err(detail)
+            } else {
+                Ok(detail)
+            }
+        });
+    steps.push(step(ProbeStage::Http, started, result));
+    endpoint(kind, name, target, steps)
+}
+
+async fn probe_server(name: &str, host: &str, port: u16) -> EndpointReport {
+    let mut steps = Vec::new();
+    if let Some(address) = resolve(host, port, &mut steps).await {
+        connect(address, &mut steps).await;
+    }
+    endpoint(EndpointKind::GameServer, name, format!("{}:{}", host, port), steps)
+}
+
+async fn resolve(host: &str, port: u16, steps: &mut Vec<ProbeStep>) -> Option<SocketAddr> {
+    let started = Instant::now();
+    let result = match timeout(STEP_TIMEOUT, lookup_host((host, port))).await {
+        Ok(Ok(addresses)) => {
+            let addresses: Vec<SocketAddr> = addresses.collect();
+            match addresses.first() {
+                Some(_) => Ok(addresses),
+                None => Err(format!("{} has no addresses", host)),
+            }
+        }
+        Ok(Err(e)) => Err(format!("{} could not be resolved: {}", host, e)),
+        Err(_) => Err(format!("{} did not resolve within {}s", host, STEP_TIMEOUT.as_secs())),
+    };
+    
+    let address = result.as_ref().ok().and_then(|addresses| addresses.first().copied());
+    steps.push(step(
+        ProbeStage::Dns,
+        started,
+        result.map(|addresses| addresses.iter().map(|a| a.ip().to_string()).collect::<Vec<_>>().join(", ")),
+    ));
+    address
+}
+
+async fn connect(address: SocketAddr, steps: &mut Vec<ProbeStep>) -> Option<TcpStream> {
+    let started = Instant::now();
+    let result = match timeout(STEP_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) => Err(format!("{}: {}", address, e)),
+        Err(_) => Err(format!("{}: no answer within {}s", address, STEP_TIMEOUT.as_secs())),
+    };
+    
+    let detail = result.as_ref().map(|_| address.to_string()).map_err(|e| e.clone());
+    steps.push(step(ProbeStage::Tcp, started, detail));
+    result.ok()
+}
+
+// Certificate problems (an antivirus intercepting HTTPS, a clock that is years off) show up here
+async fn handshake(host: &str, stream: TcpStream, steps: &mut Vec<ProbeStep>) -> bool {
+    let started = Instant::now();
+    let connector = match native_tls::TlsConnector::new() {
+        Ok(connector) => TlsConnector::from(connector),
+        Err(e) => {
+            steps.push(step(ProbeStage::Tls, started, Err(e.to_string())));
+            return false;
+        }
+    };
+    
+    let result = match timeout(STEP_TIMEOUT, connector.connect(host, stream)).await {
+        Ok(Ok(_)) => Ok("certificate accepted".to_string()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("no handshake within {}s", STEP_TIMEOUT.as_secs())),
+    };
+    let ok = result.is_ok();
+    steps.push(step(ProbeStage::Tls, started, result));
+    ok
+}
+
+fn step(stage: ProbeStage, started: Instant, result: std::result::Result<String, String>) -> ProbeStep {
+    let ok = result.is_ok();
+    ProbeStep {
+        stage,
+        ok,
+        duration_ms: started.elapsed().as_millis() as u64,
+        detail: result.unwrap_or_else(|e| e),
+    }
+}
+
+fn endpoint(kind: EndpointKind, name: &str, target: String, steps: Vec<ProbeStep>) -> EndpointReport {
+    EndpointReport {
+        kind,
+        name: name.to_string(),
+        target,
+        ok: !steps.is_empty() && steps.iter().all(|step| step.ok),
+        steps,
+    }
+}
+
+// Wrapped in a code block so Discord keeps the alignment
+fn render(report: &ConnectionReport) -> String {
+    let mut text = String::from("```\n");
+    let _ = writeln!(
+        text,
+        "Connection check: {} launcher {} at {}",
+        report.server_name.as_deref().unwrap_or("Beam Patcher"),
+        report.launcher_version,
+        report.generated_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let Some(id) = &report.install_id {
+        let _ = writeln!(text, "Install ID: {}", id);
+    }
+    for endpoint in &report.endpoints {
+        let _ = writeln!(
+            text,
+            "\n[{}] {} ({})",
+            if endpoint.ok { "OK" } else { "FAIL" },
+            endpoint.name,
+            endpoint.target
+        );
+        for step in &endpoint.steps {
+            let _ = writeln!(
+                text,
+                "  {:<4} {:<4} {:>6} ms  {}",
+                step.stage,
+                if step.ok { "ok" } else { "FAIL" },
+                step.duration_ms,
+                step.detail
+            );
+        }
+    }
+    text.push_str("```");
+    text
+}
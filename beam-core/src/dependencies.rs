@@ -0,0 +1,315 @@
+use crate::config::RedistributableConfig;
+use crate::{Config, Error, Result};
+use beam_formats::pe::{Machine, PeImage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Runtimes old clients are commonly built against, for naming what to install when the server ships nothing
+const KNOWN_RUNTIMES: &[(&str, &str)] = &[
+    ("msvcr71.dll", "Visual C++ 2003 runtime"),
+    ("msvcp71.dll", "Visual C++ 2003 runtime"),
+    ("msvcr80.dll", "Visual C++ 2005 Redistributable"),
+    ("msvcp80.dll", "Visual C++ 2005 Redistributable"),
+    ("msvcr90.dll", "Visual C++ 2008 Redistributable"),
+    ("msvcp90.dll", "Visual C++ 2008 Redistributable"),
+    ("msvcr100.dll", "Visual C++ 2010 Redistributable"),
+    ("msvcp100.dll", "Visual C++ 2010 Redistributable"),
+    ("msvcr110.dll", "Visual C++ 2012 Redistributable"),
+    ("msvcp110.dll", "Visual C++ 2012 Redistributable"),
+    ("msvcr120.dll", "Visual C++ 2013 Redistributable"),
+    ("msvcp120.dll", "Visual C++ 2013 Redistributable"),
+    ("vcruntime140.dll", "Visual C++ 2015-2022 Redistributable"),
+    ("msvcp140.dll", "Visual C++ 2015-2022 Redistributable"),
+    ("d3dx9_*.dll", "DirectX End-User Runtime (June 2010)"),
+    ("d3dx8*.dll", "DirectX End-User Runtime (June 2010)"),
+    ("d3dcompiler_4*.dll", "DirectX End-User Runtime (June 2010)"),
+    ("xinput1_*.dll", "DirectX End-User Runtime (June 2010)"),
+    ("x3daudio1_*.dll", "DirectX End-User Runtime (June 2010)"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyProblem {
+    Missing,
+    // A DLL next to the client built for another architecture; Windows reports this as 0xc000007b
+    WrongArchitecture,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyIssue {
+    pub dll: String,
+    pub problem: DependencyProblem,
+    // The executable or DLL that imports it
+    pub required_by: String,
+    pub delay_loaded: bool,
+    // Configured redistributable, or a well-known runtime, that provides the DLL
+    pub provided_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyReport {
+    pub client: String,
+    pub architecture: String,
+    // False where the system DLL folders cannot be searched, i.e. outside Windows
+    pub checked_system: bool,
+    pub issues: Vec<DependencyIssue>,
+}
+
+impl DependencyReport {
+    // Delay-loaded DLLs only break the feature using them, so they do not stop a launch
+    pub fn blocking(&self) -> impl Iterator<Item = &DependencyIssue> {
+        self.issues.iter().filter(|issue| !issue.delay_loaded)
+    }
+    
+    pub fn check_launch(&self) -> Result<()> {
+        let problems: Vec<String> = self
+            .blocking()
+            .map(|issue| match (issue.problem, &issue.provided_by) {
+                (DependencyProblem::WrongArchitecture, _) => format!("{} (the copy in the game folder is not built for {})", issue.dll, self.architecture),
+                (DependencyProblem::Missing, Some(provider)) => format!("{} (from {})", issue.dll, provider),
+                (DependencyProblem::Missing, None) => issue.dll.clone(),
+            })
+            .collect();
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(Error::MissingDependencies(format!(
+            "{} cannot start without {}",
+            self.client,
+            problems.join(", ")
+        )))
+    }
+}
+
+// Walks the client's imports the way the Windows loader resolves them: the game folder first, then the
+// system folders. DLLs found in the game folder are scanned in turn, since they can pull in runtimes too.
+pub fn check_client(config: &Config) -> Result<DependencyReport> {
+    let game_dir = config
+        .app
+        .game_directory
+        .as_ref()
+        .map(PathBuf::from)
+        .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?;
+    let client = game_dir.join(&config.app.client_exe);
+    let image = PeImage::parse(&fs::read(&client)?)?;
+    let search_dirs = system_dirs(image.machine);
+    let redistributables = config.dependencies.as_ref().map(|d| d.redistributables.as_slice()).unwrap_or(&[]);
+    
+    let mut report = DependencyReport {
+        client: config.app.client_exe.clone(),
+        architecture: image.machine.to_string(),
+        checked_system: !search_dirs.is_empty(),
+        issues: Vec::new(),
+    };
+    
+    let mut seen = HashSet::new();
+    let mut pending = vec![(config.app.client_exe.clone(), image)];
+    while let Some((module, image)) = pending.pop() {
+        for import in &image.imports {
+            let key = import.dll.to_ascii_lowercase();
+            if !seen.insert(key.clone()) || is_api_set(&key) {
+                continue;
+            }
+            
+            let local = game_dir.join(&import.dll);
+            if local.is_file() {
+                match fs::read(&local).map_err(Error::from).and_then(|data| Ok(PeImage::parse(&data)?)) {
+                    Ok(dll) if dll.machine != image.machine => report.issues.push(DependencyIssue {
+                        dll: import.dll.clone(),
+                        problem: DependencyProblem::WrongArchitecture,
+                        required_by: module.clone(),
+                        delay_loaded: import.delay_loaded,
+                        provided_by: provider(&key, redistributables),
+                    }),
+                    Ok(dll) => pending.push((import.dll.clone(), dll)),
+                    Err(e) => warn!("Could not read {:?}: {}", local, e),
+                }
+                continue;
+            }
+            
+            if search_dirs.is_empty() || search_dirs.iter().any(|dir| dir.join(&import.dll).is_file()) || in_side_by_side(&key, image.machine) {
+                continue;
+            }
+            report.issues.push(DependencyIssue {
+                dll: import.dll.clone(),
+                problem: DependencyProblem::Missing,
+                required_by: module.clone(),
+                delay_loaded: import.delay_loaded,
+                provided_by: provider(&key, redistributables),
+            });
+        }
+    }
+    
+    if !report.issues.is_empty() {
+        info!("Client dependency problems: {:?}", report.issues.iter().map(|i| &i.dll).collect::<Vec<_>>());
+    }
+    Ok(report)
+}
+
+// Installs every configured redistributable that provides one of the reported DLLs, then checks again
+pub async fn install_redistributables(config: &Config, report: &DependencyReport) -> Result<DependencyReport> {
+    let redistributables = config.dependencies.as_ref().map(|d| d.redistributables.as_slice()).unwrap_or(&[]);
+    let wanted: Vec<&RedistributableConfig> = redistributables
+        .iter()
+        .filter(|redist| report.issues.iter().any(|issue| redist.provides.iter().any(|pattern| matches_pattern(pattern, &issue.dll))))
+        .collect();
+    if wanted.is_empty() {
+        return Err(Error::MissingDependencies(
+            "No redistributable is configured for the missing files; install them manually".to_string(),
+        ));
+    }
+    
+    for redist in wanted {
+        install(config, redist).await?;
+    }
+    check_client(config)
+}
+
+async fn install(config: &Config, redist: &RedistributableConfig) -> Result<()> {
+    info!("Downloading {} from {}", redist.name, redist.url);
+    let response = crate::http::client(&config.http)?.get(&redist.url).send().await?;
+    if !response.status().is_success() {
+        return Err(Error::DownloadFailed(format!("Failed to download {}: HTTP {}", redist.name, response.status())));
+    }
+    let package = response.bytes().await?.to_vec();
+    if let Some(expected) = &redist.sha256 {
+        if !format!("{:x}", Sha256::digest(&package)).eq_ignore_ascii_case(expected) {
+            return Err(Error::DownloadFailed(format!("Checksum mismatch for {}", redist.name)));
+        }
+    }
+    
+    let file_name = redist.url.rsplit('/').next().and_then(|name| name.split('?').next()).unwrap_or("redist.exe");
+    if file_name.to_ascii_lowercase().ends_with(".zip") {
+        let game_dir = config
+            .app
+            .game_directory
+            .as_ref()
+            .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?;
+        return extract_dlls(&package, Path::new(game_dir), &redist.name);
+    }
+    run_installer(&package, file_name, redist)
+}
+
+// App-local runtimes: top-level DLLs of the package go next to the client
+fn extract_dlls(package: &[u8], game_dir: &Path, name: &str) -> Result<()> {
+    let invalid = |e: zip::result::ZipError| Error::DownloadFailed(format!("Invalid {} package: {}", name, e));
+    let mut archive = zip::ZipArchive::new(Cursor::new(package)).map_err(invalid)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(invalid)?;
+        let path = beam_formats::path::sanitize_entry_path(entry.name())?;
+        let is_dll = path.extension().map(|ext| ext.eq_ignore_ascii_case("dll")).unwrap_or(false);
+        if entry.is_dir() || !is_dll || path.components().count() != 1 {
+            continue;
+        }
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        fs::write(game_dir.join(&path), data)?;
+        info!("Installed {:?} from {}", path, name);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn run_installer(package: &[u8], file_name: &str, redist: &RedistributableConfig) -> Result<()> {
+    let dir = std::env::temp_dir().join("beam-redist");
+    fs::create_dir_all(&dir)?;
+    let installer = dir.join(file_name);
+    fs::write(&installer, package)?;
+    
+    info!("Running {} installer", redist.name);
+    let status = std::process::Command::new(&installer).args(&redist.args).status()?;
+    let _ = fs::remove_file(&installer);
+    // 3010: installed, reboot required
+    match status.code() {
+        Some(0) | Some(3010) => Ok(()),
+        code => Err(Error::MissingDependencies(format!(
+            "{} installer failed (exit code {})",
+            redist.name,
+            code.map(|c| c.to_string()).unwrap_or_else(|| "unknown".to_string())
+        ))),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn run_installer(_package: &[u8], _file_name: &str, redist: &RedistributableConfig) -> Result<()> {
+    Err(Error::MissingDependencies(format!("{} can only be installed on Windows", redist.name)))
+}
+
+fn provider(dll: &str, redistributables: &[RedistributableConfig]) -> Option<String> {
+    redistributables
+        .iter()
+        .find(|redist| redist.provides.iter().any(|pattern| matches_pattern(pattern, dll)))
+        .map(|redist| redist.name.clone())
+        .or_else(|| {
+            KNOWN_RUNTIMES
+                .iter()
+                .find(|(pattern, _)| matches_pattern(pattern, dll))
+                .map(|(_, name)| name.to_string())
+        })
+}
+
+// Case-insensitive, with at most one `*`
+fn matches_pattern(pattern: &str, dll: &str) -> bool {
+    let (pattern, dll) = (pattern.to_ascii_lowercase(), dll.to_ascii_lowercase());
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => dll.len() >= prefix.len() + suffix.len() && dll.starts_with(prefix) && dll.ends_with(suffix),
+        None => pattern == dll,
+    }
+}
+
+// Virtual DLLs Windows maps onto its own modules; they never exist as files
+fn is_api_set(dll: &str) -> bool {
+    dll.starts_with("api-ms-") || dll.starts_with("ext-ms-")
+}
+
+#[cfg(target_os = "windows")]
+fn windows_dir() -> PathBuf {
+    std::env::var_os("SystemRoot").map(PathBuf::from).unwrap_or_else(|| PathBuf::from(r"C:\Windows"))
+}
+
+// A 32-bit client on 64-bit Windows loads its system DLLs from SysWOW64
+#[cfg(target_os = "windows")]
+fn system_dirs(machine: Machine) -> Vec<PathBuf> {
+    let windows = windows_dir();
+    let wow64 = windows.join("SysWOW64");
+    let mut dirs = vec![if machine == Machine::X86 && wow64.is_dir() { wow64 } else { windows.join("System32") }, windows.clone()];
+    if let Some(path) = std::env::var_os("PATH") {
+        dirs.extend(std::env::split_paths(&path));
+    }
+    dirs
+}
+
+#[cfg(not(target_os = "windows"))]
+fn system_dirs(_machine: Machine) -> Vec<PathBuf> {
+    Vec::new()
+}
+
+// The Visual C++ 2005 and 2008 runtimes live in WinSxS rather than System32
+#[cfg(target_os = "windows")]
+fn in_side_by_side(dll: &str, machine: Machine) -> bool {
+    let assembly = match dll {
+        "msvcr80.dll" | "msvcp80.dll" | "msvcm80.dll" => "microsoft.vc80.crt_",
+        "msvcr90.dll" | "msvcp90.dll" | "msvcm90.dll" => "microsoft.vc90.crt_",
+        "mfc80.dll" | "mfc80u.dll" => "microsoft.vc80.mfc_",
+        "mfc90.dll" | "mfc90u.dll" => "microsoft.vc90.mfc_",
+        _ => return false,
+    };
+    let prefix = format!("{}_{}", if machine == Machine::X86 { "x86" } else { "amd64" }, assembly);
+    let Ok(entries) = fs::read_dir(windows_dir().join("WinSxS")) else {
+        return false;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().to_ascii_lowercase().starts_with(&prefix))
+        .any(|entry| entry.path().join(dll).is_file())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn in_side_by_side(_dll: &str, _machine: Machine) -> bool {
+    false
+}
@@ -1,60 +1,163 @@
+use crate::body;
+use crate::config::PatchListFormat;
+use crate::install_id;
+use crate::mirror_auth::{self, MirrorRequest};
+use crate::mirror_selector::MirrorSelector;
+use crate::pinning;
+use crate::preflight::MirrorCheck;
+use crate::rollout;
+use crate::progress::{self, ProgressEvent, ProgressReporter};
+use crate::s3;
+use crate::url_template::{self, UrlVariables};
 use crate::{Config, Error, Result};
-use futures::StreamExt;
-use reqwest::Client;
+use beam_formats::path::sanitize_entry_path;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
-use tokio::fs::File;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::{File, OpenOptions};
 use tokio::io::AsyncWriteExt;
 use tracing::{debug, info, warn};
 
+pub const DEFAULT_STALL_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_CHANNEL: &str = "stable";
+// Fresh connections to the same mirror before moving on to the next one
+pub(crate) const STALL_RETRIES: u32 = 2;
+// Bytes between two `Downloading` events of a single-connection download
+const PROGRESS_INTERVAL_BYTES: u64 = 256 * 1024;
+// Marks a line of a plain-text patch list as extracted into the game directory
+const EXTRACT_FLAG: &str = "extract";
+
+#[derive(Clone)]
 pub struct Downloader {
     client: Client,
     config: Config,
+    stall_timeout: Duration,
+    reporter: Option<ProgressReporter>,
+    sso_token: Option<String>,
+    // Set with `mirror_selection`; otherwise mirrors are tried by priority
+    selector: Option<Arc<MirrorSelector>>,
 }
 
 impl Downloader {
     pub fn new(config: Config) -> Result<Self> {
-        let client = Client::builder()
-            .user_agent("Beam-Patcher/1.0")
+        let client = crate::http::client_builder(&config.http)?
             .connect_timeout(std::time::Duration::from_secs(30))
             .timeout(std::time::Duration::from_secs(300))
             .tcp_keepalive(std::time::Duration::from_secs(60))
             .pool_idle_timeout(std::time::Duration::from_secs(90))
             .pool_max_idle_per_host(10)
+            // Exposes the server certificate for mirrors with `pin_sha256`
+            .tls_info(true)
             .build()?;
         
-        Ok(Downloader { client, config })
+        Ok(Downloader {
+            client,
+            stall_timeout: stall_timeout(&config),
+            selector: config.patcher.mirror_selection.as_ref().map(|selection| Arc::new(MirrorSelector::new(selection))),
+            config,
+            reporter: None,
+            sso_token: None,
+        })
+    }
+    
+    // Sent to mirrors with a `signed_url_endpoint` to obtain download URLs
+    pub fn set_sso_token(&mut self, token: Option<String>) {
+        self.sso_token = token;
+    }
+    
+    pub fn set_progress_reporter(&mut self, reporter: ProgressReporter) {
+        self.reporter = Some(reporter);
     }
     
     pub async fn download_file(
         &self,
         filename: &str,
         destination: &Path,
+    ) -> Result<PathBuf> {
+        self.download_from_mirrors(filename, destination, &UrlVariables::from_config(&self.config), false).await
+    }
+    
+    // Patches from an optional channel expand `{channel}` to their own channel. With `resume`, bytes already in
+    // `destination` are kept and only the rest is requested from mirrors that support ranges.
+    pub async fn download_patch(&self, patch: &PatchInfo, destination: &Path, resume: bool) -> Result<PathBuf> {
+        let variables = UrlVariables::from_config(&self.config).for_channel(patch.channel.as_deref());
+        self.download_from_mirrors(&patch.filename, destination, &variables, resume).await
+    }
+    
+    async fn download_from_mirrors(
+        &self,
+        filename: &str,
+        destination: &Path,
+        variables: &UrlVariables,
+        resume: bool,
     ) -> Result<PathBuf> {
         let mut mirrors = self.config.patcher.mirrors.clone();
         mirrors.sort_by_key(|m| m.priority);
+        if let Some(selector) = &self.selector {
+            mirrors = selector.rank(&self.client, &mirrors, variables).await;
+        }
         
         let mut last_error = None;
+        // Once a mirror has failed part-way, the next one continues the partial file instead of starting over. A
+        // fresh download drops what was there before, so only bytes from these mirrors are ever continued.
+        if !resume && tokio::fs::try_exists(destination).await? {
+            tokio::fs::remove_file(destination).await?;
+        }
+        let mut resume = resume;
         
         for mirror in &mirrors {
-            if mirror.url.is_empty() {
+            if mirror.url.is_empty() && mirror.s3.is_none() {
                 warn!("Skipping mirror {} with empty URL", mirror.name);
                 continue;
             }
             
-            let url = format!("{}/{}", mirror.url, filename);
-            info!("Attempting download from mirror: {} ({})", mirror.name, url);
-            
-            match self.download_from_url(&url, destination).await {
-                Ok(path) => {
-                    info!("Successfully downloaded from mirror: {}", mirror.name);
-                    return Ok(path);
-                }
+            let target = match mirror_auth::resolve(&self.client, mirror, filename, variables, self.sso_token.as_deref()).await {
+                Ok(target) => target,
                 Err(e) => {
-                    warn!("Failed to download from mirror {}: {}", mirror.name, e);
+                    warn!("Failed to resolve {} on mirror {}: {}", filename, mirror.name, e);
                     last_error = Some(e);
+                    continue;
+                }
+            };
+            // Signed URLs are credentials, so only the file name is logged
+            info!("Attempting download from mirror: {} ({})", mirror.name, filename);
+            
+            let mut attempt = 0;
+            loop {
+                match self.download_from_url(filename, &target, destination, resume || attempt > 0).await {
+                    Ok(path) => {
+                        info!("Successfully downloaded from mirror: {}", mirror.name);
+                        return Ok(path);
+                    }
+                    Err(e @ Error::Stalled(_)) => {
+                        attempt += 1;
+                        warn!("Download from mirror {} stalled ({}), attempt {}", mirror.name, e, attempt);
+                        progress::report(&self.reporter, ProgressEvent::Stalled {
+                            file: filename.to_string(),
+                            source: mirror.name.clone(),
+                            attempt,
+                        });
+                        if attempt > STALL_RETRIES {
+                            last_error = Some(e);
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to download from mirror {}: {}", mirror.name, e);
+                        last_error = Some(e);
+                        break;
+                    }
                 }
             }
+            if let (Some(selector), Some(e)) = (&self.selector, &last_error) {
+                selector.mark_failed(&mirror.name, &e.to_string());
+            }
+            resume = true;
         }
         
         Err(last_error.unwrap_or_else(|| {
@@ -62,14 +165,44 @@ impl Downloader {
         }))
     }
     
+    // A retry after a stall continues the partial file when the mirror honours the range request
     async fn download_from_url(
         &self,
-        url: &str,
+        filename: &str,
+        target: &MirrorRequest,
         destination: &Path,
+        resume: bool,
     ) -> Result<PathBuf> {
-        debug!("Downloading: {}", url);
+        debug!("Downloading to {:?}", destination);
         
-        let response = self.client.get(url).send().await?;
+        let filepath = destination.to_path_buf();
+        let partial = match tokio::fs::metadata(&filepath).await {
+            Ok(metadata) if resume => metadata.len(),
+            _ => 0,
+        };
+        
+        let mut request = target.get(&self.client);
+        if partial > 0 {
+            request = request.header("Range", format!("bytes={}-", partial));
+        }
+        let response = send_to_mirror(target, request, self.stall_timeout).await?;
+        
+        // Either the launcher stopped after the last byte arrived, or the partial file is not from this download
+        if partial > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            let size = response
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("bytes */"))
+                .and_then(|size| size.parse::<u64>().ok());
+            if size == Some(partial) {
+                debug!("{:?} is already complete", filepath);
+                return Ok(filepath);
+            }
+            // The next attempt starts from scratch
+            tokio::fs::remove_file(&filepath).await?;
+            return Err(Error::DownloadFailed(format!("Cannot resume {}: HTTP 416", filename)));
+        }
         
         if !response.status().is_success() {
             return Err(Error::DownloadFailed(format!(
@@ -78,20 +211,31 @@ impl Downloader {
             )));
         }
         
-        let _total_size = response.content_length().unwrap_or(0);
-        let mut _downloaded: u64 = 0;
-        
-        let filepath = destination.to_path_buf();
         tokio::fs::create_dir_all(filepath.parent().unwrap()).await?;
-        let mut file = File::create(&filepath).await?;
+        let resumed = partial > 0 && response.status().as_u16() == 206;
+        let mut file = if resumed {
+            debug!("Resuming {:?} at byte {}", filepath, partial);
+            OpenOptions::new().append(true).open(&filepath).await?
+        } else {
+            File::create(&filepath).await?
+        };
         
+        let mut downloaded = if resumed { partial } else { 0 };
+        let total = response.content_length().map(|len| len + downloaded).unwrap_or(0);
+        let mut reported = downloaded;
         let mut stream = response.bytes_stream();
         
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
+        while let Some(chunk) = next_chunk(&mut stream, self.stall_timeout).await? {
             file.write_all(&chunk).await?;
-            
-            _downloaded += chunk.len() as u64;
+            downloaded += chunk.len() as u64;
+            if downloaded - reported >= PROGRESS_INTERVAL_BYTES || downloaded == total {
+                reported = downloaded;
+                progress::report(&self.reporter, ProgressEvent::Downloading {
+                    file: filename.to_string(),
+                    downloaded_bytes: downloaded,
+                    total_bytes: total,
+                });
+            }
         }
         
         file.flush().await?;
@@ -101,10 +245,31 @@ impl Downloader {
     }
     
     pub async fn download_patch_list(&self) -> Result<Vec<PatchInfo>> {
-        let url = &self.config.patcher.patch_list_url;
+        let mut mirrors: Vec<_> = self.config.patcher.mirrors.iter().collect();
+        mirrors.sort_by_key(|m| m.priority);
+        if let Some(s3) = mirrors.iter().filter_map(|m| m.s3.as_ref()).find(|s3| s3.list_patches) {
+            let patches = s3::list_patches(&self.client, s3).await?;
+            info!("Found {} patches", patches.len());
+            return Ok(self.filter_rollout(self.filter_channel(patches)));
+        }
+        
+        let url = &url_template::expand(
+            &self.config.patcher.patch_list_url,
+            &UrlVariables::from_config(&self.config),
+            None,
+            url_template::DEFAULT_SHARD_COUNT,
+        );
         info!("Downloading patch list from: {}", url);
         
-        let response = self.client.get(url).send().await?;
+        let mut request = self.client.get(url);
+        let mut pins = Vec::new();
+        // A patch list on a private mirror needs that mirror's credentials, and comes from its pinned server
+        if let Some(mirror) = url_template::mirror_for_url(&self.config.patcher.mirrors, url) {
+            request = request.headers(mirror_auth::mirror_headers(mirror)?);
+            pins = pinning::parse_pins(&mirror.pin_sha256)?;
+        }
+        let response = request.send().await?;
+        pinning::check(&response, &pins)?;
         
         if !response.status().is_success() {
             return Err(Error::DownloadFailed(format!(
@@ -113,11 +278,71 @@ impl Downloader {
             )));
         }
         
-        let content = response.text().await?;
-        let patches = self.parse_patch_list(&content)?;
+        let is_json = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.contains("json"))
+            .unwrap_or(false);
+        let content = body::read_text(response, body::MAX_PATCH_LIST_SIZE, "patch list").await?;
+        
+        // Servers often send static files as text/plain, so a leading bracket also selects the JSON manifest
+        let trimmed = content.trim_start_matches('\u{feff}').trim_start();
+        let patches = if self.config.patcher.patch_list_format == PatchListFormat::Plist {
+            parse_plist(&content)
+        } else if is_json || trimmed.starts_with('{') || trimmed.starts_with('[') {
+            parse_patch_manifest(trimmed)?
+        } else {
+            self.parse_patch_list(&content)?
+        };
         
         info!("Found {} patches", patches.len());
-        Ok(patches)
+        Ok(self.filter_rollout(self.filter_channel(patches)))
+    }
+    
+    // Staged patches this install is not part of yet wait for a later wave, and patches with variants are
+    // replaced by the one this install was given
+    fn filter_rollout(&self, patches: Vec<PatchInfo>) -> Vec<PatchInfo> {
+        if patches.iter().all(|patch| patch.rollout.is_none() && patch.variants.is_empty()) {
+            return patches;
+        }
+        let install_id = install_id::load_or_create(&self.config)
+            .map_err(|e| warn!("No install ID for staged patches: {}", e))
+            .ok();
+        let total = patches.len();
+        let patches: Vec<PatchInfo> = patches
+            .into_iter()
+            .filter(|patch| rollout::includes(patch, install_id.as_deref()))
+            .map(|patch| rollout::resolve_variant(patch, install_id.as_deref()))
+            .collect();
+        if patches.len() < total {
+            info!("{} staged patches are not rolled out to this install yet", total - patches.len());
+        }
+        patches
+    }
+    
+    fn filter_channel(&self, patches: Vec<PatchInfo>) -> Vec<PatchInfo> {
+        let channel = self.config.patcher.channel.as_deref().unwrap_or(DEFAULT_CHANNEL);
+        let optional_channels = &self.config.patcher.optional_channels;
+        let total = patches.len();
+        let patches: Vec<PatchInfo> = patches
+            .into_iter()
+            .filter_map(|mut patch| {
+                if patch.applies_to_channel(channel) {
+                    return Some(patch);
+                }
+                // Extra content such as HD textures, offered on top of the main channel
+                if optional_channels.iter().any(|c| patch.applies_to_channel(c)) {
+                    patch.optional = true;
+                    return Some(patch);
+                }
+                None
+            })
+            .collect();
+        if patches.len() < total {
+            debug!("Skipped {} patches for other channels than {}", total - patches.len(), channel);
+        }
+        patches
     }
     
     fn parse_patch_list(&self, content: &str) -> Result<Vec<PatchInfo>> {
@@ -129,24 +354,100 @@ impl Downloader {
                 continue;
             }
             
-            let parts: Vec<&str> = line.split_whitespace().collect();
+            // Anything after a whitespace-preceded `#` is a player-facing description
+            let (line, description) = match line.char_indices().find(|&(i, c)| {
+                c == '#' && line[..i].ends_with(char::is_whitespace)
+            }) {
+                Some((i, _)) => (line[..i].trim_end(), Some(line[i + 1..].trim().to_string()).filter(|d| !d.is_empty())),
+                None => (line, None),
+            };
+            
+            let mut parts: Vec<&str> = line.split_whitespace().collect();
             if parts.is_empty() {
                 continue;
             }
+            // Numbered lists lead with the entry's index; a file name alone is never just digits
+            let index = match parts.as_slice() {
+                [index, _, ..] if index.chars().all(|c| c.is_ascii_digit()) => index.parse().ok(),
+                _ => None,
+            };
+            if index.is_some() {
+                parts.remove(0);
+            }
             
             let filename = parts[0].to_string();
-            let checksum = if parts.len() > 1 {
-                Some(parts[1].to_string())
-            } else {
-                None
-            };
+            let extract = parts[1..].contains(&EXTRACT_FLAG);
+            let checksum = parts[1..].iter().find(|part| **part != EXTRACT_FLAG).map(|part| part.to_string());
             
-            patches.push(PatchInfo { filename, checksum });
+            patches.push(PatchInfo { index, filename, checksum, extract, description, ..Default::default() });
         }
         
         Ok(patches)
     }
     
+    // Asks every mirror for `patch` with a HEAD request, for the dry run
+    pub async fn probe_patch(&self, patch: &PatchInfo) -> Vec<MirrorCheck> {
+        let variables = UrlVariables::from_config(&self.config).for_channel(patch.channel.as_deref());
+        let mut mirrors = self.config.patcher.mirrors.clone();
+        mirrors.sort_by_key(|m| m.priority);
+        
+        let mut checks = Vec::with_capacity(mirrors.len());
+        for mirror in mirrors.iter().filter(|m| !m.url.is_empty() || m.s3.is_some()) {
+            let result = async {
+                let target = mirror_auth::resolve(&self.client, mirror, &patch.filename, &variables, self.sso_token.as_deref()).await?;
+                let response = send_to_mirror(&target, target.head(&self.client), self.stall_timeout).await?;
+                if !response.status().is_success() {
+                    return Err(Error::DownloadFailed(format!("HTTP {}", response.status())));
+                }
+                // `content_length` is the size of the (empty) HEAD body
+                Ok(response
+                    .headers()
+                    .get(reqwest::header::CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok()))
+            }
+            .await;
+            let check = match result {
+                Ok(size) => {
+                    let error = match (patch.size, size) {
+                        (Some(expected), Some(size)) if size != expected => {
+                            Some(format!("{} bytes, the list says {}", size, expected))
+                        }
+                        _ => None,
+                    };
+                    MirrorCheck { mirror: mirror.name.clone(), ok: error.is_none(), size, error }
+                }
+                Err(e) => MirrorCheck { mirror: mirror.name.clone(), ok: false, size: None, error: Some(e.to_string()) },
+            };
+            checks.push(check);
+        }
+        checks
+    }
+    
+    // SHA-256 of `patch` as `mirror` serves it, hashed while it streams in instead of being saved
+    pub async fn remote_checksum(&self, mirror: &str, patch: &PatchInfo) -> Result<String> {
+        let mirror = self
+            .config
+            .patcher
+            .mirrors
+            .iter()
+            .find(|m| m.name == mirror)
+            .ok_or_else(|| Error::InvalidConfig(format!("Unknown mirror {}", mirror)))?;
+        let variables = UrlVariables::from_config(&self.config).for_channel(patch.channel.as_deref());
+        let target = mirror_auth::resolve(&self.client, mirror, &patch.filename, &variables, self.sso_token.as_deref()).await?;
+        let response = send_to_mirror(&target, target.get(&self.client), self.stall_timeout).await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed(format!("HTTP error: {}", response.status())));
+        }
+        
+        let mut hasher = Sha256::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = next_chunk(&mut stream, self.stall_timeout).await? {
+            hasher.update(&chunk);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+    
     pub async fn verify_checksum(&self, file_path: &Path, expected: &str) -> Result<bool> {
         if !self.config.patcher.verify_checksums {
             return Ok(true);
@@ -162,8 +463,197 @@ impl Downloader {
     }
 }
 
-#[derive(Debug, Clone)]
+pub(crate) fn stall_timeout(config: &Config) -> Duration {
+    Duration::from_secs(config.patcher.download_stall_timeout.unwrap_or(DEFAULT_STALL_TIMEOUT_SECS))
+}
+
+// Sends a request, treating a server that does not answer within the stall timeout as stalled
+pub(crate) async fn send_watched(request: RequestBuilder, stall_timeout: Duration) -> Result<Response> {
+    match tokio::time::timeout(stall_timeout, request.send()).await {
+        Ok(response) => Ok(response?),
+        Err(_) => Err(Error::Stalled(format!("no response for {}s", stall_timeout.as_secs()))),
+    }
+}
+
+// `send_watched` for a mirror file, refusing answers from a server that does not match the mirror's pins
+pub(crate) async fn send_to_mirror(target: &MirrorRequest, request: RequestBuilder, stall_timeout: Duration) -> Result<Response> {
+    let response = send_watched(request, stall_timeout).await?;
+    target.check_pin(&response)?;
+    Ok(response)
+}
+
+// Next piece of a response body; a silence longer than the stall timeout aborts the transfer
+pub(crate) async fn next_chunk<S>(stream: &mut S, stall_timeout: Duration) -> Result<Option<Bytes>>
+where
+    S: Stream<Item = reqwest::Result<Bytes>> + Unpin,
+{
+    match tokio::time::timeout(stall_timeout, stream.next()).await {
+        Ok(Some(chunk)) => Ok(Some(chunk?)),
+        Ok(None) => Ok(None),
+        Err(_) => Err(Error::Stalled(format!("no data for {}s", stall_timeout.as_secs()))),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PatchInfo {
+    #[serde(default)]
+    pub id: Option<String>,
+    // Position in a numbered list (`12 patch.thor`); once applied, entries up to it are not offered again
+    #[serde(default)]
+    pub index: Option<u64>,
     pub filename: String,
+    // Hex SHA-256 of the patch file
+    #[serde(default, rename = "sha256")]
     pub checksum: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    // Patches without a channel are served to every channel
+    #[serde(default)]
+    pub channel: Option<String>,
+    // GRF the patch is merged into, relative to the game directory; defaults to the patcher's `grf_targets` and
+    // `target_grf`
+    #[serde(default)]
+    pub target: Option<String>,
+    // Extracted as loose files below the game directory instead of merged into a GRF, for client executables,
+    // DLLs and System/ files
+    #[serde(default)]
+    pub extract: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub min_launcher_version: Option<String>,
+    // Optional patches can be skipped by the player and are applied after all required ones
+    #[serde(default)]
+    pub optional: bool,
+    // Percentage of installs that get the patch yet; see `rollout`
+    #[serde(default)]
+    pub rollout: Option<f64>,
+    // Patches sharing a key reach the same installs; defaults to the patch id
+    #[serde(default)]
+    pub rollout_key: Option<String>,
+    // Alternative builds of the patch for an A/B test; installs outside every variant get the patch itself
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<PatchVariant>,
+    // Name of the variant this install was given, which replaced `filename`, `checksum` and `size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchVariant {
+    pub name: String,
+    pub filename: String,
+    #[serde(default, rename = "sha256")]
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    // Percentage of installs that get this variant
+    pub weight: f64,
+}
+
+impl PatchInfo {
+    // Identity for resume state and history; plain-text lists only have the file name
+    pub fn id(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.filename)
+    }
+    
+    pub fn rollout_key(&self) -> &str {
+        self.rollout_key.as_deref().unwrap_or_else(|| self.id())
+    }
+    
+    pub fn applies_to_channel(&self, channel: &str) -> bool {
+        self.channel.as_deref().map(|c| c.eq_ignore_ascii_case(channel)).unwrap_or(true)
+    }
+    
+    pub fn requires_newer_launcher(&self, launcher_version: &str) -> bool {
+        self.min_launcher_version
+            .as_deref()
+            .map(|min| version_parts(launcher_version) < version_parts(min))
+            .unwrap_or(false)
+    }
+}
+
+// "1.2.10" > "1.2.9"; a leading `v` and pre-release suffixes are ignored
+fn version_parts(version: &str) -> Vec<u64> {
+    let mut parts: Vec<u64> = version
+        .trim()
+        .trim_start_matches(['v', 'V'])
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    while parts.len() > 1 && parts.last() == Some(&0) {
+        parts.pop();
+    }
+    parts
+}
+
+// `{"patches": [...]}` or a bare array, in application order
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatchManifest {
+    Wrapped { patches: Vec<PatchInfo> },
+    List(Vec<PatchInfo>),
+}
+
+// Thor and NeoCube lists number their entries (`12 patch.thor`) and use `//` comments
+fn parse_plist(content: &str) -> Vec<PatchInfo> {
+    let mut patches = Vec::new();
+    
+    for line in content.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() || line.starts_with("//") || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        
+        let mut parts = line.split_whitespace();
+        let (index, filename) = match (parts.next(), parts.next()) {
+            (Some(index), Some(filename)) if index.chars().all(|c| c.is_ascii_digit()) => (index.parse().ok(), filename),
+            (Some(filename), None) => (None, filename),
+            _ => {
+                warn!("Skipping unrecognized plist line: {}", line);
+                continue;
+            }
+        };
+        
+        // Loose files and `*` deletion markers have no GRF equivalent
+        let extension = filename.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+        if sanitize_entry_path(filename).is_err() || !["thor", "gpf", "rgz", "beam", "beamd"].contains(&extension.as_str()) {
+            warn!("Skipping plist entry that is not a patch archive: {}", filename);
+            continue;
+        }
+        patches.push(PatchInfo { index, filename: filename.to_string(), ..Default::default() });
+    }
+    
+    patches
+}
+
+// Also used by tools that write manifests, to check them the way the launcher will read them
+pub fn parse_patch_manifest(content: &str) -> Result<Vec<PatchInfo>> {
+    let manifest: PatchManifest = serde_json::from_str(content)
+        .map_err(|e| Error::DownloadFailed(format!("Invalid JSON patch manifest: {}", e)))?;
+    let patches = match manifest {
+        PatchManifest::Wrapped { patches } | PatchManifest::List(patches) => patches,
+    };
+    
+    // File names end up below the temp directory and targets below the game directory
+    for patch in &patches {
+        if sanitize_entry_path(&patch.filename).is_err() || patch.target.as_deref().is_some_and(|t| sanitize_entry_path(t).is_err()) {
+            return Err(Error::DownloadFailed(format!("Invalid patch file name in manifest: {:?}", patch.filename)));
+        }
+        if patch.rollout.is_some_and(|rollout| !(0.0..=100.0).contains(&rollout)) {
+            return Err(Error::DownloadFailed(format!("Rollout of {} must be between 0 and 100", patch.id())));
+        }
+        if patch.variants.iter().any(|variant| sanitize_entry_path(&variant.filename).is_err() || variant.weight < 0.0)
+            || patch.variants.iter().map(|variant| variant.weight).sum::<f64>() > 100.0
+        {
+            return Err(Error::DownloadFailed(format!(
+                "Variants of {} need valid file names and weights adding up to at most 100",
+                patch.id()
+            )));
+        }
+    }
+    Ok(patches)
 }
@@ -20,6 +20,9 @@ pub enum Error {
     #[error("Download failed: {0}")]
     DownloadFailed(String),
     
+    #[error("Connection stalled: {0}")]
+    Stalled(String),
+    
     #[error("Patch failed: {0}")]
     PatchFailed(String),
     
@@ -31,6 +34,42 @@ pub enum Error {
     
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
+    
+    #[error("Migration failed: {0}")]
+    MigrationFailed(String),
+    
+    #[error("Packaging failed: {0}")]
+    PackagingFailed(String),
+    
+    #[error("Not enough disk space: {0}")]
+    LowDiskSpace(String),
+    
+    #[error("Play time restricted: {0}")]
+    PlayTimeRestricted(String),
+    
+    #[error("Locked by kiosk mode: {0}")]
+    KioskLocked(String),
+    
+    #[error("Missing dependencies: {0}")]
+    MissingDependencies(String),
+    
+    #[error("Scheduled event: {0}")]
+    EventWarning(String),
+    
+    #[error("Local changes would be overwritten: {0}")]
+    Conflicts(String),
+    
+    #[error("Patch signature rejected: {0}")]
+    Signature(String),
+    
+    #[error("Game files need repair: {0}")]
+    DamagedFiles(String),
+    
+    #[error("Mirror certificate rejected: {0}")]
+    CertificatePin(String),
+    
+    #[error("Operation cancelled")]
+    Cancelled,
 }
 
 impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
@@ -39,4 +78,65 @@ impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
     }
 }
 
+impl Error {
+    // Failures worth retrying the patch run for; everything else needs user action
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Network(_) | Error::DownloadFailed(_) | Error::Stalled(_) => true,
+            Error::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+    
+    // Stable identifier the UI keys recovery actions on; see `recovery::recovery_actions`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io(e) if is_disk_full(e) => "disk_full",
+            Error::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => "permission_denied",
+            Error::Io(_) => "io",
+            Error::Format(_) => "corrupt_archive",
+            Error::Network(_) => "network",
+            Error::Config(_) | Error::InvalidConfig(_) => "invalid_config",
+            Error::SelfUpdate(_) | Error::UpdateFailed(_) => "update_failed",
+            Error::DownloadFailed(_) => "download_failed",
+            Error::Stalled(_) => "connection_stalled",
+            Error::PatchFailed(_) => "patch_failed",
+            Error::AuthFailed(_) => "auth_failed",
+            Error::MigrationFailed(_) => "migration_failed",
+            Error::PackagingFailed(_) => "packaging_failed",
+            Error::LowDiskSpace(_) => "low_disk_space",
+            Error::PlayTimeRestricted(_) => "play_time_restricted",
+            Error::KioskLocked(_) => "kiosk_locked",
+            Error::MissingDependencies(_) => "missing_dependencies",
+            Error::EventWarning(_) => "event_warning",
+            Error::Conflicts(_) => "file_conflicts",
+            Error::Signature(_) => "invalid_signature",
+            Error::DamagedFiles(_) => "damaged_files",
+            Error::CertificatePin(_) => "certificate_pin_mismatch",
+            Error::Cancelled => "cancelled",
+        }
+    }
+}
+
+// ENOSPC on unix, ERROR_HANDLE_DISK_FULL / ERROR_DISK_FULL on Windows
+fn is_disk_full(e: &std::io::Error) -> bool {
+    if e.kind() == std::io::ErrorKind::StorageFull {
+        return true;
+    }
+    match e.raw_os_error() {
+        #[cfg(unix)]
+        Some(28) => true,
+        #[cfg(windows)]
+        Some(39) | Some(112) => true,
+        _ => false,
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
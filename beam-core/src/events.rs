@@ -0,0 +1,370 @@
+use crate::config::HttpConfig;
+use crate::{Config, Error, Result};
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+const CALENDAR_REFRESH_SECS: u64 = 600;
+// Countdowns show minutes, so a few seconds of lag is invisible
+const NOTICE_TICK_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventCalendar {
+    // IANA name (e.g. "Europe/Berlin"); times in the feed are wall-clock times in this zone
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    pub events: Vec<EventDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventDefinition {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    pub schedule: EventSchedule,
+    // Shows a banner from this many minutes before the start until the end
+    #[serde(default)]
+    pub banner_minutes: Option<i64>,
+    // Banner text, `{countdown}` being the time until the start, or until the end once running
+    #[serde(default)]
+    pub banner: Option<String>,
+    // Launching while the event runs asks the player to confirm this first, e.g. during maintenance
+    #[serde(default)]
+    pub launch_warning: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventSchedule {
+    Once {
+        start: String,
+        #[serde(default)]
+        end: Option<String>,
+        #[serde(default)]
+        duration_minutes: Option<i64>,
+    },
+    Daily {
+        start: String,
+        duration_minutes: i64,
+    },
+    Weekly {
+        days: Vec<String>,
+        start: String,
+        duration_minutes: i64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventStatus {
+    pub id: Option<String>,
+    pub name: String,
+    pub category: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub active: bool,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    // Seconds until the event starts, or until it ends while active
+    pub seconds_remaining: i64,
+    pub countdown: String,
+}
+
+// A banner or launch warning the launcher shows right now
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventNotice {
+    pub id: Option<String>,
+    pub name: String,
+    pub active: bool,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub countdown: String,
+    // None for events that only warn on launch
+    pub banner: Option<String>,
+    // Set while the event runs and launching should be confirmed
+    pub launch_warning: Option<String>,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+impl EventCalendar {
+    pub async fn fetch(http: &HttpConfig, url: &str) -> Result<Self> {
+        info!("Fetching event calendar from: {}", url);
+        
+        let response = crate::http::client(http)?.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed(format!(
+                "Failed to fetch event calendar: HTTP {}",
+                response.status()
+            )));
+        }
+        
+        Ok(response.json().await?)
+    }
+    
+    pub fn upcoming(&self) -> Vec<EventStatus> {
+        self.upcoming_at(Utc::now())
+    }
+    
+    // Active events first, then upcoming ones by start time; finished one-off events are dropped
+    pub fn upcoming_at(&self, now: DateTime<Utc>) -> Vec<EventStatus> {
+        let mut statuses: Vec<EventStatus> = self
+            .events
+            .iter()
+            .filter_map(|event| match self.next_occurrence(event, now) {
+                Ok(occurrence) => occurrence.map(|(start, end)| EventStatus::new(event, start, end, now)),
+                Err(e) => {
+                    warn!("Skipping event {}: {}", event.name, e);
+                    None
+                }
+            })
+            .collect();
+        
+        statuses.sort_by(|a, b| b.active.cmp(&a.active).then(a.starts_at.cmp(&b.starts_at)));
+        statuses
+    }
+    
+    pub fn notices(&self) -> Vec<EventNotice> {
+        self.notices_at(Utc::now())
+    }
+    
+    // Events inside their banner period or running with a launch warning, in the order of `upcoming_at`
+    pub fn notices_at(&self, now: DateTime<Utc>) -> Vec<EventNotice> {
+        let mut notices: Vec<EventNotice> = self
+            .events
+            .iter()
+            .filter(|event| event.banner_minutes.is_some() || event.launch_warning.is_some())
+            .filter_map(|event| {
+                let (start, end) = self.next_occurrence(event, now).ok().flatten()?;
+                EventNotice::new(event, EventStatus::new(event, start, end, now), now)
+            })
+            .collect();
+        
+        notices.sort_by(|a, b| b.active.cmp(&a.active).then(a.starts_at.cmp(&b.starts_at)));
+        notices
+    }
+    
+    fn next_occurrence(&self, event: &EventDefinition, now: DateTime<Utc>) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        let tz = parse_timezone(event.timezone.as_deref().unwrap_or(&self.timezone))?;
+        
+        match &event.schedule {
+            EventSchedule::Once { start, end, duration_minutes } => {
+                let start = parse_datetime(start, tz)?;
+                let end = match (end, duration_minutes) {
+                    (Some(end), _) => parse_datetime(end, tz)?,
+                    (None, Some(minutes)) => start + Duration::minutes(*minutes),
+                    (None, None) => start,
+                };
+                Ok((end > now).then_some((start, end)))
+            }
+            EventSchedule::Daily { start, duration_minutes } => {
+                Ok(next_recurring(tz, now, parse_time(start)?, *duration_minutes, |_| true))
+            }
+            EventSchedule::Weekly { days, start, duration_minutes } => {
+                let weekdays = days
+                    .iter()
+                    .map(|day| day.parse::<Weekday>().map_err(|_| Error::InvalidConfig(format!("Invalid weekday: {}", day))))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(next_recurring(tz, now, parse_time(start)?, *duration_minutes, |date| {
+                    weekdays.contains(&date.weekday())
+                }))
+            }
+        }
+    }
+}
+
+impl EventStatus {
+    fn new(event: &EventDefinition, start: DateTime<Utc>, end: DateTime<Utc>, now: DateTime<Utc>) -> Self {
+        let active = start <= now;
+        let seconds_remaining = if active { (end - now).num_seconds() } else { (start - now).num_seconds() };
+        
+        EventStatus {
+            id: event.id.clone(),
+            name: event.name.clone(),
+            category: event.category.clone(),
+            description: event.description.clone(),
+            url: event.url.clone(),
+            active,
+            starts_at: start,
+            ends_at: end,
+            seconds_remaining,
+            countdown: format_countdown(seconds_remaining),
+        }
+    }
+}
+
+impl EventNotice {
+    fn new(event: &EventDefinition, status: EventStatus, now: DateTime<Utc>) -> Option<Self> {
+        let banner = event
+            .banner_minutes
+            .filter(|minutes| status.active || status.starts_at - Duration::minutes(*minutes) <= now)
+            .map(|_| match &event.banner {
+                Some(text) => text.replace("{countdown}", &status.countdown),
+                None if status.active => format!("{} ends in {}", status.name, status.countdown),
+                None => format!("{} starts in {}", status.name, status.countdown),
+            });
+        let launch_warning = event.launch_warning.clone().filter(|_| status.active);
+        if banner.is_none() && launch_warning.is_none() {
+            return None;
+        }
+        
+        Some(EventNotice {
+            id: status.id,
+            name: status.name,
+            active: status.active,
+            starts_at: status.starts_at,
+            ends_at: status.ends_at,
+            countdown: status.countdown,
+            banner,
+            launch_warning,
+        })
+    }
+}
+
+// Launching is refused while a running event carries a launch warning, until the player confirms it
+pub fn check_launch(notices: &[EventNotice]) -> Result<()> {
+    let warnings: Vec<&str> = notices.iter().filter_map(|notice| notice.launch_warning.as_deref()).collect();
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    Err(Error::EventWarning(warnings.join("\n")))
+}
+
+// Keeps the calendar fresh and re-evaluates notices every few seconds, so banner countdowns and launch
+// warnings come from the backend instead of timers in the page. Reports only changes.
+pub struct NoticeWatcher {
+    http: HttpConfig,
+    url: String,
+    calendar: Option<EventCalendar>,
+    fetched_at: Option<std::time::Instant>,
+    last: Option<Vec<EventNotice>>,
+}
+
+impl NoticeWatcher {
+    // None without `ui.events_url`
+    pub fn new(config: &Config) -> Option<Self> {
+        Some(NoticeWatcher {
+            http: config.http.clone(),
+            url: config.ui.events_url.clone()?,
+            calendar: None,
+            fetched_at: None,
+            last: None,
+        })
+    }
+    
+    // The notices of the latest poll, empty before the first one
+    pub fn last(&self) -> &[EventNotice] {
+        self.last.as_deref().unwrap_or_default()
+    }
+    
+    // Refetches the calendar when it is older than the refresh interval, keeping the previous one on failure,
+    // and returns the notices when they differ from the previous poll
+    pub async fn poll(&mut self) -> Option<Vec<EventNotice>> {
+        let stale = self
+            .fetched_at
+            .is_none_or(|at| at.elapsed() >= std::time::Duration::from_secs(CALENDAR_REFRESH_SECS));
+        if stale {
+            match EventCalendar::fetch(&self.http, &self.url).await {
+                Ok(calendar) => self.calendar = Some(calendar),
+                Err(e) => warn!("Failed to refresh the event calendar: {}", e),
+            }
+            self.fetched_at = Some(std::time::Instant::now());
+        }
+        
+        let notices = self.calendar.as_ref().map(EventCalendar::notices).unwrap_or_default();
+        if self.last.as_ref() == Some(&notices) {
+            return None;
+        }
+        self.last = Some(notices.clone());
+        Some(notices)
+    }
+    
+    // Polls until the returned future is dropped, calling `on_change` with the first result and every change
+    pub async fn run<F: FnMut(&[EventNotice])>(mut self, mut on_change: F) {
+        loop {
+            if let Some(notices) = self.poll().await {
+                on_change(&notices);
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(NOTICE_TICK_SECS)).await;
+        }
+    }
+}
+
+// Walks local dates from a week back (to catch an occurrence still running) to a week ahead
+fn next_recurring<F>(
+    tz: Tz,
+    now: DateTime<Utc>,
+    start_time: NaiveTime,
+    duration_minutes: i64,
+    matches: F,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)>
+where
+    F: Fn(NaiveDate) -> bool,
+{
+    let today = now.with_timezone(&tz).date_naive();
+    
+    (-7..=7)
+        .map(|offset| today + Duration::days(offset))
+        .filter(|date| matches(*date))
+        .filter_map(|date| resolve_local(tz, date.and_time(start_time)))
+        .map(|start| (start, start + Duration::minutes(duration_minutes)))
+        .find(|(_, end)| *end > now)
+}
+
+// Times that fall into a DST gap are shifted forward an hour, ambiguous ones take the first instance
+pub(crate) fn resolve_local(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => Some(dt.with_timezone(&Utc)),
+        LocalResult::None => tz
+            .from_local_datetime(&(local + Duration::hours(1)))
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc)),
+    }
+}
+
+pub(crate) fn parse_timezone(name: &str) -> Result<Tz> {
+    name.parse::<Tz>()
+        .map_err(|_| Error::InvalidConfig(format!("Unknown timezone: {}", name)))
+}
+
+pub(crate) fn parse_time(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M:%S"))
+        .map_err(|_| Error::InvalidConfig(format!("Invalid time (expected HH:MM): {}", value)))
+}
+
+// RFC 3339 timestamps carry their own offset; anything else is wall-clock time in `tz`
+fn parse_datetime(value: &str, tz: Tz) -> Result<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    
+    let local = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M"))
+        .map_err(|_| Error::InvalidConfig(format!("Invalid date/time: {}", value)))?;
+    
+    resolve_local(tz, local).ok_or_else(|| Error::InvalidConfig(format!("Invalid local time: {}", value)))
+}
+
+// "2d 4h", "2h 13m", "13m", or "<1m"; only the two largest units are shown
+pub fn format_countdown(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    let (days, hours, minutes) = (minutes / 1440, (minutes / 60) % 24, minutes % 60);
+    
+    match (days, hours, minutes) {
+        (0, 0, 0) => "<1m".to_string(),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
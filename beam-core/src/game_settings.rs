@@ -1,5 +1,8 @@
-use crate::Result;
+use crate::{client_info, multi_user, Config, Error, Result};
+use beam_formats::text::{self, TextEncoding};
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::{info, warn};
@@ -29,26 +32,167 @@ impl Default for GameSettings {
     }
 }
 
+const OPTION_INFO_PATH: &str = "SaveData/OptionInfo.lua";
+// Shared by every Ragnarok client on the machine, so only our own values are ever removed from it
+#[cfg(target_os = "windows")]
+const REGISTRY_KEY: &str = r"Software\Gravity Soft\Ragnarok";
+#[cfg(target_os = "windows")]
+const REGISTRY_VALUES: &[&str] = &["Width", "Height", "Fullscreen", "SoundVolume", "BGMVolume"];
+// Clients linked from 2015 on keep their options in SaveData/OptionInfo.lua instead of DATA.INI
+const OPTION_INFO_SINCE: (i32, u32, u32) = (2015, 1, 1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingsBackend {
+    DataIni,
+    OptionInfo,
+}
+
+impl SettingsBackend {
+    fn file(&self) -> &'static str {
+        match self {
+            SettingsBackend::DataIni => "DATA.INI",
+            SettingsBackend::OptionInfo => OPTION_INFO_PATH,
+        }
+    }
+    
+    // Prefix identifying the line that holds `key`, and the full line for a new value
+    fn line(&self, key: &str, value: &str) -> (String, String) {
+        match self {
+            SettingsBackend::DataIni => (format!("{}=", key), format!("{}={}", key, value)),
+            SettingsBackend::OptionInfo => (
+                format!("OptionInfoList[\"{}\"]", key),
+                format!("OptionInfoList[\"{}\"] = {}", key, value),
+            ),
+        }
+    }
+    
+    fn parse_line<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        match self {
+            SettingsBackend::DataIni => line.split_once('='),
+            SettingsBackend::OptionInfo => {
+                let (key, rest) = line.trim().strip_prefix("OptionInfoList[\"")?.split_once("\"]")?;
+                let value = rest.trim_start().strip_prefix('=')?;
+                Some((key, value.trim().trim_end_matches(';')))
+            }
+        }
+    }
+}
+
+// `app.settings_backend` if set; otherwise whichever settings file the client already wrote, and for a fresh
+// install the client's build date
+pub fn select_backend(config: &Config, build_date: Option<DateTime<Utc>>) -> SettingsBackend {
+    if let Some(backend) = config.app.settings_backend {
+        return backend;
+    }
+    let Some(game_dir) = config.app.game_directory.as_ref().map(Path::new) else {
+        return SettingsBackend::DataIni;
+    };
+    if game_dir.join(OPTION_INFO_PATH).exists() {
+        return SettingsBackend::OptionInfo;
+    }
+    if game_dir.join("DATA.INI").exists() {
+        return SettingsBackend::DataIni;
+    }
+    let (year, month, day) = OPTION_INFO_SINCE;
+    match (build_date, Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).single()) {
+        (Some(date), Some(since)) if date >= since => SettingsBackend::OptionInfo,
+        _ => SettingsBackend::DataIni,
+    }
+}
+
 pub struct GameSettingsManager {
     game_directory: PathBuf,
+    user_settings_dir: Option<PathBuf>,
+    backend: SettingsBackend,
+    // Kiosk installs keep the operator's settings
+    locked: bool,
 }
 
 impl GameSettingsManager {
     pub fn new<P: AsRef<Path>>(game_directory: P) -> Self {
         GameSettingsManager {
             game_directory: game_directory.as_ref().to_path_buf(),
+            user_settings_dir: None,
+            backend: SettingsBackend::DataIni,
+            locked: false,
+        }
+    }
+    
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let game_dir = config.app.game_directory.as_ref()
+            .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?;
+        let mut manager = Self::new(game_dir);
+        manager.locked = config.app.kiosk;
+        manager.backend = select_backend(config, client_info::client_build_date(Path::new(game_dir), &config.app.client_exe));
+        
+        match config.multi_user() {
+            Some(multi_user) => {
+                let server_name = config.app.server_name.as_deref().unwrap_or(&config.app.name);
+                Ok(manager.with_user_settings_dir(multi_user::user_settings_dir(multi_user, server_name)?))
+            }
+            None => Ok(manager),
+        }
+    }
+    
+    // Keeps DATA.INI in a per-user folder instead of the shared game directory
+    pub fn with_user_settings_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.user_settings_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+    
+    pub fn backend(&self) -> SettingsBackend {
+        self.backend
+    }
+    
+    // Per-user copies sit flat in the user's folder, whichever backend
+    fn settings_path(&self) -> PathBuf {
+        match &self.user_settings_dir {
+            Some(dir) => dir.join(Path::new(self.backend.file()).file_name().unwrap_or_default()),
+            None => self.game_directory.join(self.backend.file()),
+        }
+    }
+    
+    // Makes the per-user settings file visible to the client: passed via `launch_arg` if the client supports one,
+    // otherwise copied over the shared file right before launch. Returns extra launch arguments.
+    pub fn prepare_launch(&self, launch_arg: Option<&str>) -> Result<Vec<OsString>> {
+        if self.user_settings_dir.is_none() {
+            return Ok(Vec::new());
+        }
+        let user_ini = self.settings_path();
+        
+        if !user_ini.exists() {
+            return Ok(Vec::new());
+        }
+        
+        if let Some(arg) = launch_arg {
+            return Ok(vec![OsString::from(arg), user_ini.into_os_string()]);
+        }
+        
+        let shared = self.game_directory.join(self.backend.file());
+        if let Some(parent) = shared.parent() {
+            fs::create_dir_all(parent)?;
         }
+        fs::copy(&user_ini, shared)?;
+        info!("Staged per-user {} from {:?}", self.backend.file(), user_ini);
+        Ok(Vec::new())
     }
     
     pub fn apply_settings(&self, settings: &GameSettings) -> Result<()> {
-        info!("Applying game settings to {:?}", self.game_directory);
+        if self.locked {
+            return Err(Error::KioskLocked("Changing game settings is disabled on this installation".to_string()));
+        }
+        let data_ini_path = self.settings_path();
+        info!("Applying game settings to {:?}", data_ini_path);
         
-        let data_ini_path = self.game_directory.join("DATA.INI");
+        if let Some(dir) = data_ini_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
         
         if data_ini_path.exists() {
-            self.modify_data_ini(&data_ini_path, settings)?;
+            self.modify_settings_file(&data_ini_path, settings)?;
         } else {
-            self.create_data_ini(&data_ini_path, settings)?;
+            self.create_settings_file(&data_ini_path, settings)?;
         }
         
         #[cfg(target_os = "windows")]
@@ -57,26 +201,63 @@ impl GameSettingsManager {
         Ok(())
     }
     
-    fn modify_data_ini(&self, path: &Path, settings: &GameSettings) -> Result<()> {
-        let content = fs::read_to_string(path)?;
-        let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+    // DATA.INI is usually ANSI (CP949 on Korean clients) and may hold paths in any code page, so lines are edited
+    // as raw bytes and everything but the touched values is written back unchanged
+    fn modify_settings_file(&self, path: &Path, settings: &GameSettings) -> Result<()> {
+        let content = fs::read(path)?;
+        let encoding = text::decode_text(&content, usize::MAX).encoding;
+        let utf16 = matches!(encoding, TextEncoding::Utf16Le | TextEncoding::Utf16Be);
+        let content = if utf16 {
+            text::decode_text(&content, usize::MAX).text.into_bytes()
+        } else {
+            content
+        };
+        
+        let crlf = content.windows(2).any(|pair| pair == b"\r\n");
+        let mut lines: Vec<Vec<u8>> = content.split(|&b| b == b'\n').map(|line| line.to_vec()).collect();
+        if lines.last().map(|line| line.is_empty()).unwrap_or(false) {
+            lines.pop();
+        }
         
         self.update_ini_value(&mut lines, "WIDTH", &settings.resolution_width.to_string());
         self.update_ini_value(&mut lines, "HEIGHT", &settings.resolution_height.to_string());
-        self.update_ini_value(&mut lines, "SCREENMODE", if settings.fullscreen { "1" } else { "0" });
+        if self.backend == SettingsBackend::DataIni {
+            self.update_ini_value(&mut lines, "SCREENMODE", if settings.fullscreen { "1" } else { "0" });
+        }
         self.update_ini_value(&mut lines, "SOUNDVOLUME", if settings.sound_enabled { "100" } else { "0" });
         self.update_ini_value(&mut lines, "BGMVOLUME", if settings.bgm_enabled { "100" } else { "0" });
         self.update_ini_value(&mut lines, "ISFULLSCREENMODE", if settings.fullscreen { "1" } else { "0" });
         self.update_ini_value(&mut lines, "MOUSEEXCLUSIVE", if settings.mouse_freedom { "0" } else { "1" });
         
-        let new_content = lines.join("\n");
-        fs::write(path, new_content)?;
+        for line in lines.iter_mut() {
+            if crlf && !line.ends_with(b"\r") {
+                line.push(b'\r');
+            }
+            line.push(b'\n');
+        }
+        let new_content = lines.concat();
+        
+        if utf16 {
+            let text = String::from_utf8_lossy(&new_content);
+            let mut encoded = Vec::with_capacity(text.len() * 2 + 2);
+            let big_endian = encoding == TextEncoding::Utf16Be;
+            encoded.extend_from_slice(if big_endian { &[0xFE, 0xFF] } else { &[0xFF, 0xFE] });
+            for unit in text.encode_utf16() {
+                encoded.extend_from_slice(&if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() });
+            }
+            fs::write(path, encoded)?;
+        } else {
+            fs::write(path, new_content)?;
+        }
         
-        info!("DATA.INI updated successfully");
+        info!("{} updated successfully", self.backend.file());
         Ok(())
     }
     
-    fn create_data_ini(&self, path: &Path, settings: &GameSettings) -> Result<()> {
+    fn create_settings_file(&self, path: &Path, settings: &GameSettings) -> Result<()> {
+        if self.backend == SettingsBackend::OptionInfo {
+            return self.create_option_info(path, settings);
+        }
         let content = format!(
             "[SETTING]\n\
              WIDTH={}\n\
@@ -103,17 +284,38 @@ impl GameSettingsManager {
         Ok(())
     }
     
-    fn update_ini_value(&self, lines: &mut Vec<String>, key: &str, value: &str) {
-        let key_prefix = format!("{}=", key);
+    fn create_option_info(&self, path: &Path, settings: &GameSettings) -> Result<()> {
+        let mut content = String::from("OptionInfoList = {}\n");
+        let values = [
+            ("WIDTH", settings.resolution_width.to_string()),
+            ("HEIGHT", settings.resolution_height.to_string()),
+            ("ISFULLSCREENMODE", (if settings.fullscreen { "1" } else { "0" }).to_string()),
+            ("SOUNDVOLUME", (if settings.sound_enabled { "100" } else { "0" }).to_string()),
+            ("BGMVOLUME", (if settings.bgm_enabled { "100" } else { "0" }).to_string()),
+            ("MOUSEEXCLUSIVE", (if settings.mouse_freedom { "0" } else { "1" }).to_string()),
+        ];
+        for (key, value) in values {
+            content.push_str(&self.backend.line(key, &value).1);
+            content.push('\n');
+        }
+        
+        fs::write(path, content)?;
+        info!("OptionInfo.lua created successfully");
+        Ok(())
+    }
+    
+    fn update_ini_value(&self, lines: &mut Vec<Vec<u8>>, key: &str, value: &str) {
+        let (key_prefix, new_line) = self.backend.line(key, value);
+        let new_line = new_line.into_bytes();
         
         for line in lines.iter_mut() {
-            if line.starts_with(&key_prefix) {
-                *line = format!("{}={}", key, value);
+            if line.starts_with(key_prefix.as_bytes()) {
+                *line = new_line;
                 return;
             }
         }
         
-        lines.push(format!("{}={}", key, value));
+        lines.push(new_line);
     }
     
     #[cfg(target_os = "windows")]
@@ -123,7 +325,7 @@ impl GameSettingsManager {
         
         let hkcu = RegKey::predef(HKEY_CURRENT_USER);
         
-        match hkcu.create_subkey(r"Software\Gravity Soft\Ragnarok") {
+        match hkcu.create_subkey(REGISTRY_KEY) {
             Ok((key, _)) => {
                 let _ = key.set_value("Width", &settings.resolution_width);
                 let _ = key.set_value("Height", &settings.resolution_height);
@@ -147,14 +349,20 @@ impl GameSettingsManager {
     }
     
     pub fn load_settings(&self) -> Result<GameSettings> {
-        let data_ini_path = self.game_directory.join("DATA.INI");
+        let data_ini_path = self.settings_path();
         
         if data_ini_path.exists() {
-            return self.load_from_data_ini(&data_ini_path);
+            return self.load_from_settings_file(&data_ini_path);
+        }
+        
+        // A user without their own settings yet starts from the shared ones
+        let shared_ini_path = self.game_directory.join(self.backend.file());
+        if shared_ini_path.exists() {
+            return self.load_from_settings_file(&shared_ini_path);
         }
         
         let setup_exe_path = self.game_directory.join("opensetup.exe");
-        if setup_exe_path.exists() {
+        if self.backend == SettingsBackend::DataIni && setup_exe_path.exists() {
             info!("DATA.INI not found, attempting to read from opensetup.exe");
             return self.load_from_setup_exe(&setup_exe_path);
         }
@@ -162,12 +370,12 @@ impl GameSettingsManager {
         Ok(GameSettings::default())
     }
     
-    fn load_from_data_ini(&self, path: &Path) -> Result<GameSettings> {
-        let content = fs::read_to_string(path)?;
+    fn load_from_settings_file(&self, path: &Path) -> Result<GameSettings> {
+        let content = text::decode_text(&fs::read(path)?, usize::MAX).text;
         let mut settings = GameSettings::default();
         
         for line in content.lines() {
-            if let Some((key, value)) = line.split_once('=') {
+            if let Some((key, value)) = self.backend.parse_line(line) {
                 match key.trim() {
                     "WIDTH" => settings.resolution_width = value.trim().parse().unwrap_or(1920),
                     "HEIGHT" => settings.resolution_height = value.trim().parse().unwrap_or(1080),
@@ -200,7 +408,7 @@ impl GameSettingsManager {
             let hkcu = RegKey::predef(HKEY_CURRENT_USER);
             let mut settings = GameSettings::default();
             
-            if let Ok(key) = hkcu.open_subkey(r"Software\Gravity Soft\Ragnarok") {
+            if let Ok(key) = hkcu.open_subkey(REGISTRY_KEY) {
                 if let Ok(width) = key.get_value::<u32, _>("Width") {
                     settings.resolution_width = width;
                 }
@@ -231,3 +439,73 @@ impl GameSettingsManager {
         }
     }
 }
+
+// Deletes the values `apply_registry_settings` writes, and the key itself once nothing else is left in it
+#[cfg(target_os = "windows")]
+pub fn remove_registry_settings() -> usize {
+    use winreg::enums::*;
+    use winreg::RegKey;
+    
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let Ok(key) = hkcu.open_subkey_with_flags(REGISTRY_KEY, KEY_READ | KEY_WRITE) else {
+        return 0;
+    };
+    let removed = REGISTRY_VALUES.iter().filter(|name| key.delete_value(name).is_ok()).count();
+    if key.enum_values().next().is_none() && key.enum_keys().next().is_none() {
+        drop(key);
+        let _ = hkcu.delete_subkey(REGISTRY_KEY);
+    }
+    removed
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn remove_registry_settings() -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn apply(content: &[u8]) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("DATA.INI");
+        fs::write(&path, content).unwrap();
+        GameSettingsManager::new(dir.path()).apply_settings(&GameSettings::default()).unwrap();
+        fs::read(&path).unwrap()
+    }
+    
+    #[test]
+    fn cp949_data_ini_keeps_other_lines() {
+        let mut content = b"[SETTING]\r\nWIDTH=800\r\nNAME=".to_vec();
+        content.extend_from_slice(&beam_formats::path::encode_name("라그나로크"));
+        content.extend_from_slice(b"\r\nPATH=C:\\");
+        content.extend_from_slice(&beam_formats::path::encode_name("게임"));
+        content.extend_from_slice(b"\r\n");
+        
+        let written = apply(&content);
+        let lines: Vec<&[u8]> = written.split(|&b| b == b'\n').collect();
+        assert!(lines.iter().take(lines.len() - 1).all(|line| line.ends_with(b"\r")));
+        assert!(lines.contains(&&b"WIDTH=1920\r"[..]));
+        for line in content.split(|&b| b == b'\n').filter(|line| !line.starts_with(b"WIDTH=")) {
+            assert!(lines.contains(&line), "{:?} changed", String::from_utf8_lossy(line));
+        }
+    }
+    
+    #[test]
+    fn utf16_data_ini_keeps_other_lines() {
+        let encode = |text: &str| text.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>();
+        let mut content = vec![0xFF, 0xFE];
+        content.extend(encode("[SETTING]\r\nWIDTH=800\r\nNAME=라그나로크\r\n"));
+        
+        let written = apply(&content);
+        assert!(written.starts_with(&[0xFF, 0xFE]));
+        for line in ["[SETTING]\r\n", "WIDTH=1920\r\n", "NAME=라그나로크\r\n"] {
+            let line = encode(line);
+            assert!(written.windows(line.len()).any(|window| window == line));
+        }
+        let units: Vec<u16> = written[2..].chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        let text = String::from_utf16(&units).unwrap();
+        assert_eq!(text.matches('\n').count(), text.matches("\r\n").count());
+    }
+}
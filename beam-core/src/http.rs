@@ -0,0 +1,50 @@
+use crate::config::HttpConfig;
+use crate::{Error, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use reqwest::{Client, ClientBuilder};
+
+pub const DEFAULT_USER_AGENT: &str = "Beam-Patcher/1.0";
+
+// Every HTTP client the launcher creates starts here, so firewalls that filter by User-Agent and
+// access tokens sent as headers (Cloudflare Access and the like) apply to all of its requests
+pub fn client_builder(config: &HttpConfig) -> Result<ClientBuilder> {
+    Ok(Client::builder()
+        .user_agent(user_agent(config)?)
+        .default_headers(default_headers(config)?))
+}
+
+pub fn client(config: &HttpConfig) -> Result<Client> {
+    Ok(client_builder(config)?.build()?)
+}
+
+fn user_agent(config: &HttpConfig) -> Result<HeaderValue> {
+    let user_agent = config.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+    HeaderValue::from_str(user_agent)
+        .map_err(|_| Error::InvalidConfig(format!("Invalid http.user_agent `{}`", user_agent)))
+}
+
+pub fn default_headers(config: &HttpConfig) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    
+    for (name, value) in &config.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::InvalidConfig(format!("Invalid http header name `{}`", name)))?;
+        if name == USER_AGENT {
+            return Err(Error::InvalidConfig("Set the User-Agent through http.user_agent".to_string()));
+        }
+        let mut value = HeaderValue::from_str(value)
+            .map_err(|_| Error::InvalidConfig(format!("Invalid value for http header {}", name)))?;
+        // Service tokens stay out of debug logs
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    
+    Ok(headers)
+}
+
+// Run at config load so a typo shows up at startup instead of on the first request
+pub fn validate(config: &HttpConfig) -> Result<()> {
+    user_agent(config)?;
+    default_headers(config)?;
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use crate::paths;
+use crate::{Config, Result};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const INSTALL_ID_FILE: &str = "install-id";
+
+// A random UUID per user and server that staged rollouts, run reports and connection reports identify the
+// install by. It is not derived from the account, the hardware or the network, so it says nothing about the
+// player, and it is kept outside the game directory to survive reinstalling the client.
+pub fn path(config: &Config) -> PathBuf {
+    paths::data_dir(&config.app.name).join(INSTALL_ID_FILE)
+}
+
+pub fn load_or_create(config: &Config) -> Result<String> {
+    let path = path(config);
+    if let Ok(content) = std::fs::read_to_string(&path) {
+        let id = content.trim();
+        if is_valid(id) {
+            return Ok(id.to_string());
+        }
+        warn!("Replacing invalid install ID in {:?}", path);
+    }
+    
+    let id = generate()?;
+    save(&path, &id)?;
+    info!("Created install ID");
+    Ok(id)
+}
+
+// Replaces the ID, e.g. when the player wants to stop being recognized. The install also lands elsewhere in
+// staged rollouts, so patches it had already received stay but new waves decide afresh.
+pub fn regenerate(config: &Config) -> Result<String> {
+    let id = generate()?;
+    save(&path(config), &id)?;
+    info!("Regenerated install ID");
+    Ok(id)
+}
+
+fn save(path: &Path, id: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, format!("{}\n", id))?;
+    Ok(())
+}
+
+// A version 4 UUID
+fn generate() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(std::io::Error::from)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+}
+
+fn is_valid(id: &str) -> bool {
+    id.len() == 36
+        && id.char_indices().all(|(i, c)| if matches!(i, 8 | 13 | 18 | 23) { c == '-' } else { c.is_ascii_hexdigit() })
+}
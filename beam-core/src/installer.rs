@@ -0,0 +1,267 @@
+use crate::archive::{self, ExtractOptions};
+use crate::config::{InstallerConfig, MirrorConfig};
+use crate::parallel_downloader::ResumeState;
+use crate::progress::ProgressReporter;
+use crate::verifier::FileManifest;
+use crate::{paths, Config, Downloader, Error, ParallelDownloader, Result, Verifier};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// In the cache directory: which install is under way and where it goes
+const INSTALL_STATE_FILE: &str = "install.json";
+// Volumes of a split package fetched at once; each one is itself downloaded in parallel chunks
+const PARALLEL_VOLUMES: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InstallState {
+    package: String,
+    destination: String,
+}
+
+// An install the launcher was closed during, for the UI to continue on the next start
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingInstall {
+    pub package: String,
+    pub destination: String,
+    pub downloaded_bytes: u64,
+    // Only counts volumes whose download has started; zero until the first session learned a size
+    pub total_bytes: u64,
+    pub percentage: f32,
+}
+
+// Downloads the full client package and unpacks it into a new game directory
+pub struct Installer {
+    config: Config,
+    installer: InstallerConfig,
+    cache_dir: PathBuf,
+    reporter: Option<ProgressReporter>,
+    sso_token: Option<String>,
+}
+
+impl Installer {
+    pub fn new(config: Config) -> Result<Self> {
+        let installer = config
+            .installer
+            .clone()
+            .ok_or_else(|| Error::InvalidConfig("No full client package is configured".to_string()))?;
+        Ok(Installer {
+            cache_dir: paths::cache_dir(&config.app.name).join("installer"),
+            config,
+            installer,
+            reporter: None,
+            sso_token: None,
+        })
+    }
+    
+    pub fn with_progress_reporter(mut self, reporter: ProgressReporter) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+    
+    pub fn with_sso_token(mut self, token: Option<String>) -> Self {
+        self.sso_token = token;
+        self
+    }
+    
+    // `<package>.001`, `<package>.002`, ... for a split package, otherwise the package itself
+    fn volumes(&self) -> Vec<String> {
+        match self.installer.parts {
+            Some(parts) if parts > 1 => (1..=parts)
+                .map(|part| format!("{}.{:03}", self.installer.package, part))
+                .collect(),
+            _ => vec![self.installer.package.clone()],
+        }
+    }
+    
+    // A volume is downloaded under this name and renamed once complete and verified, so one found under
+    // its own name on the next run is not fetched again
+    fn partial_path(&self, volume: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.part", volume))
+    }
+    
+    fn resume_state_path(&self, volume: &str) -> PathBuf {
+        self.cache_dir.join(format!("{}.resume.json", volume))
+    }
+    
+    pub fn pending(&self) -> Option<PendingInstall> {
+        let content = fs::read_to_string(self.cache_dir.join(INSTALL_STATE_FILE)).ok()?;
+        let state: InstallState = serde_json::from_str(&content).ok()?;
+        // The server switched to another package; the partial download is useless
+        if state.package != self.installer.package {
+            return None;
+        }
+        
+        let (mut downloaded_bytes, mut total_bytes) = (0, 0);
+        for volume in self.volumes() {
+            if let Ok(metadata) = fs::metadata(self.cache_dir.join(&volume)) {
+                downloaded_bytes += metadata.len();
+                total_bytes += metadata.len();
+            } else if let Some(resume) = ResumeState::load(self.resume_state_path(&volume)) {
+                downloaded_bytes += resume.bytes_done();
+                total_bytes += resume.total_size;
+            }
+        }
+        Some(PendingInstall {
+            package: state.package,
+            destination: state.destination,
+            downloaded_bytes,
+            total_bytes,
+            percentage: if total_bytes > 0 { downloaded_bytes as f32 * 100.0 / total_bytes as f32 } else { 0.0 },
+        })
+    }
+    
+    // Picks up a partial download of the same package, whichever mirror it came from
+    pub async fn install(&self, destination: &Path) -> Result<PathBuf> {
+        self.config.ensure_unlocked("Installing the game")?;
+        fs::create_dir_all(&self.cache_dir)?;
+        let state = InstallState {
+            package: self.installer.package.clone(),
+            destination: paths::to_config_string(destination)?,
+        };
+        let content = serde_json::to_string_pretty(&state)
+            .map_err(|e| Error::DownloadFailed(format!("Failed to serialize install state: {}", e)))?;
+        fs::write(self.cache_dir.join(INSTALL_STATE_FILE), content)?;
+        
+        let volumes = self.download().await?;
+        
+        let options = ExtractOptions {
+            manifest: self.manifest().await?,
+            reporter: self.reporter.clone(),
+        };
+        let destination = destination.to_path_buf();
+        let target = destination.clone();
+        // Split volumes are read in sequence instead of being joined first, which would need the package's size again in free space
+        let report = tokio::task::spawn_blocking(move || archive::extract_parts(&volumes, &target, &options))
+            .await
+            .map_err(|e| Error::PatchFailed(format!("Extraction task failed: {}", e)))??;
+        info!("Installed {} files into {:?}", report.files, destination);
+        
+        self.clear();
+        Ok(destination)
+    }
+    
+    async fn download(&self) -> Result<Vec<PathBuf>> {
+        let downloader = ParallelDownloader::new(self.config.clone())?.with_sso_token(self.sso_token.clone());
+        let downloader = match &self.reporter {
+            Some(reporter) => downloader.with_progress_reporter(reporter.clone()),
+            None => downloader,
+        };
+        let checksums = self.checksums().await?;
+        
+        let mut mirrors = self.config.patcher.mirrors.clone();
+        mirrors.sort_by_key(|m| m.priority);
+        let volumes = self.volumes();
+        let downloads: Vec<_> = volumes
+            .iter()
+            .enumerate()
+            .map(|(index, volume)| self.download_volume(&downloader, &mirrors, index, volume, checksums.get(volume)))
+            .collect();
+        stream::iter(downloads).buffered(PARALLEL_VOLUMES).try_collect().await
+    }
+    
+    async fn download_volume(
+        &self,
+        downloader: &ParallelDownloader,
+        mirrors: &[MirrorConfig],
+        index: usize,
+        volume: &str,
+        checksum: Option<&String>,
+    ) -> Result<PathBuf> {
+        let path = self.cache_dir.join(volume);
+        if path.exists() {
+            return Ok(path);
+        }
+        
+        let partial = self.partial_path(volume);
+        // Each volume starts on a different mirror so a split package is spread across all of them; the
+        // rest are its fallback
+        let first = index % mirrors.len().max(1);
+        let mut last_error = None;
+        for mirror in mirrors[first..].iter().chain(&mirrors[..first]) {
+            let result = match downloader
+                .download_resumable(mirror, volume, &partial, &self.resume_state_path(volume))
+                .await
+            {
+                Ok(_) => verify(&partial, volume, checksum).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {
+                    fs::rename(&partial, &path)?;
+                    return Ok(path);
+                }
+                Err(e) => {
+                    warn!("Failed to download {} from mirror {}: {}", volume, mirror.name, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::DownloadFailed("All mirrors failed".to_string())))
+    }
+    
+    // Expected SHA-256 per volume
+    async fn checksums(&self) -> Result<HashMap<String, String>> {
+        let mut checksums = HashMap::new();
+        if let Some(sha256) = &self.installer.sha256 {
+            checksums.insert(self.installer.package.clone(), sha256.clone());
+        }
+        
+        if let Some(name) = &self.installer.checksums {
+            // `sha256sum` format, as `package-client` writes it
+            for line in self.fetch_text(name).await?.lines() {
+                if let Some((checksum, file)) = line.split_once(char::is_whitespace) {
+                    checksums.insert(file.trim_start_matches([' ', '*']).to_string(), checksum.to_string());
+                }
+            }
+            if let Some(volume) = self.volumes().into_iter().find(|volume| !checksums.contains_key(volume)) {
+                return Err(Error::DownloadFailed(format!("{} has no checksum for {}", name, volume)));
+            }
+        }
+        Ok(checksums)
+    }
+    
+    async fn manifest(&self) -> Result<Option<FileManifest>> {
+        let Some(name) = &self.installer.manifest else {
+            return Ok(None);
+        };
+        serde_json::from_str(&self.fetch_text(name).await?)
+            .map(Some)
+            .map_err(|e| Error::DownloadFailed(format!("Invalid package manifest {}: {}", name, e)))
+    }
+    
+    // Small files published next to the package
+    async fn fetch_text(&self, name: &str) -> Result<String> {
+        let mut downloader = Downloader::new(self.config.clone())?;
+        downloader.set_sso_token(self.sso_token.clone());
+        let path = downloader.download_file(name, &self.cache_dir.join(name)).await?;
+        let content = fs::read_to_string(&path)?;
+        let _ = fs::remove_file(&path);
+        Ok(content)
+    }
+    
+    // Drops the downloaded and partial volumes and the pending install
+    pub fn clear(&self) {
+        for volume in self.volumes() {
+            let _ = fs::remove_file(self.cache_dir.join(&volume));
+            let _ = fs::remove_file(self.partial_path(&volume));
+            let _ = fs::remove_file(self.resume_state_path(&volume));
+        }
+        let _ = fs::remove_file(self.cache_dir.join(INSTALL_STATE_FILE));
+    }
+}
+
+async fn verify(path: &Path, volume: &str, expected: Option<&String>) -> Result<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    if Verifier::compute_file_checksum(path).await?.eq_ignore_ascii_case(expected) {
+        return Ok(());
+    }
+    // Starting over is the only way out of a corrupt partial download
+    let _ = fs::remove_file(path);
+    Err(Error::DownloadFailed(format!("Checksum mismatch for {}", volume)))
+}
@@ -0,0 +1,198 @@
+use crate::config::IntegrityCheckConfig;
+use crate::verifier::{VerificationResult, Verifier};
+use crate::{Config, Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const INTEGRITY_FILE: &str = ".beam-integrity.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckReason {
+    NeverVerified,
+    Launches,
+    Days,
+    AbnormalExit,
+}
+
+impl CheckReason {
+    pub fn message(&self) -> &'static str {
+        match self {
+            CheckReason::NeverVerified => "the game files were never checked",
+            CheckReason::Launches => "scheduled check after several launches",
+            CheckReason::Days => "scheduled check",
+            CheckReason::AbnormalExit => "the game did not close normally last time",
+        }
+    }
+}
+
+// Kept in the game directory: when its files were last checked and what happened since
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntegrityState {
+    #[serde(default)]
+    pub last_verified: Option<DateTime<Utc>>,
+    // Files the last check found missing or damaged
+    #[serde(default)]
+    pub last_issues: usize,
+    #[serde(default)]
+    pub launches_since_verified: u32,
+    // The client exited with an error or crashed after the last check
+    #[serde(default)]
+    pub abnormal_exit: bool,
+}
+
+// For the "Health" line of the client status card
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityStatus {
+    pub last_verified: Option<DateTime<Utc>>,
+    // "verified 2 days ago", "never verified" or "3 damaged files"
+    pub summary: String,
+    // None until the files were checked once
+    pub healthy: Option<bool>,
+    // Why the next launch checks the files first; None when it does not
+    pub due: Option<CheckReason>,
+}
+
+impl IntegrityState {
+    pub fn path(config: &Config) -> Option<PathBuf> {
+        config.app.game_directory.as_ref().map(|dir| Path::new(dir).join(INTEGRITY_FILE))
+    }
+    
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt integrity state {:?}: {}", path, e);
+                IntegrityState::default()
+            }),
+            Err(_) => IntegrityState::default(),
+        }
+    }
+    
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::PatchFailed(format!("Failed to serialize integrity state: {}", e)))?;
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+    
+    pub fn due(&self, policy: &IntegrityCheckConfig, now: DateTime<Utc>) -> Option<CheckReason> {
+        if policy.after_abnormal_exit && self.abnormal_exit {
+            return Some(CheckReason::AbnormalExit);
+        }
+        if policy.every_launches.is_some_and(|launches| self.launches_since_verified >= launches) {
+            return Some(CheckReason::Launches);
+        }
+        let days = policy.every_days?;
+        match self.last_verified {
+            None => Some(CheckReason::NeverVerified),
+            Some(last) if now - last >= Duration::days(days as i64) => Some(CheckReason::Days),
+            Some(_) => None,
+        }
+    }
+    
+    pub fn status(&self, policy: Option<&IntegrityCheckConfig>) -> IntegrityStatus {
+        self.status_at(policy, Utc::now())
+    }
+    
+    pub fn status_at(&self, policy: Option<&IntegrityCheckConfig>, now: DateTime<Utc>) -> IntegrityStatus {
+        let summary = match self.last_verified {
+            None => "never verified".to_string(),
+            Some(_) if self.last_issues > 0 => format!("{} damaged files", self.last_issues),
+            Some(last) => format!("verified {}", ago(now - last)),
+        };
+        IntegrityStatus {
+            last_verified: self.last_verified,
+            summary,
+            healthy: self.last_verified.map(|_| self.last_issues == 0),
+            due: policy.and_then(|policy| self.due(policy, now)),
+        }
+    }
+    
+    pub fn record_verification(&mut self, result: &VerificationResult) {
+        self.last_verified = Some(Utc::now());
+        self.last_issues = result.corrupted.len() + result.missing.len();
+        self.launches_since_verified = 0;
+        self.abnormal_exit = false;
+    }
+}
+
+fn ago(elapsed: Duration) -> String {
+    match (elapsed.num_days(), elapsed.num_hours()) {
+        (0, 0) => "just now".to_string(),
+        (0, 1) => "1 hour ago".to_string(),
+        (0, hours) => format!("{} hours ago", hours),
+        (1, _) => "yesterday".to_string(),
+        (days, _) => format!("{} days ago", days),
+    }
+}
+
+// Records a finished check of the game files, quick or full
+pub fn record_verification(config: &Config, result: &VerificationResult) -> Result<()> {
+    let Some(path) = IntegrityState::path(config) else {
+        return Ok(());
+    };
+    let mut state = IntegrityState::load(&path);
+    state.record_verification(result);
+    state.save(&path)
+}
+
+// Runs the quick check when `integrity_check` says it is due and fails when files are missing or damaged. A
+// check that cannot run, e.g. without a connection, does not keep the player from playing and is tried again
+// at the next launch.
+pub async fn check_before_launch(config: &Config) -> Result<()> {
+    let (Some(policy), Some(path)) = (&config.integrity_check, IntegrityState::path(config)) else {
+        return Ok(());
+    };
+    let state = IntegrityState::load(&path);
+    let Some(reason) = state.due(policy, Utc::now()) else {
+        return Ok(());
+    };
+    
+    info!("Checking game files before launch: {}", reason.message());
+    let result = match Verifier::from_config(config.clone())?.quick_verify_game_files().await {
+        Ok(result) => result,
+        Err(e) => {
+            warn!("Skipping the scheduled file check: {}", e);
+            return Ok(());
+        }
+    };
+    record_verification(config, &result)?;
+    
+    let damaged: Vec<&str> = result.missing.iter().chain(&result.corrupted).map(String::as_str).collect();
+    if damaged.is_empty() {
+        return Ok(());
+    }
+    Err(Error::DamagedFiles(format!(
+        "{} game file(s) are missing or damaged: {}",
+        damaged.len(),
+        damaged.iter().take(5).copied().collect::<Vec<_>>().join(", ")
+    )))
+}
+
+pub fn record_launch(config: &Config) -> Result<()> {
+    update(config, |state| state.launches_since_verified = state.launches_since_verified.saturating_add(1))
+}
+
+// Called when the client process ends; a crash or error exit makes the next launch check the files
+pub fn record_exit(config: &Config, success: bool) -> Result<()> {
+    if success {
+        return Ok(());
+    }
+    warn!("The game client exited abnormally");
+    update(config, |state| state.abnormal_exit = true)
+}
+
+fn update(config: &Config, change: impl FnOnce(&mut IntegrityState)) -> Result<()> {
+    let Some(path) = IntegrityState::path(config) else {
+        return Ok(());
+    };
+    let mut state = IntegrityState::load(&path);
+    change(&mut state);
+    state.save(&path)
+}
@@ -0,0 +1,209 @@
+use crate::config::{MirrorConfig, PatchListFormat};
+use crate::legacy_skin;
+use crate::{Config, Error, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LegacyPatcher {
+    Thor,
+    NeonCube,
+}
+
+#[derive(Debug, Clone)]
+pub struct LegacyImport {
+    pub patcher: LegacyPatcher,
+    pub config: Config,
+    // Skin folder the old patcher drew its window from, relative paths resolved against the ini
+    pub skin_dir: Option<PathBuf>,
+    // Settings that could not be carried over and need a look by hand
+    pub notes: Vec<String>,
+}
+
+// Section names differ between patcher releases, so `get` looks keys up across all sections
+pub(crate) struct Ini {
+    // (section, key, value), names lowercased, in file order
+    pub(crate) entries: Vec<(String, String, String)>,
+}
+
+impl Ini {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        // Legacy configs are usually saved in the ANSI code page; only ASCII values matter here
+        Ok(Ini::parse(&String::from_utf8_lossy(&std::fs::read(path)?)))
+    }
+    
+    pub(crate) fn parse(content: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut section = String::new();
+        
+        for line in content.lines() {
+            let line = line.trim_start_matches('\u{feff}').trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_ascii_lowercase();
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            
+            let value = strip_comment(value.trim());
+            let value = value.trim_matches(|c| c == '\'' || c == '"').trim();
+            entries.push((section.clone(), key.trim().to_ascii_lowercase(), value.to_string()));
+        }
+        
+        Ini { entries }
+    }
+    
+    pub(crate) fn get(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter()
+            .filter_map(|key| self.entries.iter().find(|(_, k, v)| k == key && !v.is_empty()))
+            .map(|(_, _, value)| value.as_str())
+            .next()
+    }
+}
+
+// `key = "value" ; comment`; semicolons inside quotes are kept
+fn strip_comment(value: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in value.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (';', None) if value[..i].ends_with(char::is_whitespace) => return value[..i].trim_end(),
+            _ => {}
+        }
+    }
+    value
+}
+
+// Reads a Thor (config.ini) or NeonCube (neoncube.ini) patcher configuration into a Beam config
+pub fn import_legacy_config<P: AsRef<Path>>(path: P) -> Result<LegacyImport> {
+    let path = path.as_ref();
+    let ini = Ini::load(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    
+    let (patcher, patch_url, patch_list_url) = if let Some(site) = ini.get(&["patch_site"]) {
+        let root = site_url(site, ini.get(&["patch_port", "port"]));
+        let patch_folder = ini.get(&["patch_folder"]).unwrap_or("/patch/");
+        let patch_list = ini.get(&["patch_list"]).unwrap_or("/patch/patch.txt");
+        (LegacyPatcher::NeonCube, join_url(&root, patch_folder), join_url(&root, patch_list))
+    } else if let Some(root) = ini.get(&["rooturl", "root_url", "patchserver"]) {
+        let root = site_url(root, None);
+        let patch_folder = ini.get(&["file_url", "fileurl", "patchurl", "patchpath", "patch_folder"]).unwrap_or("data/");
+        let patch_list = ini.get(&["patchlist", "filelistname", "plist", "patch_list"]).unwrap_or("plist.txt");
+        (LegacyPatcher::Thor, join_url(&root, patch_folder), join_url(&root, patch_list))
+    } else {
+        return Err(Error::InvalidConfig(format!(
+            "{} does not look like a Thor or NeonCube patcher configuration (no RootURL or patch_site)",
+            path.display()
+        )));
+    };
+    
+    let mut notes = Vec::new();
+    let mut config = Config::default();
+    
+    if let Some(title) = ini.get(&["title", "windowtitle", "window_title", "server_name", "servername"]) {
+        config.app.name = title.to_string();
+        config.app.window_title = title.to_string();
+        config.app.server_name = Some(title.to_string());
+    }
+    if let Some(exe) = ini.get(&["clientexe", "client_exe", "exename", "exe_name", "exe"]) {
+        config.app.client_exe = exe.to_string();
+    }
+    config.app.setup_exe = ini.get(&["setupexe", "setup_exe", "setup"]).map(|s| s.to_string());
+    if !base_dir.as_os_str().is_empty() {
+        // Legacy patchers sit next to the client, so their folder is the game directory
+        config.app.game_directory = Some(base_dir.to_string_lossy().to_string());
+    }
+    
+    config.patcher.mirrors = vec![MirrorConfig {
+        name: "Primary Mirror".to_string(),
+        url: patch_url.trim_end_matches('/').to_string(),
+        priority: 1,
+        ..config.patcher.mirrors[0].clone()
+    }];
+    config.patcher.patch_list_url = patch_list_url;
+    config.patcher.patch_list_format = PatchListFormat::Plist;
+    if let Some(grf) = ini.get(&["defaultgrf", "default_grf", "grf_file", "grf", "targetgrf"]) {
+        config.patcher.target_grf = grf.to_string();
+    }
+    
+    // Neither tool had SSO, a self-updater or server pings in the same sense, so those start out disabled
+    config.sso = None;
+    config.updater = None;
+    config.server = None;
+    
+    let skin_dir = ini
+        .get(&["skin", "skindir", "skin_dir", "skinfolder"])
+        .map(|skin| base_dir.join(skin.replace('\\', "/")))
+        .filter(|dir| dir.is_dir());
+    let background = ini.get(&["background", "backgroundimage", "bg", "bgimage"]);
+    let notice_url = ini.get(&["notice_url", "noticeurl", "notice", "newsurl", "news_url"]);
+    
+    match &skin_dir {
+        Some(dir) => {
+            let skin = legacy_skin::load_legacy_skin(dir, &legacy_skin::asset_dir(dir))?;
+            notes.extend(skin.notes.iter().cloned());
+            skin.apply(&mut config.ui);
+            if let (Some(background), None) = (background, &config.ui.background) {
+                config.ui.background = Some(format!("{}/{}", legacy_skin::asset_dir(dir), background.replace('\\', "/")));
+            }
+            notes.push(format!(
+                "Copy the skin folder {} to {} next to the patcher",
+                dir.display(),
+                legacy_skin::asset_dir(dir)
+            ));
+        }
+        None => {
+            if let Some(background) = background {
+                config.ui.background = Some(base_dir.join(background.replace('\\', "/")).to_string_lossy().to_string());
+            }
+        }
+    }
+    
+    // Legacy notice pages are plain HTML, shown in the skin's notice box when there is one
+    if let Some(notice) = notice_url {
+        match config.ui.layout.notice_box.as_mut() {
+            Some(notice_box) => notice_box.url = Some(notice.to_string()),
+            None => notes.push(format!(
+                "The notice page {} is HTML; ui.news_feed_url expects the JSON news feed format",
+                notice
+            )),
+        }
+    }
+    if let Some(remote) = ini.get(&["remoteconfigfile", "remote_config"]) {
+        notes.push(format!(
+            "Settings in the remote config {} were not read; check patch_list_url and the mirror URL against it",
+            join_url(&site_url(ini.get(&["rooturl", "root_url"]).unwrap_or_default(), None), remote)
+        ));
+    }
+    if ini.get(&["backupgrf", "merge_grf", "ftp_host", "ftp"]).is_some() {
+        notes.push("FTP and GRF backup settings have no Beam equivalent and were dropped".to_string());
+    }
+    
+    info!("Imported {:?} patcher config from {:?}", patcher, path);
+    Ok(LegacyImport { patcher, config, skin_dir, notes })
+}
+
+// NeonCube stores a bare host name next to a separate port
+fn site_url(site: &str, port: Option<&str>) -> String {
+    let site = site.trim_end_matches('/');
+    let url = if site.contains("://") { site.to_string() } else { format!("http://{}", site) };
+    match port.and_then(|p| p.parse::<u16>().ok()) {
+        Some(80) | None => url,
+        Some(port) if url.starts_with("http://") && !url["http://".len()..].contains(':') => format!("{}:{}", url, port),
+        Some(_) => url,
+    }
+}
+
+fn join_url(root: &str, path: &str) -> String {
+    if path.contains("://") {
+        return path.to_string();
+    }
+    format!("{}/{}", root.trim_end_matches('/'), path.trim_start_matches('/'))
+}
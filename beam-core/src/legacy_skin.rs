@@ -0,0 +1,232 @@
+use crate::config::{ButtonPosition, CustomButton, LayoutConfig, NoticeBox, UiConfig};
+use crate::legacy_import::Ini;
+use crate::{Error, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+// Ini names tried in order before falling back to any *.ini in the skin folder
+const SKIN_INI_NAMES: &[&str] = &["skin.ini", "neoncube.ini", "style.ini", "config.ini"];
+const BACKGROUND_NAMES: &[&str] = &["bg", "background", "skin", "main"];
+const IMAGE_EXTENSIONS: &[&str] = &["bmp", "png", "jpg", "jpeg", "gif"];
+
+// Elements the Beam window draws itself; legacy positions for them cannot be carried over
+const BUILTIN_ELEMENTS: &[&str] = &["start", "play", "exit", "close", "cancel", "minimize", "min", "progress", "progressbar", "status"];
+const WINDOW_ELEMENTS: &[&str] = &["", "window", "main", "background", "bg", "skin", "style", "general", "neoncube"];
+const NOTICE_ELEMENTS: &[&str] = &["notice", "noticebox", "notice_box", "news", "browser", "html"];
+
+#[derive(Debug, Clone)]
+pub struct LegacySkin {
+    pub background: Option<String>,
+    pub layout: LayoutConfig,
+    pub buttons: Vec<CustomButton>,
+    // Parts of the skin that were skipped, for the operator to redo by hand
+    pub notes: Vec<String>,
+}
+
+impl LegacySkin {
+    pub fn apply(&self, ui: &mut UiConfig) {
+        if self.background.is_some() {
+            ui.background = self.background.clone();
+        }
+        ui.layout = self.layout.clone();
+        ui.custom_buttons = self.buttons.clone();
+    }
+}
+
+// Where the converted config expects the skin images: the folder copied below the patcher's assets
+pub fn asset_dir(skin_dir: &Path) -> String {
+    let name = skin_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "skin".to_string());
+    format!("assets/{}", name)
+}
+
+#[derive(Default)]
+struct Element {
+    x: Option<u32>,
+    y: Option<u32>,
+    width: Option<u32>,
+    height: Option<u32>,
+    image: Option<String>,
+    url: Option<String>,
+    label: Option<String>,
+}
+
+// Reads a Thor/NeonCube style skin folder. Elements are either ini sections (`[homepage]` with `x=`, `y=`)
+// or prefixed keys in one section (`homepage_x=`); images are referenced as `<asset_dir>/<file>`.
+pub fn load_legacy_skin(dir: &Path, asset_dir: &str) -> Result<LegacySkin> {
+    if !dir.is_dir() {
+        return Err(Error::InvalidConfig(format!("Skin folder {} does not exist", dir.display())));
+    }
+    
+    let mut notes = Vec::new();
+    let elements = match find_skin_ini(dir)? {
+        Some(path) => {
+            info!("Reading legacy skin layout from {:?}", path);
+            collect_elements(&Ini::load(&path)?)
+        }
+        None => {
+            notes.push(format!("No skin ini in {}; only the background was taken over", dir.display()));
+            BTreeMap::new()
+        }
+    };
+    let asset = |file: &str| format!("{}/{}", asset_dir.trim_end_matches('/'), file.replace('\\', "/"));
+    
+    let window = WINDOW_ELEMENTS.iter().find_map(|name| elements.get(*name).filter(|e| e.image.is_some() || e.width.is_some()));
+    let background_file = window
+        .and_then(|w| w.image.clone())
+        .or_else(|| find_background(dir));
+    
+    // The background image defines the window size when the ini does not
+    let image_size = background_file
+        .as_ref()
+        .and_then(|file| image::image_dimensions(dir.join(file.replace('\\', "/"))).ok());
+    let (width, height) = match (window.and_then(|w| w.width.zip(w.height)), image_size) {
+        (Some(size), _) | (None, Some(size)) => size,
+        (None, None) => {
+            notes.push("Skin window size is unknown; kept 800x600".to_string());
+            (800, 600)
+        }
+    };
+    
+    let notice_box = NOTICE_ELEMENTS.iter().find_map(|name| elements.get(*name)).and_then(|notice| {
+        Some(NoticeBox {
+            x: notice.x?,
+            y: notice.y?,
+            width: notice.width?,
+            height: notice.height?,
+            url: notice.url.clone(),
+        })
+    });
+    
+    let mut buttons = Vec::new();
+    for (name, element) in &elements {
+        if WINDOW_ELEMENTS.contains(&name.as_str()) || NOTICE_ELEMENTS.contains(&name.as_str()) {
+            continue;
+        }
+        let (Some(x), Some(y)) = (element.x, element.y) else {
+            continue;
+        };
+        if BUILTIN_ELEMENTS.contains(&name.as_str()) {
+            notes.push(format!("The {} button is drawn by Beam; its skin position ({}, {}) was not used", name, x, y));
+            continue;
+        }
+        let Some(url) = element.url.clone() else {
+            notes.push(format!("Skin button `{}` has no link and was skipped", name));
+            continue;
+        };
+        
+        if let Some(image) = &element.image {
+            if !dir.join(image.replace('\\', "/")).exists() {
+                notes.push(format!("Image {} for skin button `{}` is missing", image, name));
+            }
+        }
+        buttons.push(CustomButton {
+            label: element.label.clone().unwrap_or_else(|| title_case(name)),
+            image: element.image.as_deref().map(asset),
+            url,
+            position: ButtonPosition {
+                x: format!("{}px", x),
+                y: format!("{}px", y),
+            },
+        });
+    }
+    
+    Ok(LegacySkin {
+        background: background_file.as_deref().map(asset),
+        layout: LayoutConfig {
+            width,
+            height,
+            use_custom_layout: true,
+            notice_box,
+        },
+        buttons,
+        notes,
+    })
+}
+
+fn find_skin_ini(dir: &Path) -> Result<Option<PathBuf>> {
+    if let Some(path) = SKIN_INI_NAMES.iter().map(|name| dir.join(name)).find(|p| p.is_file()) {
+        return Ok(Some(path));
+    }
+    let mut inis: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map(|e| e.eq_ignore_ascii_case("ini")).unwrap_or(false))
+        .collect();
+    inis.sort();
+    Ok(inis.into_iter().next())
+}
+
+fn find_background(dir: &Path) -> Option<String> {
+    BACKGROUND_NAMES.iter().find_map(|name| {
+        IMAGE_EXTENSIONS
+            .iter()
+            .map(|ext| format!("{}.{}", name, ext))
+            .find(|file| dir.join(file).is_file())
+    })
+}
+
+fn collect_elements(ini: &Ini) -> BTreeMap<String, Element> {
+    let mut elements: BTreeMap<String, Element> = BTreeMap::new();
+    
+    for (section, key, value) in &ini.entries {
+        let (name, property) = match split_property(key) {
+            Some(("", property)) => (section.as_str(), property),
+            Some((prefix, property)) => (prefix, property),
+            None => continue,
+        };
+        let element = elements.entry(element_name(name)).or_default();
+        let number = value.trim().parse::<i64>().ok().map(|n| n.max(0) as u32);
+        
+        match property {
+            "x" | "left" => element.x = number,
+            "y" | "top" => element.y = number,
+            "width" | "w" => element.width = number,
+            "height" | "h" => element.height = number,
+            "image" | "img" | "normal" | "bmp" | "file" => element.image = Some(value.clone()),
+            "url" | "link" | "href" => element.url = Some(value.clone()),
+            _ => element.label = Some(value.clone()),
+        }
+    }
+    
+    elements
+}
+
+// `homepage_x` -> ("homepage", "x"); a bare `x` belongs to its section
+fn split_property(key: &str) -> Option<(&str, &str)> {
+    const PROPERTIES: &[&str] = &[
+        "x", "left", "y", "top", "width", "w", "height", "h", "image", "img", "normal", "bmp", "file", "url", "link",
+        "href", "label", "text", "caption",
+    ];
+    if PROPERTIES.contains(&key) {
+        return Some(("", key));
+    }
+    let (prefix, property) = key.rsplit_once(['_', '.'])?;
+    PROPERTIES.contains(&property).then_some((prefix, property))
+}
+
+// `btn_homepage`, `homepage_button` and `HomePageBtn` all name the same element
+fn element_name(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    let name = name
+        .trim_start_matches("button_")
+        .trim_start_matches("btn_")
+        .trim_end_matches("_button")
+        .trim_end_matches("_btn")
+        .trim_end_matches("button")
+        .trim_end_matches("btn");
+    name.trim_matches('_').to_string()
+}
+
+fn title_case(name: &str) -> String {
+    name.split(['_', ' '])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
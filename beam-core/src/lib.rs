@@ -9,15 +9,89 @@ pub mod parallel_downloader;
 pub mod game_settings;
 pub mod server_checker;
 pub mod client_checker;
+pub mod checksum_db;
+pub mod migration;
+pub mod multi_user;
+pub mod packager;
+pub mod torrent;
+pub mod news;
+pub mod events;
+pub mod patch_history;
+pub mod screenshots;
+pub mod preview;
+pub mod recovery;
+pub mod paths;
+pub mod priority;
+pub mod progress;
+pub mod mirror_auth;
+pub mod s3;
+pub mod legacy_import;
+pub mod legacy_skin;
+pub mod run_snapshot;
+pub mod patch_queue;
+pub mod characters;
+pub mod play_time;
+pub mod run_report;
+pub mod system_info;
+pub mod wrapper;
+pub mod dependencies;
+pub mod client_info;
+pub mod http;
+pub mod connection_check;
+pub mod url_template;
+pub mod mirror_selector;
+pub mod installer;
+pub mod archive;
+pub mod uninstall;
+pub mod storage;
+pub mod simulate;
+pub mod population;
+pub mod conflicts;
+pub mod integrity;
+pub mod pinning;
+pub mod install_id;
+pub mod rollout;
+pub mod preflight;
+pub mod body;
 
 pub use config::Config;
-pub use downloader::Downloader;
-pub use patcher::Patcher;
+pub use downloader::{Downloader, PatchInfo, PatchVariant};
+pub use patcher::{Patcher, PatchOutcome, PatchReport};
 pub use updater::Updater;
-pub use sso::SsoClient;
+pub use sso::{LoginStep, MfaChallenge, SsoClient, SsoSession, TotpProvisioning};
 pub use error::{Error, Result};
 pub use verifier::{Verifier, VerificationResult};
 pub use parallel_downloader::ParallelDownloader;
-pub use game_settings::{GameSettings, GameSettingsManager};
-pub use server_checker::{ServerChecker, ServerStatusResult};
-pub use client_checker::{ClientChecker, ClientStatusResult};
+pub use game_settings::{GameSettings, GameSettingsManager, SettingsBackend};
+pub use server_checker::{ServerChecker, ServerStatusResult, StatusPoller};
+pub use client_checker::{ClientChecker, ClientDiagnostics, ClientStatusResult};
+pub use checksum_db::{ChecksumDatabase, FileOrigin};
+pub use migration::{GameDirectoryMigrator, MigrationMode, MigrationProgress, MigrationReport};
+pub use packager::{ClientPackager, PackageOptions, PackageReport};
+pub use torrent::{TorrentInfo, TorrentOptions};
+pub use news::{NewsAction, NewsItem};
+pub use events::{EventCalendar, EventNotice, EventStatus, NoticeWatcher};
+pub use patch_history::{PatchHistory, PatchHistoryEntry};
+pub use population::{PopulationHistory, PopulationSample};
+pub use recovery::{RecoveryAction, RecoverySuggestion};
+pub use progress::{ProgressEvent, ProgressReporter};
+pub use legacy_import::{LegacyImport, LegacyPatcher};
+pub use legacy_skin::LegacySkin;
+pub use run_snapshot::{PatchPhase, PatchRunSnapshot};
+pub use patch_queue::{PatchQueue, QueueItem, QueueItemStatus};
+pub use characters::{Character, CharacterSelection};
+pub use play_time::{PlayTimePolicy, PlayTimeStatus};
+pub use run_report::{RunKind, RunReport};
+pub use system_info::{SettingsWarning, SystemInfo};
+pub use wrapper::{WrapperManager, WrapperState, WrapperStatus};
+pub use dependencies::{DependencyIssue, DependencyReport};
+pub use client_info::ClientInfo;
+pub use connection_check::ConnectionReport;
+pub use installer::{Installer, PendingInstall};
+pub use archive::{ArchiveFormat, ExtractOptions, ExtractReport};
+pub use uninstall::{UninstallOptions, UninstallPlan, UninstallReport};
+pub use storage::StorageUsage;
+pub use simulate::{SimulatedPatch, SimulationReport};
+pub use conflicts::{ConflictChoice, ConflictResolution, FileConflict};
+pub use integrity::{IntegrityState, IntegrityStatus};
+pub use preflight::PreflightReport;
@@ -0,0 +1,348 @@
+use crate::{paths, Config, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const MIGRATION_STATE_FILE: &str = ".beam-migration";
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+// Leave some headroom so the destination drive is not filled to the last byte
+const FREE_SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationMode {
+    Move,
+    Copy,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MigrationProgress {
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub current_file: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MigrationReport {
+    pub files_copied: usize,
+    pub files_resumed: usize,
+    pub bytes_copied: u64,
+    pub shortcuts_updated: usize,
+}
+
+pub struct GameDirectoryMigrator {
+    source: PathBuf,
+    destination: PathBuf,
+    mode: MigrationMode,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+impl GameDirectoryMigrator {
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(source: P, destination: Q, mode: MigrationMode) -> Self {
+        GameDirectoryMigrator {
+            source: source.as_ref().to_path_buf(),
+            destination: destination.as_ref().to_path_buf(),
+            mode,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+    
+    pub fn with_retries(mut self, max_retries: u32, retry_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay = retry_delay;
+        self
+    }
+    
+    pub async fn migrate<F>(&self, mut on_progress: F) -> Result<MigrationReport>
+    where
+        F: FnMut(&MigrationProgress),
+    {
+        self.validate_paths()?;
+        info!("Migrating game directory {:?} -> {:?} ({:?})", self.source, self.destination, self.mode);
+        
+        let mut files = Vec::new();
+        collect_files(&self.source, &self.source, &mut files)?;
+        files.sort();
+        
+        // Same-volume moves are a single rename; fall back to copying when that is not possible
+        if self.mode == MigrationMode::Move && !self.destination.exists() {
+            if let Some(parent) = self.destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if std::fs::rename(&self.source, &self.destination).is_ok() {
+                info!("Game directory moved by rename");
+                return Ok(MigrationReport {
+                    files_copied: files.len(),
+                    bytes_copied: files.iter().map(|(_, _, size)| size).sum(),
+                    shortcuts_updated: update_shortcuts(&self.source, &self.destination),
+                    ..Default::default()
+                });
+            }
+        }
+        
+        std::fs::create_dir_all(&self.destination)?;
+        let state_path = self.destination.join(MIGRATION_STATE_FILE);
+        let completed = self.load_state(&state_path)?;
+        let mut state_log = std::fs::OpenOptions::new().append(true).open(&state_path)?;
+        
+        let mut progress = MigrationProgress {
+            files_total: files.len(),
+            ..Default::default()
+        };
+        let mut pending_bytes = 0;
+        for (relative, _, size) in &files {
+            progress.bytes_total += size;
+            if !completed.contains(relative) {
+                pending_bytes += size;
+            }
+        }
+        
+        self.check_free_space(pending_bytes)?;
+        
+        let mut report = MigrationReport::default();
+        for (relative, relative_path, size) in &files {
+            progress.current_file = relative.clone();
+            
+            if completed.contains(relative) {
+                debug!("Already migrated: {}", relative);
+                report.files_resumed += 1;
+            } else {
+                let target = self.destination.join(relative_path);
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                self.copy_with_retry(&self.source.join(relative_path), &target).await?;
+                
+                writeln!(state_log, "{}", relative)?;
+                
+                report.files_copied += 1;
+                report.bytes_copied += size;
+            }
+            
+            progress.files_done += 1;
+            progress.bytes_done += size;
+            on_progress(&progress);
+        }
+        
+        if self.mode == MigrationMode::Move {
+            info!("Removing original game directory: {:?}", self.source);
+            self.remove_with_retry(&self.source).await?;
+        }
+        
+        report.shortcuts_updated = update_shortcuts(&self.source, &self.destination);
+        drop(state_log);
+        std::fs::remove_file(&state_path)?;
+        
+        info!(
+            "Migration complete: {} files copied, {} resumed, {} bytes",
+            report.files_copied, report.files_resumed, report.bytes_copied
+        );
+        Ok(report)
+    }
+    
+    fn validate_paths(&self) -> Result<()> {
+        if !self.source.is_dir() {
+            return Err(Error::MigrationFailed(format!(
+                "Game directory does not exist: {}",
+                self.source.display()
+            )));
+        }
+        
+        let source = self.source.canonicalize()?;
+        let destination = absolute_path(&self.destination)?;
+        if destination.starts_with(&source) || source.starts_with(&destination) {
+            return Err(Error::MigrationFailed(
+                "Destination must not be inside the game directory or contain it".to_string()
+            ));
+        }
+        
+        // A non-empty destination is only accepted when it holds our own resume state
+        if destination.is_dir()
+            && !destination.join(MIGRATION_STATE_FILE).exists()
+            && std::fs::read_dir(&destination)?.next().is_some()
+        {
+            return Err(Error::MigrationFailed(format!(
+                "Destination is not empty: {}",
+                destination.display()
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    // The state file is the source path on the first line, then one line per file already copied
+    fn load_state(&self, state_path: &Path) -> Result<HashSet<String>> {
+        let source_line = self.source.to_string_lossy().to_string();
+        
+        if let Ok(content) = std::fs::read_to_string(state_path) {
+            let mut lines = content.lines();
+            if lines.next() == Some(source_line.as_str()) {
+                let completed: HashSet<String> = lines.map(|line| line.to_string()).collect();
+                info!("Resuming migration, {} files already copied", completed.len());
+                return Ok(completed);
+            }
+        }
+        
+        std::fs::write(state_path, format!("{}\n", source_line))?;
+        Ok(HashSet::new())
+    }
+    
+    fn check_free_space(&self, required: u64) -> Result<()> {
+        let available = fs2::available_space(&self.destination)?;
+        let required_with_margin = required.saturating_add(FREE_SPACE_MARGIN);
+        
+        if available < required_with_margin {
+            return Err(Error::MigrationFailed(format!(
+                "Not enough free space at {}: {} MB required, {} MB available",
+                self.destination.display(),
+                required_with_margin / (1024 * 1024),
+                available / (1024 * 1024)
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    // Files held open by a running client or antivirus scanner are retried before giving up
+    async fn copy_with_retry(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match tokio::fs::copy(from, to).await {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < self.max_retries && is_retryable(&e) => {
+                    attempt += 1;
+                    warn!("Copy of {:?} failed ({}), retry {}/{}", from, e, attempt, self.max_retries);
+                    tokio::time::sleep(self.retry_delay * attempt).await;
+                }
+                Err(e) => {
+                    return Err(Error::MigrationFailed(format!(
+                        "Failed to copy {}: {}",
+                        from.display(),
+                        e
+                    )));
+                }
+            }
+        }
+    }
+    
+    async fn remove_with_retry(&self, dir: &Path) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match tokio::fs::remove_dir_all(dir).await {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < self.max_retries && is_retryable(&e) => {
+                    attempt += 1;
+                    warn!("Removing {:?} failed ({}), retry {}/{}", dir, e, attempt, self.max_retries);
+                    tokio::time::sleep(self.retry_delay * attempt).await;
+                }
+                Err(e) => {
+                    return Err(Error::MigrationFailed(format!(
+                        "Files copied, but the original directory could not be removed: {}",
+                        e
+                    )));
+                }
+            }
+        }
+    }
+}
+
+// Moves or copies the install, then points the config at the new location and saves it
+pub async fn migrate_game_directory<F>(
+    config: &mut Config,
+    config_path: &Path,
+    destination: &Path,
+    mode: MigrationMode,
+    on_progress: F,
+) -> Result<MigrationReport>
+where
+    F: FnMut(&MigrationProgress),
+{
+    config.ensure_unlocked("Moving the game folder")?;
+    let source = config.app.game_directory.clone()
+        .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?;
+    // Checked up front so a destination the config cannot hold is refused before anything is moved
+    let destination_setting = paths::to_config_string(destination)?;
+    
+    let report = GameDirectoryMigrator::new(&source, destination, mode)
+        .migrate(on_progress)
+        .await?;
+    
+    config.app.game_directory = Some(destination_setting);
+    config.save(config_path)?;
+    
+    Ok(report)
+}
+
+fn is_retryable(error: &std::io::Error) -> bool {
+    // 32 = ERROR_SHARING_VIOLATION, 33 = ERROR_LOCK_VIOLATION on Windows
+    matches!(error.kind(), std::io::ErrorKind::PermissionDenied)
+        || matches!(error.raw_os_error(), Some(32) | Some(33))
+}
+
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    if path.exists() {
+        return Ok(path.canonicalize()?);
+    }
+    
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => Ok(absolute_path(parent)?.join(name)),
+        _ => Ok(std::env::current_dir()?.join(path)),
+    }
+}
+
+// (state-file name, relative path, size); the name is only a key, files are always opened by their real path
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf, u64)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        
+        if file_type.is_dir() {
+            collect_files(root, &path, out)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            out.push((paths::relative_name(relative), relative.to_path_buf(), entry.metadata()?.len()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn update_shortcuts(old_dir: &Path, new_dir: &Path) -> usize {
+    use std::process::Command;
+    
+    // Retarget .lnk files on the desktop and in the Start Menu that point into the old install
+    let script = format!(
+        r#"$old = '{old}'; $new = '{new}'; $shell = New-Object -ComObject WScript.Shell; $count = 0
+$dirs = @([Environment]::GetFolderPath('Desktop'), [Environment]::GetFolderPath('CommonDesktopDirectory'), [Environment]::GetFolderPath('Programs'))
+foreach ($dir in $dirs) {{ Get-ChildItem -Path $dir -Filter *.lnk -Recurse -ErrorAction SilentlyContinue | ForEach-Object {{
+  $lnk = $shell.CreateShortcut($_.FullName)
+  if ($lnk.TargetPath -and $lnk.TargetPath.StartsWith($old, [StringComparison]::OrdinalIgnoreCase)) {{
+    $lnk.TargetPath = $new + $lnk.TargetPath.Substring($old.Length)
+    if ($lnk.WorkingDirectory.StartsWith($old, [StringComparison]::OrdinalIgnoreCase)) {{ $lnk.WorkingDirectory = $new + $lnk.WorkingDirectory.Substring($old.Length) }}
+    $lnk.Save(); $count++ }} }} }}
+Write-Output $count"#,
+        old = old_dir.display().to_string().replace('\'', "''"),
+        new = new_dir.display().to_string().replace('\'', "''"),
+    );
+    
+    match Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).trim().parse().unwrap_or(0),
+        Err(e) => {
+            warn!("Failed to update shortcuts: {}", e);
+            0
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn update_shortcuts(_old_dir: &Path, _new_dir: &Path) -> usize {
+    0
+}
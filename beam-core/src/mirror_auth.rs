@@ -0,0 +1,166 @@
+use crate::config::MirrorConfig;
+use crate::pinning::{self, Pin};
+use crate::s3::{self, S3Credentials};
+use crate::url_template::{self, UrlVariables};
+use crate::{Error, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::debug;
+
+// Where to fetch one file from a mirror and which headers the request needs
+#[derive(Debug, Clone)]
+pub struct MirrorRequest {
+    pub url: String,
+    headers: HeaderMap,
+    // S3 signatures cover the method and time, so they are computed per request
+    s3: Option<S3Credentials>,
+    pins: Vec<Pin>,
+}
+
+#[derive(Debug, Serialize)]
+struct SignedUrlRequest<'a> {
+    mirror: &'a str,
+    file: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedUrlResponse {
+    url: String,
+}
+
+impl MirrorRequest {
+    pub fn plain(url: impl Into<String>) -> Self {
+        MirrorRequest {
+            url: url.into(),
+            headers: HeaderMap::new(),
+            s3: None,
+            pins: Vec::new(),
+        }
+    }
+    
+    pub fn get(&self, client: &Client) -> RequestBuilder {
+        self.request(client, Method::GET)
+    }
+    
+    pub fn head(&self, client: &Client) -> RequestBuilder {
+        self.request(client, Method::HEAD)
+    }
+    
+    // Fails when the mirror has `pin_sha256` and the server that answered is not the pinned one
+    pub fn check_pin(&self, response: &Response) -> Result<()> {
+        pinning::check(response, &self.pins)
+    }
+    
+    fn request(&self, client: &Client, method: Method) -> RequestBuilder {
+        let mut request = client.request(method.clone(), &self.url).headers(self.headers.clone());
+        if let (Some(credentials), Ok(url)) = (&self.s3, Url::parse(&self.url)) {
+            request = request.headers(s3::sign(credentials, &method, &url, chrono::Utc::now()));
+        }
+        request
+    }
+}
+
+// The query string is left out because signed URLs carry their credentials there
+impl fmt::Display for MirrorRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.url.split('?').next().unwrap_or(&self.url))
+    }
+}
+
+// Signed URLs already carry their credentials, so the bearer token is only sent to the mirror itself
+pub async fn resolve(
+    client: &Client,
+    mirror: &MirrorConfig,
+    file: &str,
+    variables: &UrlVariables,
+    sso_token: Option<&str>,
+) -> Result<MirrorRequest> {
+    let mut headers = mirror_headers(mirror)?;
+    let pins = pinning::parse_pins(&mirror.pin_sha256)?;
+    
+    if let Some(config) = &mirror.s3 {
+        let credentials = s3::credentials(config);
+        if credentials.is_some() {
+            headers.remove(AUTHORIZATION);
+        }
+        return Ok(MirrorRequest { url: s3::object_url(config, file)?, headers, s3: credentials, pins });
+    }
+    
+    let url = match &mirror.signed_url_endpoint {
+        Some(endpoint) => {
+            headers.remove(AUTHORIZATION);
+            sign_url(client, endpoint, mirror, &pins, file, sso_token).await?
+        }
+        None => url_template::mirror_file_url(mirror, variables, file),
+    };
+    
+    Ok(MirrorRequest { url, headers, s3: None, pins })
+}
+
+// Headers for a URL below the mirror, e.g. the patch list hosted on the same private CDN
+pub fn mirror_headers(mirror: &MirrorConfig) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    
+    for (name, value) in &mirror.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+            Error::InvalidConfig(format!("Invalid header name `{}` for mirror {}", name, mirror.name))
+        })?;
+        headers.insert(name, sensitive_value(mirror, value)?);
+    }
+    if let Some(token) = &mirror.bearer_token {
+        headers.insert(AUTHORIZATION, sensitive_value(mirror, &format!("Bearer {}", token))?);
+    }
+    
+    Ok(headers)
+}
+
+fn sensitive_value(mirror: &MirrorConfig, value: &str) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(value).map_err(|_| {
+        Error::InvalidConfig(format!("Invalid header value for mirror {}", mirror.name))
+    })?;
+    // Keeps tokens out of debug logs
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+async fn sign_url(
+    client: &Client,
+    endpoint: &str,
+    mirror: &MirrorConfig,
+    pins: &[Pin],
+    file: &str,
+    sso_token: Option<&str>,
+) -> Result<String> {
+    debug!("Requesting signed URL for {} from {}", file, endpoint);
+    
+    let mut request = client.post(endpoint).json(&SignedUrlRequest { mirror: &mirror.name, file });
+    if let Some(token) = sso_token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await?;
+    // The signed URL decides where the download comes from, so it must come from the mirror's own server
+    pinning::check(&response, pins)?;
+    
+    match response.status().as_u16() {
+        200..=299 => {}
+        401 | 403 => {
+            return Err(Error::AuthFailed(format!(
+                "Mirror {} refused to sign a download URL (HTTP {}); log in again",
+                mirror.name,
+                response.status()
+            )))
+        }
+        _ => {
+            return Err(Error::DownloadFailed(format!(
+                "Signed URL request for {} failed: HTTP {}",
+                file,
+                response.status()
+            )))
+        }
+    }
+    
+    let signed: SignedUrlResponse = response.json().await?;
+    Ok(signed.url)
+}
@@ -0,0 +1,133 @@
+use crate::config::{MirrorConfig, MirrorSelectionConfig};
+use crate::connection_check::mirror_probe_target;
+use crate::url_template::UrlVariables;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorHealth {
+    pub name: String,
+    pub healthy: bool,
+    // Time until the response headers arrived; None when the mirror did not answer
+    pub latency_ms: Option<u64>,
+    pub detail: String,
+}
+
+// Orders mirrors by how fast they answer instead of by their configured priority. All mirrors are probed at once
+// with a HEAD request and the results are reused for `cache_seconds`, so a run with many patches probes once. A
+// mirror that fails a download is marked down until the next probe, and the remaining ones keep their order.
+pub struct MirrorSelector {
+    probe_timeout: Duration,
+    cache_ttl: Duration,
+    // By mirror name, with the time it was measured
+    cache: Mutex<HashMap<String, (Instant, MirrorHealth)>>,
+}
+
+impl MirrorSelector {
+    pub fn new(config: &MirrorSelectionConfig) -> Self {
+        MirrorSelector {
+            probe_timeout: Duration::from_millis(config.probe_timeout_ms),
+            cache_ttl: Duration::from_secs(config.cache_seconds),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    // Health of every mirror, probing concurrently the ones without a fresh result
+    pub async fn probe_all(&self, client: &Client, mirrors: &[MirrorConfig], variables: &UrlVariables) -> Vec<MirrorHealth> {
+        let stale: Vec<&MirrorConfig> = {
+            let cache = self.cache.lock().unwrap();
+            mirrors
+                .iter()
+                .filter(|mirror| cache.get(&mirror.name).is_none_or(|(at, _)| at.elapsed() >= self.cache_ttl))
+                .collect()
+        };
+        if !stale.is_empty() {
+            let probed = futures::future::join_all(
+                stale.into_iter().map(|mirror| self.probe(client, mirror, variables)),
+            ).await;
+            let now = Instant::now();
+            let mut cache = self.cache.lock().unwrap();
+            for health in probed {
+                debug!(
+                    "Mirror {}: {} ({})",
+                    health.name,
+                    health.latency_ms.map_or_else(|| "no answer".to_string(), |ms| format!("{} ms", ms)),
+                    health.detail
+                );
+                cache.insert(health.name.clone(), (now, health));
+            }
+        }
+        
+        let cache = self.cache.lock().unwrap();
+        mirrors.iter().filter_map(|mirror| cache.get(&mirror.name).map(|(_, health)| health.clone())).collect()
+    }
+    
+    // Healthy mirrors fastest first, then the rest by priority; mirrors that are down are still tried last
+    pub async fn rank(&self, client: &Client, mirrors: &[MirrorConfig], variables: &UrlVariables) -> Vec<MirrorConfig> {
+        let health: HashMap<String, MirrorHealth> = self
+            .probe_all(client, mirrors, variables)
+            .await
+            .into_iter()
+            .map(|health| (health.name.clone(), health))
+            .collect();
+        
+        let mut ranked = mirrors.to_vec();
+        ranked.sort_by_key(|mirror| {
+            let latency = health
+                .get(&mirror.name)
+                .filter(|health| health.healthy)
+                .and_then(|health| health.latency_ms);
+            (latency.is_none(), latency.unwrap_or(u64::MAX), mirror.priority)
+        });
+        if let Some(first) = ranked.first() {
+            info!("Fastest mirror: {}", first.name);
+        }
+        ranked
+    }
+    
+    // Keeps a mirror that just failed a download from being tried first until it is probed again
+    pub fn mark_failed(&self, mirror: &str, detail: &str) {
+        let health = MirrorHealth {
+            name: mirror.to_string(),
+            healthy: false,
+            latency_ms: None,
+            detail: detail.to_string(),
+        };
+        self.cache.lock().unwrap().insert(mirror.to_string(), (Instant::now(), health));
+    }
+    
+    async fn probe(&self, client: &Client, mirror: &MirrorConfig, variables: &UrlVariables) -> MirrorHealth {
+        let down = |detail: String| MirrorHealth {
+            name: mirror.name.clone(),
+            healthy: false,
+            latency_ms: None,
+            detail,
+        };
+        if mirror.url.is_empty() && mirror.s3.is_none() {
+            return down("No URL".to_string());
+        }
+        let (url, headers) = match mirror_probe_target(mirror, variables) {
+            Ok(target) => target,
+            Err(e) => return down(e.to_string()),
+        };
+        
+        let started = Instant::now();
+        let request = client.head(&url).headers(headers).timeout(self.probe_timeout).send();
+        match request.await {
+            // Any answer short of a server error means the mirror is up; folders often answer 403 or 404
+            Ok(response) if !response.status().is_server_error() => MirrorHealth {
+                name: mirror.name.clone(),
+                healthy: true,
+                latency_ms: Some(started.elapsed().as_millis() as u64),
+                detail: format!("HTTP {}", response.status()),
+            },
+            Ok(response) => down(format!("HTTP {}", response.status())),
+            Err(e) if e.is_timeout() => down(format!("No answer within {} ms", self.probe_timeout.as_millis())),
+            Err(e) => down(e.to_string()),
+        }
+    }
+}
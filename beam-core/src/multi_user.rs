@@ -0,0 +1,71 @@
+use crate::config::MultiUserConfig;
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+// Where this user's DATA.INI lives when game files are shared between Windows accounts
+pub fn user_settings_dir(config: &MultiUserConfig, server_name: &str) -> Result<PathBuf> {
+    if let Some(dir) = &config.settings_dir {
+        return Ok(PathBuf::from(dir));
+    }
+    
+    let documents = dirs::document_dir()
+        .or_else(dirs::home_dir)
+        .ok_or_else(|| Error::InvalidConfig("Could not determine the user's documents folder".to_string()))?;
+    
+    let folder: String = server_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    
+    Ok(documents.join("Beam Patcher").join(folder.trim()))
+}
+
+// Patching writes into the shared directory, which standard accounts often cannot do (e.g. under Program Files)
+pub fn ensure_shared_write_access(game_dir: &Path) -> Result<()> {
+    let probe = game_dir.join(".beam-write-test");
+    
+    match std::fs::write(&probe, b"") {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Err(Error::PatchFailed(format!(
+            "No write access to shared game directory {}. Run `beam-patcher --grant-shared-access` once as administrator.",
+            game_dir.display()
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Gives the local Users group modify rights on the shared install; needs an elevated process
+#[cfg(target_os = "windows")]
+pub fn grant_shared_access(game_dir: &Path) -> Result<()> {
+    use std::process::Command;
+    
+    info!("Granting Users modify access to {:?}", game_dir);
+    
+    // S-1-5-32-545 is BUILTIN\Users, used instead of the name so localized Windows installs work
+    let output = Command::new("icacls")
+        .arg(game_dir)
+        .args(["/grant", "*S-1-5-32-545:(OI)(CI)M", "/T", "/C", "/Q"])
+        .output()?;
+    
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        tracing::warn!("icacls failed: {}", message);
+        return Err(Error::PatchFailed(format!(
+            "Failed to update permissions on {} (run as administrator): {}",
+            game_dir.display(),
+            message
+        )));
+    }
+    
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn grant_shared_access(game_dir: &Path) -> Result<()> {
+    info!("Shared access handling is only needed on Windows, skipping {:?}", game_dir);
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use crate::config::HttpConfig;
+use crate::{Error, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewsItem {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub date: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    // HTML snippet, sanitized before it reaches the UI
+    #[serde(default, alias = "content")]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub action: Option<NewsAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NewsAction {
+    OpenUrl { url: String },
+    PatchNotes {
+        #[serde(default)]
+        version: Option<String>,
+        #[serde(default)]
+        url: Option<String>,
+    },
+    SwitchChannel { channel: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct NewsClick<'a> {
+    id: Option<&'a str>,
+    title: &'a str,
+    url: Option<&'a str>,
+    action: Option<&'a NewsAction>,
+}
+
+pub async fn fetch_news(http: &HttpConfig, url: &str) -> Result<Vec<NewsItem>> {
+    info!("Fetching news feed from: {}", url);
+    
+    let response = crate::http::client(http)?.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(Error::DownloadFailed(format!(
+            "Failed to fetch news feed: HTTP {}",
+            response.status()
+        )));
+    }
+    
+    let body = response.text().await?;
+    parse_feed(&body)
+}
+
+// Accepts the JSON array format or an RSS 2.0 / Atom feed
+pub fn parse_feed(content: &str) -> Result<Vec<NewsItem>> {
+    let trimmed = content.trim_start_matches('\u{feff}').trim_start();
+    
+    let mut items = if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        parse_json(trimmed)?
+    } else {
+        parse_xml(trimmed)?
+    };
+    
+    for item in &mut items {
+        item.body = item.body.as_deref().map(ammonia::clean);
+        if item.action.is_none() {
+            item.action = item.url.clone().map(|url| NewsAction::OpenUrl { url });
+        }
+    }
+    
+    debug!("Parsed {} news items", items.len());
+    Ok(items)
+}
+
+// Opt-in: only sent when `ui.news_click_tracking_url` is configured
+pub async fn track_click(http: &HttpConfig, tracking_url: &str, item: &NewsItem) -> Result<()> {
+    let click = NewsClick {
+        id: item.id.as_deref(),
+        title: &item.title,
+        url: item.url.as_deref(),
+        action: item.action.as_ref(),
+    };
+    
+    let response = crate::http::client(http)?.post(tracking_url).json(&click).send().await?;
+    if !response.status().is_success() {
+        return Err(Error::DownloadFailed(format!(
+            "News click tracking failed: HTTP {}",
+            response.status()
+        )));
+    }
+    
+    Ok(())
+}
+
+fn parse_json(content: &str) -> Result<Vec<NewsItem>> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum JsonFeed {
+        Items(Vec<NewsItem>),
+        Wrapped { items: Vec<NewsItem> },
+    }
+    
+    match serde_json::from_str(content) {
+        Ok(JsonFeed::Items(items)) | Ok(JsonFeed::Wrapped { items }) => Ok(items),
+        Err(e) => Err(Error::InvalidConfig(format!("Invalid news feed: {}", e))),
+    }
+}
+
+fn parse_xml(content: &str) -> Result<Vec<NewsItem>> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+    
+    let mut items = Vec::new();
+    let mut current: Option<NewsItem> = None;
+    let mut field: Vec<u8> = Vec::new();
+    
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| Error::InvalidConfig(format!("Invalid news feed: {}", e)))?;
+        
+        match event {
+            Event::Start(e) => {
+                let name = e.name().as_ref().to_vec();
+                if name == b"item" || name == b"entry" {
+                    current = Some(NewsItem::default());
+                } else if let Some(item) = current.as_mut() {
+                    apply_attributes(item, &e)?;
+                }
+                field = name;
+            }
+            Event::Empty(e) => {
+                if let Some(item) = current.as_mut() {
+                    apply_attributes(item, &e)?;
+                }
+            }
+            Event::Text(text) => {
+                if let Some(item) = current.as_mut() {
+                    let value = text
+                        .unescape()
+                        .map_err(|e| Error::InvalidConfig(format!("Invalid news feed: {}", e)))?;
+                    apply_text(item, &field, &value);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(item) = current.as_mut() {
+                    apply_text(item, &field, &String::from_utf8_lossy(&data.into_inner()));
+                }
+            }
+            Event::End(e) => {
+                let name = e.name();
+                if name.as_ref() == b"item" || name.as_ref() == b"entry" {
+                    items.extend(current.take());
+                }
+                field.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    
+    Ok(items)
+}
+
+fn apply_text(item: &mut NewsItem, field: &[u8], value: &str) {
+    let value = value.trim().to_string();
+    if value.is_empty() {
+        return;
+    }
+    
+    match field {
+        b"title" => item.title = value,
+        b"link" => item.url = Some(value),
+        b"guid" | b"id" => item.id = Some(value),
+        b"pubDate" | b"published" | b"updated" | b"dc:date" if item.date.is_empty() => item.date = value,
+        b"category" if item.category.is_empty() => item.category = value,
+        // Full content wins over the short description/summary
+        b"content:encoded" | b"content" => item.body = Some(value),
+        b"description" | b"summary" if item.body.is_none() => item.body = Some(value),
+        _ => {}
+    }
+}
+
+// Atom links, categories and RSS media elements carry their data in attributes
+fn apply_attributes(item: &mut NewsItem, element: &BytesStart) -> Result<()> {
+    let attribute = |key: &str| -> Result<Option<String>> {
+        match element.try_get_attribute(key) {
+            Ok(Some(attr)) => attr
+                .unescape_value()
+                .map(|v| Some(v.to_string()))
+                .map_err(|e| Error::InvalidConfig(format!("Invalid news feed: {}", e))),
+            Ok(None) => Ok(None),
+            Err(e) => Err(Error::InvalidConfig(format!("Invalid news feed: {}", e))),
+        }
+    };
+    
+    match element.name().as_ref() {
+        b"link" => {
+            let rel = attribute("rel")?;
+            if rel.is_none() || rel.as_deref() == Some("alternate") {
+                if let Some(href) = attribute("href")? {
+                    item.url = Some(href);
+                }
+            }
+        }
+        b"category" if item.category.is_empty() => {
+            if let Some(term) = attribute("term")? {
+                item.category = term;
+            }
+        }
+        b"enclosure" | b"media:content" | b"media:thumbnail" => {
+            let is_image = attribute("type")?.map(|t| t.starts_with("image/")).unwrap_or(true);
+            if is_image && item.image.is_none() {
+                item.image = attribute("url")?;
+            }
+        }
+        _ => {}
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use crate::paths;
+use crate::torrent::{self, TorrentInfo, TorrentOptions};
+use crate::verifier::{FileEntry, FileManifest};
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+// Player-generated files that should never end up in a distributed client
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    "*.log",
+    "*.bak",
+    "*.dmp",
+    "errorlog.txt",
+    "savedata/",
+    "screenshot/",
+    "chat/",
+    "replay/",
+    ".beam-*",
+];
+
+// Already-compressed formats are stored as-is; deflating them only costs time
+const STORED_EXTENSIONS: &[&str] = &["grf", "gpf", "rgz", "thor", "zip", "7z", "rar", "mp3", "ogg", "jpg", "png", "bik", "mp4"];
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct PackageOptions {
+    pub output: PathBuf,
+    pub excludes: Vec<String>,
+    pub split_size: Option<u64>,
+    pub torrent: Option<TorrentOptions>,
+}
+
+impl PackageOptions {
+    pub fn new<P: AsRef<Path>>(output: P) -> Self {
+        PackageOptions {
+            output: output.as_ref().to_path_buf(),
+            excludes: DEFAULT_EXCLUDES.iter().map(|s| s.to_string()).collect(),
+            split_size: None,
+            torrent: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageReport {
+    pub parts: Vec<PathBuf>,
+    pub manifest_path: PathBuf,
+    pub checksums_path: PathBuf,
+    pub files_packaged: usize,
+    pub files_excluded: usize,
+    pub total_size: u64,
+    pub torrent: Option<TorrentInfo>,
+}
+
+pub struct ClientPackager {
+    game_dir: PathBuf,
+    options: PackageOptions,
+}
+
+impl ClientPackager {
+    pub fn new<P: AsRef<Path>>(game_dir: P, options: PackageOptions) -> Self {
+        ClientPackager {
+            game_dir: game_dir.as_ref().to_path_buf(),
+            options,
+        }
+    }
+    
+    pub fn package(&self) -> Result<PackageReport> {
+        if !self.game_dir.is_dir() {
+            return Err(Error::InvalidConfig(format!(
+                "Game directory does not exist: {}",
+                self.game_dir.display()
+            )));
+        }
+        
+        let mut files = Vec::new();
+        collect_files(&self.game_dir, &self.game_dir, &mut files)?;
+        files.sort();
+        
+        let output_dir = self.options.output.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        std::fs::create_dir_all(output_dir)?;
+        
+        // Never package our own output if it is being written inside the game directory
+        let output_name = self.options.output.file_name().map(|n| n.to_string_lossy().to_lowercase());
+        let total_files = files.len();
+        files.retain(|(relative, _)| {
+            let excluded = self.is_excluded(relative)
+                || output_name.as_deref().map(|name| relative.to_lowercase().starts_with(name)).unwrap_or(false);
+            if excluded {
+                debug!("Excluding {}", relative);
+            }
+            !excluded
+        });
+        let files_excluded = total_files - files.len();
+        
+        info!("Packaging {} files from {:?} ({} excluded)", files.len(), self.game_dir, files_excluded);
+        
+        let mut zip = zip::ZipWriter::new(BufWriter::new(File::create(&self.options.output)?));
+        let mut manifest = FileManifest { files: Vec::with_capacity(files.len()) };
+        let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+        
+        for (relative, relative_path) in &files {
+            let full_path = self.game_dir.join(relative_path);
+            let size = std::fs::metadata(&full_path)?.len();
+            
+            let options = SimpleFileOptions::default()
+                .compression_method(compression_for(relative))
+                .large_file(size >= u32::MAX as u64);
+            zip.start_file(relative.as_str(), options)
+                .map_err(|e| Error::PackagingFailed(e.to_string()))?;
+            
+            let mut reader = BufReader::new(File::open(&full_path)?);
+            let mut hasher = Sha256::new();
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                zip.write_all(&buffer[..read])?;
+            }
+            
+            manifest.files.push(FileEntry {
+                path: relative.clone(),
+                checksum: format!("{:x}", hasher.finalize()),
+                size,
+            });
+        }
+        
+        zip.finish().map_err(|e| Error::PackagingFailed(e.to_string()))?.flush()?;
+        
+        let total_size = std::fs::metadata(&self.options.output)?.len();
+        let parts = match self.options.split_size {
+            Some(split_size) if total_size > split_size => split_file(&self.options.output, split_size)?,
+            _ => vec![self.options.output.clone()],
+        };
+        
+        let manifest_path = output_dir.join("manifest.json");
+        let manifest_json = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| Error::PackagingFailed(e.to_string()))?;
+        std::fs::write(&manifest_path, manifest_json)?;
+        
+        let checksums_path = PathBuf::from(format!("{}.sha256", self.options.output.display()));
+        write_checksums(&parts, &checksums_path)?;
+        
+        let torrent = match &self.options.torrent {
+            Some(torrent_options) => {
+                let name = self.options.output.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let torrent_path = PathBuf::from(format!("{}.torrent", self.options.output.display()));
+                Some(torrent::create_torrent(&parts, &name, torrent_options, &torrent_path)?)
+            }
+            None => None,
+        };
+        
+        info!("Client package written: {} part(s), {} bytes", parts.len(), total_size);
+        
+        Ok(PackageReport {
+            parts,
+            manifest_path,
+            checksums_path,
+            files_packaged: manifest.files.len(),
+            files_excluded,
+            total_size,
+            torrent,
+        })
+    }
+    
+    fn is_excluded(&self, relative: &str) -> bool {
+        let relative = relative.to_lowercase();
+        self.options.excludes.iter().any(|pattern| matches_exclude(&pattern.to_lowercase(), &relative))
+    }
+}
+
+// `dir/` excludes a directory at any depth, anything else is a `*` wildcard matched against the file name
+// (or the whole path when the pattern contains a slash)
+fn matches_exclude(pattern: &str, path: &str) -> bool {
+    if let Some(dir) = pattern.strip_suffix('/') {
+        return path.split('/').rev().skip(1).any(|component| wildcard_match(dir, component));
+    }
+    
+    if pattern.contains('/') {
+        wildcard_match(pattern, path)
+    } else {
+        wildcard_match(pattern, path.rsplit('/').next().unwrap_or(path))
+    }
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            if !text.starts_with(prefix) {
+                return false;
+            }
+            let remaining = &text[prefix.len()..];
+            (0..=remaining.len())
+                .filter(|&i| remaining.is_char_boundary(i))
+                .any(|i| wildcard_match(rest, &remaining[i..]))
+        }
+    }
+}
+
+fn compression_for(path: &str) -> CompressionMethod {
+    let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    if STORED_EXTENSIONS.contains(&extension.as_str()) {
+        CompressionMethod::Stored
+    } else {
+        CompressionMethod::Deflated
+    }
+}
+
+// Splits into `name.001`, `name.002`, ... (7-Zip style volumes) and removes the original
+fn split_file(path: &Path, part_size: u64) -> Result<Vec<PathBuf>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut parts = Vec::new();
+    
+    loop {
+        let part_path = PathBuf::from(format!("{}.{:03}", path.display(), parts.len() + 1));
+        let mut writer = BufWriter::new(File::create(&part_path)?);
+        let copied = std::io::copy(&mut (&mut reader).take(part_size), &mut writer)?;
+        writer.flush()?;
+        
+        if copied == 0 {
+            drop(writer);
+            std::fs::remove_file(&part_path)?;
+            break;
+        }
+        parts.push(part_path);
+    }
+    
+    std::fs::remove_file(path)?;
+    Ok(parts)
+}
+
+fn write_checksums(parts: &[PathBuf], checksums_path: &Path) -> Result<()> {
+    let mut content = String::new();
+    
+    for part in parts {
+        let mut reader = BufReader::new(File::open(part)?);
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut reader, &mut hasher)?;
+        
+        let name = part.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        content.push_str(&format!("{:x}  {}\n", hasher.finalize(), name));
+    }
+    
+    std::fs::write(checksums_path, content)?;
+    Ok(())
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, out)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            out.push((paths::relative_name(relative), relative.to_path_buf()));
+        }
+    }
+    Ok(())
+}
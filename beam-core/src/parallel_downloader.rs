@@ -1,8 +1,16 @@
+use crate::config::MirrorConfig;
+use crate::downloader::{next_chunk, send_to_mirror, stall_timeout, STALL_RETRIES};
+use crate::mirror_auth::{self, MirrorRequest};
+use crate::progress::{self, ProgressEvent, ProgressReporter};
+use crate::url_template::UrlVariables;
 use crate::{Config, Error, Result};
 use futures::stream::{FuturesUnordered, StreamExt};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::Semaphore;
@@ -16,6 +24,29 @@ pub struct ParallelDownloader {
     #[allow(dead_code)]
     config: Config,
     max_parallel: usize,
+    watch: StallWatch,
+    sso_token: Option<String>,
+}
+
+// Shared by every chunk task so each one can notice and report its own stall
+#[derive(Clone)]
+struct StallWatch {
+    timeout: Duration,
+    reporter: Option<ProgressReporter>,
+}
+
+impl StallWatch {
+    // Reports the stall and tells whether another attempt is allowed
+    fn retry(&self, target: &MirrorRequest, attempt: u32, error: &Error) -> bool {
+        let url = target.to_string();
+        warn!("Download of {} stalled ({}), attempt {}", url, error, attempt);
+        progress::report(&self.reporter, ProgressEvent::Stalled {
+            file: url.rsplit('/').next().unwrap_or(&url).to_string(),
+            source: url.clone(),
+            attempt,
+        });
+        attempt <= STALL_RETRIES
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -25,28 +56,243 @@ pub struct DownloadProgress {
     pub percentage: f32,
 }
 
+// Which chunks of a partial download are on disk, kept next to it so the transfer survives the launcher
+// being closed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeState {
+    // Without the query string, which may carry an expiring signature
+    pub url: String,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    pub total_size: u64,
+    pub chunk_size: u64,
+    // One bit per chunk, set once the whole chunk is written
+    pub chunks: Vec<u64>,
+}
+
+impl ResumeState {
+    fn new(url: String, etag: Option<String>, last_modified: Option<String>, total_size: u64) -> Self {
+        let chunk_count = total_size.div_ceil(CHUNK_SIZE);
+        ResumeState {
+            url,
+            etag,
+            last_modified,
+            total_size,
+            chunk_size: CHUNK_SIZE,
+            chunks: vec![0; chunk_count.div_ceil(64) as usize],
+        }
+    }
+    
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+    
+    // Written to a temporary file first so a crash never leaves a state that claims unwritten chunks
+    fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string(self)
+            .map_err(|e| Error::DownloadFailed(format!("Failed to serialize resume state: {}", e)))?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+    
+    fn chunk_count(&self) -> u64 {
+        self.total_size.div_ceil(self.chunk_size)
+    }
+    
+    fn is_done(&self, index: u64) -> bool {
+        self.chunks.get((index / 64) as usize).map(|word| word & (1 << (index % 64)) != 0).unwrap_or(false)
+    }
+    
+    fn mark_done(&mut self, index: u64) {
+        if let Some(word) = self.chunks.get_mut((index / 64) as usize) {
+            *word |= 1 << (index % 64);
+        }
+    }
+    
+    fn chunk_range(&self, index: u64) -> RangeInclusive<u64> {
+        let start = index * self.chunk_size;
+        start..=(start + self.chunk_size).min(self.total_size) - 1
+    }
+    
+    pub fn bytes_done(&self) -> u64 {
+        (0..self.chunk_count())
+            .filter(|&index| self.is_done(index))
+            .map(|index| {
+                let range = self.chunk_range(index);
+                range.end() - range.start() + 1
+            })
+            .sum()
+    }
+    
+    // Validators are only compared when both sides have them: mirrors serving the same file rarely agree on ETags
+    fn same_file(&self, other: &ResumeState) -> bool {
+        let same = |a: &Option<String>, b: &Option<String>| match (a, b) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        self.total_size == other.total_size
+            && self.chunk_size == other.chunk_size
+            && self.chunks.len() == other.chunks.len()
+            && (self.url != other.url || (same(&self.etag, &other.etag) && same(&self.last_modified, &other.last_modified)))
+    }
+}
+
 impl ParallelDownloader {
     pub fn new(config: Config) -> Result<Self> {
-        let client = Client::builder()
-            .user_agent("Beam-Patcher/1.0")
+        let client = crate::http::client_builder(&config.http)?
             .timeout(std::time::Duration::from_secs(300))
+            .tls_info(true)
             .build()?;
         
         Ok(ParallelDownloader {
             client,
+            watch: StallWatch {
+                timeout: stall_timeout(&config),
+                reporter: None,
+            },
             config,
             max_parallel: MAX_PARALLEL_CHUNKS,
+            sso_token: None,
         })
     }
     
+    pub fn with_progress_reporter(mut self, reporter: ProgressReporter) -> Self {
+        self.watch.reporter = Some(reporter);
+        self
+    }
+    
+    pub fn with_sso_token(mut self, token: Option<String>) -> Self {
+        self.sso_token = token;
+        self
+    }
+    
+    // Uses the mirror's headers, bearer token or signed URLs; see `mirror_auth`
+    pub async fn download_from_mirror(
+        &self,
+        mirror: &MirrorConfig,
+        filename: &str,
+        destination: &Path,
+    ) -> Result<PathBuf> {
+        let variables = UrlVariables::from_config(&self.config);
+        let target = mirror_auth::resolve(&self.client, mirror, filename, &variables, self.sso_token.as_deref()).await?;
+        if destination.exists() {
+            self.resume_target(&target, destination).await
+        } else {
+            self.download_target(&target, destination).await
+        }
+    }
+    
+    // Like `download_from_mirror`, but finished chunks are recorded in `state_path` as they complete, so a
+    // later call, even from a new launcher process, only fetches what is missing
+    pub async fn download_resumable(
+        &self,
+        mirror: &MirrorConfig,
+        filename: &str,
+        destination: &Path,
+        state_path: &Path,
+    ) -> Result<PathBuf> {
+        let variables = UrlVariables::from_config(&self.config);
+        let target = mirror_auth::resolve(&self.client, mirror, filename, &variables, self.sso_token.as_deref()).await?;
+        
+        let head_response = send_to_mirror(&target, target.head(&self.client), self.watch.timeout).await?;
+        if !head_response.status().is_success() {
+            return Err(Error::DownloadFailed(format!("HTTP error: {}", head_response.status())));
+        }
+        let header = |name: &str| head_response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        // `content_length()` is the body size, which a HEAD response never has
+        let total_size = header("content-length")
+            .and_then(|length| length.parse::<u64>().ok())
+            .ok_or_else(|| Error::DownloadFailed("Content-Length header missing".to_string()))?;
+        let supports_range = header("accept-ranges").as_deref() == Some("bytes");
+        let remote = ResumeState::new(target.to_string(), header("etag"), header("last-modified"), total_size);
+        
+        if !supports_range {
+            info!("{} does not support range requests; the download cannot be resumed", mirror.name);
+            let _ = std::fs::remove_file(state_path);
+            return self.download_single_threaded(&target, destination, total_size).await;
+        }
+        
+        let on_disk = tokio::fs::metadata(destination).await.map(|m| m.len()).ok();
+        let state = match ResumeState::load(state_path) {
+            Some(saved) if saved.same_file(&remote) && on_disk == Some(total_size) => {
+                let done = saved.bytes_done();
+                info!("Resuming download of {} at {} / {} bytes", filename, done, total_size);
+                progress::report(&self.watch.reporter, ProgressEvent::Resuming {
+                    file: filename.to_string(),
+                    downloaded_bytes: done,
+                    total_bytes: total_size,
+                });
+                ResumeState { chunks: saved.chunks, ..remote }
+            }
+            _ => {
+                if let Some(parent) = destination.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                let file = File::create(destination).await?;
+                file.set_len(total_size).await?;
+                remote
+            }
+        };
+        state.save(state_path)?;
+        
+        let pending: Vec<u64> = (0..state.chunk_count()).filter(|&index| !state.is_done(index)).collect();
+        let state = Arc::new(std::sync::Mutex::new(state));
+        let semaphore = Arc::new(Semaphore::new(self.max_parallel));
+        let mut futures = FuturesUnordered::new();
+        
+        for chunk_index in pending {
+            let range = state.lock().unwrap().chunk_range(chunk_index);
+            let client = self.client.clone();
+            let target = target.clone();
+            let destination = destination.to_path_buf();
+            let watch = self.watch.clone();
+            let permit = semaphore.clone().acquire_owned().await.unwrap();
+            
+            futures.push(tokio::spawn(async move {
+                let result = download_chunk(&client, &target, &destination, range, chunk_index, &watch).await;
+                drop(permit);
+                result.map(|_| chunk_index)
+            }));
+        }
+        
+        while let Some(result) = futures.next().await {
+            let chunk_index = result.map_err(|e| Error::DownloadFailed(format!("Task join error: {}", e)))??;
+            let mut state = state.lock().unwrap();
+            state.mark_done(chunk_index);
+            state.save(state_path)?;
+            progress::report(&self.watch.reporter, ProgressEvent::Downloading {
+                file: filename.to_string(),
+                downloaded_bytes: state.bytes_done(),
+                total_bytes: total_size,
+            });
+        }
+        
+        let _ = std::fs::remove_file(state_path);
+        info!("Download completed: {:?}", destination);
+        Ok(destination.to_path_buf())
+    }
+    
     pub async fn download_file_parallel(
         &self,
         url: &str,
         destination: &Path,
     ) -> Result<PathBuf> {
-        info!("Starting parallel download: {}", url);
+        self.download_target(&MirrorRequest::plain(url), destination).await
+    }
+    
+    async fn download_target(
+        &self,
+        target: &MirrorRequest,
+        destination: &Path,
+    ) -> Result<PathBuf> {
+        info!("Starting parallel download: {}", target);
         
-        let head_response = self.client.head(url).send().await?;
+        let head_response = send_to_mirror(target, target.head(&self.client), self.watch.timeout).await?;
         
         if !head_response.status().is_success() {
             return Err(Error::DownloadFailed(format!(
@@ -67,7 +313,7 @@ impl ParallelDownloader {
         
         if !supports_range || total_size < CHUNK_SIZE {
             info!("Server doesn't support range requests or file too small, using single-threaded download");
-            return self.download_single_threaded(url, destination, total_size).await;
+            return self.download_single_threaded(target, destination, total_size).await;
         }
         
         info!("Downloading {} bytes in parallel chunks", total_size);
@@ -91,12 +337,13 @@ impl ParallelDownloader {
             let end = std::cmp::min(start + CHUNK_SIZE - 1, total_size - 1);
             
             let client = self.client.clone();
-            let url = url.to_string();
+            let target = target.clone();
             let destination = destination.to_path_buf();
+            let watch = self.watch.clone();
             let permit = semaphore.clone().acquire_owned().await.unwrap();
             
             futures.push(tokio::spawn(async move {
-                let result = download_chunk(&client, &url, &destination, start, end, chunk_index).await;
+                let result = download_chunk(&client, &target, &destination, start..=end, chunk_index, &watch).await;
                 drop(permit);
                 result
             }));
@@ -122,13 +369,34 @@ impl ParallelDownloader {
     
     async fn download_single_threaded(
         &self,
-        url: &str,
+        target: &MirrorRequest,
         destination: &Path,
         total_size: u64,
     ) -> Result<PathBuf> {
-        debug!("Single-threaded download: {}", url);
+        debug!("Single-threaded download: {}", target);
         
-        let response = self.client.get(url).send().await?;
+        // Without range support a stalled transfer can only start over
+        let mut attempt = 0;
+        loop {
+            match self.try_download_single_threaded(target, destination, total_size).await {
+                Err(e @ Error::Stalled(_)) => {
+                    attempt += 1;
+                    if !self.watch.retry(target, attempt, &e) {
+                        return Err(e);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+    
+    async fn try_download_single_threaded(
+        &self,
+        target: &MirrorRequest,
+        destination: &Path,
+        total_size: u64,
+    ) -> Result<PathBuf> {
+        let response = send_to_mirror(target, target.get(&self.client), self.watch.timeout).await?;
         
         if !response.status().is_success() {
             return Err(Error::DownloadFailed(format!(
@@ -143,8 +411,7 @@ impl ParallelDownloader {
         let mut stream = response.bytes_stream();
         let mut downloaded: u64 = 0;
         
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
+        while let Some(chunk) = next_chunk(&mut stream, self.watch.timeout).await? {
             file.write_all(&chunk).await?;
             downloaded += chunk.len() as u64;
             
@@ -164,14 +431,42 @@ impl ParallelDownloader {
         &self,
         url: &str,
         destination: &Path,
+    ) -> Result<PathBuf> {
+        self.resume_target(&MirrorRequest::plain(url), destination).await
+    }
+    
+    async fn resume_target(
+        &self,
+        target: &MirrorRequest,
+        destination: &Path,
     ) -> Result<PathBuf> {
         if !destination.exists() {
-            return self.download_file_parallel(url, destination).await;
+            return self.download_target(target, destination).await;
         }
         
+        // Each retry after a stall picks up wherever the previous attempt stopped
+        let mut attempt = 0;
+        loop {
+            match self.try_resume_download(target, destination).await {
+                Err(e @ Error::Stalled(_)) => {
+                    attempt += 1;
+                    if !self.watch.retry(target, attempt, &e) {
+                        return Err(e);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+    
+    async fn try_resume_download(
+        &self,
+        target: &MirrorRequest,
+        destination: &Path,
+    ) -> Result<PathBuf> {
         let current_size = tokio::fs::metadata(destination).await?.len();
         
-        let head_response = self.client.head(url).send().await?;
+        let head_response = send_to_mirror(target, target.head(&self.client), self.watch.timeout).await?;
         let total_size = head_response
             .content_length()
             .ok_or_else(|| Error::DownloadFailed("Content-Length header missing".to_string()))?;
@@ -186,12 +481,8 @@ impl ParallelDownloader {
             current_size, total_size
         );
         
-        let response = self
-            .client
-            .get(url)
-            .header("Range", format!("bytes={}-", current_size))
-            .send()
-            .await?;
+        let request = target.get(&self.client).header("Range", format!("bytes={}-", current_size));
+        let response = send_to_mirror(target, request, self.watch.timeout).await?;
         
         if !response.status().is_success() && response.status().as_u16() != 206 {
             return Err(Error::DownloadFailed(format!(
@@ -209,8 +500,7 @@ impl ParallelDownloader {
         let mut stream = response.bytes_stream();
         let mut downloaded = current_size;
         
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
+        while let Some(chunk) = next_chunk(&mut stream, self.watch.timeout).await? {
             file.write_all(&chunk).await?;
             downloaded += chunk.len() as u64;
             
@@ -229,42 +519,70 @@ impl ParallelDownloader {
 
 async fn download_chunk(
     client: &Client,
-    url: &str,
+    target: &MirrorRequest,
     destination: &Path,
-    start: u64,
-    end: u64,
+    range: RangeInclusive<u64>,
     chunk_index: u64,
+    watch: &StallWatch,
 ) -> Result<()> {
     debug!(
         "Downloading chunk {}: bytes {}-{}",
-        chunk_index, start, end
+        chunk_index, range.start(), range.end()
     );
     
-    let response = client
-        .get(url)
-        .header("Range", format!("bytes={}-{}", start, end))
-        .send()
-        .await?;
+    // Bytes already written stay put; a retry only requests the rest of the chunk
+    let mut offset = *range.start();
+    let mut attempt = 0;
+    loop {
+        match download_range(client, target, destination, &mut offset, *range.end(), watch.timeout).await {
+            Err(e @ Error::Stalled(_)) => {
+                attempt += 1;
+                if !watch.retry(target, attempt, &e) {
+                    return Err(e);
+                }
+            }
+            result => {
+                result?;
+                break;
+            }
+        }
+    }
+    
+    debug!("Chunk {} downloaded successfully", chunk_index);
     
-    if !response.status().is_success() && response.status().as_u16() != 206 {
+    Ok(())
+}
+
+async fn download_range(
+    client: &Client,
+    target: &MirrorRequest,
+    destination: &Path,
+    offset: &mut u64,
+    end: u64,
+    stall_timeout: Duration,
+) -> Result<()> {
+    let request = target.get(client).header("Range", format!("bytes={}-{}", offset, end));
+    let response = send_to_mirror(target, request, stall_timeout).await?;
+    
+    if response.status().as_u16() != 206 {
         return Err(Error::DownloadFailed(format!(
             "HTTP error: {}",
             response.status()
         )));
     }
     
-    let bytes = response.bytes().await?;
-    
     let mut file = OpenOptions::new()
         .write(true)
         .open(destination)
         .await?;
+    file.seek(std::io::SeekFrom::Start(*offset)).await?;
     
-    file.seek(std::io::SeekFrom::Start(start)).await?;
-    file.write_all(&bytes).await?;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = next_chunk(&mut stream, stall_timeout).await? {
+        file.write_all(&chunk).await?;
+        *offset += chunk.len() as u64;
+    }
     file.flush().await?;
     
-    debug!("Chunk {} downloaded successfully", chunk_index);
-    
     Ok(())
 }
@@ -0,0 +1,92 @@
+use crate::downloader::PatchInfo;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{debug, warn};
+
+pub const PATCH_HISTORY_FILE: &str = ".beam-patch-history.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchHistoryEntry {
+    pub id: String,
+    #[serde(default)]
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub applied_at: DateTime<Utc>,
+    pub files_patched: usize,
+    #[serde(default)]
+    pub manual: bool,
+    // The A/B variant of the patch that was applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PatchHistory {
+    pub entries: Vec<PatchHistoryEntry>,
+    // Highest patch list index applied together with every numbered patch below it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_index: Option<u64>,
+}
+
+impl PatchHistory {
+    // A missing or unreadable history starts empty rather than blocking patching
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt patch history {:?}: {}", path, e);
+                PatchHistory::default()
+            }),
+            Err(_) => PatchHistory::default(),
+        }
+    }
+    
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::Error::PatchFailed(format!("Failed to serialize patch history: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+    
+    // The full patch list is replayed on every run, so a patch is only recorded the first time it is seen
+    pub fn contains(&self, id: &str, checksum: Option<&str>) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.id == id && entry.checksum.as_deref() == checksum)
+    }
+    
+    pub fn record(&mut self, entry: PatchHistoryEntry) -> bool {
+        if !entry.manual && self.contains(&entry.id, entry.checksum.as_deref()) {
+            return false;
+        }
+        
+        debug!("Recording patch history entry: {}", entry.id);
+        self.entries.push(entry);
+        true
+    }
+    
+    // Numbered patches up to the last applied index are not downloaded again
+    pub fn is_below_last_index(&self, patch: &PatchInfo) -> bool {
+        matches!((patch.index, self.last_index), (Some(index), Some(last)) if index <= last)
+    }
+    
+    pub fn advance_index(&mut self, index: u64) -> bool {
+        if self.last_index.is_some_and(|last| last >= index) {
+            return false;
+        }
+        debug!("Last applied patch index: {}", index);
+        self.last_index = Some(index);
+        true
+    }
+    
+    // Newest first
+    pub fn recent(&self, limit: usize) -> Vec<PatchHistoryEntry> {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.applied_at));
+        entries.truncate(limit);
+        entries
+    }
+}
@@ -0,0 +1,273 @@
+use crate::downloader::PatchInfo;
+use crate::patch_history::PatchHistory;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const PATCH_QUEUE_FILE: &str = ".beam-patch-queue.json";
+// Completed-patch list written by launchers before the queue existed, one `id checksum` line each
+const LEGACY_STATE_FILE: &str = ".beam-patch-state";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueItemStatus {
+    Pending,
+    Downloading,
+    // Downloaded and verified; applying is next
+    Downloaded,
+    Applying,
+    Done,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub patch: PatchInfo,
+    pub status: QueueItemStatus,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    // Already in the patch history from an earlier run; the run replays it anyway
+    #[serde(default)]
+    pub applied: bool,
+    // Priority of the item's channel, for optional patches
+    #[serde(default)]
+    pub priority: i32,
+    // The download stopped part-way; the file in the patch folder is continued rather than fetched again
+    #[serde(default)]
+    pub partial_download: bool,
+}
+
+impl QueueItem {
+    fn key(&self) -> String {
+        patch_key(&self.patch)
+    }
+}
+
+fn patch_key(patch: &PatchInfo) -> String {
+    format!("{} {}", patch.id(), patch.checksum.as_deref().unwrap_or("-"))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueFile {
+    items: Vec<QueueItem>,
+    // Higher runs first among optional patches; required patches always keep the server's order
+    #[serde(default)]
+    channel_priorities: BTreeMap<String, i32>,
+}
+
+// Patches of the current run in the order they will be applied, kept on disk so the UI can change the
+// queue while the patcher works through it and a crashed run continues where it stopped
+pub struct PatchQueue {
+    path: PathBuf,
+    file: QueueFile,
+}
+
+impl PatchQueue {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let file = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt patch queue {:?}: {}", path, e);
+                QueueFile::default()
+            }),
+            Err(_) => QueueFile::default(),
+        };
+        PatchQueue { path, file }
+    }
+    
+    pub fn save(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.file)
+            .map_err(|e| Error::PatchFailed(format!("Failed to serialize patch queue: {}", e)))?;
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+    
+    // Brings the queue in line with the server's patch list; known patches keep their status
+    pub fn sync(&mut self, patches: &[PatchInfo], history: &PatchHistory) {
+        let mut known: BTreeMap<String, QueueItem> = self.file.items.drain(..).map(|item| (item.key(), item)).collect();
+        let legacy_completed = self.take_legacy_state();
+        
+        self.file.items = patches
+            .iter()
+            .map(|patch| {
+                let key = patch_key(patch);
+                let mut item = known.remove(&key).unwrap_or_else(|| QueueItem {
+                    patch: patch.clone(),
+                    status: QueueItemStatus::Pending,
+                    attempts: 0,
+                    last_error: None,
+                    applied: false,
+                    priority: 0,
+                    partial_download: false,
+                });
+                if legacy_completed.contains(&key) {
+                    item.status = QueueItemStatus::Done;
+                }
+                // A download that was in flight when the launcher died continues its partial file. An interrupted
+                // apply starts over from the verified download: GRF saves are atomic or journaled.
+                match item.status {
+                    QueueItemStatus::Downloading => {
+                        item.status = QueueItemStatus::Pending;
+                        item.partial_download = true;
+                    }
+                    QueueItemStatus::Applying => item.status = QueueItemStatus::Downloaded,
+                    _ => {}
+                }
+                item.patch = patch.clone();
+                item.applied = history.contains(patch.id(), patch.checksum.as_deref());
+                item
+            })
+            .collect();
+        self.sort();
+    }
+    
+    fn take_legacy_state(&self) -> HashSet<String> {
+        let legacy = self.path.with_file_name(LEGACY_STATE_FILE);
+        let Ok(content) = std::fs::read_to_string(&legacy) else {
+            return HashSet::new();
+        };
+        info!("Importing interrupted run from {:?}", legacy);
+        let _ = std::fs::remove_file(&legacy);
+        content.lines().map(|line| line.to_string()).collect()
+    }
+    
+    // Required patches first in list order, then optional ones by channel priority
+    fn sort(&mut self) {
+        let priorities = &self.file.channel_priorities;
+        for item in &mut self.file.items {
+            item.priority = match (&item.patch.channel, item.patch.optional) {
+                (Some(channel), true) => priorities.get(&channel.to_ascii_lowercase()).copied().unwrap_or(0),
+                _ => 0,
+            };
+        }
+        // Stable, so ties keep the server's order
+        self.file.items.sort_by_key(|item| (item.patch.optional, -item.priority));
+    }
+    
+    // Each run gives failed patches a fresh attempt; skipped ones stay skipped until retried
+    pub fn start_run(&mut self) -> usize {
+        let mut resumed = 0;
+        for item in &mut self.file.items {
+            match item.status {
+                QueueItemStatus::Failed => item.status = QueueItemStatus::Pending,
+                QueueItemStatus::Done => resumed += 1,
+                _ => {}
+            }
+        }
+        resumed
+    }
+    
+    pub fn items(&self) -> &[QueueItem] {
+        &self.file.items
+    }
+    
+    // Includes patches whose download was started ahead of their turn
+    pub fn next_pending(&self) -> Option<&QueueItem> {
+        self.file
+            .items
+            .iter()
+            .find(|item| {
+                matches!(
+                    item.status,
+                    QueueItemStatus::Pending | QueueItemStatus::Downloading | QueueItemStatus::Downloaded
+                )
+            })
+    }
+    
+    // Highest index up to which every numbered patch in the queue is done; a failed or skipped one holds it back
+    // so it is offered again
+    pub fn done_through(&self) -> Option<u64> {
+        let mut numbered: Vec<(u64, bool)> = self.file
+            .items
+            .iter()
+            .filter_map(|item| item.patch.index.map(|index| (index, item.status == QueueItemStatus::Done)))
+            .collect();
+        numbered.sort_unstable();
+        numbered.into_iter().take_while(|(_, done)| *done).last().map(|(index, _)| index)
+    }
+    
+    pub fn get(&self, patch: &PatchInfo) -> Option<&QueueItem> {
+        let key = patch_key(patch);
+        self.file.items.iter().find(|item| item.key() == key)
+    }
+    
+    pub fn set_status(&mut self, patch: &PatchInfo, status: QueueItemStatus) {
+        let key = patch_key(patch);
+        if let Some(item) = self.file.items.iter_mut().find(|item| item.key() == key) {
+            if matches!(status, QueueItemStatus::Downloading) {
+                item.attempts += 1;
+            }
+            if matches!(status, QueueItemStatus::Done) {
+                item.last_error = None;
+            }
+            if matches!(status, QueueItemStatus::Downloaded | QueueItemStatus::Done) {
+                item.partial_download = false;
+            }
+            item.status = status;
+        }
+    }
+    
+    pub fn fail(&mut self, patch: &PatchInfo, error: &Error) {
+        let key = patch_key(patch);
+        if let Some(item) = self.file.items.iter_mut().find(|item| item.key() == key) {
+            // A retry after a dropped connection picks the download up where it stopped
+            item.partial_download |= item.status == QueueItemStatus::Downloading;
+            item.status = QueueItemStatus::Failed;
+            item.last_error = Some(error.to_string());
+        }
+    }
+    
+    fn find_mut(&mut self, id: &str) -> Result<&mut QueueItem> {
+        self.file
+            .items
+            .iter_mut()
+            .find(|item| item.patch.id() == id)
+            .ok_or_else(|| Error::PatchFailed(format!("Patch {} is not in the queue", id)))
+    }
+    
+    pub fn retry(&mut self, id: &str) -> Result<()> {
+        let item = self.find_mut(id)?;
+        if matches!(item.status, QueueItemStatus::Failed | QueueItemStatus::Skipped) {
+            item.status = QueueItemStatus::Pending;
+            item.last_error = None;
+        }
+        Ok(())
+    }
+    
+    // Required patches cannot be skipped: later patches may build on them
+    pub fn skip(&mut self, id: &str) -> Result<()> {
+        let item = self.find_mut(id)?;
+        if !item.patch.optional {
+            return Err(Error::PatchFailed(format!("Patch {} is required and cannot be skipped", id)));
+        }
+        if matches!(item.status, QueueItemStatus::Pending | QueueItemStatus::Failed) {
+            item.status = QueueItemStatus::Skipped;
+        }
+        Ok(())
+    }
+    
+    pub fn set_channel_priority(&mut self, channel: &str, priority: i32) {
+        self.file.channel_priorities.insert(channel.to_ascii_lowercase(), priority);
+        self.sort();
+    }
+    
+    // A finished run replays the whole list next time; skips, failed optional patches (for the UI to
+    // show) and channel priorities carry over
+    pub fn finish(mut self) -> Result<()> {
+        self.file.items.retain(|item| matches!(item.status, QueueItemStatus::Skipped | QueueItemStatus::Failed));
+        if self.file.items.is_empty() && self.file.channel_priorities.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        self.save()
+    }
+}
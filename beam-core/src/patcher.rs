@@ -1,176 +1,998 @@
-use crate::{Config, Downloader, Error, Result};
-use beam_formats::{grf::Grf, gpf::Gpf, rgz::Rgz, thor::Thor, beam::BeamArchive};
+use crate::conflicts::{ConflictLog, ConflictResolution, Decision, FileConflict, CONFLICTS_FILE};
+use crate::patch_history::{PatchHistory, PatchHistoryEntry, PATCH_HISTORY_FILE};
+use crate::patch_queue::{PatchQueue, QueueItem, QueueItemStatus, PATCH_QUEUE_FILE};
+use crate::preflight::{self, PatchCheck, PreflightReport};
+use crate::priority::{self, PriorityGuard};
+use crate::progress::{self, ProgressEvent, ProgressReporter};
+use crate::run_snapshot::{PatchPhase, PatchRunSnapshot, RUN_SNAPSHOT_FILE};
+use crate::downloader::PatchInfo;
+use crate::{paths, Config, Downloader, Error, Result};
+use beam_formats::{grf::Grf, gpf::Gpf, rgz::Rgz, thor::{Thor, ThorEntry, ThorTarget}, beam::BeamArchive};
+use beam_formats::beamd::{DeltaKind, DeltaPatch};
+use beam_formats::content_index::ContentHash;
+use beam_formats::progress::OperationControl;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
 
+// Headroom left on the drive so patching never fills it to the last byte
+const FREE_SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PatchReport {
+    pub patched: usize,
+    pub skipped_unchanged: usize,
+    pub patches_resumed: usize,
+    // Files changed on this computer that were kept instead of patched
+    pub kept_local_changes: usize,
+    // Patches of a full run in the order they were attempted; a patch retried after a network error appears twice
+    pub patches: Vec<PatchOutcome>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PatchOutcome {
+    pub id: String,
+    pub description: Option<String>,
+    pub files_patched: usize,
+    pub files_unchanged: usize,
+    pub duration_ms: u64,
+    // The A/B variant of the patch this install was given
+    pub variant: Option<String>,
+    // Set for optional patches that failed without stopping the run
+    pub error: Option<String>,
+}
+
+impl PatchReport {
+    fn record(&mut self, changed: bool) {
+        if changed {
+            self.patched += 1;
+        } else {
+            self.skipped_unchanged += 1;
+        }
+    }
+    
+    fn merge(&mut self, other: &PatchReport) {
+        self.patched += other.patched;
+        self.skipped_unchanged += other.skipped_unchanged;
+        self.patches_resumed += other.patches_resumed;
+        self.kept_local_changes += other.kept_local_changes;
+    }
+}
+
 pub struct Patcher {
     config: Config,
     downloader: Downloader,
     temp_dir: PathBuf,
+    cancel: Arc<AtomicBool>,
+    reporter: Option<ProgressReporter>,
+}
+
+// Per-file progress of the patch being applied, sent whenever the whole percentage changes
+struct ApplyProgress<'a> {
+    reporter: &'a Option<ProgressReporter>,
+    patch: String,
+    done: u64,
+    total: u64,
+    percent: Option<u64>,
+}
+
+impl ApplyProgress<'_> {
+    fn file_done(&mut self, file: &str) {
+        self.done += 1;
+        let percent = self.done * 100 / self.total.max(1);
+        if self.percent == Some(percent) {
+            return;
+        }
+        self.percent = Some(percent);
+        progress::report(self.reporter, ProgressEvent::Applying {
+            patch: self.patch.clone(),
+            file: file.to_string(),
+            files_done: self.done,
+            files_total: self.total,
+        });
+    }
+}
+
+// The GRFs one patch writes to. A patch with a target goes into that GRF alone; otherwise each file goes where the
+// `grf_targets` prefixes route it, and each GRF is opened when its first file comes up.
+struct PatchGrfs {
+    target: Option<PathBuf>,
+    open: Vec<(PathBuf, Grf)>,
+}
+
+// Downloads of upcoming patches that run while earlier ones are applied. At most `limit` transfers run at once,
+// and downloads stay at most twice that many patches ahead of the one being applied so they do not fill the
+// drive. Patches are still applied one at a time in queue order. Dropping it stops whatever is still running.
+struct Prefetcher {
+    limit: usize,
+    semaphore: Arc<Semaphore>,
+    // By patch id
+    running: HashMap<String, (PatchInfo, JoinHandle<Result<()>>)>,
+    // Verified downloads, or the error to report when the patch's turn comes
+    finished: HashMap<String, Result<()>>,
+}
+
+impl Prefetcher {
+    fn new(limit: usize) -> Self {
+        Prefetcher {
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit)),
+            running: HashMap::new(),
+            finished: HashMap::new(),
+        }
+    }
+    
+    // Records finished downloads in the queue, so a crash afterwards keeps them, and starts the next ones
+    async fn advance(
+        &mut self,
+        queue: &mut PatchQueue,
+        downloader: &Downloader,
+        temp_dir: &Path,
+        launcher_version: &str,
+    ) -> Result<()> {
+        let done: Vec<String> = self
+            .running
+            .iter()
+            .filter(|(_, (_, handle))| handle.is_finished())
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in done {
+            let Some((patch, handle)) = self.running.remove(&id) else {
+                continue;
+            };
+            let result = join_download(handle).await;
+            if result.is_ok() {
+                queue.set_status(&patch, QueueItemStatus::Downloaded);
+            }
+            self.finished.insert(id, result);
+        }
+        
+        let upcoming: Vec<QueueItem> = queue
+            .items()
+            .iter()
+            .filter(|item| {
+                matches!(
+                    item.status,
+                    QueueItemStatus::Pending | QueueItemStatus::Downloading | QueueItemStatus::Downloaded
+                )
+            })
+            .take(self.limit * 2)
+            .filter(|item| {
+                item.status == QueueItemStatus::Pending
+                    && !self.running.contains_key(item.patch.id())
+                    && !self.finished.contains_key(item.patch.id())
+                    && !item.patch.requires_newer_launcher(launcher_version)
+            })
+            .cloned()
+            .collect();
+        for item in upcoming {
+            queue.set_status(&item.patch, QueueItemStatus::Downloading);
+            let handle = tokio::spawn(download_and_verify(
+                downloader.clone(),
+                self.semaphore.clone(),
+                item.patch.clone(),
+                temp_dir.join(&item.patch.filename),
+                item.partial_download,
+            ));
+            self.running.insert(item.patch.id().to_string(), (item.patch, handle));
+        }
+        queue.save()
+    }
+    
+    fn contains(&self, patch: &PatchInfo) -> bool {
+        self.running.contains_key(patch.id()) || self.finished.contains_key(patch.id())
+    }
+    
+    // Waits for the download of `patch` if it is still running
+    async fn take(&mut self, patch: &PatchInfo) -> Result<()> {
+        if let Some(result) = self.finished.remove(patch.id()) {
+            return result;
+        }
+        match self.running.remove(patch.id()) {
+            Some((_, handle)) => join_download(handle).await,
+            None => Err(Error::DownloadFailed(format!("{} was not being downloaded", patch.filename))),
+        }
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        for (_, handle) in self.running.values() {
+            handle.abort();
+        }
+    }
+}
+
+async fn join_download(handle: JoinHandle<Result<()>>) -> Result<()> {
+    handle
+        .await
+        .map_err(|e| Error::DownloadFailed(format!("Download task stopped: {}", e)))?
+}
+
+async fn download_and_verify(
+    downloader: Downloader,
+    semaphore: Arc<Semaphore>,
+    patch: PatchInfo,
+    patch_path: PathBuf,
+    partial: bool,
+) -> Result<()> {
+    let _permit = semaphore
+        .acquire_owned()
+        .await
+        .map_err(|e| Error::DownloadFailed(e.to_string()))?;
+    let existing = if partial {
+        tokio::fs::metadata(&patch_path).await.map(|metadata| metadata.len()).unwrap_or(0)
+    } else {
+        0
+    };
+    if existing > 0 {
+        info!("Resuming download of {} at byte {}", patch.filename, existing);
+    }
+    downloader.download_patch(&patch, &patch_path, existing > 0).await?;
+    
+    if let Err(e) = verify_download(&downloader, &patch, &patch_path).await {
+        let _ = tokio::fs::remove_file(&patch_path).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+async fn verify_download(downloader: &Downloader, patch: &PatchInfo, patch_path: &Path) -> Result<()> {
+    if let Some(size) = patch.size {
+        let actual = tokio::fs::metadata(patch_path).await?.len();
+        if actual != size {
+            return Err(Error::PatchFailed(format!(
+                "Size mismatch for {}: expected {} bytes, got {}",
+                patch.filename, size, actual
+            )));
+        }
+    }
+    
+    if let Some(checksum) = &patch.checksum {
+        if !downloader.verify_checksum(patch_path, checksum).await? {
+            return Err(Error::PatchFailed(format!(
+                "Checksum mismatch for {}",
+                patch.filename
+            )));
+        }
+    }
+    Ok(())
+}
+
+// `extract_file` checks each entry's MD5 itself, so one call both decompresses and verifies it
+pub(crate) fn extract_verified(beam: &BeamArchive, filename: &str) -> Result<Vec<u8>> {
+    beam.extract_file(filename).map_err(|e| match e {
+        beam_formats::Error::ChecksumMismatch(_) => Error::PatchFailed(format!("MD5 verification failed for: {}", filename)),
+        e => e.into(),
+    })
 }
 
 impl Patcher {
     pub fn new(config: Config) -> Result<Self> {
         let downloader = Downloader::new(config.clone())?;
-        let temp_dir = std::env::temp_dir().join("beam_patcher");
+        let temp_dir = paths::patch_temp_dir();
         std::fs::create_dir_all(&temp_dir)?;
         
+        if let Some(limits) = &config.patcher.decompression_limits {
+            beam_formats::limits::set_decompression_limits(limits.to_limits());
+        }
+        let table_cache = config.patcher.grf_table_cache.then(|| paths::cache_dir(&config.app.name).join("grf-tables"));
+        beam_formats::table_cache::set_table_cache_dir(table_cache);
+        
         Ok(Patcher {
             config,
             downloader,
             temp_dir,
+            cancel: Arc::new(AtomicBool::new(false)),
+            reporter: None,
         })
     }
     
-    pub async fn run_full_patch(&self) -> Result<()> {
+    // Receives which patch of the run is next, download bytes and the files of each patch as they are applied
+    pub fn with_progress_reporter(mut self, reporter: ProgressReporter) -> Self {
+        self.downloader.set_progress_reporter(reporter.clone());
+        self.reporter = Some(reporter);
+        self
+    }
+    
+    fn apply_progress(&self, patch_path: &Path, files: usize) -> ApplyProgress<'_> {
+        ApplyProgress {
+            reporter: &self.reporter,
+            patch: patch_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+            done: 0,
+            total: files as u64,
+            percent: None,
+        }
+    }
+    
+    // Lets mirrors with a signed URL endpoint authorize downloads for the logged-in player
+    pub fn with_sso_token(mut self, token: Option<String>) -> Self {
+        self.downloader.set_sso_token(token);
+        self
+    }
+    
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancel.clone()
+    }
+    
+    pub fn cancel(&self) {
+        info!("Patch cancellation requested");
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+    
+    fn check_cancelled(&self) -> Result<()> {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+    
+    fn operation_control(&self) -> OperationControl {
+        OperationControl::new()
+            .with_cancel_flag(self.cancel.clone())
+            .with_progress(|item, done, total| {
+                debug!("Writing GRF entry {}/{}: {}", done, total, item);
+            })
+    }
+    
+    fn enter_background_priority(&self) -> Option<PriorityGuard> {
+        self.config.patcher.background_priority.as_ref().map(priority::enter_background)
+    }
+    
+    // Retries transient failures, resuming after the last patch that completed
+    pub async fn run_full_patch(&self) -> Result<PatchReport> {
+        let mut report = PatchReport::default();
+        self.run_full_patch_into(&mut report).await?;
+        Ok(report)
+    }
+    
+    // Like `run_full_patch`, but `report` keeps what was done before a failure
+    pub async fn run_full_patch_into(&self, report: &mut PatchReport) -> Result<()> {
         info!("Starting full patch process");
+        self.check_shared_access()?;
+        let _priority = self.enter_background_priority();
         
-        let patches = self.downloader.download_patch_list().await?;
+        let retry = self.config.patcher.run_retry.clone().unwrap_or_default();
+        let mut attempt = 0;
+        
+        // A run picking up after a crash keeps the original start time
+        let snapshot_path = self.snapshot_path();
+        let mut snapshot = PatchRunSnapshot::load(&snapshot_path).unwrap_or_default();
+        snapshot.phase = PatchPhase::FetchingList;
+        snapshot.save(&snapshot_path)?;
+        
+        loop {
+            match self.run_patch_pass(report, &mut snapshot).await {
+                Ok(()) => break,
+                Err(e) if e.is_transient() && attempt < retry.max_retries => {
+                    attempt += 1;
+                    warn!(
+                        "Patch run failed ({}), resuming in {}s (retry {}/{})",
+                        e, retry.delay_seconds, attempt, retry.max_retries
+                    );
+                    tokio::time::sleep(std::time::Duration::from_secs(retry.delay_seconds)).await;
+                    self.check_cancelled()?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        PatchRunSnapshot::clear(&snapshot_path)?;
+        
+        info!(
+            "All patches applied successfully ({} entries patched, {} already up to date, {} patches resumed)",
+            report.patched, report.skipped_unchanged, report.patches_resumed
+        );
+        Ok(())
+    }
+    
+    async fn run_patch_pass(&self, report: &mut PatchReport, snapshot: &mut PatchRunSnapshot) -> Result<()> {
+        let history_path = self.history_path();
+        let mut history = PatchHistory::load(&history_path);
+        
+        let patches = self.patch_list(&history).await?;
         info!("Found {} patches to apply", patches.len());
         
-        for (idx, patch) in patches.iter().enumerate() {
-            info!("Processing patch {}/{}: {}", idx + 1, patches.len(), patch.filename);
+        let queue_path = self.queue_path();
+        let mut queue = PatchQueue::load(&queue_path);
+        queue.sync(&patches, &history);
+        report.patches_resumed += queue.start_run();
+        queue.save()?;
+        
+        snapshot.total = patches.len();
+        
+        let mut prefetcher = self
+            .config
+            .patcher
+            .concurrent_downloads
+            .filter(|&limit| limit > 1)
+            .map(Prefetcher::new);
+        
+        loop {
+            self.check_cancelled()?;
             
-            let patch_path = self.temp_dir.join(&patch.filename);
+            // Reloaded for every patch so retries, skips and priorities set from the UI take effect mid-run
+            let mut queue = PatchQueue::load(&queue_path);
+            if let Some(prefetcher) = &mut prefetcher {
+                prefetcher.advance(&mut queue, &self.downloader, &self.temp_dir, &self.config.app.version).await?;
+            }
+            let Some(patch) = queue.next_pending().map(|item| item.patch.clone()) else {
+                break;
+            };
+            let position = queue.items().iter().position(|item| item.patch.id() == patch.id()).unwrap_or(0) + 1;
             
-            self.downloader
-                .download_file(&patch.filename, &patch_path)
-                .await?;
+            info!("Processing patch {}/{}: {}", position, queue.items().len(), patch.filename);
+            snapshot.current = position;
+            snapshot.patch = Some(patch.filename.clone());
+            progress::report(&self.reporter, ProgressEvent::PatchStarted {
+                patch: patch.filename.clone(),
+                current: position,
+                total: queue.items().len(),
+            });
             
-            if let Some(checksum) = &patch.checksum {
-                if !self.downloader.verify_checksum(&patch_path, checksum).await? {
-                    return Err(Error::PatchFailed(format!(
-                        "Checksum mismatch for {}",
-                        patch.filename
-                    )));
+            let started = Instant::now();
+            let result = self.process_patch(&patch, &mut queue, snapshot, prefetcher.as_mut()).await;
+            let mut outcome = PatchOutcome {
+                id: patch.id().to_string(),
+                description: patch.description.clone(),
+                files_patched: 0,
+                files_unchanged: 0,
+                duration_ms: started.elapsed().as_millis() as u64,
+                variant: patch.variant.clone(),
+                error: None,
+            };
+            
+            match result {
+                Ok(patch_report) => {
+                    report.merge(&patch_report);
+                    outcome.files_patched = patch_report.patched;
+                    outcome.files_unchanged = patch_report.skipped_unchanged;
+                    report.patches.push(outcome);
+                    queue.set_status(&patch, QueueItemStatus::Done);
+                    queue.save()?;
+                    
+                    let mut changed = history.record(PatchHistoryEntry {
+                        id: patch.id().to_string(),
+                        checksum: patch.checksum.clone(),
+                        description: patch.description.clone(),
+                        applied_at: chrono::Utc::now(),
+                        files_patched: patch_report.patched,
+                        manual: false,
+                        variant: patch.variant.clone(),
+                    });
+                    if let Some(index) = queue.done_through() {
+                        changed |= history.advance_index(index);
+                    }
+                    if changed {
+                        history.save(&history_path)?;
+                    }
+                }
+                Err(e) => {
+                    queue.fail(&patch, &e);
+                    queue.save()?;
+                    outcome.error = Some(e.to_string());
+                    report.patches.push(outcome);
+                    // Optional content must not hold back the rest of the update
+                    if patch.optional && !matches!(e, Error::Cancelled) {
+                        warn!("Optional patch {} failed, continuing: {}", patch.id(), e);
+                        continue;
+                    }
+                    return Err(e);
                 }
             }
+        }
+        
+        // A finished run starts from the top next time; unchanged entries are skipped cheaply anyway
+        PatchQueue::load(&queue_path).finish()?;
+        Ok(())
+    }
+    
+    async fn process_patch(
+        &self,
+        patch: &PatchInfo,
+        queue: &mut PatchQueue,
+        snapshot: &mut PatchRunSnapshot,
+        prefetcher: Option<&mut Prefetcher>,
+    ) -> Result<PatchReport> {
+        let snapshot_path = self.snapshot_path();
+        
+        if patch.requires_newer_launcher(&self.config.app.version) {
+            return Err(Error::UpdateFailed(format!(
+                "Patch {} requires launcher version {} or newer (installed: {}); update the launcher to continue",
+                patch.id(),
+                patch.min_launcher_version.as_deref().unwrap_or_default(),
+                self.config.app.version
+            )));
+        }
+        
+        let patch_path = self.temp_dir.join(&patch.filename);
+        
+        // The queue remembers how far an earlier run got with this patch
+        let (status, partial) = queue
+            .get(patch)
+            .map(|item| (item.status, item.partial_download))
+            .unwrap_or((QueueItemStatus::Pending, false));
+        let downloaded = status == QueueItemStatus::Downloaded && patch_path.exists();
+        
+        // Set when the download was started ahead of this patch's turn, which also verified it
+        let mut verified = false;
+        if let Some(prefetcher) = prefetcher.filter(|prefetcher| prefetcher.contains(patch)) {
+            snapshot.phase = PatchPhase::Downloading;
+            snapshot.save(&snapshot_path)?;
+            prefetcher.take(patch).await?;
+            verified = true;
+        } else if downloaded {
+            info!("Applying {} downloaded by an earlier run", patch.filename);
+        } else {
+            queue.set_status(patch, QueueItemStatus::Downloading);
+            queue.save()?;
+            snapshot.phase = PatchPhase::Downloading;
+            snapshot.save(&snapshot_path)?;
             
-            self.apply_patch(&patch_path).await?;
+            let existing = if partial {
+                tokio::fs::metadata(&patch_path).await.map(|metadata| metadata.len()).unwrap_or(0)
+            } else {
+                0
+            };
+            if existing > 0 {
+                info!("Resuming download of {} at byte {}", patch.filename, existing);
+            }
+            self.downloader
+                .download_patch(patch, &patch_path, existing > 0)
+                .await?;
+        }
+        
+        if !verified {
+            snapshot.phase = PatchPhase::Verifying;
+            snapshot.save(&snapshot_path)?;
             
-            tokio::fs::remove_file(&patch_path).await?;
+            // A bad file is not resumed or reused
+            if let Err(e) = verify_download(&self.downloader, patch, &patch_path).await {
+                let _ = tokio::fs::remove_file(&patch_path).await;
+                return Err(e);
+            }
         }
+        queue.set_status(patch, QueueItemStatus::Downloaded);
+        queue.save()?;
         
-        info!("All patches applied successfully");
-        Ok(())
+        queue.set_status(patch, QueueItemStatus::Applying);
+        queue.save()?;
+        snapshot.phase = PatchPhase::Applying;
+        snapshot.save(&snapshot_path)?;
+        
+        let target = patch.target.as_deref().or_else(|| self.patch_rule(patch.id(), &patch.filename));
+        let patch_report = self.apply_patch_to(&patch_path, target, patch.extract).await?;
+        
+        tokio::fs::remove_file(&patch_path).await?;
+        Ok(patch_report)
     }
     
-    pub async fn apply_patch(&self, patch_path: &Path) -> Result<()> {
+    pub async fn apply_patch(&self, patch_path: &Path) -> Result<PatchReport> {
+        let name = patch_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        self.apply_patch_to(patch_path, self.patch_rule(&name, &name), false).await
+    }
+    
+    async fn apply_patch_to(&self, patch_path: &Path, target: Option<&str>, extract: bool) -> Result<PatchReport> {
         let extension = patch_path
             .extension()
             .and_then(|s| s.to_str())
             .unwrap_or("");
         
         info!("Applying patch: {:?} (type: {})", patch_path, extension);
+        self.check_signature(patch_path, extension)?;
         
-        match extension.to_lowercase().as_str() {
-            "beam" => self.apply_beam_patch(patch_path).await,
-            "thor" => self.apply_thor_patch(patch_path).await,
-            "rgz" => self.apply_rgz_patch(patch_path).await,
-            "gpf" => self.apply_gpf_patch(patch_path).await,
-            _ => {
-                warn!("Unknown patch format: {}", extension);
-                Err(Error::PatchFailed(format!("Unknown patch format: {}", extension)))
+        let mut conflicts = ConflictLog::load(self.conflicts_path());
+        let patch_name = patch_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        conflicts.begin(&patch_name, self.config.patcher.conflict_policy);
+        
+        let report = if extract || self.config.patcher.extract_to_game_dir {
+            self.extract_patch_to_game_dir(patch_path, extension, &mut conflicts)?
+        } else {
+            match extension.to_lowercase().as_str() {
+                "beam" => self.apply_beam_patch(patch_path, target, &mut conflicts).await?,
+                "beamd" => self.apply_delta_patch(patch_path, target, &mut conflicts).await?,
+                "thor" => self.apply_thor_patch(patch_path, target, &mut conflicts).await?,
+                "rgz" => self.apply_rgz_patch(patch_path, target, &mut conflicts).await?,
+                "gpf" => self.apply_gpf_patch(patch_path, target, &mut conflicts).await?,
+                _ => {
+                    warn!("Unknown patch format: {}", extension);
+                    return Err(Error::PatchFailed(format!("Unknown patch format: {}", extension)));
+                }
+            }
+        };
+        conflicts.save()?;
+        Ok(report)
+    }
+    
+    // Signed BEAM patches must verify against a configured key; with `require_signed_patches` unsigned patches
+    // and other formats are refused too. Checked before anything is written.
+    fn check_signature(&self, patch_path: &Path, extension: &str) -> Result<()> {
+        let config = &self.config.patcher;
+        if !config.require_signed_patches && config.patch_signing_keys.is_empty() {
+            return Ok(());
+        }
+        let name = patch_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        if !extension.eq_ignore_ascii_case("beam") {
+            if config.require_signed_patches {
+                return Err(Error::Signature(format!("{} is not a BEAM patch, and only signed patches are accepted", name)));
+            }
+            return Ok(());
+        }
+        
+        let beam = BeamArchive::open(patch_path)?;
+        let Some(signer) = beam.signed_by() else {
+            if config.require_signed_patches {
+                return Err(Error::Signature(format!("{} is not signed", name)));
             }
+            return Ok(());
+        };
+        if !config.signing_keys()?.contains(&signer) {
+            return Err(Error::Signature(format!(
+                "{} is signed with an unknown key ({})",
+                name,
+                beam_formats::beam::encode_key(&signer)
+            )));
         }
+        beam.verify_signature(&signer).map_err(|e| match e {
+            beam_formats::Error::Signature(reason) => Error::Signature(format!("{}: {}", name, reason)),
+            e => e.into(),
+        })?;
+        info!("{} is signed with a trusted key", name);
+        Ok(())
     }
     
-    async fn apply_beam_patch(&self, patch_path: &Path) -> Result<()> {
+    async fn apply_beam_patch(&self, patch_path: &Path, target: Option<&str>, conflicts: &mut ConflictLog) -> Result<PatchReport> {
         info!("Applying BEAM patch with MD5 verification");
         let beam = BeamArchive::open(patch_path)?;
         
-        let grf_path = self.get_grf_path()?;
-        let mut grf = self.open_or_create_grf(&grf_path)?;
+        let mut grfs = self.patch_grfs(target)?;
+        let mut report = PatchReport::default();
+        let files = beam.list_files();
+        let mut progress = self.apply_progress(patch_path, files.len());
         
-        for filename in beam.list_files() {
+        for filename in files {
+            self.check_cancelled()?;
             info!("Extracting and verifying: {}", filename);
             
-            if !beam.verify_file(filename)? {
-                return Err(Error::PatchFailed(format!(
-                    "MD5 verification failed for: {}",
-                    filename
-                )));
-            }
-            
-            let data = beam.extract_file(filename)?;
+            let data = extract_verified(&beam, filename)?;
             info!("Patching file: {} ({} bytes)", filename, data.len());
-            grf.patch_file(filename, &data)?;
+            let (grf_path, grf) = self.grf_for(&mut grfs, filename)?;
+            self.patch_grf_entry(grf, grf_path, filename, &data, conflicts, &mut report)?;
+            progress.file_done(filename);
         }
+        conflicts.ensure_resolved()?;
         
         info!("Saving GRF file table...");
-        grf.save()?;
+        self.save_grfs(&mut grfs)?;
         
         info!("BEAM patch applied successfully with all checksums verified");
-        Ok(())
+        Ok(report)
+    }
+    
+    // Every diff is checked against the file in the GRF before anything is saved, so a client whose copy differs
+    // from the patch's baseline is left untouched
+    async fn apply_delta_patch(&self, patch_path: &Path, target: Option<&str>, conflicts: &mut ConflictLog) -> Result<PatchReport> {
+        let delta = DeltaPatch::open(patch_path)?;
+        let mut grfs = self.patch_grfs(target)?;
+        let mut report = PatchReport::default();
+        let mut progress = self.apply_progress(patch_path, delta.entries().len());
+        
+        for entry in delta.entries() {
+            self.check_cancelled()?;
+            let (grf_path, grf) = self.grf_for(&mut grfs, &entry.filename)?;
+            if entry.kind == DeltaKind::Remove {
+                if grf.remove_file(&entry.filename)? {
+                    info!("Removing file: {}", entry.filename);
+                    report.record(true);
+                }
+            } else {
+                let current = match grf.get_entry(&entry.filename) {
+                    Some(_) => Some(grf.extract_file(&entry.filename)?),
+                    None => None,
+                };
+                match delta.apply_to(entry, current.as_deref())? {
+                    Some(data) => {
+                        info!("Patching file: {} ({} bytes)", entry.filename, data.len());
+                        self.patch_grf_entry(grf, grf_path, &entry.filename, &data, conflicts, &mut report)?;
+                    }
+                    None => report.record(false),
+                }
+            }
+            progress.file_done(&entry.filename);
+        }
+        conflicts.ensure_resolved()?;
+        
+        info!("Saving GRF file table...");
+        self.save_grfs(&mut grfs)?;
+        Ok(report)
     }
     
-    async fn apply_thor_patch(&self, patch_path: &Path) -> Result<()> {
+    async fn apply_thor_patch(&self, patch_path: &Path, target: Option<&str>, conflicts: &mut ConflictLog) -> Result<PatchReport> {
         let thor = Thor::open(patch_path)?;
         
-        let grf_path = self.get_grf_path()?;
-        let mut grf = self.open_or_create_grf(&grf_path)?;
+        // A target from the patch list wins over the one the patch names itself
+        let target = match (target, &thor.target) {
+            (Some(target), _) => Some(target),
+            (None, ThorTarget::Root) => return self.extract_thor_to_game_dir(patch_path, &thor, conflicts),
+            (None, ThorTarget::Grf(grf)) => Some(grf.as_str()),
+            (None, ThorTarget::DefaultGrf) => None,
+        };
+        let mut grfs = self.patch_grfs(target)?;
+        let mut report = PatchReport::default();
+        let mut progress = self.apply_progress(patch_path, thor.get_entries().len());
         
         for entry in thor.get_entries() {
             match entry {
-                beam_formats::thor::ThorEntry::Add { filename, data } => {
+                ThorEntry::Add { filename, data } => {
                     info!("Adding/updating file: {}", filename);
-                    grf.patch_file(filename, data)?;
+                    let (grf_path, grf) = self.grf_for(&mut grfs, filename)?;
+                    self.patch_grf_entry(grf, grf_path, filename, data, conflicts, &mut report)?;
+                    progress.file_done(filename);
                 }
-                beam_formats::thor::ThorEntry::Remove { filename } => {
-                    info!("Removing file: {}", filename);
+                ThorEntry::Remove { filename } => {
+                    let (_, grf) = self.grf_for(&mut grfs, filename)?;
+                    if grf.remove_file(filename)? {
+                        info!("Removing file: {}", filename);
+                        report.record(true);
+                    }
+                    progress.file_done(filename);
                 }
             }
         }
+        conflicts.ensure_resolved()?;
         
         info!("Saving GRF file table...");
-        grf.save()?;
+        self.save_grfs(&mut grfs)?;
+        
+        Ok(report)
+    }
+    
+    // Patches flagged for the filesystem, or every patch with patcher.extract_to_game_dir, land as loose files
+    // next to the client instead of inside a GRF
+    fn extract_patch_to_game_dir(&self, patch_path: &Path, extension: &str, conflicts: &mut ConflictLog) -> Result<PatchReport> {
+        let mut report = PatchReport::default();
         
+        match extension.to_lowercase().as_str() {
+            "beam" => {
+                let beam = BeamArchive::open(patch_path)?;
+                let files = beam.list_files();
+                let mut progress = self.apply_progress(patch_path, files.len());
+                for filename in files {
+                    self.check_cancelled()?;
+                    self.write_game_file(filename, &extract_verified(&beam, filename)?, conflicts, &mut report)?;
+                    progress.file_done(filename);
+                }
+            }
+            "beamd" => {
+                let delta = DeltaPatch::open(patch_path)?;
+                // All diffs are applied in memory first, and folders are only created when writing, so a
+                // mismatching file leaves the folder untouched
+                let mut results = Vec::with_capacity(delta.entries().len());
+                for entry in delta.entries() {
+                    self.check_cancelled()?;
+                    let path = self.resolve_game_path(&entry.filename)?;
+                    let result = if entry.kind == DeltaKind::Remove {
+                        None
+                    } else {
+                        let current = match std::fs::read(&path) {
+                            Ok(data) => Some(data),
+                            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                            Err(e) => return Err(e.into()),
+                        };
+                        delta.apply_to(entry, current.as_deref())?
+                    };
+                    results.push((entry, path, result));
+                }
+                
+                let mut progress = self.apply_progress(patch_path, results.len());
+                for (entry, path, result) in results {
+                    self.check_cancelled()?;
+                    match result {
+                        Some(data) => self.write_game_file(&entry.filename, &data, conflicts, &mut report)?,
+                        None if entry.kind == DeltaKind::Remove => match std::fs::remove_file(&path) {
+                            Ok(()) => report.record(true),
+                            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                            Err(e) => return Err(e.into()),
+                        },
+                        None => report.record(false),
+                    }
+                    progress.file_done(&entry.filename);
+                }
+            }
+            "thor" => return self.extract_thor_to_game_dir(patch_path, &Thor::open(patch_path)?, conflicts),
+            "rgz" => {
+                let rgz = Rgz::open(patch_path)?;
+                let mut progress = self.apply_progress(patch_path, rgz.get_entries().len());
+                for entry in rgz.get_entries() {
+                    self.check_cancelled()?;
+                    match entry {
+                        beam_formats::rgz::RgzEntry::File { name, data } => {
+                            self.write_game_file(name, data, conflicts, &mut report)?;
+                            progress.file_done(name);
+                        }
+                        beam_formats::rgz::RgzEntry::Directory { name } => {
+                            std::fs::create_dir_all(self.resolve_game_path(name)?)?;
+                            progress.file_done(name);
+                        }
+                    }
+                }
+            }
+            "gpf" => {
+                let gpf = Gpf::open(patch_path)?;
+                let files = gpf.list_files();
+                let mut progress = self.apply_progress(patch_path, files.len());
+                for filename in files {
+                    self.check_cancelled()?;
+                    self.write_game_file(filename, &gpf.extract_file(filename)?, conflicts, &mut report)?;
+                    progress.file_done(filename);
+                }
+            }
+            _ => {
+                warn!("Unknown patch format: {}", extension);
+                return Err(Error::PatchFailed(format!("Unknown patch format: {}", extension)));
+            }
+        }
+        conflicts.ensure_resolved()?;
+        
+        Ok(report)
+    }
+    
+    // THOR patches that do not merge into a GRF write loose files below the game directory
+    fn extract_thor_to_game_dir(&self, patch_path: &Path, thor: &Thor, conflicts: &mut ConflictLog) -> Result<PatchReport> {
+        let mut report = PatchReport::default();
+        let mut progress = self.apply_progress(patch_path, thor.get_entries().len());
+        
+        for entry in thor.get_entries() {
+            self.check_cancelled()?;
+            match entry {
+                ThorEntry::Add { filename, data } => {
+                    self.write_game_file(filename, data, conflicts, &mut report)?;
+                    progress.file_done(filename);
+                }
+                ThorEntry::Remove { filename } => {
+                    match std::fs::remove_file(self.resolve_game_path(filename)?) {
+                        Ok(()) => {
+                            info!("Removed file: {}", filename);
+                            report.record(true);
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                    progress.file_done(filename);
+                }
+            }
+        }
+        conflicts.ensure_resolved()?;
+        
+        Ok(report)
+    }
+    
+    // Files that already have this content are left alone, as are files changed on this computer that are kept
+    fn write_game_file(&self, name: &str, data: &[u8], conflicts: &mut ConflictLog, report: &mut PatchReport) -> Result<()> {
+        let path = self.game_file_path(name)?;
+        let existing = std::fs::read(&path).ok();
+        let current = existing.as_deref().filter(|_| conflicts.is_tracked(None, name)).map(ContentHash::of);
+        match conflicts.check(None, name, current, ContentHash::of(data)) {
+            Decision::Write if existing.as_deref() == Some(data) => report.record(false),
+            Decision::Write => {
+                info!("Extracting file: {}", name);
+                std::fs::write(&path, data)?;
+                report.record(true);
+            }
+            Decision::Keep => report.kept_local_changes += 1,
+            Decision::Unresolved => {}
+        }
         Ok(())
     }
     
-    async fn apply_rgz_patch(&self, patch_path: &Path) -> Result<()> {
+    fn patch_grf_entry(
+        &self,
+        grf: &mut Grf,
+        grf_path: &Path,
+        filename: &str,
+        data: &[u8],
+        conflicts: &mut ConflictLog,
+        report: &mut PatchReport,
+    ) -> Result<()> {
+        let grf_name = grf_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let current = if conflicts.is_tracked(Some(&grf_name), filename) && grf.get_entry(filename).is_some() {
+            Some(ContentHash::of_entry(grf, filename)?.0)
+        } else {
+            None
+        };
+        match conflicts.check(Some(&grf_name), filename, current, ContentHash::of(data)) {
+            Decision::Write => report.record(grf.patch_file_if_changed(filename, data)?),
+            Decision::Keep => report.kept_local_changes += 1,
+            Decision::Unresolved => {}
+        }
+        Ok(())
+    }
+    
+    // Where an entry goes below the game directory. Besides rejecting `..` and absolute names, the path must still
+    // resolve inside the game directory, and the file itself must not be a link, so a symlink or junction in the
+    // client folder cannot redirect a write or a removal. Nothing is created on disk.
+    fn resolve_game_path(&self, name: &str) -> Result<PathBuf> {
+        let game_dir = match self.config.app.game_directory.as_deref() {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from("."),
+        };
+        let path = beam_formats::path::confine_to(&game_dir, name)?;
+        
+        if std::fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            return Err(Error::PatchFailed(format!("{} is a link in the game directory", name)));
+        }
+        Ok(path)
+    }
+    
+    // A resolved path with its parent directories created, ready to be written
+    fn game_file_path(&self, name: &str) -> Result<PathBuf> {
+        let path = self.resolve_game_path(name)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+    
+    async fn apply_rgz_patch(&self, patch_path: &Path, target: Option<&str>, conflicts: &mut ConflictLog) -> Result<PatchReport> {
         let rgz = Rgz::open(patch_path)?;
         
-        let grf_path = self.get_grf_path()?;
-        let mut grf = self.open_or_create_grf(&grf_path)?;
+        let mut grfs = self.patch_grfs(target)?;
+        let mut report = PatchReport::default();
+        let mut progress = self.apply_progress(patch_path, rgz.get_entries().len());
         
         for entry in rgz.get_entries() {
             match entry {
                 beam_formats::rgz::RgzEntry::File { name, data } => {
                     info!("Adding file: {}", name);
-                    grf.patch_file(name, data)?;
+                    let (grf_path, grf) = self.grf_for(&mut grfs, name)?;
+                    self.patch_grf_entry(grf, grf_path, name, data, conflicts, &mut report)?;
+                    progress.file_done(name);
                 }
                 beam_formats::rgz::RgzEntry::Directory { name } => {
                     debug!("Creating directory: {}", name);
+                    progress.file_done(name);
                 }
             }
         }
+        conflicts.ensure_resolved()?;
         
         info!("Saving GRF file table...");
-        grf.save()?;
+        self.save_grfs(&mut grfs)?;
         
-        Ok(())
+        Ok(report)
     }
     
-    async fn apply_gpf_patch(&self, patch_path: &Path) -> Result<()> {
+    async fn apply_gpf_patch(&self, patch_path: &Path, target: Option<&str>, conflicts: &mut ConflictLog) -> Result<PatchReport> {
         let gpf = Gpf::open(patch_path)?;
         
-        let grf_path = self.get_grf_path()?;
-        let mut grf = self.open_or_create_grf(&grf_path)?;
+        let mut grfs = self.patch_grfs(target)?;
+        let mut report = PatchReport::default();
+        let files = gpf.list_files();
+        let mut progress = self.apply_progress(patch_path, files.len());
         
-        for filename in gpf.list_files() {
+        for filename in files {
+            self.check_cancelled()?;
             info!("Patching file: {}", filename);
             let data = gpf.extract_file(filename)?;
-            grf.patch_file(filename, &data)?;
+            let (grf_path, grf) = self.grf_for(&mut grfs, filename)?;
+            self.patch_grf_entry(grf, grf_path, filename, &data, conflicts, &mut report)?;
+            progress.file_done(filename);
         }
+        conflicts.ensure_resolved()?;
         
         info!("Saving GRF file table...");
-        grf.save()?;
+        self.save_grfs(&mut grfs)?;
         
-        Ok(())
+        Ok(report)
     }
     
-    pub async fn manual_patch(&self, patch_path: &Path) -> Result<()> {
+    pub async fn manual_patch(&self, patch_path: &Path) -> Result<PatchReport> {
+        self.config.ensure_unlocked("Manual patching")?;
         if !self.config.patcher.allow_manual_patch {
             return Err(Error::PatchFailed(
                 "Manual patching is disabled".to_string()
@@ -178,27 +1000,378 @@ impl Patcher {
         }
         
         info!("Applying manual patch: {:?}", patch_path);
-        self.apply_patch(patch_path).await
+        self.check_shared_access()?;
+        let _priority = self.enter_background_priority();
+        let report = self.apply_patch(patch_path).await?;
+        
+        let history_path = self.history_path();
+        let mut history = PatchHistory::load(&history_path);
+        history.record(PatchHistoryEntry {
+            id: patch_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            checksum: None,
+            description: None,
+            applied_at: chrono::Utc::now(),
+            files_patched: report.patched,
+            manual: true,
+            variant: None,
+        });
+        history.save(&history_path)?;
+        
+        Ok(report)
+    }
+    
+    pub fn patch_history(&self) -> PatchHistory {
+        PatchHistory::load(self.history_path())
+    }
+    
+    // The server's patch list without numbered patches an earlier run got through
+    async fn patch_list(&self, history: &PatchHistory) -> Result<Vec<PatchInfo>> {
+        let mut patches = self.downloader.download_patch_list().await?;
+        let total = patches.len();
+        patches.retain(|patch| !history.is_below_last_index(patch));
+        if patches.len() < total {
+            debug!(
+                "Skipped {} patches up to index {}",
+                total - patches.len(),
+                history.last_index.unwrap_or_default()
+            );
+        }
+        Ok(patches)
+    }
+    
+    // The server's patch list merged with the persisted queue, in the order a run applies it
+    pub async fn queue(&self) -> Result<Vec<QueueItem>> {
+        let history = self.patch_history();
+        let patches = self.patch_list(&history).await?;
+        let mut queue = PatchQueue::load(self.queue_path());
+        queue.sync(&patches, &history);
+        queue.save()?;
+        Ok(queue.items().to_vec())
+    }
+    
+    pub fn retry_queue_item(&self, id: &str) -> Result<()> {
+        let mut queue = PatchQueue::load(self.queue_path());
+        queue.retry(id)?;
+        queue.save()
+    }
+    
+    pub fn skip_queue_item(&self, id: &str) -> Result<()> {
+        let mut queue = PatchQueue::load(self.queue_path());
+        queue.skip(id)?;
+        queue.save()
+    }
+    
+    pub fn set_channel_priority(&self, channel: &str, priority: i32) -> Result<()> {
+        let mut queue = PatchQueue::load(self.queue_path());
+        queue.set_channel_priority(channel, priority);
+        queue.save()
+    }
+    
+    // Files changed on this computer that the last patch would have replaced, waiting for the player's choice
+    pub fn conflicts(&self) -> Vec<FileConflict> {
+        ConflictLog::load(self.conflicts_path()).unresolved()
+    }
+    
+    // Takes effect when the patch is applied again
+    pub fn resolve_conflicts(&self, resolutions: &[ConflictResolution]) -> Result<()> {
+        let mut conflicts = ConflictLog::load(self.conflicts_path());
+        for resolution in resolutions {
+            conflicts.resolve(resolution)?;
+        }
+        conflicts.save()
+    }
+    
+    // Where the last run stopped if it never completed
+    pub fn interrupted_run(&self) -> Option<PatchRunSnapshot> {
+        PatchRunSnapshot::load(self.snapshot_path())
+    }
+    
+    // Checks what the next run needs without changing anything: the patch list, every pending patch on every
+    // mirror (and its checksum on the first mirror that has it) and the GRFs the patches go into. Patches are
+    // streamed through a hash, not saved, so the check downloads them once.
+    pub async fn dry_run(&self) -> Result<PreflightReport> {
+        let mut report = PreflightReport::default();
+        let patches = match self.pending_patches().await {
+            Ok(patches) => patches,
+            Err(e) => {
+                report.patch_list_error = Some(e.to_string());
+                return Ok(report);
+            }
+        };
+        info!("Dry run: checking {} pending patches", patches.len());
+        
+        let mut grfs: Vec<PathBuf> = Vec::new();
+        for patch in &patches {
+            self.check_cancelled()?;
+            let mut check = PatchCheck::new(patch, self.downloader.probe_patch(patch).await);
+            let source = check.mirrors.iter().find(|mirror| mirror.ok).map(|mirror| mirror.mirror.clone());
+            if let (Some(expected), Some(source), true) = (&patch.checksum, source, self.config.patcher.verify_checksums) {
+                match self.downloader.remote_checksum(&source, patch).await {
+                    Ok(actual) => {
+                        let matches = actual.eq_ignore_ascii_case(expected);
+                        check.checksum_ok = Some(matches);
+                        if !matches {
+                            check.error = Some(format!("checksum on {} does not match the list", source));
+                        }
+                    }
+                    Err(e) => check.error = Some(format!("download from {} failed: {}", source, e)),
+                }
+            }
+            
+            if !(patch.extract || self.config.patcher.extract_to_game_dir) {
+                match self.patch_grf_paths(patch) {
+                    Ok(paths) => {
+                        for path in paths {
+                            if !grfs.contains(&path) {
+                                grfs.push(path);
+                            }
+                        }
+                    }
+                    Err(e) => check.error = Some(e.to_string()),
+                }
+            }
+            report.patches.push(check);
+        }
+        report.grfs = grfs.iter().map(|path| preflight::check_grf(path)).collect();
+        
+        for problem in report.problems() {
+            warn!("Dry run: {}", problem);
+        }
+        Ok(report)
+    }
+    
+    // Patches on the server for this channel that have not been applied yet
+    pub async fn pending_patches(&self) -> Result<Vec<PatchInfo>> {
+        let history = self.patch_history();
+        let patches = self.patch_list(&history).await?;
+        Ok(patches
+            .into_iter()
+            .filter(|patch| !history.contains(patch.id(), patch.checksum.as_deref()))
+            .collect())
+    }
+    
+    fn queue_path(&self) -> PathBuf {
+        match &self.config.app.game_directory {
+            Some(game_dir) => Path::new(game_dir).join(PATCH_QUEUE_FILE),
+            None => PathBuf::from(PATCH_QUEUE_FILE),
+        }
+    }
+    
+    fn snapshot_path(&self) -> PathBuf {
+        match &self.config.app.game_directory {
+            Some(game_dir) => Path::new(game_dir).join(RUN_SNAPSHOT_FILE),
+            None => PathBuf::from(RUN_SNAPSHOT_FILE),
+        }
     }
     
-    fn get_grf_path(&self) -> Result<PathBuf> {
-        let grf_filename = &self.config.patcher.target_grf;
+    fn conflicts_path(&self) -> PathBuf {
+        match &self.config.app.game_directory {
+            Some(game_dir) => Path::new(game_dir).join(CONFLICTS_FILE),
+            None => PathBuf::from(CONFLICTS_FILE),
+        }
+    }
+    
+    fn history_path(&self) -> PathBuf {
+        match &self.config.app.game_directory {
+            Some(game_dir) => Path::new(game_dir).join(PATCH_HISTORY_FILE),
+            None => PathBuf::from(PATCH_HISTORY_FILE),
+        }
+    }
+    
+    fn check_shared_access(&self) -> Result<()> {
+        match (self.config.multi_user(), &self.config.app.game_directory) {
+            (Some(_), Some(game_dir)) => crate::multi_user::ensure_shared_write_access(Path::new(game_dir)),
+            _ => Ok(()),
+        }
+    }
+    
+    // Manifest entries may name their own GRF; everything else goes into patcher.target_grf
+    // Targets come from patch lists, archives and `grf_targets`, so they are kept below the game directory and use
+    // its separators
+    fn get_grf_path(&self, target: Option<&str>) -> Result<PathBuf> {
+        let grf_filename = match target {
+            Some(target) => beam_formats::path::sanitize_entry_path(target)?,
+            None => PathBuf::from(&self.config.patcher.target_grf),
+        };
         
-        if let Some(game_dir) = &self.config.app.game_directory {
-            let path = Path::new(game_dir).join(grf_filename);
-            Ok(path)
-        } else {
-            Ok(PathBuf::from(grf_filename))
+        Ok(match &self.config.app.game_directory {
+            Some(game_dir) => Path::new(game_dir).join(grf_filename),
+            None => grf_filename,
+        })
+    }
+    
+    // GRF of the first `grf_targets` rule that names the patch by id or file name
+    fn patch_rule(&self, id: &str, filename: &str) -> Option<&str> {
+        let rules = &self.config.patcher.grf_targets;
+        rules.iter().find(|rule| rule.matches_patch(id, filename)).map(|rule| rule.grf.as_str())
+    }
+    
+    fn routes_by_prefix(&self) -> bool {
+        self.config.patcher.grf_targets.iter().any(|rule| !rule.prefixes.is_empty())
+    }
+    
+    // Opens the target GRF up front, like a single-GRF setup always did; routed GRFs are opened by `grf_for`
+    fn patch_grfs(&self, target: Option<&str>) -> Result<PatchGrfs> {
+        if target.is_none() && self.routes_by_prefix() {
+            return Ok(PatchGrfs { target: None, open: Vec::new() });
+        }
+        let path = self.get_grf_path(target)?;
+        let grf = self.open_or_create_grf(&path)?;
+        Ok(PatchGrfs { target: Some(path.clone()), open: vec![(path, grf)] })
+    }
+    
+    fn grf_for<'a>(&self, grfs: &'a mut PatchGrfs, filename: &str) -> Result<(&'a Path, &'a mut Grf)> {
+        let path = match &grfs.target {
+            Some(path) => path.clone(),
+            None => {
+                let rules = &self.config.patcher.grf_targets;
+                let rule = rules.iter().find(|rule| rule.matches_file(filename));
+                self.get_grf_path(rule.map(|rule| rule.grf.as_str()))?
+            }
+        };
+        let index = match grfs.open.iter().position(|(open, _)| *open == path) {
+            Some(index) => index,
+            None => {
+                let grf = self.open_or_create_grf(&path)?;
+                grfs.open.push((path, grf));
+                grfs.open.len() - 1
+            }
+        };
+        let (path, grf) = &mut grfs.open[index];
+        Ok((path.as_path(), grf))
+    }
+    
+    fn save_grfs(&self, grfs: &mut PatchGrfs) -> Result<()> {
+        for (_, grf) in &mut grfs.open {
+            self.save_grf(grf)?;
+        }
+        Ok(())
+    }
+    
+    // Every GRF the patch may write to; without a target that is `target_grf` and each prefix rule's GRF
+    fn patch_grf_paths(&self, patch: &PatchInfo) -> Result<Vec<PathBuf>> {
+        let target = patch.target.as_deref().or_else(|| self.patch_rule(patch.id(), &patch.filename));
+        let mut paths = vec![self.get_grf_path(target)?];
+        if target.is_none() {
+            for rule in self.config.patcher.grf_targets.iter().filter(|rule| !rule.prefixes.is_empty()) {
+                let path = self.get_grf_path(Some(&rule.grf))?;
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        Ok(paths)
+    }
+    
+    // A rebuild needs room for a second copy of the GRF; without it, patch in place if the user has agreed to that.
+    // With `append_patches` every update is appended in place and the rebuild is left to `defragment_threshold`.
+    fn save_grf(&self, grf: &mut Grf) -> Result<()> {
+        let control = self.operation_control();
+        let path = match grf.path() {
+            Some(path) if path.exists() => path.to_path_buf(),
+            _ => return Ok(grf.save_with(&control)?),
+        };
+        
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let available = fs2::available_space(dir)?;
+        let rebuild_required = std::fs::metadata(&path)?.len()
+            .saturating_add(grf.pending_size())
+            .saturating_add(FREE_SPACE_MARGIN);
+        let in_place_required = grf.pending_size().saturating_add(FREE_SPACE_MARGIN);
+        
+        // 0x101-0x103 archives cannot be written in their own format; the first patch rebuilds them as 0x200,
+        // which every client still in use reads
+        let legacy = grf.version < 0x200;
+        if legacy && available >= rebuild_required {
+            warn!("Rebuilding {:?} as GRF 0x200 to patch it", path);
+            return Ok(grf.convert_version_with(0x200, &control)?);
+        }
+        
+        if !legacy && self.config.patcher.append_patches && available >= in_place_required {
+            grf.save_in_place_with(&control)?;
+            self.defragment_if_needed(grf, &path, &control);
+            return Ok(());
+        }
+        
+        if available >= rebuild_required {
+            return Ok(grf.save_with(&control)?);
+        }
+        
+        if legacy || !self.config.patcher.allow_in_place_apply || available < in_place_required {
+            let hint = if legacy || available < in_place_required {
+                "free up disk space"
+            } else {
+                "free up disk space or allow in-place patching (patcher.allow_in_place_apply)"
+            };
+            return Err(Error::LowDiskSpace(format!(
+                "updating {} needs {} MB free, {} MB available; {}",
+                path.display(),
+                rebuild_required / (1024 * 1024),
+                available / (1024 * 1024),
+                hint
+            )));
+        }
+        
+        warn!(
+            "Only {} MB free, appending to {:?} in place instead of rebuilding it",
+            available / (1024 * 1024),
+            path
+        );
+        Ok(grf.save_in_place_with(&control)?)
+    }
+    
+    // The update is already saved, so a failed or skipped compaction is only logged and tried again next time
+    fn defragment_if_needed(&self, grf: &mut Grf, path: &Path, control: &OperationControl) {
+        let Some(threshold) = self.config.patcher.defragment_threshold else {
+            return;
+        };
+        let Ok(size) = std::fs::metadata(path).map(|metadata| metadata.len()) else {
+            return;
+        };
+        let dead_space = grf.dead_space();
+        if dead_space.saturating_mul(100) < size.saturating_mul(threshold as u64) {
+            return;
+        }
+        
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let available = fs2::available_space(dir).unwrap_or(0);
+        if available < size.saturating_add(FREE_SPACE_MARGIN) {
+            warn!(
+                "{:?} is {} MB dead space, but only {} MB are free to defragment it",
+                path,
+                dead_space / (1024 * 1024),
+                available / (1024 * 1024)
+            );
+            return;
+        }
+        
+        info!("Defragmenting {:?} ({} MB dead space)", path, dead_space / (1024 * 1024));
+        if let Err(e) = grf.defragment_with(control) {
+            warn!("Failed to defragment {:?}: {}", path, e);
         }
     }
     
     fn open_or_create_grf(&self, path: &Path) -> Result<Grf> {
-        if path.exists() {
+        if Grf::recover_interrupted_save(path)? {
+            warn!("Recovered {:?} from an interrupted save", path);
+        }
+        
+        let mut grf = if path.exists() {
             info!("Opening existing GRF: {:?}", path);
-            Ok(Grf::open(path)?)
+            Grf::open(path)?
         } else {
             info!("GRF not found, creating new: {:?}", path);
-            Ok(Grf::create_new(path)?)
+            match &self.config.patcher.new_grf {
+                Some(new_grf) => Grf::create_with(path, &new_grf.to_create_options()?)?,
+                None => Grf::create_new(path)?,
+            }
+        };
+        
+        if let Some(alignment) = self.config.patcher.grf_alignment {
+            grf.set_alignment(alignment)?;
         }
+        grf.set_flag_policy(self.config.patcher.grf_entry_flags)?;
+        
+        Ok(grf)
     }
 }
@@ -0,0 +1,114 @@
+use crate::{Error, Result};
+use std::path::Path;
+
+// Manifest, archive and state-file names for files below the game directory, always with forward slashes.
+// Names that are not valid Unicode (CP949 bytes of Korean clients unpacked on Linux) are decoded as EUC-KR
+// instead of being replaced with U+FFFD, which would make every such file look identical.
+pub fn relative_name(relative: &Path) -> String {
+    let name = match relative.to_str() {
+        Some(name) => name.to_string(),
+        None => decode_os_name(relative),
+    };
+    beam_formats::path::to_manifest_name(&name)
+}
+
+#[cfg(unix)]
+fn decode_os_name(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    
+    beam_formats::path::decode_name(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn decode_os_name(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+// Paths stored in config.yml must round-trip exactly, so a lossy conversion is refused
+pub fn to_config_string(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| Error::InvalidConfig(format!("Path is not valid Unicode: {}", path.display())))
+}
+
+// Where patches are downloaded to before being applied
+pub fn patch_temp_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join("beam_patcher")
+}
+
+// Per-server folder for downloads that outlive a launcher session, outside the game directory so they
+// survive it being deleted or not existing yet
+pub fn cache_dir(app_name: &str) -> std::path::PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("beam-patcher")
+        .join(folder_name(app_name))
+}
+
+// Per-server folder for state that must outlive cache cleanups, such as the install ID
+pub fn data_dir(app_name: &str) -> std::path::PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("beam-patcher")
+        .join(folder_name(app_name))
+}
+
+fn folder_name(app_name: &str) -> String {
+    app_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+// Windows refuses paths over 260 characters unless they are in `\\?\` form, which a deep client tree
+// unpacked below a long install folder easily exceeds. The form turns off `/` and `..` handling, so the
+// path is made absolute first and anything joined to it must use `\`.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> std::path::PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match absolute.to_str() {
+        Some(text) if text.starts_with(r"\\?\") => absolute,
+        Some(text) if text.starts_with(r"\\") => format!(r"\\?\UNC\{}", &text[2..]).into(),
+        Some(text) => format!(r"\\?\{}", text).into(),
+        None => absolute,
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> std::path::PathBuf {
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[cfg(unix)]
+    #[test]
+    fn relative_name_decodes_cp949() {
+        use std::os::unix::ffi::OsStrExt;
+        
+        let mut bytes = b"data/".to_vec();
+        bytes.extend_from_slice(&beam_formats::path::encode_name("유저인터페이스"));
+        bytes.extend_from_slice(b"/a.bmp");
+        let name = relative_name(Path::new(std::ffi::OsStr::from_bytes(&bytes)));
+        assert_eq!(name, "data/유저인터페이스/a.bmp");
+    }
+    
+    #[test]
+    fn config_strings_round_trip_non_ascii() {
+        for dir in ["C:/Игры/Ragnarok Online", "D:/游戏/라그나로크"] {
+            let path = Path::new(dir);
+            assert_eq!(Path::new(&to_config_string(path).unwrap()), path);
+        }
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn config_strings_refuse_non_unicode() {
+        use std::os::unix::ffi::OsStrExt;
+        
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/games/\xb6\xf3\xb1\xd7"));
+        assert!(matches!(to_config_string(path), Err(Error::InvalidConfig(_))));
+    }
+}
@@ -0,0 +1,98 @@
+// Certificate pinning for mirrors. A pin is the SHA-256 of either the server certificate or its public key
+// (SubjectPublicKeyInfo, like HPKP pins), in hex or base64. A public key pin keeps working across certificate
+// renewals that reuse the key.
+use crate::{Error, Result};
+use base64::Engine;
+use reqwest::tls::TlsInfo;
+use reqwest::Response;
+use sha2::{Digest, Sha256};
+
+pub type Pin = [u8; 32];
+
+pub fn parse_pin(pin: &str) -> Result<Pin> {
+    let pin = pin.trim();
+    let pin = pin.strip_prefix("sha256/").unwrap_or(pin);
+    let bytes = if pin.len() == 64 && pin.bytes().all(|b| b.is_ascii_hexdigit()) {
+        (0..32).map(|i| u8::from_str_radix(&pin[i * 2..i * 2 + 2], 16).unwrap_or_default()).collect()
+    } else {
+        base64::engine::general_purpose::STANDARD.decode(pin).unwrap_or_default()
+    };
+    bytes.try_into().map_err(|_| {
+        Error::InvalidConfig(format!("`{}` is not a SHA-256 pin in hex or base64", pin))
+    })
+}
+
+pub fn parse_pins(pins: &[String]) -> Result<Vec<Pin>> {
+    pins.iter().map(|pin| parse_pin(pin)).collect()
+}
+
+// Fails unless the server that answered presented a certificate matching one of `pins`. The client must be
+// built with `tls_info(true)`, otherwise every pinned mirror is rejected.
+pub fn check(response: &Response, pins: &[Pin]) -> Result<()> {
+    if pins.is_empty() {
+        return Ok(());
+    }
+    let host = response.url().host_str().unwrap_or_default();
+    let certificate = response
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(TlsInfo::peer_certificate)
+        .ok_or_else(|| Error::CertificatePin(format!("{} did not answer over HTTPS", host)))?;
+    
+    let certificate_hash: Pin = Sha256::digest(certificate).into();
+    let key_hash: Option<Pin> = public_key_info(certificate).map(|spki| Sha256::digest(spki).into());
+    if pins.iter().any(|pin| *pin == certificate_hash || Some(*pin) == key_hash) {
+        return Ok(());
+    }
+    Err(Error::CertificatePin(format!(
+        "{} presented a certificate that matches none of the mirror's pins",
+        host
+    )))
+}
+
+// The DER SubjectPublicKeyInfo of an X.509 certificate: the seventh field of tbsCertificate, or the sixth when
+// the optional version is left out
+fn public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (certificate, _) = der_element(certificate)?;
+    let (mut tbs, _) = der_element(contents(certificate)?)?;
+    tbs = contents(tbs)?;
+    let mut field = 0;
+    loop {
+        let (element, rest) = der_element(tbs)?;
+        // [0] EXPLICIT version
+        if field == 0 && element[0] != 0xa0 {
+            field += 1;
+        }
+        if field == 6 {
+            return Some(element);
+        }
+        field += 1;
+        tbs = rest;
+    }
+}
+
+// Splits one tag-length-value element off the front of `data`
+fn der_element(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (header, length) = der_length(data)?;
+    let end = header.checked_add(length)?;
+    (end <= data.len()).then(|| data.split_at(end))
+}
+
+fn contents(element: &[u8]) -> Option<&[u8]> {
+    let (header, _) = der_length(element)?;
+    element.get(header..)
+}
+
+// Header size and content length of the element starting `data`
+fn der_length(data: &[u8]) -> Option<(usize, usize)> {
+    let first = *data.get(1)?;
+    if first < 0x80 {
+        return Some((2, first as usize));
+    }
+    let count = (first & 0x7f) as usize;
+    if count == 0 || count > 4 {
+        return None;
+    }
+    let length = data.get(2..2 + count)?.iter().fold(0usize, |length, &b| (length << 8) | b as usize);
+    Some((2 + count, length))
+}
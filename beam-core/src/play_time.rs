@@ -0,0 +1,182 @@
+use crate::config::{HttpConfig, PlayTimeConfig};
+use crate::events::{format_countdown, parse_time, parse_timezone, resolve_local};
+use crate::{Error, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+// When the game may be started, either from `play_time` in the config or from the operator's server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayTimePolicy {
+    // IANA name; window times are wall-clock times in this zone
+    #[serde(default)]
+    pub timezone: Option<String>,
+    // Play is allowed only inside one of these; no windows means any time of day
+    #[serde(default)]
+    pub windows: Vec<PlayWindow>,
+    // Play left today by the server's count, for daily limits the launcher cannot track itself
+    #[serde(default)]
+    pub remaining_minutes: Option<i64>,
+    // Shown to the player while launching is blocked
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayWindow {
+    // Weekdays the window opens on ("Mon", "saturday", ...); empty means every day
+    #[serde(default)]
+    pub days: Vec<String>,
+    pub start: String,
+    // An end at or before `start` closes the next day; "24:00" closes at midnight
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayTimeStatus {
+    pub allowed: bool,
+    // Until the current window closes or the daily allowance is used up; None when play is unrestricted
+    pub seconds_remaining: Option<i64>,
+    pub countdown: Option<String>,
+    // Start of the next window while blocked; None when only the daily allowance is used up
+    pub next_allowed_at: Option<DateTime<Utc>>,
+    pub message: Option<String>,
+}
+
+impl PlayTimePolicy {
+    // The player's access token lets the server answer for this account, e.g. by age
+    pub async fn fetch(http: &HttpConfig, url: &str, token: Option<&str>) -> Result<Self> {
+        info!("Fetching play time policy from: {}", url);
+        
+        let mut request = crate::http::client(http)?.get(url);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed(format!(
+                "Failed to fetch play time policy: HTTP {}",
+                response.status()
+            )));
+        }
+        
+        Ok(response.json().await?)
+    }
+    
+    pub fn validate(&self) -> Result<()> {
+        self.status_at(Utc::now()).map(|_| ())
+    }
+    
+    pub fn status(&self) -> Result<PlayTimeStatus> {
+        self.status_at(Utc::now())
+    }
+    
+    pub fn status_at(&self, now: DateTime<Utc>) -> Result<PlayTimeStatus> {
+        let window = self.current_window(now)?;
+        let quota = self.remaining_minutes.map(|minutes| minutes.max(0) * 60);
+        
+        let (allowed, window_remaining, next_allowed_at) = match window {
+            WindowState::Unrestricted => (true, None, None),
+            WindowState::Open { ends_at } => (true, Some((ends_at - now).num_seconds()), None),
+            WindowState::Closed { opens_at } => (false, None, opens_at),
+        };
+        let allowed = allowed && quota != Some(0);
+        let seconds_remaining = match (window_remaining, quota) {
+            _ if !allowed => None,
+            (Some(window), Some(quota)) => Some(window.min(quota)),
+            (window, quota) => window.or(quota),
+        };
+        
+        Ok(PlayTimeStatus {
+            allowed,
+            seconds_remaining,
+            countdown: seconds_remaining.map(format_countdown),
+            next_allowed_at,
+            message: (!allowed).then(|| self.message.clone()).flatten(),
+        })
+    }
+    
+    fn current_window(&self, now: DateTime<Utc>) -> Result<WindowState> {
+        if self.windows.is_empty() {
+            return Ok(WindowState::Unrestricted);
+        }
+        let tz = parse_timezone(self.timezone.as_deref().unwrap_or("UTC"))?;
+        let today = now.with_timezone(&tz).date_naive();
+        
+        let mut intervals = Vec::new();
+        for window in &self.windows {
+            let days = window
+                .days
+                .iter()
+                .map(|day| day.parse::<Weekday>().map_err(|_| Error::InvalidConfig(format!("Invalid weekday: {}", day))))
+                .collect::<Result<Vec<_>>>()?;
+            let start = parse_time(&window.start)?;
+            let (end, end_offset) = match window.end.as_str() {
+                "24:00" => (NaiveTime::MIN, 1),
+                end => {
+                    let end = parse_time(end)?;
+                    (end, if end <= start { 1 } else { 0 })
+                }
+            };
+            
+            // From yesterday, for a window that opened before midnight, to a week ahead
+            for offset in -1..=7 {
+                let date = today + Duration::days(offset);
+                if !days.is_empty() && !days.contains(&date.weekday()) {
+                    continue;
+                }
+                let opens = resolve_local(tz, date.and_time(start));
+                let closes = resolve_local(tz, (date + Duration::days(end_offset)).and_time(end));
+                if let (Some(opens), Some(closes)) = (opens, closes) {
+                    intervals.push((opens, closes));
+                }
+            }
+        }
+        intervals.sort();
+        
+        // Back-to-back windows such as 18:00-24:00 and 00:00-02:00 count as one
+        let mut merged: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+        for (opens, closes) in intervals {
+            match merged.last_mut() {
+                Some(last) if opens <= last.1 => last.1 = last.1.max(closes),
+                _ => merged.push((opens, closes)),
+            }
+        }
+        
+        Ok(match merged.iter().find(|(opens, closes)| *opens <= now && now < *closes) {
+            Some((_, closes)) => WindowState::Open { ends_at: *closes },
+            None => WindowState::Closed {
+                opens_at: merged.iter().map(|(opens, _)| *opens).find(|opens| *opens > now),
+            },
+        })
+    }
+}
+
+enum WindowState {
+    Unrestricted,
+    Open { ends_at: DateTime<Utc> },
+    Closed { opens_at: Option<DateTime<Utc>> },
+}
+
+// The server's policy wins when `policy_url` is set; if it cannot be reached, the local schedule applies
+pub async fn current_status(http: &HttpConfig, config: &PlayTimeConfig, token: Option<&str>) -> Result<PlayTimeStatus> {
+    if let Some(url) = &config.policy_url {
+        match PlayTimePolicy::fetch(http, url, token).await {
+            Ok(policy) => return policy.status(),
+            Err(e) => warn!("Using the local play time schedule: {}", e),
+        }
+    }
+    config.policy.status()
+}
+
+pub fn check_launch(status: &PlayTimeStatus) -> Result<()> {
+    if status.allowed {
+        return Ok(());
+    }
+    let reason = match (&status.message, status.next_allowed_at) {
+        (Some(message), _) => message.clone(),
+        (None, Some(next)) => format!("Play is not allowed right now; the game can be started again at {}", next.to_rfc3339()),
+        (None, None) => "Today's play time is used up".to_string(),
+    };
+    Err(Error::PlayTimeRestricted(reason))
+}
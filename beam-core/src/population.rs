@@ -0,0 +1,102 @@
+use crate::config::HttpConfig;
+use crate::{paths, Config, Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+pub const POPULATION_HISTORY_FILE: &str = "population-history.json";
+
+// One sample every five minutes covers the day a sparkline shows in 288 entries
+const SAMPLE_INTERVAL_MINUTES: i64 = 5;
+const RETENTION_HOURS: i64 = 24;
+const MAX_SAMPLES: usize = 288;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PopulationSample {
+    pub at: DateTime<Utc>,
+    pub online: u32,
+}
+
+// Player counts seen by this launcher over the last day, oldest first. It lives in the per-user cache, so
+// it only covers the time the launcher was open.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PopulationHistory {
+    samples: VecDeque<PopulationSample>,
+}
+
+impl PopulationHistory {
+    pub fn path(config: &Config) -> PathBuf {
+        paths::cache_dir(&config.app.name).join(POPULATION_HISTORY_FILE)
+    }
+    
+    // A missing or unreadable history starts empty; it is only ever drawn
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt population history {:?}: {}", path, e);
+                PopulationHistory::default()
+            }),
+            Err(_) => PopulationHistory::default(),
+        }
+    }
+    
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(self)
+            .map_err(|e| Error::PatchFailed(format!("Failed to serialize population history: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+    
+    // Keeps at most one sample per interval and returns whether this one was kept. A clock that went back
+    // drops the samples that now lie in the future.
+    pub fn record(&mut self, sample: PopulationSample) -> bool {
+        while self.samples.back().is_some_and(|last| last.at > sample.at) {
+            self.samples.pop_back();
+        }
+        if self.samples.back().is_some_and(|last| sample.at - last.at < Duration::minutes(SAMPLE_INTERVAL_MINUTES)) {
+            return false;
+        }
+        
+        self.samples.push_back(sample);
+        let cutoff = sample.at - Duration::hours(RETENTION_HOURS);
+        while self.samples.front().is_some_and(|first| first.at < cutoff) || self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        true
+    }
+    
+    // The samples of the last `hours`, oldest first
+    pub fn recent(&self, hours: u32) -> Vec<PopulationSample> {
+        let from = Utc::now() - Duration::hours(hours as i64);
+        self.samples.iter().filter(|sample| sample.at >= from).copied().collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PlayerCountResponse {
+    Count(u32),
+    Object { online: u32 },
+}
+
+// `server.player_count_url` answers with a bare number or `{"online": <number>}`
+pub async fn fetch_player_count(http: &HttpConfig, url: &str) -> Result<u32> {
+    let response = crate::http::client(http)?.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(Error::DownloadFailed(format!(
+            "Failed to fetch player count: HTTP {}",
+            response.status()
+        )));
+    }
+    
+    match response.json().await? {
+        PlayerCountResponse::Count(online) | PlayerCountResponse::Object { online } => Ok(online),
+    }
+}
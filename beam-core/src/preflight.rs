@@ -0,0 +1,110 @@
+use crate::downloader::PatchInfo;
+use beam_formats::grf::Grf;
+use serde::Serialize;
+use std::path::Path;
+
+// What a patch run would run into, found without downloading to disk or writing anything
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PreflightReport {
+    // Why the patch list could not be read; no patch is checked then
+    pub patch_list_error: Option<String>,
+    // Patches not applied yet, in list order
+    pub patches: Vec<PatchCheck>,
+    // Every GRF those patches go into
+    pub grfs: Vec<GrfCheck>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchCheck {
+    pub id: String,
+    pub filename: String,
+    pub mirrors: Vec<MirrorCheck>,
+    // Whether the patch as the first working mirror serves it matches the list's SHA-256; None when the list has
+    // no checksum, `verify_checksums` is off or no mirror has the patch
+    pub checksum_ok: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorCheck {
+    pub mirror: String,
+    // The mirror serves the patch, at the listed size when the list has one
+    pub ok: bool,
+    pub size: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrfCheck {
+    pub path: String,
+    // A missing GRF is created by the first patch, so only its folder has to be writable
+    pub exists: bool,
+    pub writable: bool,
+    // Number of files, for a GRF that opens
+    pub files: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl PreflightReport {
+    pub fn ok(&self) -> bool {
+        self.patch_list_error.is_none()
+            && self.patches.iter().all(|patch| patch.error.is_none())
+            && self.grfs.iter().all(|grf| grf.error.is_none())
+    }
+    
+    // One line per problem found
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems: Vec<String> = self.patch_list_error.iter().map(|e| format!("Patch list: {}", e)).collect();
+        problems.extend(self.patches.iter().filter_map(|patch| patch.error.as_ref().map(|e| format!("{}: {}", patch.id, e))));
+        problems.extend(self.grfs.iter().filter_map(|grf| grf.error.as_ref().map(|e| format!("{}: {}", grf.path, e))));
+        problems
+    }
+}
+
+impl PatchCheck {
+    pub(crate) fn new(patch: &PatchInfo, mirrors: Vec<MirrorCheck>) -> Self {
+        let error = (!mirrors.iter().any(|mirror| mirror.ok)).then(|| {
+            let details: Vec<String> = mirrors
+                .iter()
+                .map(|mirror| format!("{} ({})", mirror.mirror, mirror.error.as_deref().unwrap_or("unavailable")))
+                .collect();
+            format!("no mirror serves the patch: {}", details.join(", "))
+        });
+        PatchCheck {
+            id: patch.id().to_string(),
+            filename: patch.filename.clone(),
+            mirrors,
+            checksum_ok: None,
+            error,
+        }
+    }
+}
+
+// Opens the GRF read-only and checks it could be written, without changing it
+pub(crate) fn check_grf(path: &Path) -> GrfCheck {
+    let mut check = GrfCheck {
+        path: path.display().to_string(),
+        exists: path.exists(),
+        writable: false,
+        files: None,
+        error: None,
+    };
+    if !check.exists {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        check.writable = std::fs::metadata(dir).is_ok_and(|metadata| metadata.is_dir() && !metadata.permissions().readonly());
+        if !check.writable {
+            check.error = Some(format!("cannot create the GRF in {}", dir.display()));
+        }
+        return check;
+    }
+    
+    check.writable = std::fs::OpenOptions::new().write(true).open(path).is_ok();
+    match Grf::open(path) {
+        Ok(grf) => check.files = Some(grf.file_count()),
+        Err(e) => check.error = Some(format!("does not open: {}", e)),
+    }
+    if check.error.is_none() && !check.writable {
+        check.error = Some("is not writable".to_string());
+    }
+    check
+}
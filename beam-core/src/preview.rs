@@ -0,0 +1,20 @@
+use crate::{Error, Result};
+use base64::Engine;
+use beam_formats::raster::RgbaImage;
+use std::io::Cursor;
+
+// Lossless PNG so palette swatches and minimap cells keep their exact colours
+pub fn png_data_url(image: &RgbaImage) -> Result<String> {
+    let buffer = image::RgbaImage::from_raw(image.width, image.height, image.pixels.clone())
+        .ok_or_else(|| Error::InvalidConfig("Preview image has an invalid pixel buffer".to_string()))?;
+    
+    let mut encoded = Vec::new();
+    buffer
+        .write_to(&mut Cursor::new(&mut encoded), image::ImageOutputFormat::Png)
+        .map_err(|e| Error::InvalidConfig(format!("Failed to encode preview: {}", e)))?;
+    
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(encoded)
+    ))
+}
@@ -0,0 +1,117 @@
+use crate::config::BackgroundPriorityConfig;
+use tracing::{info, warn};
+
+// Lowered for the lifetime of the guard. Windows restores the old priority on drop; elsewhere the CPU
+// priority stays low until the process exits because raising it again needs root.
+#[derive(Default)]
+pub struct PriorityGuard {
+    #[cfg(target_os = "windows")]
+    restore: Option<u32>,
+}
+
+pub fn enter_background(config: &BackgroundPriorityConfig) -> PriorityGuard {
+    if !config.cpu && !config.io {
+        return PriorityGuard::default();
+    }
+    info!("Lowering patcher priority (cpu: {}, io: {})", config.cpu, config.io);
+    imp::enter(config)
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for PriorityGuard {
+    fn drop(&mut self) {
+        if let Some(class) = self.restore {
+            imp::set_priority_class(class);
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use super::*;
+    use std::ffi::c_void;
+    
+    const NORMAL_PRIORITY_CLASS: u32 = 0x0000_0020;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    // Background mode lowers CPU, I/O and memory priority together; only valid for the current process
+    const PROCESS_MODE_BACKGROUND_BEGIN: u32 = 0x0010_0000;
+    const PROCESS_MODE_BACKGROUND_END: u32 = 0x0020_0000;
+    
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn GetPriorityClass(process: *mut c_void) -> u32;
+        fn SetPriorityClass(process: *mut c_void, class: u32) -> i32;
+    }
+    
+    pub(super) fn set_priority_class(class: u32) -> bool {
+        // SAFETY: the pseudo handle of the current process is always valid and needs no closing
+        unsafe { SetPriorityClass(GetCurrentProcess(), class) != 0 }
+    }
+    
+    pub(super) fn enter(config: &BackgroundPriorityConfig) -> PriorityGuard {
+        if config.io {
+            if set_priority_class(PROCESS_MODE_BACKGROUND_BEGIN) {
+                return PriorityGuard { restore: Some(PROCESS_MODE_BACKGROUND_END) };
+            }
+            warn!("Failed to enter background processing mode: {}", std::io::Error::last_os_error());
+        }
+        
+        // SAFETY: see set_priority_class
+        let previous = match unsafe { GetPriorityClass(GetCurrentProcess()) } {
+            0 => NORMAL_PRIORITY_CLASS,
+            class => class,
+        };
+        if set_priority_class(BELOW_NORMAL_PRIORITY_CLASS) {
+            return PriorityGuard { restore: Some(previous) };
+        }
+        warn!("Failed to lower process priority: {}", std::io::Error::last_os_error());
+        PriorityGuard::default()
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod imp {
+    use super::*;
+    use std::process::Command;
+    
+    const NICE_INCREMENT: &str = "10";
+    
+    pub(super) fn enter(config: &BackgroundPriorityConfig) -> PriorityGuard {
+        let ids = thread_ids();
+        
+        if config.cpu {
+            run("renice", &["-n", NICE_INCREMENT, "-p"], &ids);
+        }
+        if config.io {
+            // Lowest best-effort level rather than the idle class, which can starve the patcher while a game loads
+            if cfg!(target_os = "linux") {
+                run("ionice", &["-c", "2", "-n", "7", "-p"], &ids);
+            } else {
+                warn!("I/O priority is not supported on this platform");
+            }
+        }
+        
+        PriorityGuard::default()
+    }
+    
+    // Linux keeps priorities per thread, so every runtime worker that already exists has to be adjusted;
+    // threads started later inherit the setting from the worker that spawns them
+    fn thread_ids() -> Vec<String> {
+        match std::fs::read_dir("/proc/self/task") {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect(),
+            Err(_) => vec![std::process::id().to_string()],
+        }
+    }
+    
+    fn run(program: &str, args: &[&str], ids: &[String]) {
+        match Command::new(program).args(args).args(ids).output() {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => warn!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()),
+            Err(e) => warn!("Could not run {}: {}", program, e),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use serde::Serialize;
+use std::sync::Arc;
+
+// Notifications from a running patch that the UI or CLI may want to surface
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    // A patch of the run is next; `current` counts from 1
+    PatchStarted {
+        patch: String,
+        current: usize,
+        total: usize,
+    },
+    // No bytes arrived for the stall timeout and the transfer is being restarted; `source` is the
+    // mirror name or URL that stalled
+    Stalled {
+        file: String,
+        source: String,
+        attempt: u32,
+    },
+    // A download picked up where an earlier launcher session left off
+    Resuming {
+        file: String,
+        downloaded_bytes: u64,
+        total_bytes: u64,
+    },
+    Downloading {
+        file: String,
+        downloaded_bytes: u64,
+        total_bytes: u64,
+    },
+    // Unpacking a client package; `file` is the archive entry being written
+    Extracting {
+        file: String,
+        extracted_bytes: u64,
+        total_bytes: u64,
+    },
+    // Writing the files of a downloaded patch into the GRF or game directory
+    Applying {
+        patch: String,
+        file: String,
+        files_done: u64,
+        files_total: u64,
+    },
+}
+
+impl ProgressEvent {
+    pub fn message(&self) -> String {
+        match self {
+            ProgressEvent::PatchStarted { patch, current, total } => format!("Patch {}/{}: {}", current, total, patch),
+            ProgressEvent::Stalled { .. } => "Connection stalled — retrying".to_string(),
+            ProgressEvent::Resuming { downloaded_bytes, total_bytes, .. } => {
+                format!("Resuming download — {}% done", percent(*downloaded_bytes, *total_bytes))
+            }
+            // Servers that send no length leave the total at 0
+            ProgressEvent::Downloading { file, total_bytes: 0, .. } => format!("Downloading {}", file),
+            ProgressEvent::Downloading { file, downloaded_bytes, total_bytes } => {
+                format!("Downloading {} — {}%", file, percent(*downloaded_bytes, *total_bytes))
+            }
+            ProgressEvent::Extracting { extracted_bytes, total_bytes, .. } => {
+                format!("Extracting game files — {}%", percent(*extracted_bytes, *total_bytes))
+            }
+            ProgressEvent::Applying { patch, files_done, files_total, .. } => {
+                format!("Applying {} — {}%", patch, percent(*files_done, *files_total))
+            }
+        }
+    }
+}
+
+fn percent(done: u64, total: u64) -> u64 {
+    done * 100 / total.max(1)
+}
+
+pub type ProgressReporter = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+pub(crate) fn report(reporter: &Option<ProgressReporter>, event: ProgressEvent) {
+    if let Some(reporter) = reporter {
+        reporter(&event);
+    }
+}
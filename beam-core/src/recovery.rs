@@ -0,0 +1,145 @@
+use crate::config::PatcherConfig;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryAction {
+    Retry,
+    // Moves the failing primary mirror to the back of the list for the next attempt
+    SwitchMirror,
+    VerifyFiles,
+    FreeDiskSpace,
+    RunAsAdmin,
+    // Opt-in for this session to patch the GRF in place instead of rebuilding it
+    ApplyInPlace,
+    InstallRedistributable,
+    // Lists the files a patch would replace and lets the player keep or replace each
+    ResolveConflicts,
+}
+
+impl RecoveryAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecoveryAction::Retry => "Try again",
+            RecoveryAction::SwitchMirror => "Use another mirror",
+            RecoveryAction::VerifyFiles => "Repair game files",
+            RecoveryAction::FreeDiskSpace => "Free up disk space",
+            RecoveryAction::RunAsAdmin => "Run as administrator",
+            RecoveryAction::ApplyInPlace => "Patch in place",
+            RecoveryAction::InstallRedistributable => "Install missing files",
+            RecoveryAction::ResolveConflicts => "Review changed files",
+        }
+    }
+    
+    pub fn description(&self) -> &'static str {
+        match self {
+            RecoveryAction::Retry => "Run the patcher again; finished patches are not downloaded twice.",
+            RecoveryAction::SwitchMirror => "Download from a different patch mirror.",
+            RecoveryAction::VerifyFiles => "Check the game files and restore damaged ones.",
+            RecoveryAction::FreeDiskSpace => "The drive holding the game is full. Delete some files and try again.",
+            RecoveryAction::RunAsAdmin => "The game folder is not writable by your account. Restart the patcher as administrator.",
+            RecoveryAction::ApplyInPlace => "Append the update to the existing GRF instead of rebuilding it. Needs far less free space, but the GRF keeps growing until it is rebuilt, and an interruption is rolled back from a journal.",
+            RecoveryAction::InstallRedistributable => "Download and install the runtimes the game needs, then press PLAY again.",
+            RecoveryAction::ResolveConflicts => "The update would replace files you changed. Choose which ones to keep, then patching continues.",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoverySuggestion {
+    pub action: RecoveryAction,
+    pub label: String,
+    pub description: String,
+}
+
+impl From<RecoveryAction> for RecoverySuggestion {
+    fn from(action: RecoveryAction) -> Self {
+        RecoverySuggestion {
+            action,
+            label: action.label().to_string(),
+            description: action.description().to_string(),
+        }
+    }
+}
+
+// Most useful fix first; unknown codes and errors that need a config change get nothing
+pub fn recovery_actions(error_code: &str) -> Vec<RecoverySuggestion> {
+    use RecoveryAction::*;
+    
+    let actions: &[RecoveryAction] = match error_code {
+        "network" => &[Retry, SwitchMirror],
+        "download_failed" | "connection_stalled" => &[SwitchMirror, Retry],
+        "disk_full" => &[FreeDiskSpace, Retry],
+        "low_disk_space" => &[FreeDiskSpace, ApplyInPlace],
+        "permission_denied" => &[RunAsAdmin],
+        "corrupt_archive" | "patch_failed" => &[VerifyFiles, Retry],
+        "io" => &[Retry, VerifyFiles],
+        "update_failed" => &[Retry, RunAsAdmin],
+        "migration_failed" => &[Retry, FreeDiskSpace],
+        "auth_failed" | "cancelled" => &[Retry],
+        "missing_dependencies" => &[InstallRedistributable],
+        "file_conflicts" => &[ResolveConflicts],
+        "damaged_files" => &[VerifyFiles],
+        // A tampered patch may come from one mirror only
+        "invalid_signature" => &[SwitchMirror],
+        "certificate_pin_mismatch" => &[SwitchMirror],
+        _ => &[],
+    };
+    
+    actions.iter().copied().map(RecoverySuggestion::from).collect()
+}
+
+pub fn error_recovery_actions(error: &Error) -> Vec<RecoverySuggestion> {
+    recovery_actions(error.code())
+}
+
+// Demotes the mirror currently tried first; returns the name of the new primary mirror
+pub fn switch_mirror(config: &mut PatcherConfig) -> Option<String> {
+    if config.mirrors.len() < 2 {
+        return None;
+    }
+    
+    config.mirrors.sort_by_key(|m| m.priority);
+    config.mirrors.rotate_left(1);
+    for (priority, mirror) in config.mirrors.iter_mut().enumerate() {
+        mirror.priority = priority as u32 + 1;
+    }
+    
+    let primary = config.mirrors[0].name.clone();
+    info!("Switched primary mirror to {}", primary);
+    Some(primary)
+}
+
+// Starts a new elevated instance of the running executable; the caller is expected to exit afterwards
+pub fn relaunch_as_admin() -> Result<()> {
+    let exe = std::env::current_exe()?;
+    
+    #[cfg(target_os = "windows")]
+    {
+        let args: Vec<String> = std::env::args().skip(1).map(|a| format!("'{}'", a.replace('\'', "''"))).collect();
+        let mut command = format!(
+            "Start-Process -FilePath '{}' -Verb RunAs",
+            exe.to_string_lossy().replace('\'', "''")
+        );
+        if !args.is_empty() {
+            command.push_str(&format!(" -ArgumentList {}", args.join(",")));
+        }
+        
+        let status = std::process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &command])
+            .status()?;
+        if !status.success() {
+            return Err(Error::InvalidConfig("Elevation was declined".to_string()));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        Err(Error::InvalidConfig(format!(
+            "Relaunching as administrator is only supported on Windows; restart {} with sudo or fix the game folder permissions",
+            exe.display()
+        )))
+    }
+}
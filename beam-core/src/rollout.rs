@@ -0,0 +1,50 @@
+// Staged rollouts and A/B variants. Every install falls into a fixed spot between 0 and 100 for each rollout key,
+// derived from the install ID, and receives a staged patch once the patch's `rollout` percentage passes that
+// spot. Raising the percentage only adds players, so nobody who already has a patch loses it in the next wave.
+// Variants are chosen from a separate spot, so the installs in a rollout are spread over variants like the rest.
+use crate::downloader::PatchInfo;
+use tracing::info;
+use sha2::{Digest, Sha256};
+
+// Where this install falls in the rollout of `key`, from 0 (inclusive) to 100 (exclusive)
+pub fn bucket(install_id: &str, key: &str) -> f64 {
+    let hash = Sha256::digest(format!("{}:{}", install_id, key).as_bytes());
+    let value = u64::from_be_bytes(hash[..8].try_into().unwrap_or_default());
+    (value % 10_000) as f64 / 100.0
+}
+
+// Whether `install_id` gets `patch` yet; patches without a rollout go to everyone
+pub fn includes(patch: &PatchInfo, install_id: Option<&str>) -> bool {
+    let Some(percentage) = patch.rollout else {
+        return true;
+    };
+    if percentage >= 100.0 {
+        return true;
+    }
+    // Without an install ID the launcher waits for the full rollout
+    install_id.is_some_and(|id| bucket(id, patch.rollout_key()) < percentage)
+}
+
+// `patch` with the variant `install_id` falls into; installs without an ID, or outside every variant's share,
+// get the patch as listed
+pub fn resolve_variant(mut patch: PatchInfo, install_id: Option<&str>) -> PatchInfo {
+    let Some(install_id) = install_id.filter(|_| !patch.variants.is_empty()) else {
+        return patch;
+    };
+    let spot = bucket(install_id, &format!("variant:{}", patch.rollout_key()));
+    let mut end = 0.0;
+    let chosen = patch.variants.iter().find(|variant| {
+        end += variant.weight;
+        spot < end
+    });
+    if let Some(variant) = chosen.cloned() {
+        info!("Using variant {} of {}", variant.name, patch.id());
+        // The id stays, so history and resume state follow the patch whichever build it is
+        patch.id = Some(patch.id().to_string());
+        patch.filename = variant.filename;
+        patch.checksum = variant.checksum;
+        patch.size = variant.size;
+        patch.variant = Some(variant.name);
+    }
+    patch
+}
@@ -0,0 +1,98 @@
+use crate::install_id;
+use crate::patch_history::PatchHistory;
+use crate::patcher::PatchReport;
+use crate::verifier::VerificationResult;
+use crate::{Config, Error, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunKind {
+    Patch,
+    ManualPatch,
+    Verify,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportError {
+    // Same codes the UI keys recovery actions on
+    pub code: String,
+    pub message: String,
+}
+
+// Outcome of one headless run, written with `--output` for fleet tools collecting results from many machines
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub kind: RunKind,
+    // The run finished without errors and, for verify runs, found every file intact
+    pub success: bool,
+    pub machine: Option<String>,
+    // See `install_id`; None when it could not be stored
+    pub install_id: Option<String>,
+    pub launcher_version: String,
+    pub game_directory: Option<String>,
+    // Newest patch in the patch history once the run ended
+    pub latest_patch: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch: Option<PatchReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerificationResult>,
+    pub errors: Vec<ReportError>,
+}
+
+impl RunReport {
+    pub fn start(kind: RunKind, config: &Config) -> Self {
+        let now = Utc::now();
+        RunReport {
+            kind,
+            success: false,
+            machine: std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")).ok(),
+            install_id: install_id::load_or_create(config).ok(),
+            launcher_version: config.app.version.clone(),
+            game_directory: config.app.game_directory.clone(),
+            latest_patch: None,
+            started_at: now,
+            finished_at: now,
+            duration_ms: 0,
+            patch: None,
+            verification: None,
+            errors: Vec::new(),
+        }
+    }
+    
+    pub fn record_error(&mut self, error: &Error) {
+        self.errors.push(ReportError {
+            code: error.code().to_string(),
+            message: error.to_string(),
+        });
+    }
+    
+    pub fn finish(&mut self, history: &PatchHistory) {
+        self.finished_at = Utc::now();
+        self.duration_ms = (self.finished_at - self.started_at).num_milliseconds().max(0) as u64;
+        self.latest_patch = history.recent(1).first().map(|entry| entry.id.clone());
+        
+        let intact = self
+            .verification
+            .as_ref()
+            .map(|v| v.corrupted.is_empty() && v.missing.is_empty())
+            .unwrap_or(true);
+        self.success = self.errors.is_empty() && intact;
+    }
+    
+    // Written to a temporary file first so collectors never pick up half a report
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::PatchFailed(format!("Failed to serialize run report: {}", e)))?;
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::warn;
+
+pub const RUN_SNAPSHOT_FILE: &str = ".beam-patch-run.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchPhase {
+    FetchingList,
+    Downloading,
+    Verifying,
+    Applying,
+}
+
+// Where a patch run was when the launcher last saw it; removed once the run completes, so a snapshot
+// found at startup means the previous launcher crashed, was killed, or stopped on an error
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchRunSnapshot {
+    pub phase: PatchPhase,
+    // 1-based position of `patch` in the patch list
+    pub current: usize,
+    pub total: usize,
+    pub patch: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PatchRunSnapshot {
+    pub fn new() -> Self {
+        let now = Utc::now();
+        PatchRunSnapshot {
+            phase: PatchPhase::FetchingList,
+            current: 0,
+            total: 0,
+            patch: None,
+            started_at: now,
+            updated_at: now,
+        }
+    }
+    
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                warn!("Ignoring corrupt patch run snapshot {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+    
+    // Written next to the target and renamed over, so a crash mid-write keeps the previous snapshot
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        self.updated_at = Utc::now();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::Error::PatchFailed(format!("Failed to serialize patch run snapshot: {}", e)))?;
+        
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+    
+    pub fn clear<P: AsRef<Path>>(path: P) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+    
+    pub fn message(&self) -> String {
+        if self.total == 0 {
+            "Resuming previous update".to_string()
+        } else {
+            format!("Resuming previous update (patch {}/{})", self.current.max(1), self.total)
+        }
+    }
+}
+
+impl Default for PatchRunSnapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
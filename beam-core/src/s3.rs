@@ -0,0 +1,284 @@
+use crate::config::S3MirrorConfig;
+use crate::downloader::PatchInfo;
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, Method, Url};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+// Patch objects are streamed, so their hash is not part of the signature
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+const PATCH_EXTENSIONS: &[&str] = &["thor", "rgz", "gpf", "beam", "beamd"];
+
+#[derive(Debug, Clone)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    pub region: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Object {
+    pub key: String,
+    pub size: u64,
+    pub etag: Option<String>,
+}
+
+pub fn validate(config: &S3MirrorConfig) -> Result<()> {
+    if config.bucket.is_empty() {
+        return Err(Error::InvalidConfig("S3 mirror needs a bucket".to_string()));
+    }
+    if config.access_key_id.is_some() != config.secret_access_key.is_some() {
+        return Err(Error::InvalidConfig(format!(
+            "S3 bucket {} needs both access_key_id and secret_access_key",
+            config.bucket
+        )));
+    }
+    bucket_url(config)?;
+    Ok(())
+}
+
+pub fn credentials(config: &S3MirrorConfig) -> Option<S3Credentials> {
+    Some(S3Credentials {
+        access_key_id: config.access_key_id.clone()?,
+        secret_access_key: config.secret_access_key.clone()?,
+        session_token: config.session_token.clone(),
+        region: config.region.clone(),
+    })
+}
+
+// URL of `file` below the configured prefix
+pub fn object_url(config: &S3MirrorConfig, file: &str) -> Result<String> {
+    let key = format!("{}{}", config.prefix, file);
+    Ok(format!("{}/{}", bucket_url(config)?, uri_encode(&key, false)))
+}
+
+fn bucket_url(config: &S3MirrorConfig) -> Result<String> {
+    let endpoint = match &config.endpoint {
+        Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+        None => format!("https://s3.{}.amazonaws.com", config.region),
+    };
+    let url = Url::parse(&endpoint)
+        .map_err(|e| Error::InvalidConfig(format!("Invalid S3 endpoint {}: {}", endpoint, e)))?;
+    
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::InvalidConfig(format!("S3 endpoint has no host: {}", endpoint)))?;
+    
+    // Bucket names with dots break TLS for virtual-hosted style, and IP or localhost endpoints
+    // (self-hosted MinIO) have no per-bucket host names, so both always use path style
+    let bare_host = host == "localhost" || host.trim_matches(|c| c == '[' || c == ']').parse::<std::net::IpAddr>().is_ok();
+    if config.path_style || config.bucket.contains('.') || bare_host {
+        return Ok(format!("{}/{}", endpoint, uri_encode(&config.bucket, true)));
+    }
+    
+    let port = url.port().map(|port| format!(":{}", port)).unwrap_or_default();
+    Ok(format!("{}://{}.{}{}", url.scheme(), config.bucket, host, port))
+}
+
+// AWS Signature Version 4 headers for a request without a signed body
+pub fn sign(credentials: &S3Credentials, method: &Method, url: &Url, now: DateTime<Utc>) -> HeaderMap {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, credentials.region);
+    
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let mut canonical_headers = vec![
+        ("host", host),
+        ("x-amz-content-sha256", UNSIGNED_PAYLOAD.to_string()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        canonical_headers.push(("x-amz-security-token", token.clone()));
+    }
+    let signed_headers = canonical_headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+    
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k, true), uri_encode(&v, true)))
+        .collect();
+    query.sort();
+    let canonical_query = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+    
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method.as_str(),
+        url.path(),
+        canonical_query,
+        canonical_headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect::<String>(),
+        signed_headers,
+        UNSIGNED_PAYLOAD
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        amz_date,
+        scope,
+        Sha256::digest(canonical_request.as_bytes())
+    );
+    
+    let key = [date.as_str(), credentials.region.as_str(), "s3", "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", credentials.secret_access_key).into_bytes(), |key, part| hmac(&key, part));
+    let signature: String = hmac(&key, &string_to_sign).iter().map(|b| format!("{:02x}", b)).collect();
+    
+    let mut headers = HeaderMap::new();
+    for (name, value) in &canonical_headers[1..] {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            headers.insert(*name, value);
+        }
+    }
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    );
+    if let Ok(mut value) = HeaderValue::from_str(&authorization) {
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    headers
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+// RFC 3986 encoding as SigV4 expects it; object keys keep their slashes
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+pub async fn list_objects(client: &Client, config: &S3MirrorConfig) -> Result<Vec<S3Object>> {
+    let credentials = credentials(config);
+    let base = bucket_url(config)?;
+    // `/?list-type=2` on a bucket host, `/bucket?list-type=2` in path style
+    let separator = if base.trim_end_matches('/').matches('/').count() > 2 { "" } else { "/" };
+    let mut objects = Vec::new();
+    let mut continuation: Option<String> = None;
+    
+    loop {
+        // Encoded by hand: form encoding would turn spaces into `+`, which S3 signs differently
+        let mut query = vec!["list-type=2".to_string()];
+        if !config.prefix.is_empty() {
+            query.push(format!("prefix={}", uri_encode(&config.prefix, true)));
+        }
+        if let Some(token) = &continuation {
+            query.push(format!("continuation-token={}", uri_encode(token, true)));
+        }
+        let url = Url::parse(&format!("{}{}?{}", base, separator, query.join("&")))
+            .map_err(|e| Error::InvalidConfig(format!("Invalid S3 bucket URL: {}", e)))?;
+        
+        let mut request = client.get(url.clone());
+        if let Some(credentials) = &credentials {
+            request = request.headers(sign(credentials, &Method::GET, &url, Utc::now()));
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed(format!(
+                "Listing bucket {} failed: HTTP {}",
+                config.bucket,
+                response.status()
+            )));
+        }
+        
+        let (page, next) = parse_list_response(&response.text().await?)?;
+        debug!("Listed {} objects in bucket {}", page.len(), config.bucket);
+        objects.extend(page);
+        
+        match next {
+            Some(token) => continuation = Some(token),
+            None => break,
+        }
+    }
+    
+    Ok(objects)
+}
+
+// Patch archives below the prefix in key order, so operators name them 0001_x.thor, 0002_y.thor, ...
+pub async fn list_patches(client: &Client, config: &S3MirrorConfig) -> Result<Vec<PatchInfo>> {
+    info!("Listing patches in bucket {} (prefix {:?})", config.bucket, config.prefix);
+    
+    let mut patches: Vec<PatchInfo> = list_objects(client, config)
+        .await?
+        .into_iter()
+        .filter_map(|object| {
+            let filename = object.key.strip_prefix(&config.prefix)?.to_string();
+            let extension = filename.rsplit('.').next()?.to_ascii_lowercase();
+            if filename.contains('/') || !PATCH_EXTENSIONS.contains(&extension.as_str()) {
+                return None;
+            }
+            // ETags are MD5 or multipart digests, not the SHA-256 the checksum column holds
+            Some(PatchInfo { filename, ..Default::default() })
+        })
+        .collect();
+    patches.sort_by(|a, b| a.filename.cmp(&b.filename));
+    
+    Ok(patches)
+}
+
+fn parse_list_response(content: &str) -> Result<(Vec<S3Object>, Option<String>)> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+    
+    let mut objects = Vec::new();
+    let mut current: Option<S3Object> = None;
+    let mut field = Vec::new();
+    let mut truncated = false;
+    let mut next_token = None;
+    
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| Error::DownloadFailed(format!("Invalid bucket listing: {}", e)))?;
+        
+        match event {
+            Event::Start(e) => {
+                field = e.name().as_ref().to_vec();
+                if field == b"Contents" {
+                    current = Some(S3Object { key: String::new(), size: 0, etag: None });
+                }
+            }
+            Event::Text(text) => {
+                let value = text
+                    .unescape()
+                    .map_err(|e| Error::DownloadFailed(format!("Invalid bucket listing: {}", e)))?
+                    .to_string();
+                match (field.as_slice(), current.as_mut()) {
+                    (b"Key", Some(object)) => object.key = value,
+                    (b"Size", Some(object)) => object.size = value.parse().unwrap_or(0),
+                    (b"ETag", Some(object)) => object.etag = Some(value.trim_matches('"').to_string()),
+                    (b"IsTruncated", None) => truncated = value == "true",
+                    (b"NextContinuationToken", None) => next_token = Some(value),
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                if e.name().as_ref() == b"Contents" {
+                    objects.extend(current.take());
+                }
+                field.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    
+    Ok((objects, next_token.filter(|_| truncated)))
+}
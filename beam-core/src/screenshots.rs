@@ -0,0 +1,154 @@
+use crate::{Error, Result};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+// The client writes screenshots here; the folder name's casing differs between client builds
+pub const SCREENSHOT_DIR: &str = "ScreenShot";
+const SCREENSHOT_EXTENSIONS: &[&str] = &["jpg", "jpeg", "bmp", "png"];
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Screenshot {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+    // `data:image/jpeg;base64,...`, ready for an <img> tag
+    pub thumbnail: Option<String>,
+}
+
+pub fn screenshot_dir(game_dir: &Path) -> PathBuf {
+    if let Ok(entries) = std::fs::read_dir(game_dir) {
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().eq_ignore_ascii_case(SCREENSHOT_DIR) && entry.path().is_dir() {
+                return entry.path();
+            }
+        }
+    }
+    game_dir.join(SCREENSHOT_DIR)
+}
+
+// Newest first; thumbnails that fail to decode are left empty instead of failing the listing
+pub fn list_screenshots(dir: &Path, limit: usize, thumbnail_size: Option<u32>) -> Result<Vec<Screenshot>> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_image = path
+            .extension()
+            .map(|ext| SCREENSHOT_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or(false);
+        
+        if is_image && entry.file_type()?.is_file() {
+            let metadata = entry.metadata()?;
+            files.push((path, metadata.len(), DateTime::<Utc>::from(metadata.modified()?)));
+        }
+    }
+    
+    files.sort_by_key(|(_, _, modified)| std::cmp::Reverse(*modified));
+    files.truncate(limit);
+    
+    Ok(files
+        .into_iter()
+        .map(|(path, size, modified)| {
+            let thumbnail = thumbnail_size.and_then(|max| match thumbnail_data_url(&path, max) {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    warn!("Failed to create thumbnail for {:?}: {}", path, e);
+                    None
+                }
+            });
+            
+            Screenshot {
+                name: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                path,
+                size,
+                modified,
+                thumbnail,
+            }
+        })
+        .collect())
+}
+
+pub fn thumbnail_data_url(path: &Path, max_size: u32) -> Result<String> {
+    let image = image::open(path).map_err(|e| Error::InvalidConfig(format!("Unreadable image: {}", e)))?;
+    let thumbnail = image.thumbnail(max_size, max_size);
+    
+    let mut encoded = Vec::new();
+    thumbnail
+        .to_rgb8()
+        .write_to(&mut Cursor::new(&mut encoded), image::ImageOutputFormat::Jpeg(80))
+        .map_err(|e| Error::InvalidConfig(format!("Failed to encode thumbnail: {}", e)))?;
+    
+    debug!("Thumbnail for {:?}: {} bytes", path, encoded.len());
+    Ok(format!(
+        "data:image/jpeg;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(encoded)
+    ))
+}
+
+// Only bare file names inside the screenshot folder are accepted, so the UI cannot address other files
+pub fn resolve_screenshot(dir: &Path, name: &str) -> Result<PathBuf> {
+    let is_bare_name = Path::new(name).file_name().map(|n| n == name).unwrap_or(false);
+    if !is_bare_name || name.contains(['/', '\\']) {
+        return Err(Error::InvalidConfig(format!("Invalid screenshot name: {}", name)));
+    }
+    
+    let path = dir.join(name);
+    if !path.is_file() {
+        return Err(Error::InvalidConfig(format!("Screenshot not found: {}", name)));
+    }
+    Ok(path)
+}
+
+pub fn copy_screenshot(path: &Path, destination: &Path) -> Result<PathBuf> {
+    let target = if destination.is_dir() {
+        destination.join(path.file_name().unwrap_or_default())
+    } else {
+        destination.to_path_buf()
+    };
+    
+    std::fs::copy(path, &target)?;
+    Ok(target)
+}
+
+#[cfg(target_os = "windows")]
+const OPENER: &str = "explorer";
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const OPENER: &str = "xdg-open";
+
+// Opens a file or folder with the system's default application
+pub fn open_path(path: &Path) -> Result<()> {
+    std::process::Command::new(OPENER).arg(path).spawn()?;
+    Ok(())
+}
+
+// Opens the containing folder with the file selected where the platform supports it
+pub fn reveal_in_folder(path: &Path) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        std::process::Command::new("explorer").arg(select).spawn()?;
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open").arg("-R").arg(path).spawn()?;
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        open_path(path.parent().unwrap_or(path))
+    }
+}
@@ -1,14 +1,32 @@
+use crate::population::{self, PopulationHistory, PopulationSample};
 use crate::{Config, Result};
 use serde::{Deserialize, Serialize};
-use std::net::TcpStream;
+use std::path::PathBuf;
 use std::time::Duration;
-use tracing::{debug, warn};
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 30;
+const DEFAULT_MAX_POLL_INTERVAL_SECS: u64 = 300;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerStatusResult {
     pub login_online: bool,
     pub char_online: bool,
     pub map_online: bool,
+    // Only with `server.player_count_url`; None when it is not set or could not be fetched
+    #[serde(default)]
+    pub players_online: Option<u32>,
+}
+
+impl ServerStatusResult {
+    fn all_offline(&self) -> bool {
+        !self.login_online && !self.char_online && !self.map_online
+    }
+    
+    fn availability(&self) -> (bool, bool, bool) {
+        (self.login_online, self.char_online, self.map_online)
+    }
 }
 
 pub struct ServerChecker {
@@ -24,44 +42,161 @@ impl ServerChecker {
         let server_config = self.config.server.as_ref()
             .ok_or_else(|| crate::Error::InvalidConfig("Server configuration not found".to_string()))?;
         
-        let login_online = self.check_tcp_connection(
-            &server_config.login_server_ip,
-            server_config.login_server_port
-        ).await;
-        
-        let char_online = self.check_tcp_connection(
-            &server_config.char_server_ip,
-            server_config.char_server_port
-        ).await;
-        
-        let map_online = self.check_tcp_connection(
-            &server_config.map_server_ip,
-            server_config.map_server_port
-        ).await;
+        let (login_online, char_online, map_online, players_online) = tokio::join!(
+            self.check_tcp_connection(&server_config.login_server_ip, server_config.login_server_port),
+            self.check_tcp_connection(&server_config.char_server_ip, server_config.char_server_port),
+            self.check_tcp_connection(&server_config.map_server_ip, server_config.map_server_port),
+            self.fetch_player_count(server_config.player_count_url.as_deref()),
+        );
         
         Ok(ServerStatusResult {
             login_online,
             char_online,
             map_online,
+            players_online,
         })
     }
     
+    async fn fetch_player_count(&self, url: Option<&str>) -> Option<u32> {
+        let url = url?;
+        match population::fetch_player_count(&self.config.http, url).await {
+            Ok(online) => Some(online),
+            Err(e) => {
+                warn!("Failed to fetch player count from {}: {}", url, e);
+                None
+            }
+        }
+    }
+    
     async fn check_tcp_connection(&self, ip: &str, port: u16) -> bool {
         let address = format!("{}:{}", ip, port);
         debug!("Checking connection to {}", address);
         
-        match TcpStream::connect_timeout(
-            &address.parse().unwrap(),
-            Duration::from_secs(10)
-        ) {
-            Ok(_) => {
+        match tokio::time::timeout(Duration::from_secs(10), TcpStream::connect((ip, port))).await {
+            Ok(Ok(_)) => {
                 debug!("Successfully connected to {}", address);
                 true
             }
-            Err(e) => {
+            Ok(Err(e)) => {
                 warn!("Failed to connect to {}: {}", address, e);
                 false
             }
+            Err(_) => {
+                warn!("Timed out connecting to {}", address);
+                false
+            }
         }
     }
 }
+
+// Checks the servers in the background and reports only changes, so status widgets subscribe instead of
+// each polling on their own. Polls that find every server down back off exponentially up to
+// `status_poll_max_interval`, so launchers left open through an outage do not pile onto the login server
+// while it restarts, and each wait is jittered so launchers started together drift apart. Player counts are
+// sampled into the population history on the way.
+pub struct StatusPoller {
+    checker: ServerChecker,
+    interval: Duration,
+    max_interval: Duration,
+    failures: u32,
+    last: Option<ServerStatusResult>,
+    history: PopulationHistory,
+    history_path: PathBuf,
+}
+
+impl StatusPoller {
+    pub fn new(config: Config) -> Result<Self> {
+        let server_config = config.server.as_ref()
+            .ok_or_else(|| crate::Error::InvalidConfig("Server configuration not found".to_string()))?;
+        let interval = server_config.status_poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL_SECS).max(1);
+        let max_interval = server_config.status_poll_max_interval.unwrap_or(DEFAULT_MAX_POLL_INTERVAL_SECS).max(interval);
+        
+        let history_path = PopulationHistory::path(&config);
+        
+        Ok(StatusPoller {
+            checker: ServerChecker::new(config),
+            interval: Duration::from_secs(interval),
+            max_interval: Duration::from_secs(max_interval),
+            failures: 0,
+            last: None,
+            history: PopulationHistory::load(&history_path),
+            history_path,
+        })
+    }
+    
+    // The most recent result, None before the first poll
+    pub fn last(&self) -> Option<&ServerStatusResult> {
+        self.last.as_ref()
+    }
+    
+    // Checks once and returns the status when it differs from the previous poll
+    pub async fn poll(&mut self) -> Option<ServerStatusResult> {
+        let status = match self.checker.check_servers().await {
+            Ok(status) => status,
+            Err(e) => {
+                warn!("Server status check failed: {}", e);
+                ServerStatusResult { login_online: false, char_online: false, map_online: false, players_online: None }
+            }
+        };
+        
+        if let Some(online) = status.players_online {
+            let sample = PopulationSample { at: chrono::Utc::now(), online };
+            if self.history.record(sample) {
+                if let Err(e) = self.history.save(&self.history_path) {
+                    warn!("Failed to save population history: {}", e);
+                }
+            }
+        }
+        
+        if status.all_offline() {
+            self.failures = self.failures.saturating_add(1);
+        } else {
+            self.failures = 0;
+        }
+        
+        if self.last.as_ref() == Some(&status) {
+            return None;
+        }
+        // Player counts change all the time, so only servers going up or down are worth the log line
+        if self.last.as_ref().map(ServerStatusResult::availability) != Some(status.availability()) {
+            info!(
+                "Server status changed: login {}, char {}, map {}",
+                status.login_online, status.char_online, status.map_online
+            );
+        }
+        self.last = Some(status.clone());
+        Some(status)
+    }
+    
+    // How long to wait before the next poll
+    pub fn next_delay(&self) -> Duration {
+        let backoff = 2u32.saturating_pow(self.failures.saturating_sub(1).min(16));
+        let delay = self.interval.saturating_mul(backoff).min(self.max_interval);
+        // Up to 20% either way
+        delay.mul_f64(0.8 + 0.4 * random_fraction())
+    }
+    
+    // Polls until the returned future is dropped, calling `on_change` with the first result and every change
+    pub async fn run<F: FnMut(&ServerStatusResult)>(mut self, mut on_change: F) {
+        loop {
+            if let Some(status) = self.poll().await {
+                on_change(&status);
+            }
+            let delay = self.next_delay();
+            if self.failures > 0 {
+                debug!("All servers down, next status check in {:?}", delay);
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+// A value in [0, 1) from the standard library's randomly keyed hasher, which is all jitter needs
+fn random_fraction() -> f64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,167 @@
+use crate::patcher::extract_verified;
+use crate::verifier::{FileEntry, FileManifest};
+use crate::{Error, Result};
+use beam_formats::path::name_key;
+use beam_formats::{beam::BeamArchive, gpf::Gpf, rgz::Rgz, rgz::RgzEntry, thor::Thor, thor::ThorEntry};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+const PATCH_EXTENSIONS: &[&str] = &["beam", "beamd", "thor", "rgz", "gpf"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SimulatedPatch {
+    pub name: String,
+    pub added: usize,
+    pub updated: usize,
+    // Files the patch carries with exactly the content the client already has
+    pub unchanged: usize,
+    // THOR removals of files the client had
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    pub patches: Vec<SimulatedPatch>,
+    // Compared with the baseline
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    // The resulting file set, in the baseline's format so it can be diffed or used as the next baseline
+    pub manifest: FileManifest,
+}
+
+// The patch files in `dir`, in the order they are applied: by file name, as numbered patch waves sort
+pub fn patch_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut patches = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        if path.is_file() && PATCH_EXTENSIONS.contains(&extension.as_str()) {
+            patches.push(path);
+        }
+    }
+    patches.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    Ok(patches)
+}
+
+// Applies `patches` to the client described by `baseline` the way the patcher applies them to a GRF, but only
+// tracks the size and SHA-256 of each file, so no client has to be present
+pub fn simulate(baseline: &FileManifest, patches: &[PathBuf]) -> Result<SimulationReport> {
+    let mut files: BTreeMap<String, FileEntry> = baseline
+        .files
+        .iter()
+        .map(|entry| (name_key(&entry.path), entry.clone()))
+        .collect();
+    
+    let mut report = SimulationReport {
+        patches: Vec::with_capacity(patches.len()),
+        added: Vec::new(),
+        changed: Vec::new(),
+        removed: Vec::new(),
+        manifest: FileManifest { files: Vec::new() },
+    };
+    for path in patches {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        info!("Simulating {}", name);
+        let mut patch = SimulatedPatch { name, ..Default::default() };
+        let mut deletions = Vec::new();
+        for_each_file(path, &mut deletions, &mut |filename, data| {
+            let checksum = format!("{:x}", Sha256::digest(data));
+            let size = data.len() as u64;
+            // A file keeps the spelling it first had, however later patches write its name
+            match files.get_mut(&name_key(filename)) {
+                Some(old) if old.size == size && old.checksum.eq_ignore_ascii_case(&checksum) => patch.unchanged += 1,
+                Some(old) => {
+                    old.checksum = checksum;
+                    old.size = size;
+                    patch.updated += 1;
+                }
+                None => {
+                    files.insert(name_key(filename), FileEntry { path: filename.to_string(), checksum, size });
+                    patch.added += 1;
+                }
+            }
+        })?;
+        for filename in deletions {
+            if files.remove(&name_key(&filename)).is_some() {
+                patch.removed += 1;
+            }
+        }
+        report.patches.push(patch);
+    }
+    
+    let before: BTreeMap<String, &FileEntry> = baseline
+        .files
+        .iter()
+        .map(|entry| (name_key(&entry.path), entry))
+        .collect();
+    for (key, entry) in &files {
+        match before.get(key) {
+            None => report.added.push(entry.path.clone()),
+            Some(old) if !old.checksum.eq_ignore_ascii_case(&entry.checksum) || old.size != entry.size => {
+                report.changed.push(entry.path.clone())
+            }
+            Some(_) => {}
+        }
+    }
+    report.removed = before
+        .iter()
+        .filter(|(key, _)| !files.contains_key(*key))
+        .map(|(_, entry)| entry.path.clone())
+        .collect();
+    report.manifest.files = files.into_values().collect();
+    Ok(report)
+}
+
+// Hands every file a patch would write to `apply`, failing like the patcher would on a broken patch. Removals
+// go to `deletions`.
+fn for_each_file(
+    path: &Path,
+    deletions: &mut Vec<String>,
+    apply: &mut dyn FnMut(&str, &[u8]),
+) -> Result<()> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "beam" => {
+            let beam = BeamArchive::open(path)?;
+            for filename in beam.list_files() {
+                apply(filename, &extract_verified(&beam, filename)?);
+            }
+        }
+        "thor" => {
+            let thor = Thor::open(path)?;
+            for entry in thor.get_entries() {
+                match entry {
+                    ThorEntry::Add { filename, data } => apply(filename, data),
+                    ThorEntry::Remove { filename } => deletions.push(filename.clone()),
+                }
+            }
+        }
+        "rgz" => {
+            for entry in Rgz::open(path)?.get_entries() {
+                if let RgzEntry::File { name, data } = entry {
+                    apply(name, data);
+                }
+            }
+        }
+        "gpf" => {
+            let gpf = Gpf::open(path)?;
+            for filename in gpf.list_files() {
+                apply(filename, &gpf.extract_file(filename)?);
+            }
+        }
+        // Diffs need the files themselves, and only checksums are tracked here
+        "beamd" => {
+            return Err(Error::PatchFailed(format!(
+                "{}: BEAMD delta patches cannot be simulated",
+                path.display()
+            )))
+        }
+        _ => return Err(Error::PatchFailed(format!("Unknown patch format: {}", extension))),
+    }
+    Ok(())
+}
@@ -1,8 +1,12 @@
 use crate::{Config, Error, Result};
-use reqwest::Client;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
+// Grant type for answering an `mfa_required` response, as used by Auth0 and compatible providers
+pub const DEFAULT_MFA_GRANT_TYPE: &str = "http://auth0.com/oauth/grant-type/mfa-otp";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenResponse {
     pub access_token: String,
@@ -11,6 +15,137 @@ pub struct TokenResponse {
     pub refresh_token: Option<String>,
 }
 
+// Seconds before expiry from which the UI is told to count down
+pub const EXPIRY_WARNING_SECS: i64 = 300;
+// Refresh this long before expiry, or half-way through tokens that live shorter than twice this
+const REFRESH_MARGIN_SECS: i64 = 120;
+
+// A token as held by the launcher, with `expires_in` turned into a point in time
+#[derive(Debug, Clone)]
+pub struct SsoSession {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl SsoSession {
+    pub fn new(token: TokenResponse) -> Self {
+        let issued_at = Utc::now();
+        SsoSession {
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+            issued_at,
+            expires_at: issued_at + Duration::seconds(token.expires_in.min(u32::MAX as u64) as i64),
+        }
+    }
+    
+    pub fn seconds_remaining(&self) -> i64 {
+        (self.expires_at - Utc::now()).num_seconds().max(0)
+    }
+    
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+    
+    pub fn can_refresh(&self) -> bool {
+        self.refresh_token.is_some()
+    }
+    
+    pub fn refresh_due(&self) -> bool {
+        let lifetime = (self.expires_at - self.issued_at).num_seconds();
+        self.seconds_remaining() <= REFRESH_MARGIN_SECS.min(lifetime / 2)
+    }
+}
+
+// Error body of the token endpoint (RFC 6749 section 5.2), plus the MFA fields providers add to it
+#[derive(Debug, Deserialize)]
+struct TokenError {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+    #[serde(default)]
+    mfa_token: Option<String>,
+    #[serde(default, alias = "barcode_uri", alias = "otpauth_uri")]
+    otp_uri: Option<String>,
+}
+
+// The token endpoint wants a one-time code before it hands out a token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaChallenge {
+    pub mfa_token: String,
+    // Set when the account has no authenticator yet and has to add this secret first
+    pub provisioning: Option<TotpProvisioning>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginStep {
+    Authenticated(TokenResponse),
+    OtpRequired(MfaChallenge),
+}
+
+// An `otpauth://totp/Issuer:account?secret=...` provisioning link. Opening it hands the secret to the
+// player's authenticator app; the secret is also shown for typing in by hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotpProvisioning {
+    pub uri: String,
+    pub issuer: Option<String>,
+    pub account: String,
+    pub secret: String,
+    pub digits: u32,
+    pub period: u32,
+    pub algorithm: String,
+}
+
+impl TotpProvisioning {
+    pub fn parse(uri: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::AuthFailed(format!("Invalid TOTP provisioning link: {}", reason));
+        let url = Url::parse(uri).map_err(|e| invalid(&e.to_string()))?;
+        if url.scheme() != "otpauth" || url.host_str() != Some("totp") {
+            return Err(invalid("expected otpauth://totp/"));
+        }
+        
+        let label = urlencoding::decode(url.path().trim_start_matches('/'))
+            .map_err(|e| invalid(&e.to_string()))?
+            .into_owned();
+        let (label_issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim().to_string()),
+            None => (None, label.trim().to_string()),
+        };
+        
+        let mut provisioning = TotpProvisioning {
+            uri: uri.to_string(),
+            issuer: label_issuer,
+            account,
+            secret: String::new(),
+            digits: 6,
+            period: 30,
+            algorithm: "SHA1".to_string(),
+        };
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "secret" => provisioning.secret = value.to_ascii_uppercase(),
+                // The parameter wins over the label prefix when both are given
+                "issuer" => provisioning.issuer = Some(value.into_owned()),
+                "digits" => provisioning.digits = value.parse().map_err(|_| invalid("digits is not a number"))?,
+                "period" => provisioning.period = value.parse().map_err(|_| invalid("period is not a number"))?,
+                "algorithm" => provisioning.algorithm = value.to_ascii_uppercase(),
+                _ => {}
+            }
+        }
+        
+        let base32 = |c: char| c.is_ascii_uppercase() || ('2'..='7').contains(&c) || c == '=';
+        if provisioning.secret.is_empty() || !provisioning.secret.chars().all(base32) {
+            return Err(invalid("secret is missing or not base32"));
+        }
+        if !(6..=8).contains(&provisioning.digits) || provisioning.period == 0 {
+            return Err(invalid("unsupported digits or period"));
+        }
+        Ok(provisioning)
+    }
+}
+
 pub struct SsoClient {
     config: Config,
     client: Client,
@@ -18,9 +153,7 @@ pub struct SsoClient {
 
 impl SsoClient {
     pub fn new(config: Config) -> Result<Self> {
-        let client = Client::builder()
-            .user_agent("Beam-Patcher/1.0")
-            .build()?;
+        let client = crate::http::client(&config.http)?;
         
         Ok(SsoClient { config, client })
     }
@@ -43,7 +176,8 @@ impl SsoClient {
         Ok(url)
     }
     
-    pub async fn exchange_code_for_token(&self, code: &str) -> Result<TokenResponse> {
+    // Accounts with two-factor authentication get an `OtpRequired` step to finish with `submit_otp`
+    pub async fn exchange_code_for_token(&self, code: &str) -> Result<LoginStep> {
         let sso_config = self.config.sso.as_ref()
             .ok_or_else(|| Error::InvalidConfig("SSO not configured".to_string()))?;
         
@@ -56,26 +190,99 @@ impl SsoClient {
             ("redirect_uri", &sso_config.redirect_uri),
         ];
         
+        self.request_token(&params).await
+    }
+    
+    pub async fn submit_otp(&self, challenge: &MfaChallenge, otp: &str) -> Result<TokenResponse> {
+        let sso_config = self.config.sso.as_ref()
+            .ok_or_else(|| Error::InvalidConfig("SSO not configured".to_string()))?;
+        
+        let otp: String = otp.chars().filter(|c| !c.is_whitespace()).collect();
+        if otp.is_empty() || !otp.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::AuthFailed("The code must be the digits shown in your authenticator app".to_string()));
+        }
+        
+        info!("Submitting one-time code");
+        let params = [
+            ("grant_type", sso_config.mfa_grant_type.as_deref().unwrap_or(DEFAULT_MFA_GRANT_TYPE)),
+            ("mfa_token", &challenge.mfa_token),
+            ("otp", &otp),
+            ("client_id", &sso_config.client_id),
+        ];
+        
+        match self.request_token(&params).await {
+            Ok(LoginStep::Authenticated(token)) => Ok(token),
+            Ok(LoginStep::OtpRequired(_)) => Err(Error::AuthFailed("The server asked for another code".to_string())),
+            Err(e) => Err(e),
+        }
+    }
+    
+    // Providers that rotate refresh tokens send a new one; others keep the current one valid
+    pub async fn refresh(&self, session: &SsoSession) -> Result<SsoSession> {
+        let sso_config = self.config.sso.as_ref()
+            .ok_or_else(|| Error::InvalidConfig("SSO not configured".to_string()))?;
+        let refresh_token = session.refresh_token.as_deref()
+            .ok_or_else(|| Error::AuthFailed("The session cannot be refreshed".to_string()))?;
+        
+        info!("Refreshing access token");
+        let params = [
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", &sso_config.client_id),
+        ];
+        
+        match self.request_token(&params).await? {
+            LoginStep::Authenticated(token) => {
+                let mut refreshed = SsoSession::new(token);
+                if refreshed.refresh_token.is_none() {
+                    refreshed.refresh_token = session.refresh_token.clone();
+                }
+                Ok(refreshed)
+            }
+            LoginStep::OtpRequired(_) => Err(Error::AuthFailed("The server asked to log in again".to_string())),
+        }
+    }
+    
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<LoginStep> {
+        let sso_config = self.config.sso.as_ref()
+            .ok_or_else(|| Error::InvalidConfig("SSO not configured".to_string()))?;
+        
         let response = self.client
             .post(&sso_config.token_url)
-            .form(&params)
+            .form(params)
             .send()
             .await?;
         
-        if !response.status().is_success() {
-            return Err(Error::AuthFailed(format!(
-                "Token exchange failed: HTTP {}",
-                response.status()
-            )));
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let Ok(error) = serde_json::from_str::<TokenError>(&body) else {
+                return Err(Error::AuthFailed(format!("Token exchange failed: HTTP {}", status)));
+            };
+            
+            if let ("mfa_required", Some(mfa_token)) = (error.error.as_str(), error.mfa_token) {
+                info!("Token endpoint requires a one-time code");
+                let provisioning = error.otp_uri.as_deref().map(TotpProvisioning::parse).transpose()?;
+                return Ok(LoginStep::OtpRequired(MfaChallenge { mfa_token, provisioning }));
+            }
+            let reason = match error.error.as_str() {
+                "invalid_grant" if params.iter().any(|(key, _)| *key == "otp") => {
+                    "The code was not accepted; check the time on this PC and try the next code".to_string()
+                }
+                "expired_token" => "The sign-in took too long; log in again".to_string(),
+                _ => error.error_description.unwrap_or(error.error),
+            };
+            return Err(Error::AuthFailed(format!("Token exchange failed: {}", reason)));
         }
         
         let token_response: TokenResponse = response.json().await?;
         info!("Successfully obtained access token");
         
-        Ok(token_response)
+        Ok(LoginStep::Authenticated(token_response))
     }
     
-    pub async fn launch_game(&self, token: &str, executable: &str) -> Result<()> {
+    pub async fn launch_game<P: AsRef<std::path::Path>>(&self, token: &str, executable: P) -> Result<()> {
+        let executable = executable.as_ref();
         info!("Launching game with SSO token");
         
         #[cfg(target_os = "windows")]
@@ -0,0 +1,159 @@
+use crate::{paths, Config, Result};
+use beam_formats::grf::Grf;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Sizes in bytes for the disk usage panel. The GRF, log and backup figures are the parts of the game
+// directory total made up of those files; the patch cache lives outside it.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StorageUsage {
+    pub game_directory: u64,
+    pub grf_archives: u64,
+    pub patch_cache: u64,
+    pub logs: u64,
+    pub backups: u64,
+}
+
+pub fn usage(config: &Config) -> Result<StorageUsage> {
+    let mut usage = StorageUsage::default();
+    if let Some(game_dir) = game_directory(config) {
+        walk(&game_dir, &mut |path, size| {
+            usage.game_directory += size;
+            match classify(path) {
+                Some(Kind::Grf) => usage.grf_archives += size,
+                Some(Kind::Log) => usage.logs += size,
+                Some(Kind::Backup) => usage.backups += size,
+                None => {}
+            }
+        })?;
+    }
+    for dir in cache_dirs(config) {
+        walk(&dir, &mut |_, size| usage.patch_cache += size)?;
+    }
+    Ok(usage)
+}
+
+// Downloaded patches and full client volumes; a paused install starts over afterwards. Returns the bytes freed.
+pub fn clear_cache(config: &Config) -> Result<u64> {
+    let mut freed = 0;
+    for dir in cache_dirs(config) {
+        walk(&dir, &mut |_, size| freed += size)?;
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+    }
+    info!("Cleared {} bytes of patch cache", freed);
+    Ok(freed)
+}
+
+// `*.bak` files in the game directory. A `.grf.bak` is only dropped once its GRF opens; otherwise it is
+// restored over the broken archive, as the patcher would on its next start. Returns the bytes freed.
+pub fn delete_backups(config: &Config) -> Result<u64> {
+    let Some(game_dir) = game_directory(config) else {
+        return Ok(0);
+    };
+    let mut backups = Vec::new();
+    walk(&game_dir, &mut |path, size| {
+        if classify(path) == Some(Kind::Backup) {
+            backups.push((path.to_path_buf(), size));
+        }
+    })?;
+    
+    let mut freed = 0;
+    for (backup, size) in backups {
+        let grf = backup.with_extension("");
+        if classify(&grf) == Some(Kind::Grf) {
+            match Grf::recover_interrupted_save(&grf) {
+                Ok(true) => warn!("{:?} was damaged and has been restored from its backup", grf),
+                Ok(false) => freed += size,
+                Err(e) => warn!("Failed to clean up {:?}: {}", backup, e),
+            }
+        } else {
+            fs::remove_file(&backup)?;
+            freed += size;
+        }
+    }
+    info!("Deleted {} bytes of backups", freed);
+    Ok(freed)
+}
+
+// Repacks every GRF in the game directory without the space replaced entries left behind, recompressing entries
+// at `level` when given. Each archive is rebuilt next to itself, so one that fails (e.g. for lack of room) stays
+// as it was. Returns the bytes freed.
+pub fn optimize_grfs(config: &Config, level: Option<u32>) -> Result<u64> {
+    let Some(game_dir) = game_directory(config) else {
+        return Ok(0);
+    };
+    let mut grfs = Vec::new();
+    walk(&game_dir, &mut |path, _| {
+        if classify(path) == Some(Kind::Grf) {
+            grfs.push(path.to_path_buf());
+        }
+    })?;
+    
+    let mut freed = 0;
+    for path in grfs {
+        let result = Grf::recover_interrupted_save(&path).and_then(|_| {
+            let mut grf = Grf::open(&path)?;
+            if let Some(alignment) = config.patcher.grf_alignment {
+                grf.set_alignment(alignment)?;
+            }
+            grf.set_flag_policy(config.patcher.grf_entry_flags)?;
+            grf.repack(level)
+        });
+        match result {
+            Ok(saved) => freed += saved,
+            Err(e) => warn!("Failed to optimize {:?}: {}", path, e),
+        }
+    }
+    info!("Optimizing GRFs freed {} bytes", freed);
+    Ok(freed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Grf,
+    Log,
+    Backup,
+}
+
+fn classify(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    let extension = name.rsplit_once('.').map(|(_, extension)| extension)?;
+    match extension {
+        "grf" => Some(Kind::Grf),
+        "bak" => Some(Kind::Backup),
+        "log" | "dmp" => Some(Kind::Log),
+        _ if name == "errorlog.txt" => Some(Kind::Log),
+        _ => None,
+    }
+}
+
+fn game_directory(config: &Config) -> Option<PathBuf> {
+    config.app.game_directory.as_ref().map(PathBuf::from).filter(|dir| dir.is_dir())
+}
+
+fn cache_dirs(config: &Config) -> [PathBuf; 2] {
+    [paths::patch_temp_dir(), paths::cache_dir(&config.app.name)]
+}
+
+// Calls `visit` with every file below `dir` and its size; a missing `dir` has none
+fn walk(dir: &Path, visit: &mut dyn FnMut(&Path, u64)) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let metadata = fs::symlink_metadata(entry.path())?;
+        if metadata.is_dir() {
+            walk(&entry.path(), visit)?;
+        } else {
+            visit(&entry.path(), metadata.len());
+        }
+    }
+    Ok(())
+}
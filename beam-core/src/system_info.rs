@@ -0,0 +1,277 @@
+use crate::game_settings::GameSettings;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+// Resolutions offered by the settings panel, smallest first
+const RENDERER_RESOLUTIONS: &[(u32, u32)] = &[(800, 600), (1024, 768), (1280, 720), (1920, 1080)];
+// Below this the renderer stutters at HD resolutions once the map textures are loaded
+const LOW_VRAM_MB: u64 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpuVendor {
+    Nvidia,
+    Amd,
+    Intel,
+    // Microsoft Basic Display Adapter and remote desktop drivers
+    Microsoft,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuInfo {
+    pub name: String,
+    pub vendor: GpuVendor,
+    pub driver_version: Option<String>,
+    pub driver_date: Option<String>,
+    pub vram_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayInfo {
+    // Physical pixels of the primary display
+    pub width: u32,
+    pub height: u32,
+    // Windows display scaling; 100 means none
+    pub scale_percent: u32,
+}
+
+impl DisplayInfo {
+    // What the client sees: it is not DPI aware, so Windows hands it the scaled-down desktop
+    pub fn logical_size(&self) -> (u32, u32) {
+        let scale = self.scale_percent.max(100);
+        (self.width * 100 / scale, self.height * 100 / scale)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsWarning {
+    pub code: String,
+    pub message: String,
+}
+
+impl SettingsWarning {
+    fn new(code: &str, message: String) -> Self {
+        SettingsWarning {
+            code: code.to_string(),
+            message,
+        }
+    }
+}
+
+// Whatever could be found out about the graphics setup; fields the platform cannot report are left empty
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemInfo {
+    pub gpus: Vec<GpuInfo>,
+    pub display: Option<DisplayInfo>,
+    pub suggested_settings: GameSettings,
+}
+
+impl SystemInfo {
+    pub fn probe() -> Self {
+        let gpus = imp::gpus();
+        let display = imp::display();
+        for gpu in &gpus {
+            info!("GPU: {} (driver {}, {} MB)", gpu.name, gpu.driver_version.as_deref().unwrap_or("unknown"), gpu.vram_mb.unwrap_or(0));
+        }
+        if let Some(screen) = &display {
+            info!("Display: {}x{} at {}%", screen.width, screen.height, screen.scale_percent);
+        }
+        
+        let mut info = SystemInfo {
+            gpus,
+            display,
+            suggested_settings: GameSettings::default(),
+        };
+        info.suggested_settings = info.suggest();
+        info
+    }
+    
+    // The adapter the game most likely renders on: hybrid laptops list the integrated GPU too
+    pub fn primary_gpu(&self) -> Option<&GpuInfo> {
+        self.gpus
+            .iter()
+            .max_by_key(|gpu| (gpu.vendor != GpuVendor::Microsoft, gpu.vram_mb.unwrap_or(0)))
+    }
+    
+    // Windowed at the largest offered resolution that fits the desktop the client sees, frame included
+    fn suggest(&self) -> GameSettings {
+        let (max_width, max_height) = self.display.as_ref().map(|d| d.logical_size()).unwrap_or((u32::MAX, u32::MAX));
+        let low_vram = self.primary_gpu().and_then(|gpu| gpu.vram_mb).map(|vram| vram < LOW_VRAM_MB).unwrap_or(false);
+        let (width, height) = RENDERER_RESOLUTIONS
+            .iter()
+            .rev()
+            .filter(|(width, _)| !low_vram || *width <= 1280)
+            .find(|(width, height)| *width < max_width && *height < max_height)
+            .copied()
+            .unwrap_or(RENDERER_RESOLUTIONS[0]);
+        
+        GameSettings {
+            resolution_width: width,
+            resolution_height: height,
+            fullscreen: false,
+            ..GameSettings::default()
+        }
+    }
+    
+    // Known-bad combinations for the old renderer, for the settings panel to show before saving
+    pub fn check(&self, settings: &GameSettings) -> Vec<SettingsWarning> {
+        let mut warnings = Vec::new();
+        
+        if let Some(display) = &self.display {
+            if settings.fullscreen && display.scale_percent > 100 {
+                warnings.push(SettingsWarning::new(
+                    "scaling_fullscreen",
+                    format!(
+                        "Display scaling is set to {}%. Fullscreen mode will be blurry or cut off; play windowed or set scaling to 100%.",
+                        display.scale_percent
+                    ),
+                ));
+            }
+            let (width, height) = display.logical_size();
+            if settings.resolution_width > width || settings.resolution_height > height {
+                warnings.push(SettingsWarning::new(
+                    "resolution_exceeds_display",
+                    format!(
+                        "{}x{} is larger than the {}x{} the game can use on this display.",
+                        settings.resolution_width, settings.resolution_height, width, height
+                    ),
+                ));
+            }
+        }
+        
+        if let Some(gpu) = self.primary_gpu() {
+            if gpu.vendor == GpuVendor::Microsoft {
+                warnings.push(SettingsWarning::new(
+                    "no_gpu_driver",
+                    format!("{} has no 3D acceleration. Install the driver for your graphics card.", gpu.name),
+                ));
+            } else if gpu.vram_mb.map(|vram| vram < LOW_VRAM_MB).unwrap_or(false) && settings.resolution_width > 1280 {
+                warnings.push(SettingsWarning::new(
+                    "low_vram",
+                    format!("{} has little video memory; 1280x720 or lower will run smoother.", gpu.name),
+                ));
+            }
+        }
+        
+        warnings
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use super::*;
+    use std::ffi::c_void;
+    use winreg::enums::*;
+    use winreg::RegKey;
+    
+    const DISPLAY_CLASS_KEY: &str = r"SYSTEM\CurrentControlSet\Control\Class\{4d36e968-e325-11ce-bfc1-08002be10318}";
+    const HORZRES: i32 = 8;
+    const LOGPIXELSX: i32 = 88;
+    const DESKTOPVERTRES: i32 = 117;
+    const DESKTOPHORZRES: i32 = 118;
+    
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetDC(window: *mut c_void) -> *mut c_void;
+        fn ReleaseDC(window: *mut c_void, dc: *mut c_void) -> i32;
+    }
+    
+    #[link(name = "gdi32")]
+    extern "system" {
+        fn GetDeviceCaps(dc: *mut c_void, index: i32) -> i32;
+    }
+    
+    // One numbered subkey per installed driver; `Properties` and the like are not readable and get skipped
+    pub(super) fn gpus() -> Vec<GpuInfo> {
+        let Ok(class) = RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(DISPLAY_CLASS_KEY) else {
+            return Vec::new();
+        };
+        class
+            .enum_keys()
+            .filter_map(|name| name.ok())
+            .filter_map(|name| class.open_subkey(name).ok())
+            .filter_map(|key| {
+                let name: String = key.get_value("DriverDesc").ok()?;
+                let vendor = key
+                    .get_value::<String, _>("MatchingDeviceId")
+                    .ok()
+                    .and_then(|id| {
+                        let id = id.to_ascii_lowercase();
+                        let start = id.find("ven_")? + 4;
+                        u16::from_str_radix(id.get(start..start + 4)?, 16).ok()
+                    })
+                    .map(vendor_from_pci_id)
+                    .unwrap_or(GpuVendor::Other);
+                Some(GpuInfo {
+                    name,
+                    vendor,
+                    driver_version: key.get_value("DriverVersion").ok(),
+                    driver_date: key.get_value("DriverDate").ok(),
+                    vram_mb: vram_bytes(&key).map(|bytes| bytes / (1024 * 1024)),
+                })
+            })
+            .collect()
+    }
+    
+    fn vendor_from_pci_id(vendor_id: u16) -> GpuVendor {
+        match vendor_id {
+            0x10de => GpuVendor::Nvidia,
+            0x1002 | 0x1022 => GpuVendor::Amd,
+            0x8086 => GpuVendor::Intel,
+            0x1414 => GpuVendor::Microsoft,
+            _ => GpuVendor::Other,
+        }
+    }
+    
+    // Newer drivers write a QWORD; older ones a 4-byte binary value that tops out at 4 GB
+    fn vram_bytes(key: &RegKey) -> Option<u64> {
+        if let Ok(size) = key.get_value::<u64, _>("HardwareInformation.qwMemorySize") {
+            return Some(size);
+        }
+        let raw = key.get_raw_value("HardwareInformation.MemorySize").ok()?;
+        let bytes: [u8; 4] = raw.bytes.get(..4)?.try_into().ok()?;
+        Some(u32::from_le_bytes(bytes) as u64)
+    }
+    
+    pub(super) fn display() -> Option<DisplayInfo> {
+        // SAFETY: the screen DC is released before returning and only queried in between
+        unsafe {
+            let dc = GetDC(std::ptr::null_mut());
+            if dc.is_null() {
+                return None;
+            }
+            let width = GetDeviceCaps(dc, DESKTOPHORZRES);
+            let height = GetDeviceCaps(dc, DESKTOPVERTRES);
+            let logical_width = GetDeviceCaps(dc, HORZRES);
+            let dpi = GetDeviceCaps(dc, LOGPIXELSX);
+            ReleaseDC(std::ptr::null_mut(), dc);
+            
+            if width <= 0 || height <= 0 {
+                return None;
+            }
+            // A DPI-aware process sees the real DPI; an unaware one sees a shrunken desktop instead
+            let from_dpi = dpi.max(96) * 100 / 96;
+            let from_size = if logical_width > 0 { width * 100 / logical_width } else { 100 };
+            Some(DisplayInfo {
+                width: width as u32,
+                height: height as u32,
+                scale_percent: from_dpi.max(from_size) as u32,
+            })
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod imp {
+    use super::*;
+    
+    // The client only runs on Windows; elsewhere there is nothing useful to report
+    pub(super) fn gpus() -> Vec<GpuInfo> {
+        Vec::new()
+    }
+    
+    pub(super) fn display() -> Option<DisplayInfo> {
+        None
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+const MIN_PIECE_SIZE: u64 = 256 * 1024;
+const MAX_PIECE_SIZE: u64 = 16 * 1024 * 1024;
+// Keeps the .torrent small for multi-GB clients without making pieces too coarse for small ones
+const TARGET_PIECE_COUNT: u64 = 1500;
+
+#[derive(Debug, Clone, Default)]
+pub struct TorrentOptions {
+    pub trackers: Vec<String>,
+    // BEP 19 web seeds; point these at the directory the package is hosted in (ending in `/`)
+    pub web_seeds: Vec<String>,
+    pub piece_size: Option<u64>,
+    pub private: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TorrentInfo {
+    pub path: PathBuf,
+    pub info_hash: String,
+    pub magnet_link: String,
+    pub piece_size: u64,
+    pub piece_count: usize,
+}
+
+enum Bencode {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Bencode>),
+    Dict(BTreeMap<Vec<u8>, Bencode>),
+}
+
+impl Bencode {
+    fn string(s: &str) -> Self {
+        Bencode::Bytes(s.as_bytes().to_vec())
+    }
+    
+    // BTreeMap keeps keys in the raw byte order the spec requires, so output is canonical
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Bencode::Int(i) => out.extend_from_slice(format!("i{}e", i).as_bytes()),
+            Bencode::Bytes(bytes) => {
+                out.extend_from_slice(format!("{}:", bytes.len()).as_bytes());
+                out.extend_from_slice(bytes);
+            }
+            Bencode::List(items) => {
+                out.push(b'l');
+                for item in items {
+                    item.encode(out);
+                }
+                out.push(b'e');
+            }
+            Bencode::Dict(entries) => {
+                out.push(b'd');
+                for (key, value) in entries {
+                    out.extend_from_slice(format!("{}:", key.len()).as_bytes());
+                    out.extend_from_slice(key);
+                    value.encode(out);
+                }
+                out.push(b'e');
+            }
+        }
+    }
+}
+
+// One file becomes a single-file torrent; split volumes become a multi-file torrent named `name`
+pub fn create_torrent(files: &[PathBuf], name: &str, options: &TorrentOptions, output: &Path) -> Result<TorrentInfo> {
+    if files.is_empty() {
+        return Err(Error::PackagingFailed("No files to add to the torrent".to_string()));
+    }
+    
+    let mut sizes = Vec::with_capacity(files.len());
+    for file in files {
+        sizes.push(std::fs::metadata(file)?.len());
+    }
+    let total_size: u64 = sizes.iter().sum();
+    
+    let piece_size = options.piece_size.unwrap_or_else(|| default_piece_size(total_size));
+    if piece_size == 0 || !piece_size.is_power_of_two() {
+        return Err(Error::PackagingFailed("Torrent piece size must be a power of two".to_string()));
+    }
+    
+    info!("Hashing {} bytes into {} KB torrent pieces", total_size, piece_size / 1024);
+    let pieces = hash_pieces(files, piece_size)?;
+    let piece_count = pieces.len() / 20;
+    
+    let mut info_dict = BTreeMap::new();
+    info_dict.insert(b"piece length".to_vec(), Bencode::Int(piece_size as i64));
+    info_dict.insert(b"pieces".to_vec(), Bencode::Bytes(pieces));
+    if options.private {
+        info_dict.insert(b"private".to_vec(), Bencode::Int(1));
+    }
+    
+    if files.len() == 1 {
+        info_dict.insert(b"name".to_vec(), Bencode::string(&file_name(&files[0])));
+        info_dict.insert(b"length".to_vec(), Bencode::Int(total_size as i64));
+    } else {
+        info_dict.insert(b"name".to_vec(), Bencode::string(name));
+        let entries = files
+            .iter()
+            .zip(&sizes)
+            .map(|(file, size)| {
+                let mut entry = BTreeMap::new();
+                entry.insert(b"length".to_vec(), Bencode::Int(*size as i64));
+                entry.insert(b"path".to_vec(), Bencode::List(vec![Bencode::string(&file_name(file))]));
+                Bencode::Dict(entry)
+            })
+            .collect();
+        info_dict.insert(b"files".to_vec(), Bencode::List(entries));
+    }
+    
+    let info = Bencode::Dict(info_dict);
+    let mut info_bytes = Vec::new();
+    info.encode(&mut info_bytes);
+    let info_hash = format!("{:x}", Sha1::digest(&info_bytes));
+    
+    let mut root = BTreeMap::new();
+    if let Some(tracker) = options.trackers.first() {
+        root.insert(b"announce".to_vec(), Bencode::string(tracker));
+        // One tier per tracker so clients fall back through them in order
+        let tiers = options.trackers.iter().map(|t| Bencode::List(vec![Bencode::string(t)])).collect();
+        root.insert(b"announce-list".to_vec(), Bencode::List(tiers));
+    }
+    if !options.web_seeds.is_empty() {
+        let seeds = options.web_seeds.iter().map(|s| Bencode::string(s)).collect();
+        root.insert(b"url-list".to_vec(), Bencode::List(seeds));
+    }
+    if let Some(comment) = &options.comment {
+        root.insert(b"comment".to_vec(), Bencode::string(comment));
+    }
+    root.insert(b"created by".to_vec(), Bencode::string(concat!("Beam Patcher ", env!("CARGO_PKG_VERSION"))));
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    root.insert(b"creation date".to_vec(), Bencode::Int(created as i64));
+    root.insert(b"info".to_vec(), info);
+    
+    let mut torrent = Vec::new();
+    Bencode::Dict(root).encode(&mut torrent);
+    std::fs::write(output, torrent)?;
+    
+    let display_name = if files.len() == 1 { file_name(&files[0]) } else { name.to_string() };
+    let mut magnet_link = format!("magnet:?xt=urn:btih:{}&dn={}", info_hash, urlencoding::encode(&display_name));
+    for tracker in &options.trackers {
+        magnet_link.push_str(&format!("&tr={}", urlencoding::encode(tracker)));
+    }
+    for seed in &options.web_seeds {
+        magnet_link.push_str(&format!("&ws={}", urlencoding::encode(seed)));
+    }
+    
+    info!("Torrent written to {:?} (info hash {})", output, info_hash);
+    
+    Ok(TorrentInfo {
+        path: output.to_path_buf(),
+        info_hash,
+        magnet_link,
+        piece_size,
+        piece_count,
+    })
+}
+
+fn default_piece_size(total_size: u64) -> u64 {
+    (total_size / TARGET_PIECE_COUNT)
+        .next_power_of_two()
+        .clamp(MIN_PIECE_SIZE, MAX_PIECE_SIZE)
+}
+
+// Pieces span file boundaries, so the files are read as one continuous stream
+fn hash_pieces(files: &[PathBuf], piece_size: u64) -> Result<Vec<u8>> {
+    let mut pieces = Vec::new();
+    let mut piece = Vec::with_capacity(piece_size as usize);
+    
+    for file in files {
+        let mut reader = BufReader::new(File::open(file)?);
+        loop {
+            let wanted = piece_size as usize - piece.len();
+            let read = (&mut reader).take(wanted as u64).read_to_end(&mut piece)?;
+            if piece.len() == piece_size as usize {
+                pieces.extend_from_slice(&Sha1::digest(&piece));
+                piece.clear();
+            }
+            if read < wanted {
+                break;
+            }
+        }
+    }
+    
+    if !piece.is_empty() {
+        pieces.extend_from_slice(&Sha1::digest(&piece));
+    }
+    
+    Ok(pieces)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
@@ -0,0 +1,299 @@
+use crate::patch_history::PATCH_HISTORY_FILE;
+use crate::{game_settings, multi_user, paths, screenshots, Config, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+#[cfg(target_os = "windows")]
+use tracing::warn;
+
+pub const SAVEDATA_DIR: &str = "SaveData";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UninstallOptions {
+    // Hotkeys, chat settings and window layouts
+    #[serde(default)]
+    pub keep_savedata: bool,
+    #[serde(default)]
+    pub keep_screenshots: bool,
+}
+
+// What uninstalling would delete, for the confirmation prompt
+#[derive(Debug, Clone, Serialize)]
+pub struct UninstallPlan {
+    pub game_directory: PathBuf,
+    pub files: usize,
+    pub bytes: u64,
+    // Top-level folders that stay in place
+    pub kept: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UninstallReport {
+    pub files_removed: usize,
+    pub bytes_removed: u64,
+    pub kept: Vec<String>,
+    pub registry_values_removed: usize,
+    pub shortcuts_removed: usize,
+    pub protocol_handlers_removed: usize,
+    // The running launcher lives in the game folder; Windows deletes it once the launcher has exited
+    pub removal_pending_exit: bool,
+}
+
+pub fn plan(config: &Config, options: &UninstallOptions) -> Result<UninstallPlan> {
+    let game_dir = game_directory(config)?;
+    let mut plan = UninstallPlan {
+        game_directory: game_dir.clone(),
+        files: 0,
+        bytes: 0,
+        kept: Vec::new(),
+    };
+    for entry in fs::read_dir(&game_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_kept(&name, options) {
+            plan.kept.push(name);
+        } else {
+            count_tree(&entry.path(), &mut plan.files, &mut plan.bytes)?;
+        }
+    }
+    Ok(plan)
+}
+
+// Deletes the game folder, the launcher's caches and per-user settings, its registry values, and the
+// shortcuts and URL protocol handlers pointing into the folder. `config_path` is updated when it lives
+// outside the game folder, so the launcher starts without a game directory next time.
+pub fn uninstall(config: &mut Config, config_path: &Path, options: &UninstallOptions) -> Result<UninstallReport> {
+    config.ensure_unlocked("Uninstalling the game")?;
+    let game_dir = game_directory(config)?;
+    info!("Uninstalling {:?}", game_dir);
+    
+    let mut report = UninstallReport {
+        shortcuts_removed: remove_shortcuts(&game_dir),
+        protocol_handlers_removed: remove_protocol_handlers(&game_dir),
+        registry_values_removed: game_settings::remove_registry_settings(),
+        ..Default::default()
+    };
+    
+    // Windows cannot delete a running executable, so a launcher kept in the game folder is spared for now
+    let launcher = std::env::current_exe()
+        .ok()
+        .and_then(|exe| std::path::absolute(exe).ok())
+        .filter(|exe| cfg!(target_os = "windows") && exe.starts_with(&game_dir));
+    
+    for entry in fs::read_dir(&game_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_kept(&name, options) {
+            report.kept.push(name);
+        } else {
+            remove_tree(&entry.path(), launcher.as_deref(), &mut report)?;
+        }
+    }
+    
+    match &launcher {
+        Some(exe) => {
+            remove_after_exit(exe, &game_dir, report.kept.is_empty());
+            report.removal_pending_exit = true;
+        }
+        None if report.kept.is_empty() => fs::remove_dir(&game_dir)?,
+        None => {}
+    }
+    
+    for dir in [paths::cache_dir(&config.app.name), paths::data_dir(&config.app.name)] {
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+    }
+    if let Some(multi_user) = config.multi_user() {
+        let server_name = config.app.server_name.as_deref().unwrap_or(&config.app.name);
+        let settings_dir = multi_user::user_settings_dir(multi_user, server_name)?;
+        if settings_dir.exists() {
+            fs::remove_dir_all(&settings_dir)?;
+        }
+    }
+    
+    config.app.game_directory = None;
+    if config_path.exists() {
+        config.save(config_path)?;
+    }
+    
+    info!(
+        "Uninstalled: {} files ({} bytes) removed, {} top-level entries kept",
+        report.files_removed,
+        report.bytes_removed,
+        report.kept.len()
+    );
+    Ok(report)
+}
+
+// A mistyped game_directory must never turn this into deleting a drive or a home folder
+fn game_directory(config: &Config) -> Result<PathBuf> {
+    let dir = config
+        .app
+        .game_directory
+        .as_ref()
+        .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?;
+    let dir = std::path::absolute(dir)?;
+    if !dir.is_dir() {
+        return Err(Error::InvalidConfig(format!("Game directory does not exist: {}", dir.display())));
+    }
+    
+    let contains_home = dirs::home_dir().is_some_and(|home| home.starts_with(&dir));
+    let is_game = dir.join(&config.app.client_exe).is_file() || dir.join(PATCH_HISTORY_FILE).is_file();
+    if dir.parent().is_none() || contains_home || !is_game {
+        return Err(Error::InvalidConfig(format!(
+            "{} does not look like a game folder; refusing to delete it",
+            dir.display()
+        )));
+    }
+    Ok(dir)
+}
+
+fn is_kept(name: &str, options: &UninstallOptions) -> bool {
+    (options.keep_savedata && name.eq_ignore_ascii_case(SAVEDATA_DIR))
+        || (options.keep_screenshots && name.eq_ignore_ascii_case(screenshots::SCREENSHOT_DIR))
+}
+
+fn count_tree(path: &Path, files: &mut usize, bytes: &mut u64) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            count_tree(&entry?.path(), files, bytes)?;
+        }
+    } else {
+        *files += 1;
+        *bytes += metadata.len();
+    }
+    Ok(())
+}
+
+fn remove_tree(path: &Path, spare: Option<&Path>, report: &mut UninstallReport) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            remove_tree(&entry?.path(), spare, report)?;
+        }
+        if !spare.is_some_and(|spare| spare.starts_with(path)) {
+            fs::remove_dir(path)?;
+        }
+    } else if spare != Some(path) {
+        // Windows refuses to delete read-only files, which some clients ship
+        #[cfg(target_os = "windows")]
+        if metadata.permissions().readonly() {
+            let mut permissions = metadata.permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            fs::set_permissions(path, permissions)?;
+        }
+        fs::remove_file(path)?;
+        report.files_removed += 1;
+        report.bytes_removed += metadata.len();
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn remove_after_exit(exe: &Path, game_dir: &Path, remove_dir: bool) {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    
+    // The folder itself only goes when nothing was kept in it
+    let mut script = format!(
+        "ping -n 3 127.0.0.1 >nul & del /f /q \"{}\"",
+        exe.display()
+    );
+    if remove_dir {
+        script.push_str(&format!(" & rd /s /q \"{}\"", game_dir.display()));
+    }
+    if let Err(e) = Command::new("cmd").arg("/C").raw_arg(&script).creation_flags(CREATE_NO_WINDOW).spawn() {
+        warn!("Failed to schedule removal of {:?}: {}", exe, e);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn remove_after_exit(_exe: &Path, _game_dir: &Path, _remove_dir: bool) {}
+
+#[cfg(target_os = "windows")]
+fn remove_shortcuts(game_dir: &Path) -> usize {
+    use std::process::Command;
+    
+    // Desktop and Start Menu .lnk files that point into the game folder; Start Menu folders left empty go too
+    let script = format!(
+        r#"$dir = '{dir}'; $shell = New-Object -ComObject WScript.Shell; $count = 0
+$roots = @([Environment]::GetFolderPath('Desktop'), [Environment]::GetFolderPath('CommonDesktopDirectory'), [Environment]::GetFolderPath('Programs'))
+foreach ($root in $roots) {{ Get-ChildItem -Path $root -Filter *.lnk -Recurse -ErrorAction SilentlyContinue | ForEach-Object {{
+  $lnk = $shell.CreateShortcut($_.FullName)
+  if ($lnk.TargetPath -and $lnk.TargetPath.StartsWith($dir, [StringComparison]::OrdinalIgnoreCase)) {{
+    Remove-Item -LiteralPath $_.FullName -Force -ErrorAction SilentlyContinue; $count++
+    $parent = $_.Directory
+    if ($parent.FullName -ne $root -and -not (Get-ChildItem -LiteralPath $parent.FullName -Force)) {{ Remove-Item -LiteralPath $parent.FullName -Force -ErrorAction SilentlyContinue }} }} }} }}
+Write-Output $count"#,
+        dir = folder_prefix(game_dir).replace('\'', "''"),
+    );
+    
+    match Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).trim().parse().unwrap_or(0),
+        Err(e) => {
+            warn!("Failed to remove shortcuts: {}", e);
+            0
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn remove_shortcuts(_game_dir: &Path) -> usize {
+    0
+}
+
+// Per-user URL schemes (`HKCU\Software\Classes\<scheme>`) whose open command runs something from the game folder
+#[cfg(target_os = "windows")]
+fn remove_protocol_handlers(game_dir: &Path) -> usize {
+    use winreg::enums::*;
+    use winreg::RegKey;
+    
+    let Ok(classes) = RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(r"Software\Classes", KEY_READ | KEY_WRITE) else {
+        return 0;
+    };
+    let game_dir = folder_prefix(game_dir).to_lowercase();
+    let schemes: Vec<String> = classes
+        .enum_keys()
+        .filter_map(|name| name.ok())
+        .filter(|name| {
+            let Ok(key) = classes.open_subkey(name) else {
+                return false;
+            };
+            let command: String = key
+                .open_subkey(r"shell\open\command")
+                .and_then(|command| command.get_value(""))
+                .unwrap_or_default();
+            key.get_raw_value("URL Protocol").is_ok() && command.to_lowercase().contains(&game_dir)
+        })
+        .collect();
+    
+    schemes
+        .iter()
+        .filter(|scheme| match classes.delete_subkey_all(scheme) {
+            Ok(()) => {
+                info!("Removed URL protocol handler {}", scheme);
+                true
+            }
+            Err(e) => {
+                warn!("Failed to remove URL protocol handler {}: {}", scheme, e);
+                false
+            }
+        })
+        .count()
+}
+
+// With the trailing separator, so `D:\RO` does not also match `D:\RO2`
+#[cfg(target_os = "windows")]
+fn folder_prefix(game_dir: &Path) -> String {
+    format!("{}\\", game_dir.display())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn remove_protocol_handlers(_game_dir: &Path) -> usize {
+    0
+}
@@ -18,9 +18,7 @@ pub struct Updater {
 
 impl Updater {
     pub fn new(config: Config) -> Result<Self> {
-        let client = Client::builder()
-            .user_agent("Beam-Patcher/1.0")
-            .build()?;
+        let client = crate::http::client(&config.http)?;
         
         Ok(Updater { config, client })
     }
@@ -55,6 +53,7 @@ impl Updater {
         }
     }
     
+    #[cfg(feature = "self-update")]
     pub async fn perform_update(&self, version_info: &VersionInfo) -> Result<()> {
         info!("Downloading update from: {}", version_info.download_url);
         
@@ -72,4 +71,9 @@ impl Updater {
         info!("Update completed: {:?}", status);
         Ok(())
     }
+    
+    #[cfg(not(feature = "self-update"))]
+    pub async fn perform_update(&self, _version_info: &VersionInfo) -> Result<()> {
+        Err(Error::UpdateFailed("This build does not include self-update support".to_string()))
+    }
 }
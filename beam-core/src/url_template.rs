@@ -0,0 +1,102 @@
+use crate::config::MirrorConfig;
+use crate::downloader::DEFAULT_CHANNEL;
+use crate::{Config, Error, Result};
+use sha2::{Digest, Sha256};
+
+// Placeholders a mirror URL may contain; the patch list URL has no file and so only gets the first two
+pub const MIRROR_VARIABLES: &[&str] = &["channel", "version", "filename", "shard"];
+pub const PATCH_LIST_VARIABLES: &[&str] = &["channel", "version"];
+pub const DEFAULT_SHARD_COUNT: u32 = 16;
+
+// Values that are the same for every file of a run
+#[derive(Debug, Clone)]
+pub struct UrlVariables {
+    pub channel: String,
+    // Launcher version, for servers that keep per-release patch trees
+    pub version: String,
+}
+
+impl UrlVariables {
+    pub fn from_config(config: &Config) -> Self {
+        UrlVariables {
+            channel: config.patcher.channel.clone().unwrap_or_else(|| DEFAULT_CHANNEL.to_string()),
+            version: config.app.version.clone(),
+        }
+    }
+    
+    // Optional-channel patches live under their own channel's path
+    pub fn for_channel(&self, channel: Option<&str>) -> Self {
+        UrlVariables {
+            channel: channel.unwrap_or(&self.channel).to_string(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+pub fn validate(template: &str, allowed: &[&str]) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::InvalidConfig(format!("Unclosed placeholder in {}", template)))?;
+        let name = &rest[start + 1..start + end];
+        if !allowed.contains(&name) {
+            return Err(Error::InvalidConfig(format!(
+                "Unknown placeholder {{{}}} in {}; allowed: {}",
+                name,
+                template,
+                allowed.iter().map(|v| format!("{{{}}}", v)).collect::<Vec<_>>().join(", ")
+            )));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+pub fn is_template(url: &str) -> bool {
+    url.contains('{')
+}
+
+// The part before the first placeholder, for telling which mirror a URL belongs to
+pub fn literal_prefix(template: &str) -> &str {
+    template.split('{').next().unwrap_or(template)
+}
+
+// Spreads files evenly and stays the same across launcher versions, so a CDN can be laid out once
+pub fn shard(filename: &str, count: u32) -> u32 {
+    let digest = Sha256::digest(filename.to_ascii_lowercase().as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % count.max(1)
+}
+
+// Placeholders without a value are left in place; config validation rejects unknown ones
+pub fn expand(template: &str, variables: &UrlVariables, filename: Option<&str>, shard_count: u32) -> String {
+    let mut url = template
+        .replace("{channel}", &variables.channel)
+        .replace("{version}", &variables.version);
+    if let Some(filename) = filename {
+        url = url
+            .replace("{shard}", &shard(filename, shard_count).to_string())
+            .replace("{filename}", filename);
+    }
+    url
+}
+
+// Mirrors without `{filename}` keep the plain `<url>/<file>` layout
+pub fn mirror_file_url(mirror: &MirrorConfig, variables: &UrlVariables, file: &str) -> String {
+    let shard_count = mirror.shard_count.unwrap_or(DEFAULT_SHARD_COUNT);
+    if mirror.url.contains("{filename}") {
+        expand(&mirror.url, variables, Some(file), shard_count)
+    } else {
+        format!("{}/{}", expand(&mirror.url, variables, Some(file), shard_count).trim_end_matches('/'), file)
+    }
+}
+
+// The mirror a URL is served from, so requests to it get that mirror's credentials
+pub fn mirror_for_url<'a>(mirrors: &'a [MirrorConfig], url: &str) -> Option<&'a MirrorConfig> {
+    mirrors.iter().find(|m| {
+        let prefix = literal_prefix(&m.url);
+        // `https://{shard}.cdn...` has no host to go by
+        let has_host = prefix.find("://").map(|scheme| prefix.len() > scheme + 3).unwrap_or(!prefix.is_empty());
+        has_host && url.starts_with(prefix)
+    })
+}
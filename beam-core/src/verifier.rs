@@ -1,3 +1,6 @@
+use crate::body;
+use crate::url_template::{self, UrlVariables};
+use crate::wrapper::WrapperState;
 use crate::{Config, Error, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -27,7 +30,6 @@ pub struct VerificationResult {
 }
 
 pub struct Verifier {
-    #[allow(dead_code)]
     config: Config,
     manifest_url: String,
 }
@@ -40,10 +42,30 @@ impl Verifier {
         })
     }
     
+    // Reads `manifest.json` from the primary mirror
+    pub fn from_config(config: Config) -> Result<Self> {
+        let variables = UrlVariables::from_config(&config);
+        let manifest_url = config.patcher.mirrors.first()
+            .map(|m| url_template::mirror_file_url(m, &variables, "manifest.json"))
+            .unwrap_or_else(|| "https://patch.example.com/manifest.json".to_string());
+        Self::new(config, manifest_url)
+    }
+    
     pub async fn verify_game_files(&self) -> Result<VerificationResult> {
-        info!("Starting game file verification");
+        self.verify(false).await
+    }
+    
+    // Compares sizes only, hashing just the files whose size is off; finds missing and truncated files in
+    // seconds but not same-size damage
+    pub async fn quick_verify_game_files(&self) -> Result<VerificationResult> {
+        self.verify(true).await
+    }
+    
+    async fn verify(&self, quick: bool) -> Result<VerificationResult> {
+        info!("Starting {}game file verification", if quick { "quick " } else { "" });
         
         let manifest = self.download_manifest().await?;
+        let wrapper = self.config.app.game_directory.as_ref().map(WrapperState::load).unwrap_or_default();
         let mut result = VerificationResult {
             total_files: manifest.files.len(),
             verified: 0,
@@ -52,20 +74,36 @@ impl Verifier {
         };
         
         for file_entry in &manifest.files {
-            let file_path = PathBuf::from(&file_entry.path);
+            // Manifest paths are relative to the game directory
+            let file_path = match &self.config.app.game_directory {
+                Some(dir) => Path::new(dir).join(&file_entry.path),
+                None => PathBuf::from(&file_entry.path),
+            };
             
-            if !file_path.exists() {
-                warn!("Missing file: {}", file_entry.path);
-                result.missing.push(file_entry.path.clone());
+            let metadata = match tokio::fs::metadata(&file_path).await {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    warn!("Missing file: {}", file_entry.path);
+                    result.missing.push(file_entry.path.clone());
+                    continue;
+                }
+            };
+            if quick && metadata.len() == file_entry.size {
+                result.verified += 1;
                 continue;
             }
             
-            match self.verify_file(&file_path, &file_entry.checksum).await {
-                Ok(true) => {
+            match Self::compute_file_checksum(&file_path).await {
+                Ok(hash) if hash == file_entry.checksum => {
                     debug!("File verified: {}", file_entry.path);
                     result.verified += 1;
                 }
-                Ok(false) => {
+                // Replaced by the graphics wrapper the launcher manages
+                Ok(hash) if wrapper.owns(&file_entry.path, &hash) => {
+                    debug!("File belongs to the graphics wrapper: {}", file_entry.path);
+                    result.verified += 1;
+                }
+                Ok(_) => {
                     warn!("Corrupted file: {}", file_entry.path);
                     result.corrupted.push(file_entry.path.clone());
                 }
@@ -90,7 +128,7 @@ impl Verifier {
     async fn download_manifest(&self) -> Result<FileManifest> {
         info!("Downloading file manifest from: {}", self.manifest_url);
         
-        let client = reqwest::Client::new();
+        let client = crate::http::client(&self.config.http)?;
         let response = client.get(&self.manifest_url).send().await?;
         
         if !response.status().is_success() {
@@ -100,31 +138,12 @@ impl Verifier {
             )));
         }
         
-        let manifest: FileManifest = response.json().await?;
+        let manifest: FileManifest = body::read_json(response, body::MAX_MANIFEST_SIZE, "manifest").await?;
         info!("Manifest downloaded: {} files", manifest.files.len());
         
         Ok(manifest)
     }
     
-    async fn verify_file(&self, file_path: &Path, expected_checksum: &str) -> Result<bool> {
-        let mut file = File::open(file_path).await?;
-        let mut hasher = Sha256::new();
-        let mut buffer = vec![0u8; 8192];
-        
-        loop {
-            let bytes_read = file.read(&mut buffer).await?;
-            if bytes_read == 0 {
-                break;
-            }
-            hasher.update(&buffer[..bytes_read]);
-        }
-        
-        let result = hasher.finalize();
-        let hash = format!("{:x}", result);
-        
-        Ok(hash == expected_checksum)
-    }
-    
     pub async fn compute_file_checksum(file_path: &Path) -> Result<String> {
         let mut file = File::open(file_path).await?;
         let mut hasher = Sha256::new();
@@ -0,0 +1,280 @@
+use crate::config::{HttpConfig, WrapperConfig};
+use crate::{Config, Error, Result};
+use beam_formats::path::{name_key, sanitize_entry_path};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const WRAPPER_STATE_FILE: &str = ".beam-wrapper.json";
+// Client files the wrapper replaced, put back when it is removed
+const BACKUP_DIR: &str = ".beam-wrapper-backup";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedFile {
+    // Relative to the game directory
+    pub path: String,
+    pub checksum: String,
+    #[serde(default)]
+    pub backed_up: bool,
+}
+
+// What the launcher put into the game directory, so updates and removal only touch its own files and the
+// verifier does not report them as corrupted client files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WrapperState {
+    // The player's choice; None until they toggle it, so `enabled_by_default` applies
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub revision: Option<String>,
+    #[serde(default)]
+    pub files: Vec<TrackedFile>,
+}
+
+impl WrapperState {
+    pub fn load<P: AsRef<Path>>(game_dir: P) -> Self {
+        let path = game_dir.as_ref().join(WRAPPER_STATE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt wrapper state {:?}: {}", path, e);
+                WrapperState::default()
+            }),
+            Err(_) => WrapperState::default(),
+        }
+    }
+    
+    fn save(&self, game_dir: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::PatchFailed(format!("Failed to serialize wrapper state: {}", e)))?;
+        fs::write(game_dir.join(WRAPPER_STATE_FILE), content)?;
+        Ok(())
+    }
+    
+    // True when `path` is a wrapper file the launcher installed and it still has the installed content
+    pub fn owns(&self, path: &str, checksum: &str) -> bool {
+        let path = name_key(path);
+        self.files
+            .iter()
+            .any(|file| name_key(&file.path) == path && file.checksum.eq_ignore_ascii_case(checksum))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrapperStatus {
+    pub name: String,
+    pub enabled: bool,
+    pub installed: bool,
+    // Installed, but from an older package than the config names
+    pub update_available: bool,
+    pub files: Vec<String>,
+}
+
+// Installs, updates and removes the Direct3D wrapper (dgVoodoo, DXVK, ...) the server ships for its client
+pub struct WrapperManager {
+    config: WrapperConfig,
+    http: HttpConfig,
+    game_dir: PathBuf,
+    // Kiosk installs keep the operator's choice
+    locked: bool,
+}
+
+impl WrapperManager {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let wrapper = config
+            .wrapper
+            .clone()
+            .ok_or_else(|| Error::InvalidConfig("No graphics wrapper is configured".to_string()))?;
+        let game_dir = config
+            .app
+            .game_directory
+            .as_ref()
+            .ok_or_else(|| Error::InvalidConfig("Game directory not set".to_string()))?;
+        Ok(WrapperManager {
+            config: wrapper,
+            http: config.http.clone(),
+            game_dir: PathBuf::from(game_dir),
+            locked: config.app.kiosk,
+        })
+    }
+    
+    pub fn status(&self) -> WrapperStatus {
+        let state = WrapperState::load(&self.game_dir);
+        let installed = !state.files.is_empty();
+        WrapperStatus {
+            name: self.config.name.clone(),
+            enabled: state.enabled.unwrap_or(self.config.enabled_by_default),
+            installed,
+            update_available: installed && state.revision.as_deref() != Some(&self.config.revision()),
+            files: state.files.iter().map(|file| file.path.clone()).collect(),
+        }
+    }
+    
+    // Brings the game directory in line with the player's choice; called before every launch
+    pub async fn ensure(&self) -> Result<WrapperStatus> {
+        let status = self.status();
+        match (status.enabled, status.installed) {
+            (true, false) => self.install().await?,
+            (true, true) if status.update_available => self.install().await?,
+            (false, true) => self.uninstall()?,
+            _ => {}
+        }
+        Ok(self.status())
+    }
+    
+    pub async fn set_enabled(&self, enabled: bool) -> Result<WrapperStatus> {
+        if self.locked {
+            return Err(Error::KioskLocked("Changing the graphics wrapper is disabled on this installation".to_string()));
+        }
+        let mut state = WrapperState::load(&self.game_dir);
+        state.enabled = Some(enabled);
+        state.save(&self.game_dir)?;
+        self.ensure().await
+    }
+    
+    async fn install(&self) -> Result<()> {
+        info!("Installing {} from {}", self.config.name, self.config.package_url);
+        let package = self.download().await?;
+        let files = self.extract(&package)?;
+        
+        let mut state = WrapperState::load(&self.game_dir);
+        let previous = std::mem::take(&mut state.files);
+        let backups = self.game_dir.join(BACKUP_DIR);
+        
+        let mut tracked = Vec::new();
+        for (name, data) in files {
+            let target = self.game_dir.join(&name);
+            let old = previous.iter().find(|file| name_key(&file.path) == name_key(&name));
+            let mut backed_up = old.map(|file| file.backed_up).unwrap_or(false);
+            
+            match (old, fs::read(&target).map(|current| sha256(&current))) {
+                // Edited since install, e.g. the wrapper's config file through its control panel
+                (Some(old), Ok(current)) if current != old.checksum => {
+                    warn!("Keeping {} because it was changed after installation", name);
+                    tracked.push(TrackedFile { path: name, checksum: current, backed_up });
+                    continue;
+                }
+                // A client file of the same name is kept aside so removing the wrapper restores it
+                (None, Ok(_)) => {
+                    fs::create_dir_all(&backups)?;
+                    fs::rename(&target, backups.join(&name))?;
+                    backed_up = true;
+                }
+                _ => {}
+            }
+            
+            fs::write(&target, &data)?;
+            tracked.push(TrackedFile { path: name, checksum: sha256(&data), backed_up });
+        }
+        
+        // Files the new package no longer ships
+        for file in previous.iter().filter(|file| !tracked.iter().any(|t| name_key(&t.path) == name_key(&file.path))) {
+            self.remove_file(file)?;
+        }
+        
+        state.revision = Some(self.config.revision());
+        state.files = tracked;
+        state.save(&self.game_dir)?;
+        info!("{} installed ({} files)", self.config.name, state.files.len());
+        Ok(())
+    }
+    
+    fn uninstall(&self) -> Result<()> {
+        info!("Removing {}", self.config.name);
+        let mut state = WrapperState::load(&self.game_dir);
+        for file in &state.files {
+            self.remove_file(file)?;
+        }
+        let _ = fs::remove_dir(self.game_dir.join(BACKUP_DIR));
+        
+        state.files.clear();
+        state.revision = None;
+        state.save(&self.game_dir)
+    }
+    
+    fn remove_file(&self, file: &TrackedFile) -> Result<()> {
+        let target = self.game_dir.join(&file.path);
+        match fs::read(&target) {
+            Ok(current) if sha256(&current) == file.checksum => fs::remove_file(&target)?,
+            Ok(_) => {
+                warn!("Leaving {} in place because it was changed after installation", file.path);
+                return Ok(());
+            }
+            Err(_) => {}
+        }
+        
+        let backup = self.game_dir.join(BACKUP_DIR).join(&file.path);
+        if file.backed_up && backup.exists() {
+            fs::rename(&backup, &target)?;
+        }
+        Ok(())
+    }
+    
+    async fn download(&self) -> Result<Vec<u8>> {
+        let response = crate::http::client(&self.http)?.get(&self.config.package_url).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::DownloadFailed(format!(
+                "Failed to download {}: HTTP {}",
+                self.config.name,
+                response.status()
+            )));
+        }
+        let package = response.bytes().await?.to_vec();
+        
+        if let Some(expected) = &self.config.sha256 {
+            if !sha256(&package).eq_ignore_ascii_case(expected) {
+                return Err(Error::DownloadFailed(format!("Checksum mismatch for {} package", self.config.name)));
+            }
+        }
+        Ok(package)
+    }
+    
+    // Picks the configured entries out of the package; they all land next to the client executable
+    fn extract(&self, package: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(package))
+            .map_err(|e| Error::DownloadFailed(format!("Invalid {} package: {}", self.config.name, e)))?;
+        
+        let mut files = Vec::new();
+        let mut found = Vec::new();
+        for index in 0..archive.len() {
+            let mut entry = archive
+                .by_index(index)
+                .map_err(|e| Error::DownloadFailed(format!("Invalid {} package: {}", self.config.name, e)))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let entry_path = sanitize_entry_path(entry.name())?;
+            let key = name_key(&entry_path.to_string_lossy());
+            let wanted = if self.config.files.is_empty() {
+                entry_path.components().count() == 1
+            } else {
+                self.config.files.iter().any(|file| name_key(file) == key)
+            };
+            if !wanted {
+                continue;
+            }
+            
+            let Some(name) = entry_path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            files.push((name, data));
+            found.push(key);
+        }
+        
+        if let Some(missing) = self.config.files.iter().find(|file| !found.contains(&name_key(file))) {
+            return Err(Error::DownloadFailed(format!("{} package has no {}", self.config.name, missing)));
+        }
+        if files.is_empty() {
+            return Err(Error::DownloadFailed(format!("{} package contains no files to install", self.config.name)));
+        }
+        Ok(files)
+    }
+}
+
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
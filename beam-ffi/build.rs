@@ -0,0 +1,16 @@
+// Regenerates include/beam_formats.h from the exported functions; the header is committed so C and C# users
+// need no Rust toolchain. cbindgen leaves the file alone when nothing changed.
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).expect("Invalid cbindgen.toml");
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(format!("{}/include/beam_formats.h", crate_dir));
+        }
+        // A syntax error is reported by rustc with a better message
+        Err(e) => println!("cargo:warning=Could not generate beam_formats.h: {}", e),
+    }
+}
@@ -0,0 +1,143 @@
+#ifndef BEAM_FORMATS_H
+#define BEAM_FORMATS_H
+
+/* Generated by cbindgen from beam-ffi/src/lib.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A BEAM patch archive, opened or being built.
+ */
+typedef struct BeamArchive BeamArchive;
+
+/**
+ * An open GRF or GPF.
+ */
+typedef struct BeamGrf BeamGrf;
+
+/**
+ * Bytes owned by the library; release with `beam_buffer_free`.
+ */
+typedef struct BeamBuffer {
+  uint8_t *data;
+  size_t len;
+} BeamBuffer;
+
+/**
+ * Receives one archive entry: its stored name and uncompressed size. Return non-zero to stop listing.
+ */
+typedef int (*BeamListCallback)(const char *name, uint64_t size, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Message of the last failed call on this thread, or NULL. Valid until the next failing call on the thread.
+ */
+const char *beam_last_error(void);
+
+void beam_buffer_free(struct BeamBuffer buffer);
+
+/**
+ * Opens an existing GRF or GPF. NULL on failure.
+ */
+struct BeamGrf *beam_grf_open(const char *path);
+
+/**
+ * Creates an empty 0x200 GRF at `path`, replacing any file there. NULL on failure.
+ */
+struct BeamGrf *beam_grf_create(const char *path);
+
+/**
+ * Closes the archive without saving.
+ */
+void beam_grf_close(struct BeamGrf *grf);
+
+size_t beam_grf_file_count(const struct BeamGrf *grf);
+
+/**
+ * Lists the entries, including unsaved changes, sorted by name. 0 on success, -1 on failure.
+ */
+int beam_grf_list(const struct BeamGrf *grf, BeamListCallback callback, void *user_data);
+
+/**
+ * Decompresses one entry into `out`. Names match in any case and with either separator. 0 on success, -1 on
+ * failure.
+ */
+int beam_grf_extract(const struct BeamGrf *grf,
+                     const char *name,
+                     struct BeamBuffer *out);
+
+/**
+ * Adds or replaces an entry; written by `beam_grf_save`. 0 on success, -1 on failure.
+ */
+int beam_grf_patch(struct BeamGrf *grf, const char *name, const uint8_t *data, size_t len);
+
+/**
+ * Removes an entry; written by `beam_grf_save`. 1 if it existed, 0 if not, -1 on failure.
+ */
+int beam_grf_remove(struct BeamGrf *grf, const char *name);
+
+/**
+ * Adds every file of a BEAM patch to the GRF, as the patcher does; written by `beam_grf_save`. The number of
+ * entries patched, or -1 on failure.
+ */
+int beam_grf_apply(struct BeamGrf *grf,
+                   const struct BeamArchive *archive);
+
+/**
+ * Writes pending changes through a temporary copy swapped in when complete. 0 on success, -1 on failure.
+ */
+int beam_grf_save(struct BeamGrf *grf);
+
+/**
+ * Starts an empty BEAM archive to fill with `beam_archive_add`.
+ */
+struct BeamArchive *beam_archive_new(void);
+
+/**
+ * Opens an existing BEAM archive. NULL on failure.
+ */
+struct BeamArchive *beam_archive_open(const char *path);
+
+void beam_archive_close(struct BeamArchive *archive);
+
+size_t beam_archive_file_count(const struct BeamArchive *archive);
+
+/**
+ * Lists the entries sorted by name. 0 on success, -1 on failure.
+ */
+int beam_archive_list(const struct BeamArchive *archive,
+                      BeamListCallback callback,
+                      void *user_data);
+
+/**
+ * Decompresses and MD5-checks one entry into `out`. Names are exact. 0 on success, -1 on failure.
+ */
+int beam_archive_extract(const struct BeamArchive *archive,
+                         const char *name,
+                         struct BeamBuffer *out);
+
+/**
+ * Adds or replaces an entry; written by `beam_archive_save`. 0 on success, -1 on failure.
+ */
+int beam_archive_add(struct BeamArchive *archive,
+                     const char *name,
+                     const uint8_t *data,
+                     size_t len);
+
+/**
+ * Writes the archive to `path`. 0 on success, -1 on failure.
+ */
+int beam_archive_save(struct BeamArchive *archive, const char *path);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* BEAM_FORMATS_H */
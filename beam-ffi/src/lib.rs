@@ -0,0 +1,330 @@
+// C API over beam-formats for tools written in C, C++ or C#. Every function that can fail returns NULL or a
+// negative number and leaves a message for `beam_last_error`. Strings are NUL-terminated UTF-8. Handles are not
+// thread-safe; use one per thread or lock around them.
+
+#![allow(clippy::missing_safety_doc)]
+
+use beam_formats::beam;
+use beam_formats::grf::Grf;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs cannot cross into C; they only come from entry names
+    let message = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+// Unwraps a result for C: the error becomes the thread's last error and `failed` is returned instead
+fn check<T>(result: beam_formats::Result<T>, failed: T) -> T {
+    result.unwrap_or_else(|e| {
+        set_last_error(e.to_string());
+        failed
+    })
+}
+
+unsafe fn str_arg<'a>(value: *const c_char, what: &str) -> Option<&'a str> {
+    if value.is_null() {
+        set_last_error(format!("{} is NULL", what));
+        return None;
+    }
+    match CStr::from_ptr(value).to_str() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            set_last_error(format!("{} is not valid UTF-8", what));
+            None
+        }
+    }
+}
+
+unsafe fn bytes_arg<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(data, len)
+    }
+}
+
+// Calls `callback` for each name until it returns non-zero; the name is only valid during the call
+unsafe fn list_names<'a>(
+    names: impl Iterator<Item = (&'a str, u64)>,
+    callback: BeamListCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(callback) = callback else {
+        set_last_error("callback is NULL".to_string());
+        return -1;
+    };
+    let mut names: Vec<(&str, u64)> = names.collect();
+    names.sort_unstable();
+    for (name, size) in names {
+        let Ok(name) = CString::new(name) else {
+            continue;
+        };
+        if callback(name.as_ptr(), size, user_data) != 0 {
+            break;
+        }
+    }
+    0
+}
+
+/// Receives one archive entry: its stored name and uncompressed size. Return non-zero to stop listing.
+pub type BeamListCallback =
+    Option<unsafe extern "C" fn(name: *const c_char, size: u64, user_data: *mut c_void) -> c_int>;
+
+/// Bytes owned by the library; release with `beam_buffer_free`.
+#[repr(C)]
+pub struct BeamBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl BeamBuffer {
+    fn from_vec(data: Vec<u8>) -> Self {
+        let mut data = data.into_boxed_slice();
+        let buffer = BeamBuffer { data: data.as_mut_ptr(), len: data.len() };
+        std::mem::forget(data);
+        buffer
+    }
+    
+    const fn empty() -> Self {
+        BeamBuffer { data: ptr::null_mut(), len: 0 }
+    }
+}
+
+/// An open GRF or GPF.
+pub struct BeamGrf(Grf);
+
+/// A BEAM patch archive, opened or being built.
+pub struct BeamArchive(beam::BeamArchive);
+
+/// Message of the last failed call on this thread, or NULL. Valid until the next failing call on the thread.
+#[no_mangle]
+pub extern "C" fn beam_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn beam_buffer_free(buffer: BeamBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Opens an existing GRF or GPF. NULL on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_open(path: *const c_char) -> *mut BeamGrf {
+    let Some(path) = str_arg(path, "path") else {
+        return ptr::null_mut();
+    };
+    check(Grf::open(path).map(|grf| Box::into_raw(Box::new(BeamGrf(grf)))), ptr::null_mut())
+}
+
+/// Creates an empty 0x200 GRF at `path`, replacing any file there. NULL on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_create(path: *const c_char) -> *mut BeamGrf {
+    let Some(path) = str_arg(path, "path") else {
+        return ptr::null_mut();
+    };
+    check(Grf::create_new(path).map(|grf| Box::into_raw(Box::new(BeamGrf(grf)))), ptr::null_mut())
+}
+
+/// Closes the archive without saving.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_close(grf: *mut BeamGrf) {
+    if !grf.is_null() {
+        drop(Box::from_raw(grf));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_file_count(grf: *const BeamGrf) -> usize {
+    grf.as_ref().map_or(0, |grf| grf.0.file_count())
+}
+
+/// Lists the entries, including unsaved changes, sorted by name. 0 on success, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_list(
+    grf: *const BeamGrf,
+    callback: BeamListCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(grf) = grf.as_ref() else {
+        set_last_error("grf is NULL".to_string());
+        return -1;
+    };
+    let names = grf.0.list_files().into_iter().map(|name| {
+        let size = grf.0.get_entry(name).map_or(0, |entry| entry.uncompressed_size as u64);
+        (name, size)
+    });
+    list_names(names, callback, user_data)
+}
+
+/// Decompresses one entry into `out`. Names match in any case and with either separator. 0 on success, -1 on
+/// failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_extract(grf: *const BeamGrf, name: *const c_char, out: *mut BeamBuffer) -> c_int {
+    let (Some(grf), Some(name), Some(out)) = (grf.as_ref(), str_arg(name, "name"), out.as_mut()) else {
+        set_last_error("grf, name and out are required".to_string());
+        return -1;
+    };
+    *out = BeamBuffer::empty();
+    match grf.0.extract_file(name) {
+        Ok(data) => {
+            *out = BeamBuffer::from_vec(data);
+            0
+        }
+        Err(e) => {
+            set_last_error(e.to_string());
+            -1
+        }
+    }
+}
+
+/// Adds or replaces an entry; written by `beam_grf_save`. 0 on success, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_patch(grf: *mut BeamGrf, name: *const c_char, data: *const u8, len: usize) -> c_int {
+    let (Some(grf), Some(name)) = (grf.as_mut(), str_arg(name, "name")) else {
+        set_last_error("grf and name are required".to_string());
+        return -1;
+    };
+    check(grf.0.patch_file(name, bytes_arg(data, len)).map(|_| 0), -1)
+}
+
+/// Removes an entry; written by `beam_grf_save`. 1 if it existed, 0 if not, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_remove(grf: *mut BeamGrf, name: *const c_char) -> c_int {
+    let (Some(grf), Some(name)) = (grf.as_mut(), str_arg(name, "name")) else {
+        set_last_error("grf and name are required".to_string());
+        return -1;
+    };
+    check(grf.0.remove_file(name).map(c_int::from), -1)
+}
+
+/// Adds every file of a BEAM patch to the GRF, as the patcher does; written by `beam_grf_save`. The number of
+/// entries patched, or -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_apply(grf: *mut BeamGrf, archive: *const BeamArchive) -> c_int {
+    let (Some(grf), Some(archive)) = (grf.as_mut(), archive.as_ref()) else {
+        set_last_error("grf and archive are required".to_string());
+        return -1;
+    };
+    let mut patched = 0;
+    for name in archive.0.list_files() {
+        if let Err(e) = archive.0.extract_file(name).and_then(|data| grf.0.patch_file(name, &data)) {
+            set_last_error(e.to_string());
+            return -1;
+        }
+        patched += 1;
+    }
+    patched
+}
+
+/// Writes pending changes through a temporary copy swapped in when complete. 0 on success, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_grf_save(grf: *mut BeamGrf) -> c_int {
+    let Some(grf) = grf.as_mut() else {
+        set_last_error("grf is NULL".to_string());
+        return -1;
+    };
+    check(grf.0.save().map(|_| 0), -1)
+}
+
+/// Starts an empty BEAM archive to fill with `beam_archive_add`.
+#[no_mangle]
+pub extern "C" fn beam_archive_new() -> *mut BeamArchive {
+    Box::into_raw(Box::new(BeamArchive(beam::BeamArchive::new())))
+}
+
+/// Opens an existing BEAM archive. NULL on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_archive_open(path: *const c_char) -> *mut BeamArchive {
+    let Some(path) = str_arg(path, "path") else {
+        return ptr::null_mut();
+    };
+    check(beam::BeamArchive::open(path).map(|archive| Box::into_raw(Box::new(BeamArchive(archive)))), ptr::null_mut())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn beam_archive_close(archive: *mut BeamArchive) {
+    if !archive.is_null() {
+        drop(Box::from_raw(archive));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn beam_archive_file_count(archive: *const BeamArchive) -> usize {
+    archive.as_ref().map_or(0, |archive| archive.0.list_files().len())
+}
+
+/// Lists the entries sorted by name. 0 on success, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_archive_list(
+    archive: *const BeamArchive,
+    callback: BeamListCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(archive) = archive.as_ref() else {
+        set_last_error("archive is NULL".to_string());
+        return -1;
+    };
+    let names = archive.0.list_files().into_iter().map(|name| {
+        let size = archive.0.get_entry(name).map_or(0, |entry| entry.uncompressed_size as u64);
+        (name, size)
+    });
+    list_names(names, callback, user_data)
+}
+
+/// Decompresses and MD5-checks one entry into `out`. Names are exact. 0 on success, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_archive_extract(
+    archive: *const BeamArchive,
+    name: *const c_char,
+    out: *mut BeamBuffer,
+) -> c_int {
+    let (Some(archive), Some(name), Some(out)) = (archive.as_ref(), str_arg(name, "name"), out.as_mut()) else {
+        set_last_error("archive, name and out are required".to_string());
+        return -1;
+    };
+    *out = BeamBuffer::empty();
+    match archive.0.extract_file(name) {
+        Ok(data) => {
+            *out = BeamBuffer::from_vec(data);
+            0
+        }
+        Err(e) => {
+            set_last_error(e.to_string());
+            -1
+        }
+    }
+}
+
+/// Adds or replaces an entry; written by `beam_archive_save`. 0 on success, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_archive_add(
+    archive: *mut BeamArchive,
+    name: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    let (Some(archive), Some(name)) = (archive.as_mut(), str_arg(name, "name")) else {
+        set_last_error("archive and name are required".to_string());
+        return -1;
+    };
+    check(archive.0.add_file(name, bytes_arg(data, len)).map(|_| 0), -1)
+}
+
+/// Writes the archive to `path`. 0 on success, -1 on failure.
+#[no_mangle]
+pub unsafe extern "C" fn beam_archive_save(archive: *mut BeamArchive, path: *const c_char) -> c_int {
+    let (Some(archive), Some(path)) = (archive.as_mut(), str_arg(path, "path")) else {
+        set_last_error("archive and path are required".to_string());
+        return -1;
+    };
+    check(archive.0.save(path).map(|_| 0), -1)
+}
@@ -0,0 +1,63 @@
+use beam_formats::grf::Grf;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+const ENTRY_COUNT: usize = 50_000;
+
+fn build_archive(dir: &std::path::Path, alignment: u32) -> std::path::PathBuf {
+    let path = dir.join(format!("bench_{}.grf", alignment));
+    let mut grf = Grf::create_new(&path).unwrap();
+    grf.set_alignment(alignment).unwrap();
+    for i in 0..ENTRY_COUNT {
+        let data = format!("entry {} payload {}", i, "x".repeat(i % 200));
+        grf.patch_file(&format!("data\\bench\\{:05}.txt", i), data.as_bytes()).unwrap();
+    }
+    grf.save().unwrap();
+    path
+}
+
+fn bench_save(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut group = c.benchmark_group("grf_save_50k");
+    group.sample_size(10);
+    
+    for alignment in [1u32, 8] {
+        let source = build_archive(dir.path(), alignment);
+        
+        // Rewrites all 50k existing entries plus a single patched one
+        group.bench_with_input(BenchmarkId::new("patch_one", alignment), &alignment, |b, &alignment| {
+            b.iter_batched(
+                || {
+                    let path = dir.path().join("work.grf");
+                    std::fs::copy(&source, &path).unwrap();
+                    let mut grf = Grf::open(&path).unwrap();
+                    grf.set_alignment(alignment).unwrap();
+                    grf.patch_file("data\\bench\\new.txt", b"patched").unwrap();
+                    grf
+                },
+                |mut grf| grf.save().unwrap(),
+                BatchSize::PerIteration,
+            );
+        });
+        
+        // Writes 50k new entries into an empty archive
+        group.bench_with_input(BenchmarkId::new("create", alignment), &alignment, |b, &alignment| {
+            b.iter_batched(
+                || {
+                    let mut grf = Grf::create_new(dir.path().join("fresh.grf")).unwrap();
+                    grf.set_alignment(alignment).unwrap();
+                    for i in 0..ENTRY_COUNT {
+                        grf.patch_file(&format!("data\\bench\\{:05}.txt", i), b"small entry").unwrap();
+                    }
+                    grf
+                },
+                |mut grf| grf.save().unwrap(),
+                BatchSize::PerIteration,
+            );
+        });
+    }
+    
+    group.finish();
+}
+
+criterion_group!(benches, bench_save);
+criterion_main!(benches);
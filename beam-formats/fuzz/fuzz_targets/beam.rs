@@ -0,0 +1,12 @@
+#![no_main]
+
+use beam_formats::beam::BeamArchive;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(beam) = BeamArchive::from_bytes(data.to_vec()) {
+        for filename in beam.list_files() {
+            let _ = beam.extract_file(filename);
+        }
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use beam_formats::grf::Grf;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(grf) = Grf::from_bytes(data.to_vec()) {
+        for filename in grf.list_files() {
+            let _ = grf.extract_file(filename);
+        }
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use beam_formats::rgz::Rgz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Rgz::from_bytes(data);
+});
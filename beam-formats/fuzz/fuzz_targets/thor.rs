@@ -0,0 +1,8 @@
+#![no_main]
+
+use beam_formats::thor::Thor;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Thor::from_bytes(data);
+});
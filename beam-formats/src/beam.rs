@@ -1,29 +1,90 @@
-use crate::{Error, Result};
+use crate::limits::{self, DecompressionBudget};
+use crate::progress::OperationControl;
+use crate::{bounds, path, Error, Result};
+#[cfg(feature = "fs")]
+use ed25519_dalek::Signer;
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
+#[cfg(feature = "fs")]
+use std::path::Path;
 
 const BEAM_MAGIC: &[u8; 4] = b"BEAM";
-const BEAM_VERSION: u32 = 1;
+// Version 2 added the compression byte; version 1 entries are all zlib
+const BEAM_VERSION: u32 = 2;
 const HEADER_SIZE: usize = 64;
+// filename_len(1) + compression(1) + md5(16) + compressed_size(4) + uncompressed_size(4) + offset(8)
+const MIN_ENTRY_SIZE: u64 = 34;
+// Patches are built once and downloaded by every player, so the writer spends the time on a high level
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 19;
+// Header bytes 12..20 hold the offset of the signature block, or 0 for an unsigned archive. The block ends the
+// file: magic(4) + public_key(32) + signature(64), an Ed25519 signature of the SHA-256 of everything before it.
+// Older readers see reserved bytes and trailing data, so signed archives stay readable to them.
+const SIGNATURE_OFFSET_POS: u64 = 12;
+const SIGNATURE_MAGIC: &[u8; 4] = b"BSIG";
+const SIGNATURE_BLOCK_SIZE: u64 = 100;
+
+// An entry's name with its payload as it is written
+type CompressedEntry = (String, (BeamCompression, Vec<u8>));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeamCompression {
+    Store,
+    Zlib,
+    Zstd,
+}
+
+impl BeamCompression {
+    fn from_byte(byte: u8, filename: &str) -> Result<Self> {
+        match byte {
+            0 => Ok(BeamCompression::Store),
+            1 => Ok(BeamCompression::Zlib),
+            2 => Ok(BeamCompression::Zstd),
+            _ => Err(Error::Malformed(format!("{}: unknown BEAM compression {}", filename, byte))),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            BeamCompression::Store => 0,
+            BeamCompression::Zlib => 1,
+            BeamCompression::Zstd => 2,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct BeamEntry {
     pub filename: String,
+    pub compression: BeamCompression,
     pub md5_hash: [u8; 16],
     pub compressed_size: u32,
     pub uncompressed_size: u32,
     pub offset: u64,
 }
 
+#[derive(Debug, Clone, Copy)]
+struct SignatureBlock {
+    offset: u64,
+    public_key: [u8; 32],
+    signature: [u8; 64],
+}
+
 #[derive(Debug)]
 pub struct BeamArchive {
     pub version: u32,
     entries: HashMap<String, BeamEntry>,
+    signature: Option<SignatureBlock>,
     file_path: Option<PathBuf>,
+    buffer: Option<Vec<u8>>,
+    budget: DecompressionBudget,
+    // Compressed payloads of added files, written by `save`
     file_data: HashMap<String, Vec<u8>>,
 }
 
@@ -32,15 +93,48 @@ impl BeamArchive {
         BeamArchive {
             version: BEAM_VERSION,
             entries: HashMap::new(),
+            signature: None,
             file_path: None,
+            buffer: None,
+            budget: DecompressionBudget::default(),
             file_data: HashMap::new(),
         }
     }
 
+    #[cfg(feature = "fs")]
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
         let mut file = std::fs::File::open(path)?;
+        let (version, entries, signature) = Self::read_entries(&mut file)?;
         
+        Ok(BeamArchive {
+            version,
+            entries,
+            signature,
+            file_path: Some(path.to_path_buf()),
+            buffer: None,
+            budget: DecompressionBudget::default(),
+            file_data: HashMap::new(),
+        })
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut cursor = std::io::Cursor::new(&data[..]);
+        let (version, entries, signature) = Self::read_entries(&mut cursor)?;
+        
+        Ok(BeamArchive {
+            version,
+            entries,
+            signature,
+            file_path: None,
+            buffer: Some(data),
+            budget: DecompressionBudget::default(),
+            file_data: HashMap::new(),
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn read_entries<R: Read + Seek>(file: &mut R) -> Result<(u32, HashMap<String, BeamEntry>, Option<SignatureBlock>)> {
         let mut magic = [0u8; 4];
         file.read_exact(&mut magic)?;
         
@@ -51,13 +145,32 @@ impl BeamArchive {
         let mut version_buf = [0u8; 4];
         file.read_exact(&mut version_buf)?;
         let version = u32::from_le_bytes(version_buf);
+        if version == 0 || version > BEAM_VERSION {
+            return Err(Error::Unsupported(format!("BEAM version {}", version)));
+        }
         
         let mut entry_count_buf = [0u8; 4];
         file.read_exact(&mut entry_count_buf)?;
         let entry_count = u32::from_le_bytes(entry_count_buf);
         
+        let mut signature_offset_buf = [0u8; 8];
+        file.seek(SeekFrom::Start(SIGNATURE_OFFSET_POS))?;
+        file.read_exact(&mut signature_offset_buf)?;
+        let signature = match u64::from_le_bytes(signature_offset_buf) {
+            0 => None,
+            offset => Some(read_signature_block(file, offset)?),
+        };
+        
         file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
         
+        // Entries may not reach into the signature block
+        let archive_len = match &signature {
+            Some(signature) => signature.offset,
+            None => bounds::stream_len(file)?,
+        };
+        let min_entry_size = if version == 1 { MIN_ENTRY_SIZE - 1 } else { MIN_ENTRY_SIZE };
+        bounds::check_range(HEADER_SIZE as u64, entry_count as u64 * min_entry_size, archive_len, "BEAM entry table")?;
+        
         let mut entries = HashMap::new();
         
         for _ in 0..entry_count {
@@ -67,7 +180,15 @@ impl BeamArchive {
             
             let mut filename_buf = vec![0u8; filename_len];
             file.read_exact(&mut filename_buf)?;
-            let filename = String::from_utf8_lossy(&filename_buf).to_string();
+            let filename = path::decode_name(&filename_buf);
+            
+            let compression = if version == 1 {
+                BeamCompression::Zlib
+            } else {
+                let mut compression_buf = [0u8; 1];
+                file.read_exact(&mut compression_buf)?;
+                BeamCompression::from_byte(compression_buf[0], &filename)?
+            };
             
             let mut md5_hash = [0u8; 16];
             file.read_exact(&mut md5_hash)?;
@@ -84,10 +205,13 @@ impl BeamArchive {
             file.read_exact(&mut offset_buf)?;
             let offset = u64::from_le_bytes(offset_buf);
             
+            bounds::check_range(offset, compressed_size as u64, archive_len, &filename)?;
+            
             entries.insert(
                 filename.clone(),
                 BeamEntry {
                     filename,
+                    compression,
                     md5_hash,
                     compressed_size,
                     uncompressed_size,
@@ -96,38 +220,34 @@ impl BeamArchive {
             );
         }
         
-        Ok(BeamArchive {
-            version,
-            entries,
-            file_path: Some(path.to_path_buf()),
-            file_data: HashMap::new(),
-        })
+        Ok((version, entries, signature))
     }
 
+    // Compressed right away with whichever method makes `data` smallest
     pub fn add_file(&mut self, filename: &str, data: &[u8]) -> Result<()> {
+        check_name(filename)?;
         let digest = md5::compute(data);
         let md5_hash: [u8; 16] = digest.0;
         
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(data)?;
-        let compressed_data = encoder.finish()?;
-        
-        self.file_data.insert(filename.to_string(), data.to_vec());
+        let (compression, compressed_data) = compress(data)?;
         
         self.entries.insert(
             filename.to_string(),
             BeamEntry {
                 filename: filename.to_string(),
+                compression,
                 md5_hash,
                 compressed_size: compressed_data.len() as u32,
                 uncompressed_size: data.len() as u32,
                 offset: 0,
             },
         );
+        self.file_data.insert(filename.to_string(), compressed_data);
         
         Ok(())
     }
 
+    #[cfg(feature = "fs")]
     pub fn add_file_from_path<P: AsRef<Path>>(&mut self, file_path: P, archive_path: &str) -> Result<()> {
         let data = std::fs::read(file_path)?;
         self.add_file(archive_path, &data)
@@ -137,93 +257,204 @@ impl BeamArchive {
         let entry = self.entries.get(filename)
             .ok_or_else(|| Error::FileNotFound(filename.to_string()))?;
         
-        let file_path = self.file_path.as_ref()
-            .ok_or_else(|| Error::Custom("Archive not saved to file".to_string()))?;
-        
-        let mut file = std::fs::File::open(file_path)?;
-        file.seek(SeekFrom::Start(entry.offset))?;
-        
-        let mut compressed_data = vec![0u8; entry.compressed_size as usize];
-        file.read_exact(&mut compressed_data)?;
+        let compressed_data = if let Some(buffer) = &self.buffer {
+            bounds::check_range(entry.offset, entry.compressed_size as u64, buffer.len() as u64, filename)?;
+            let start = entry.offset as usize;
+            buffer[start..start + entry.compressed_size as usize].to_vec()
+        } else {
+            let file_path = self.file_path.as_ref()
+                .ok_or_else(|| Error::Custom("Archive not saved to file".to_string()))?;
+            
+            let mut file = std::fs::File::open(file_path)?;
+            file.seek(SeekFrom::Start(entry.offset))?;
+            bounds::read_bytes(&mut file, entry.compressed_size as u64, filename)?
+        };
         
-        let mut decoder = ZlibDecoder::new(&compressed_data[..]);
-        let mut decompressed = Vec::with_capacity(entry.uncompressed_size as usize);
-        decoder.read_to_end(&mut decompressed)
-            .map_err(|e| Error::Decompression(e.to_string()))?;
+        let compressed_len = compressed_data.len() as u64;
+        let size_hint = entry.uncompressed_size as u64;
+        let decompressed = match entry.compression {
+            BeamCompression::Store => limits::decompress_entry(&compressed_data[..], compressed_len, size_hint, filename)?,
+            BeamCompression::Zlib => {
+                limits::decompress_entry(ZlibDecoder::new(&compressed_data[..]), compressed_len, size_hint, filename)?
+            }
+            #[cfg(feature = "zstd")]
+            BeamCompression::Zstd => {
+                let decoder = zstd::stream::read::Decoder::new(&compressed_data[..])?;
+                limits::decompress_entry(decoder, compressed_len, size_hint, filename)?
+            }
+            #[cfg(not(feature = "zstd"))]
+            BeamCompression::Zstd => {
+                return Err(Error::Unsupported(format!("{} is zstd-compressed; built without the zstd feature", filename)))
+            }
+        };
+        self.budget.consume(decompressed.len() as u64, filename)?;
         
         let digest = md5::compute(&decompressed);
         let calculated_hash: [u8; 16] = digest.0;
         
         if calculated_hash != entry.md5_hash {
-            return Err(Error::Custom(format!(
-                "MD5 checksum mismatch for file: {}",
-                filename
-            )));
+            return Err(Error::ChecksumMismatch(filename.to_string()));
         }
         
         Ok(decompressed)
     }
 
+    #[cfg(feature = "fs")]
     pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.save_with(path, &OperationControl::default())
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save_with<P: AsRef<Path>>(&mut self, path: P, control: &OperationControl) -> Result<()> {
         let path = path.as_ref();
+        let compressed_files = self.compress_entries(control)?;
+        
+        // Only touch the destination once every entry is compressed, so cancelling leaves it intact
         let mut file = std::fs::File::create(path)?;
+        self.write_entries(&mut file, &compressed_files)?;
+        let total = compressed_files.len() as u64;
+        control.report("", total, total);
         
-        file.write_all(BEAM_MAGIC)?;
-        file.write_all(&self.version.to_le_bytes())?;
-        file.write_all(&(self.entries.len() as u32).to_le_bytes())?;
-        file.write_all(&[0u8; 52])?; // Reserved
+        self.version = BEAM_VERSION;
+        self.signature = None;
+        self.file_path = Some(path.to_path_buf());
+        self.buffer = None;
+        self.file_data.clear();
         
-        let mut data_offset = HEADER_SIZE as u64;
+        Ok(())
+    }
+
+    // The archive as `save` writes it, for building patches without a file system
+    pub fn to_bytes(&mut self) -> Result<Vec<u8>> {
+        let compressed_files = self.compress_entries(&OperationControl::default())?;
+        let mut buffer = Vec::new();
+        self.write_entries(&mut buffer, &compressed_files)?;
         
-        for entry in self.entries.values() {
-            data_offset += 1; // filename_len
-            data_offset += entry.filename.len() as u64;
-            data_offset += 16; // md5
-            data_offset += 4; // compressed_size
-            data_offset += 4; // uncompressed_size
-            data_offset += 8; // offset
-        }
+        self.version = BEAM_VERSION;
+        self.signature = None;
+        self.file_path = None;
+        self.buffer = Some(buffer.clone());
+        self.file_data.clear();
         
-        let mut current_offset = data_offset;
-        let mut compressed_files = Vec::new();
+        Ok(buffer)
+    }
+
+    // Sorted by name so identical inputs always produce byte-identical archives
+    fn compress_entries(&self, control: &OperationControl) -> Result<Vec<CompressedEntry>> {
+        let mut filenames: Vec<String> = self.entries.keys().cloned().collect();
+        filenames.sort_unstable();
         
-        for filename in self.entries.keys() {
-            let data = if let Some(data) = self.file_data.get(filename) {
-                data.clone()
-            } else if self.file_path.is_some() {
-                self.extract_file(filename)?
+        let mut compressed_files = Vec::new();
+        let total = filenames.len() as u64;
+        for (done, filename) in filenames.into_iter().enumerate() {
+            control.step(&filename, done as u64, total)?;
+            // Entries read from an archive are compressed again, so older archives get the better methods too
+            let compressed = if let Some(data) = self.file_data.get(&filename) {
+                (self.entries[&filename].compression, data.clone())
+            } else if self.file_path.is_some() || self.buffer.is_some() {
+                compress(&self.extract_file(&filename)?)?
             } else {
                 return Err(Error::Custom("No source data available".to_string()));
             };
             
-            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-            encoder.write_all(&data)?;
-            let compressed = encoder.finish()?;
-            
-            compressed_files.push((filename.clone(), compressed));
+            compressed_files.push((filename, compressed));
         }
+        Ok(compressed_files)
+    }
+
+    fn write_entries<W: Write>(
+        &mut self,
+        writer: &mut W,
+        compressed_files: &[CompressedEntry],
+    ) -> Result<()> {
+        // Checked before anything is written, so a bad name never leaves half an archive behind
+        let names = compressed_files
+            .iter()
+            .map(|(filename, _)| check_name(filename))
+            .collect::<Result<Vec<_>>>()?;
+        let data_offset = HEADER_SIZE as u64 + names.iter().map(|name| MIN_ENTRY_SIZE + name.len() as u64).sum::<u64>();
         
-        for (filename, entry) in self.entries.iter_mut() {
-            file.write_all(&[filename.len() as u8])?;
-            file.write_all(filename.as_bytes())?;
-            file.write_all(&entry.md5_hash)?;
-            file.write_all(&entry.compressed_size.to_le_bytes())?;
-            file.write_all(&entry.uncompressed_size.to_le_bytes())?;
-            file.write_all(&current_offset.to_le_bytes())?;
+        let mut current_offset = data_offset;
+        writer.write_all(BEAM_MAGIC)?;
+        writer.write_all(&BEAM_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        writer.write_all(&[0u8; 52])?; // Reserved
+        
+        for ((filename, (compression, compressed)), name) in compressed_files.iter().zip(&names) {
+            let entry = self.entries.get_mut(filename).unwrap();
+            entry.compression = *compression;
+            entry.compressed_size = compressed.len() as u32;
+            
+            writer.write_all(&[name.len() as u8])?;
+            writer.write_all(name)?;
+            writer.write_all(&[compression.to_byte()])?;
+            writer.write_all(&entry.md5_hash)?;
+            writer.write_all(&entry.compressed_size.to_le_bytes())?;
+            writer.write_all(&entry.uncompressed_size.to_le_bytes())?;
+            writer.write_all(&current_offset.to_le_bytes())?;
             
             entry.offset = current_offset;
             current_offset += entry.compressed_size as u64;
         }
         
-        for (_filename, compressed) in &compressed_files {
-            file.write_all(compressed)?;
+        for (_filename, (_compression, compressed)) in compressed_files {
+            writer.write_all(compressed)?;
         }
+        Ok(())
+    }
+
+    // Signs the archive at `path` with the Ed25519 key `secret_key`, replacing any earlier signature. Saving an
+    // archive again drops its signature, so this is the last step of building a patch.
+    #[cfg(feature = "fs")]
+    pub fn sign<P: AsRef<Path>>(path: P, secret_key: &[u8; 32]) -> Result<()> {
+        let path = path.as_ref();
+        let archive = Self::open(path)?;
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+        let offset = match archive.signature {
+            Some(signature) => signature.offset,
+            None => bounds::stream_len(&mut file)?,
+        };
+        file.set_len(offset)?;
+        file.seek(SeekFrom::Start(SIGNATURE_OFFSET_POS))?;
+        file.write_all(&offset.to_le_bytes())?;
         
-        self.file_path = Some(path.to_path_buf());
+        file.seek(SeekFrom::Start(0))?;
+        let digest = signed_digest(&mut file, offset)?;
+        let key = SigningKey::from_bytes(secret_key);
+        let signature = key.sign(&digest);
         
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(SIGNATURE_MAGIC)?;
+        file.write_all(key.verifying_key().as_bytes())?;
+        file.write_all(&signature.to_bytes())?;
         Ok(())
     }
 
+    // The public key the archive says it is signed with; only `verify_signature` shows that it is
+    pub fn signed_by(&self) -> Option<[u8; 32]> {
+        self.signature.map(|signature| signature.public_key)
+    }
+
+    // Fails unless the archive is signed by `public_key` and unchanged since
+    pub fn verify_signature(&self, public_key: &[u8; 32]) -> Result<()> {
+        let signature = self.signature.ok_or_else(|| Error::Signature("the archive is not signed".to_string()))?;
+        if signature.public_key != *public_key {
+            return Err(Error::Signature(format!("signed with another key ({})", encode_key(&signature.public_key))));
+        }
+        let digest = if let Some(buffer) = &self.buffer {
+            signed_digest(&mut &buffer[..], signature.offset)?
+        } else {
+            let file_path = self.file_path.as_ref()
+                .ok_or_else(|| Error::Custom("Archive not saved to file".to_string()))?;
+            signed_digest(&mut std::fs::File::open(file_path)?, signature.offset)?
+        };
+        
+        let key = VerifyingKey::from_bytes(public_key)
+            .map_err(|_| Error::Signature("invalid public key".to_string()))?;
+        key.verify_strict(&digest, &Signature::from_bytes(&signature.signature))
+            .map_err(|_| Error::Signature("the archive was changed after it was signed".to_string()))
+    }
+
     pub fn list_files(&self) -> Vec<&str> {
         self.entries.keys().map(|s| s.as_str()).collect()
     }
@@ -248,3 +479,131 @@ impl Default for BeamArchive {
         Self::new()
     }
 }
+
+fn read_signature_block<R: Read + Seek>(file: &mut R, offset: u64) -> Result<SignatureBlock> {
+    let archive_len = bounds::stream_len(file)?;
+    if offset.checked_add(SIGNATURE_BLOCK_SIZE) != Some(archive_len) {
+        return Err(Error::Malformed("BEAM signature block is not at the end of the archive".to_string()));
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    let mut block = [0u8; SIGNATURE_BLOCK_SIZE as usize];
+    file.read_exact(&mut block)?;
+    if &block[..4] != SIGNATURE_MAGIC {
+        return Err(Error::Malformed("Invalid BEAM signature block".to_string()));
+    }
+    let mut signature = SignatureBlock { offset, public_key: [0; 32], signature: [0; 64] };
+    signature.public_key.copy_from_slice(&block[4..36]);
+    signature.signature.copy_from_slice(&block[36..]);
+    Ok(signature)
+}
+
+fn signed_digest<R: Read>(reader: &mut R, len: u64) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    let copied = std::io::copy(&mut reader.take(len), &mut hasher)?;
+    if copied != len {
+        return Err(Error::Malformed("BEAM archive is shorter than its signature block offset".to_string()));
+    }
+    Ok(hasher.finalize().into())
+}
+
+// The Ed25519 public key for `secret_key`
+pub fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+    SigningKey::from_bytes(secret_key).verifying_key().to_bytes()
+}
+
+// Keys are written as 64 hex characters in config and key files
+pub fn encode_key(key: &[u8; 32]) -> String {
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn decode_key(text: &str) -> Result<[u8; 32]> {
+    let text = text.trim();
+    if text.len() != 64 || !text.is_ascii() {
+        return Err(Error::Custom("A key must be 64 hex characters".to_string()));
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16)
+            .map_err(|_| Error::Custom(format!("Invalid hex in key: {}", text)))?;
+    }
+    Ok(key)
+}
+
+// Names are stored behind a single length byte, in the client's code page like GRF and THOR names
+fn check_name(name: &str) -> Result<std::borrow::Cow<'_, [u8]>> {
+    let encoded = path::encode_name(name);
+    if encoded.len() > u8::MAX as usize {
+        return Err(Error::Unsupported(format!("{} is longer than the 255 bytes a BEAM name can hold", name)));
+    }
+    Ok(encoded)
+}
+
+// The smaller of zstd and zlib, with zstd winning ties as it also decompresses faster; stored as is when neither
+// saves anything
+fn compress(data: &[u8]) -> Result<(BeamCompression, Vec<u8>)> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    let mut best = (BeamCompression::Zlib, encoder.finish()?);
+
+    #[cfg(feature = "zstd")]
+    {
+        let zstd = zstd::bulk::compress(data, ZSTD_LEVEL)?;
+        if zstd.len() <= best.1.len() {
+            best = (BeamCompression::Zstd, zstd);
+        }
+    }
+
+    if data.len() <= best.1.len() {
+        best = (BeamCompression::Store, data.to_vec());
+    }
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: [(&str, &[u8]); 3] = [
+        (r"data\texture\a.bmp", b"texture"),
+        (r"data\sprite\b.spr", b"sprite sprite sprite"),
+        (r"data\c.txt", b""),
+    ];
+
+    fn build(order: &[usize]) -> Vec<u8> {
+        let mut archive = BeamArchive::new();
+        for &index in order {
+            let (name, data) = FILES[index];
+            archive.add_file(name, data).unwrap();
+        }
+        archive.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn rebuilds_are_byte_identical() {
+        let first = build(&[0, 1, 2]);
+        assert_eq!(first, build(&[2, 0, 1]));
+        assert_eq!(first, build(&[1, 2, 0]));
+        
+        let archive = BeamArchive::from_bytes(first).unwrap();
+        assert_eq!(archive.extract_file(r"data\sprite\b.spr").unwrap(), b"sprite sprite sprite");
+    }
+
+    #[test]
+    fn korean_names_round_trip_as_cp949() {
+        let name = "data\\texture\\유저인터페이스\\a.bmp";
+        let mut archive = BeamArchive::new();
+        archive.add_file(name, b"ui").unwrap();
+        let bytes = archive.to_bytes().unwrap();
+        assert!(bytes.windows(path::encode_name(name).len()).any(|window| *window == *path::encode_name(name)));
+        
+        let mut archive = BeamArchive::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(archive.list_files(), vec![name]);
+        assert_eq!(archive.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn rejects_names_over_255_bytes() {
+        let mut archive = BeamArchive::new();
+        assert!(archive.add_file(&format!("data\\{}.txt", "a".repeat(300)), b"").is_err());
+    }
+}
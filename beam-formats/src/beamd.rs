@@ -0,0 +1,298 @@
+// BEAMD delta patches. A diff entry is a bsdiff of one file against a known version of it, identified by that
+// version's MD5, so a map or sprite that changed a little costs a few KB instead of the whole file. Entries can
+// also carry a whole file (new files, or when a diff would not be smaller) or remove one. Entries are applied in
+// the order they are stored.
+use crate::limits::{self, DecompressionBudget};
+use crate::path::{self, sanitize_entry_path};
+use crate::{bounds, Error, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+const BEAMD_MAGIC: &[u8; 5] = b"BEAMD";
+const BEAMD_VERSION: u8 = 1;
+// magic(5) + version(1) + reserved(2) + entry_count(4)
+const HEADER_SIZE: u64 = 12;
+// name_len(2) + kind(1) + base_md5(16) + result_md5(16) + result_size(4) + data_size(4) + offset(8)
+const MIN_ENTRY_SIZE: u64 = 51;
+
+const KIND_FULL: u8 = 0;
+const KIND_DIFF: u8 = 1;
+const KIND_REMOVE: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaKind {
+    // The whole new file
+    Full,
+    // A bsdiff against the file whose MD5 is `base_md5`
+    Diff,
+    Remove,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeltaEntry {
+    pub filename: String,
+    pub kind: DeltaKind,
+    // Zero unless `kind` is `Diff`
+    pub base_md5: [u8; 16],
+    // MD5 and size of the file once the entry is applied; zero for removals
+    pub result_md5: [u8; 16],
+    pub result_size: u32,
+    // zlib-compressed content or diff
+    pub data_size: u32,
+    pub offset: u64,
+}
+
+#[derive(Debug)]
+pub struct DeltaPatch {
+    pub version: u8,
+    entries: Vec<DeltaEntry>,
+    buffer: Vec<u8>,
+    budget: DecompressionBudget,
+}
+
+impl DeltaPatch {
+    // Delta patches are small by design, so they are read into memory whole
+    #[cfg(feature = "fs")]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+    
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut reader = Cursor::new(&data[..]);
+        let magic: [u8; 5] = read_array(&mut reader)?;
+        if &magic != BEAMD_MAGIC {
+            return Err(Error::Custom("Invalid BEAMD magic header".to_string()));
+        }
+        let version = read_array::<1>(&mut reader)?[0];
+        if version != BEAMD_VERSION {
+            return Err(Error::Unsupported(format!("BEAMD version {}", version)));
+        }
+        reader.seek(SeekFrom::Start(8))?;
+        let entry_count = u32::from_le_bytes(read_array(&mut reader)?);
+        
+        let archive_len = data.len() as u64;
+        bounds::check_range(HEADER_SIZE, entry_count as u64 * MIN_ENTRY_SIZE, archive_len, "BEAMD entry table")?;
+        
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let name_len = u16::from_le_bytes(read_array(&mut reader)?);
+            let name = bounds::read_bytes(&mut reader, name_len as u64, "BEAMD entry name")?;
+            let filename = path::decode_name(&name);
+            let kind = match read_array::<1>(&mut reader)?[0] {
+                KIND_FULL => DeltaKind::Full,
+                KIND_DIFF => DeltaKind::Diff,
+                KIND_REMOVE => DeltaKind::Remove,
+                kind => return Err(Error::Malformed(format!("{}: unknown BEAMD entry kind {}", filename, kind))),
+            };
+            let base_md5 = read_array(&mut reader)?;
+            let result_md5 = read_array(&mut reader)?;
+            let result_size = u32::from_le_bytes(read_array(&mut reader)?);
+            let data_size = u32::from_le_bytes(read_array(&mut reader)?);
+            let offset = u64::from_le_bytes(read_array(&mut reader)?);
+            bounds::check_range(offset, data_size as u64, archive_len, &filename)?;
+            
+            entries.push(DeltaEntry {
+                filename,
+                kind,
+                base_md5,
+                result_md5,
+                result_size,
+                data_size,
+                offset,
+            });
+        }
+        
+        Ok(DeltaPatch {
+            version,
+            entries,
+            buffer: data,
+            budget: DecompressionBudget::default(),
+        })
+    }
+    
+    pub fn entries(&self) -> &[DeltaEntry] {
+        &self.entries
+    }
+    
+    // The file `entry` produces from `current`, the file as the client has it (None if missing). Returns None when
+    // `current` already is the result, so re-applying a patch changes nothing. A diff against any other content
+    // fails with `BaselineMismatch`: the file was changed by something else and needs a full patch.
+    pub fn apply_to(&self, entry: &DeltaEntry, current: Option<&[u8]>) -> Result<Option<Vec<u8>>> {
+        if entry.kind == DeltaKind::Remove {
+            return Ok(None);
+        }
+        let current_md5 = current.map(|data| md5::compute(data).0);
+        if current_md5 == Some(entry.result_md5) {
+            return Ok(None);
+        }
+        
+        let payload = self.payload(entry)?;
+        let result = if entry.kind == DeltaKind::Full {
+            payload
+        } else {
+            let base = match (current, current_md5) {
+                (Some(base), Some(md5)) if md5 == entry.base_md5 => base,
+                (None, _) => {
+                    return Err(Error::BaselineMismatch(format!(
+                        "{} is missing; this delta patch only updates an existing copy",
+                        entry.filename
+                    )))
+                }
+                _ => {
+                    return Err(Error::BaselineMismatch(format!(
+                        "{} is not the version this delta patch was made for",
+                        entry.filename
+                    )))
+                }
+            };
+            // bsdiff output never exceeds the decompressed diff, which the decompression limits already bound
+            let mut patched = Vec::with_capacity(bounds::capacity_hint(entry.result_size as u64));
+            bsdiff::patch(base, &mut Cursor::new(payload), &mut patched)
+                .map_err(|e| Error::Malformed(format!("{}: {}", entry.filename, e)))?;
+            patched
+        };
+        
+        if result.len() as u64 != entry.result_size as u64 || md5::compute(&result).0 != entry.result_md5 {
+            return Err(Error::ChecksumMismatch(entry.filename.clone()));
+        }
+        Ok(Some(result))
+    }
+    
+    fn payload(&self, entry: &DeltaEntry) -> Result<Vec<u8>> {
+        let start = entry.offset as usize;
+        let compressed = &self.buffer[start..start + entry.data_size as usize];
+        // A diff can be larger than the file it produces, so the hint is only that
+        let data = limits::decompress_entry(
+            ZlibDecoder::new(compressed),
+            compressed.len() as u64,
+            entry.result_size as u64,
+            &entry.filename,
+        )?;
+        self.budget.consume(data.len() as u64, &entry.filename)?;
+        Ok(data)
+    }
+}
+
+// Writes a BEAMD patch. Entries keep the order they are added in.
+#[derive(Debug, Default)]
+pub struct DeltaBuilder {
+    entries: Vec<(DeltaEntry, Vec<u8>)>,
+}
+
+impl DeltaBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    // Stores the change from `old` to `new`, or the whole of `new` when that is smaller
+    pub fn add_diff(&mut self, filename: &str, old: &[u8], new: &[u8]) -> Result<()> {
+        let mut diff = Vec::new();
+        bsdiff::diff(old, new, &mut diff)?;
+        let diff = compress(&diff)?;
+        let full = compress(new)?;
+        if full.len() <= diff.len() {
+            return self.push(filename, DeltaKind::Full, [0; 16], new, full);
+        }
+        self.push(filename, DeltaKind::Diff, md5::compute(old).0, new, diff)
+    }
+    
+    pub fn add_file(&mut self, filename: &str, data: &[u8]) -> Result<()> {
+        self.push(filename, DeltaKind::Full, [0; 16], data, compress(data)?)
+    }
+    
+    pub fn remove_file(&mut self, filename: &str) -> Result<()> {
+        self.push(filename, DeltaKind::Remove, [0; 16], &[], Vec::new())
+    }
+    
+    pub fn entries(&self) -> impl Iterator<Item = &DeltaEntry> {
+        self.entries.iter().map(|(entry, _)| entry)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    fn push(&mut self, filename: &str, kind: DeltaKind, base_md5: [u8; 16], result: &[u8], data: Vec<u8>) -> Result<()> {
+        sanitize_entry_path(filename)?;
+        if path::encode_name(filename).len() > u16::MAX as usize {
+            return Err(Error::Unsupported(format!("{} is too long for a BEAMD name", filename)));
+        }
+        let (Ok(result_size), Ok(data_size)) = (u32::try_from(result.len()), u32::try_from(data.len())) else {
+            return Err(Error::Unsupported(format!("{} is too large for a BEAMD entry", filename)));
+        };
+        let result_md5 = if kind == DeltaKind::Remove { [0; 16] } else { md5::compute(result).0 };
+        let entry = DeltaEntry {
+            filename: filename.to_string(),
+            kind,
+            base_md5,
+            result_md5,
+            result_size,
+            data_size,
+            offset: 0,
+        };
+        self.entries.push((entry, data));
+        Ok(())
+    }
+    
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let table_size: u64 = self
+            .entries
+            .iter()
+            .map(|(entry, _)| MIN_ENTRY_SIZE + path::encode_name(&entry.filename).len() as u64)
+            .sum();
+        
+        let mut output = Vec::new();
+        output.write_all(BEAMD_MAGIC)?;
+        output.write_all(&[BEAMD_VERSION, 0, 0])?;
+        output.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        
+        let mut offset = HEADER_SIZE + table_size;
+        for (entry, data) in &self.entries {
+            let name = path::encode_name(&entry.filename);
+            let kind = match entry.kind {
+                DeltaKind::Full => KIND_FULL,
+                DeltaKind::Diff => KIND_DIFF,
+                DeltaKind::Remove => KIND_REMOVE,
+            };
+            output.write_all(&(name.len() as u16).to_le_bytes())?;
+            output.write_all(&name)?;
+            output.write_all(&[kind])?;
+            output.write_all(&entry.base_md5)?;
+            output.write_all(&entry.result_md5)?;
+            output.write_all(&entry.result_size.to_le_bytes())?;
+            output.write_all(&(data.len() as u32).to_le_bytes())?;
+            output.write_all(&offset.to_le_bytes())?;
+            offset += data.len() as u64;
+        }
+        for (_, data) in &self.entries {
+            output.write_all(data)?;
+        }
+        Ok(output)
+    }
+    
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn read_array<const N: usize>(reader: &mut Cursor<&[u8]>) -> Result<[u8; N]> {
+    let mut buffer = [0u8; N];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
@@ -0,0 +1,46 @@
+use crate::{Error, Result};
+use std::io::{Read, Seek, SeekFrom};
+
+// Upper bound for capacity hints taken from untrusted headers; real sizes still grow past this
+pub(crate) const MAX_PREALLOC: usize = 16 * 1024 * 1024;
+
+pub(crate) fn capacity_hint(size: u64) -> usize {
+    std::cmp::min(size, MAX_PREALLOC as u64) as usize
+}
+
+pub(crate) fn stream_len<S: Seek>(stream: &mut S) -> Result<u64> {
+    let position = stream.stream_position()?;
+    let len = stream.seek(SeekFrom::End(0))?;
+    stream.seek(SeekFrom::Start(position))?;
+    Ok(len)
+}
+
+pub(crate) fn remaining<S: Seek>(stream: &mut S) -> Result<u64> {
+    let position = stream.stream_position()?;
+    let len = stream_len(stream)?;
+    Ok(len.saturating_sub(position))
+}
+
+pub(crate) fn check_range(offset: u64, len: u64, total: u64, what: &str) -> Result<()> {
+    match offset.checked_add(len) {
+        Some(end) if end <= total => Ok(()),
+        _ => Err(Error::Malformed(format!(
+            "{} ({} bytes at offset {}) exceeds input size {}",
+            what, len, offset, total
+        ))),
+    }
+}
+
+pub(crate) fn read_bytes<R: Read + Seek>(reader: &mut R, len: u64, what: &str) -> Result<Vec<u8>> {
+    let available = remaining(reader)?;
+    if len > available {
+        return Err(Error::Malformed(format!(
+            "{} declares {} bytes but only {} remain",
+            what, len, available
+        )));
+    }
+    
+    let mut buffer = vec![0u8; len as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
@@ -0,0 +1,192 @@
+use crate::grf::Grf;
+use crate::path;
+use crate::progress::OperationControl;
+use crate::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use xxhash_rust::xxh3::Xxh3;
+
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+// 128-bit XXH3 of a file's uncompressed content. Not a security checksum; patches are still verified with SHA-256.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentHash(pub u128);
+
+impl ContentHash {
+    pub fn of(data: &[u8]) -> Self {
+        ContentHash(xxhash_rust::xxh3::xxh3_128(data))
+    }
+    
+    // Hashes a stream in fixed-size pieces and returns its length too, so large entries never sit in memory whole
+    pub fn of_reader<R: Read>(mut reader: R) -> Result<(Self, u64)> {
+        let mut hasher = Xxh3::new();
+        let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+        let mut len = 0u64;
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            len += read as u64;
+        }
+        Ok((ContentHash(hasher.digest128()), len))
+    }
+    
+    // Streams one GRF entry through the hasher
+    pub fn of_entry(grf: &Grf, filename: &str) -> Result<(Self, u64)> {
+        Self::of_reader(grf.open_entry_stream(filename)?)
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl Serialize for ContentHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct IndexedEntry {
+    pub hash: ContentHash,
+    pub size: u64,
+}
+
+// Files that differ between two indexes, by name
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+// Content hashes of every file in an archive, built once and shared by diffing, duplicate detection and
+// already-applied checks. Names are compared the way the client looks them up (`path::name_key`) and reported in
+// that form; only the hash and size are kept per file, never the content.
+#[derive(Debug, Clone, Default)]
+pub struct ContentIndex {
+    entries: HashMap<String, IndexedEntry>,
+}
+
+impl ContentIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn build(grf: &Grf) -> Result<Self> {
+        Self::build_with(grf, &OperationControl::default())
+    }
+    
+    // Entries are read in on-disk order, so a multi-GB archive is streamed front to back once
+    pub fn build_with(grf: &Grf, control: &OperationControl) -> Result<Self> {
+        let mut names: Vec<(u32, &str)> = grf.list_files()
+            .into_iter()
+            .filter_map(|name| grf.get_entry(name).map(|entry| (entry.offset, name)))
+            .collect();
+        names.sort_unstable();
+        
+        let total = names.len() as u64;
+        let mut index = ContentIndex { entries: HashMap::with_capacity(names.len()) };
+        for (done, (_, name)) in names.into_iter().enumerate() {
+            control.step(name, done as u64, total)?;
+            let (hash, size) = ContentHash::of_entry(grf, name)?;
+            index.entries.insert(path::name_key(name), IndexedEntry { hash, size });
+        }
+        control.report("", total, total);
+        
+        tracing::debug!("Indexed {} GRF entries", index.len());
+        Ok(index)
+    }
+    
+    // Adds or replaces a file from outside an archive, such as a patch entry
+    pub fn insert(&mut self, name: &str, data: &[u8]) {
+        let entry = IndexedEntry { hash: ContentHash::of(data), size: data.len() as u64 };
+        self.entries.insert(path::name_key(name), entry);
+    }
+    
+    pub fn remove(&mut self, name: &str) -> Option<IndexedEntry> {
+        self.entries.remove(&path::name_key(name))
+    }
+    
+    pub fn get(&self, name: &str) -> Option<IndexedEntry> {
+        self.entries.get(&path::name_key(name)).copied()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    // Whether `name` already holds exactly `data`, i.e. writing it again would change nothing
+    pub fn matches(&self, name: &str, data: &[u8]) -> bool {
+        self.get(name)
+            .is_some_and(|entry| entry.size == data.len() as u64 && entry.hash == ContentHash::of(data))
+    }
+    
+    // A name already holding this content, for merges that can reuse stored data instead of adding it again
+    pub fn find(&self, hash: ContentHash, size: u64) -> Option<&str> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.hash == hash && entry.size == size)
+            .map(|(name, _)| name.as_str())
+            .min()
+    }
+    
+    // Groups of two or more names with identical content, largest wasted size first
+    pub fn duplicates(&self) -> Vec<Vec<&str>> {
+        let mut groups: HashMap<IndexedEntry, Vec<&str>> = HashMap::new();
+        for (name, entry) in &self.entries {
+            groups.entry(*entry).or_default().push(name);
+        }
+        
+        let mut duplicates: Vec<(u64, Vec<&str>)> = groups
+            .into_iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|(entry, mut names)| {
+                names.sort_unstable();
+                (entry.size * (names.len() as u64 - 1), names)
+            })
+            .collect();
+        duplicates.sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        duplicates.into_iter().map(|(_, names)| names).collect()
+    }
+    
+    // What changed from this index to `newer`
+    pub fn diff(&self, newer: &ContentIndex) -> IndexDiff {
+        let mut diff = IndexDiff::default();
+        for (name, entry) in &newer.entries {
+            match self.entries.get(name) {
+                None => diff.added.push(name.clone()),
+                Some(old) if old != entry => diff.changed.push(name.clone()),
+                Some(_) => diff.unchanged += 1,
+            }
+        }
+        diff.removed = self.entries
+            .keys()
+            .filter(|name| !newer.entries.contains_key(*name))
+            .cloned()
+            .collect();
+        
+        diff.added.sort_unstable();
+        diff.removed.sort_unstable();
+        diff.changed.sort_unstable();
+        diff
+    }
+}
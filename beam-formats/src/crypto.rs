@@ -0,0 +1,74 @@
+use crate::grf::GrfEntry;
+use crate::limits;
+use crate::{Error, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+// Hooks for GRF 0x300 (Gepard Shield / custom) archives, implemented by a vendor crate and installed with
+// `register_grf_crypto` before any 0x300 archive is opened
+pub trait GrfCrypto: Send + Sync {
+    fn name(&self) -> &str;
+    
+    fn decrypt_table(&self, data: &[u8], expected_size: u32) -> Result<Vec<u8>>;
+    
+    fn encrypt_table(&self, _table: &[u8]) -> Result<Vec<u8>> {
+        Err(Error::Unsupported(format!(
+            "GRF crypto '{}' does not support table encryption",
+            self.name()
+        )))
+    }
+    
+    fn decrypt_entry(&self, _entry: &GrfEntry, data: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(data)
+    }
+    
+    fn encrypt_entry(&self, _entry: &GrfEntry, data: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(data)
+    }
+}
+
+// Used when no plugin is registered: the table is plain zlib
+#[derive(Debug, Default)]
+pub struct ZlibTableCrypto;
+
+impl GrfCrypto for ZlibTableCrypto {
+    fn name(&self) -> &str {
+        "zlib"
+    }
+    
+    fn decrypt_table(&self, data: &[u8], expected_size: u32) -> Result<Vec<u8>> {
+        limits::decompress_entry(ZlibDecoder::new(data), data.len() as u64, expected_size as u64, "GRF file table")
+    }
+    
+    fn encrypt_table(&self, table: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(table)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+static GRF_CRYPTO: RwLock<Option<Arc<dyn GrfCrypto>>> = RwLock::new(None);
+
+pub fn register_grf_crypto(crypto: Arc<dyn GrfCrypto>) {
+    tracing::info!("Registering GRF 0x300 crypto plugin: {}", crypto.name());
+    *GRF_CRYPTO.write().unwrap_or_else(|e| e.into_inner()) = Some(crypto);
+}
+
+pub fn unregister_grf_crypto() {
+    *GRF_CRYPTO.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn has_grf_crypto() -> bool {
+    GRF_CRYPTO.read().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+pub fn grf_crypto() -> Arc<dyn GrfCrypto> {
+    GRF_CRYPTO
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(ZlibTableCrypto))
+}
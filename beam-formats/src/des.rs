@@ -0,0 +1,189 @@
+// The DES of GRF 0x101-0x103 archives and of entries flagged 0x02/0x04: a single round with an all-zero key, so
+// it is its own inverse. It only obfuscates, but old kRO data has to go through it to be read at all.
+
+const IP: [u8; 64] = [
+    58, 50, 42, 34, 26, 18, 10,  2,
+    60, 52, 44, 36, 28, 20, 12,  4,
+    62, 54, 46, 38, 30, 22, 14,  6,
+    64, 56, 48, 40, 32, 24, 16,  8,
+    57, 49, 41, 33, 25, 17,  9,  1,
+    59, 51, 43, 35, 27, 19, 11,  3,
+    61, 53, 45, 37, 29, 21, 13,  5,
+    63, 55, 47, 39, 31, 23, 15,  7,
+];
+
+const FP: [u8; 64] = [
+    40,  8, 48, 16, 56, 24, 64, 32,
+    39,  7, 47, 15, 55, 23, 63, 31,
+    38,  6, 46, 14, 54, 22, 62, 30,
+    37,  5, 45, 13, 53, 21, 61, 29,
+    36,  4, 44, 12, 52, 20, 60, 28,
+    35,  3, 43, 11, 51, 19, 59, 27,
+    34,  2, 42, 10, 50, 18, 58, 26,
+    33,  1, 41,  9, 49, 17, 57, 25,
+];
+
+const EXPANSION: [u8; 48] = [
+    32,  1,  2,  3,  4,  5,
+     4,  5,  6,  7,  8,  9,
+     8,  9, 10, 11, 12, 13,
+    12, 13, 14, 15, 16, 17,
+    16, 17, 18, 19, 20, 21,
+    20, 21, 22, 23, 24, 25,
+    24, 25, 26, 27, 28, 29,
+    28, 29, 30, 31, 32,  1,
+];
+
+const PERMUTATION: [u8; 32] = [
+    16,  7, 20, 21, 29, 12, 28, 17,
+     1, 15, 23, 26,  5, 18, 31, 10,
+     2,  8, 24, 14, 32, 27,  3,  9,
+    19, 13, 30,  6, 22, 11,  4, 25,
+];
+
+// Indexed directly by the six input bits instead of by row and column
+const SBOXES: [[u8; 64]; 8] = [
+    [
+        14,  0,  4, 15, 13,  7,  1,  4,  2, 14, 15,  2, 11, 13,  8,  1,
+         3, 10, 10,  6,  6, 12, 12, 11,  5,  9,  9,  5,  0,  3,  7,  8,
+         4, 15,  1, 12, 14,  8,  8,  2, 13,  4,  6,  9,  2,  1, 11,  7,
+        15,  5, 12, 11,  9,  3,  7, 14,  3, 10, 10,  0,  5,  6,  0, 13,
+    ],
+    [
+        15,  3,  1, 13,  8,  4, 14,  7,  6, 15, 11,  2,  3,  8,  4, 14,
+         9, 12,  7,  0,  2,  1, 13, 10, 12,  6,  0,  9,  5, 11, 10,  5,
+         0, 13, 14,  8,  7, 10, 11,  1, 10,  3,  4, 15, 13,  4,  1,  2,
+         5, 11,  8,  6, 12,  7,  6, 12,  9,  0,  3,  5,  2, 14, 15,  9,
+    ],
+    [
+        10, 13,  0,  7,  9,  0, 14,  9,  6,  3,  3,  4, 15,  6,  5, 10,
+         1,  2, 13,  8, 12,  5,  7, 14, 11, 12,  4, 11,  2, 15,  8,  1,
+        13,  1,  6, 10,  4, 13,  9,  0,  8,  6, 15,  9,  3,  8,  0,  7,
+        11,  4,  1, 15,  2, 14, 12,  3,  5, 11, 10,  5, 14,  2,  7, 12,
+    ],
+    [
+         7, 13, 13,  8, 14, 11,  3,  5,  0,  6,  6, 15,  9,  0, 10,  3,
+         1,  4,  2,  7,  8,  2,  5, 12, 11,  1, 12, 10,  4, 14, 15,  9,
+        10,  3,  6, 15,  9,  0,  0,  6, 12, 10, 11,  1,  7, 13, 13,  8,
+        15,  9,  1,  4,  3,  5, 14, 11,  5, 12,  2,  7,  8,  2,  4, 14,
+    ],
+    [
+         2, 14, 12, 11,  4,  2,  1, 12,  7,  4, 10,  7, 11, 13,  6,  1,
+         8,  5,  5,  0,  3, 15, 15, 10, 13,  3,  0,  9, 14,  8,  9,  6,
+         4, 11,  2,  8,  1, 12, 11,  7, 10,  1, 13, 14,  7,  2,  8, 13,
+        15,  6,  9, 15, 12,  0,  5,  9,  6, 10,  3,  4,  0,  5, 14,  3,
+    ],
+    [
+        12, 10,  1, 15, 10,  4, 15,  2,  9,  7,  2, 12,  6,  9,  8,  5,
+         0,  6, 13,  1,  3, 13,  4, 14, 14,  0,  7, 11,  5,  3, 11,  8,
+         9,  4, 14,  3, 15,  2,  5, 12,  2,  9,  8,  5, 12, 15,  3, 10,
+         7, 11,  0, 14,  4,  1, 10,  7,  1,  6, 13,  0, 11,  8,  6, 13,
+    ],
+    [
+         4, 13, 11,  0,  2, 11, 14,  7, 15,  4,  0,  9,  8,  1, 13, 10,
+         3, 14, 12,  3,  9,  5,  7, 12,  5,  2, 10, 15,  6,  8,  1,  6,
+         1,  6,  4, 11, 11, 13, 13,  8, 12,  1,  3,  4,  7, 10, 14,  7,
+        10,  9, 15,  5,  6,  0,  8, 15,  0, 14,  5,  2,  9,  3,  2, 12,
+    ],
+    [
+        13,  1,  2, 15,  8, 13,  4,  8,  6, 10, 15,  3, 11,  7,  1,  4,
+        10, 12,  9,  5,  3,  6, 14, 11,  5,  0,  0, 14, 12,  9,  7,  2,
+         7,  2, 11,  1,  4, 14,  1,  7,  9,  4, 12, 10, 14,  8,  2, 13,
+         0, 15,  6, 12, 10,  9, 13,  0, 15,  3,  3,  5,  5,  6,  8, 11,
+    ],
+];
+
+// Blocks of a mixed entry that stay plaintext except for a byte shuffle, one in every 7
+const SHUFFLE_CYCLE: usize = 7;
+
+// Bit `table[i]` of the `bits`-wide input (1 = most significant) becomes bit i of the output
+fn permute(input: u64, bits: u32, table: &[u8]) -> u64 {
+    table.iter().fold(0, |output, &bit| (output << 1) | ((input >> (bits - bit as u32)) & 1))
+}
+
+// DES's f with a zero round key
+fn feistel(right: u64) -> u64 {
+    let expanded = permute(right, 32, &EXPANSION);
+    let substituted = SBOXES.iter().enumerate().fold(0, |output, (i, sbox)| {
+        (output << 4) | sbox[((expanded >> (42 - 6 * i)) & 0x3F) as usize] as u64
+    });
+    permute(substituted, 32, &PERMUTATION)
+}
+
+pub(crate) fn decrypt_block(block: &mut [u8]) {
+    let data = permute(u64::from_be_bytes(block[..8].try_into().unwrap()), 64, &IP);
+    let data = permute(data ^ (feistel(data & 0xFFFF_FFFF) << 32), 64, &FP);
+    block[..8].copy_from_slice(&data.to_be_bytes());
+}
+
+// Decrypts an entry's stored bytes in place. Flag 0x04 covers the first 20 blocks; 0x02 (mixed) goes on to
+// decrypt every `cycle`th block after them, derived from the compressed size, and to unshuffle every 7th of the rest.
+pub(crate) fn decrypt_entry(data: &mut [u8], flags: u8, compressed_size: u32) {
+    let mut blocks = data.chunks_exact_mut(8);
+    for block in blocks.by_ref().take(20) {
+        decrypt_block(block);
+    }
+    if flags & 0x02 == 0 {
+        return;
+    }
+    
+    let digits = compressed_size.to_string().len();
+    let cycle = match digits {
+        0..=2 => 1,
+        3..=4 => digits + 1,
+        5..=6 => digits + 9,
+        _ => digits + 15,
+    };
+    let mut plain = 0;
+    for (i, block) in blocks.enumerate() {
+        if (i + 20) % cycle == 0 {
+            decrypt_block(block);
+            continue;
+        }
+        if plain % SHUFFLE_CYCLE == 0 && plain != 0 {
+            unshuffle(block);
+        }
+        plain += 1;
+    }
+}
+
+fn unshuffle(block: &mut [u8]) {
+    let b: [u8; 8] = block[..8].try_into().unwrap();
+    block[..8].copy_from_slice(&[b[3], b[4], b[6], b[0], b[1], b[2], b[5], substitute(b[7])]);
+}
+
+fn substitute(byte: u8) -> u8 {
+    match byte {
+        0x00 => 0x2B,
+        0x2B => 0x00,
+        0x6C => 0x80,
+        0x80 => 0x6C,
+        0x01 => 0x68,
+        0x68 => 0x01,
+        0x48 => 0x77,
+        0x77 => 0x48,
+        0x60 => 0xFF,
+        0xFF => 0x60,
+        0xB9 => 0xC0,
+        0xC0 => 0xB9,
+        0xFE => 0xEB,
+        0xEB => 0xFE,
+        other => other,
+    }
+}
+
+// Names in 0x101-0x103 file tables are nibble-swapped and encrypted in blocks of 8 bytes
+pub(crate) fn decrypt_name(name: &mut [u8]) {
+    for block in name.chunks_exact_mut(8) {
+        for byte in block.iter_mut() {
+            *byte = byte.rotate_left(4);
+        }
+        decrypt_block(block);
+    }
+}
+
+// Mixed entries in 0x101-0x103 archives are everything but these, which only have their header encrypted
+pub(crate) fn is_fully_encrypted(filename: &str) -> bool {
+    let extension = filename.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    !matches!(extension.as_deref(), Some("gnd" | "gat" | "act" | "str"))
+}
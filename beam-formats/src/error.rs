@@ -20,6 +20,9 @@ pub enum Error {
     #[error("Decompression error: {0}")]
     Decompression(String),
     
+    #[error("Decompression limit exceeded: {0}")]
+    DecompressionLimitExceeded(String),
+    
     #[error("Compression error: {0}")]
     Compression(String),
     
@@ -32,9 +35,27 @@ pub enum Error {
     #[error("Invalid file entry")]
     InvalidFileEntry,
     
+    #[error("Malformed archive: {0}")]
+    Malformed(String),
+    
+    #[error("Unsafe entry path: {0}")]
+    UnsafePath(String),
+    
+    #[error("Operation cancelled")]
+    Cancelled,
+    
     #[error("Unsupported operation: {0}")]
     Unsupported(String),
     
+    #[error("MD5 checksum mismatch for file: {0}")]
+    ChecksumMismatch(String),
+    
+    #[error("Delta patch does not apply: {0}")]
+    BaselineMismatch(String),
+    
+    #[error("Signature check failed: {0}")]
+    Signature(String),
+    
     #[error("{0}")]
     Custom(String),
 }
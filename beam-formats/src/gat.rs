@@ -0,0 +1,90 @@
+use crate::raster::RgbaImage;
+use crate::{Error, Result};
+
+const GAT_MAGIC: &[u8; 4] = b"GRAT";
+const GAT_HEADER_SIZE: usize = 14;
+const GAT_CELL_SIZE: usize = 20;
+// Largest official maps are 400x400; anything far beyond that is a corrupt header
+const MAX_GAT_DIMENSION: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatCellType {
+    Walkable,
+    Blocked,
+    Water,
+    WalkableWater,
+    Snipeable,
+    Other(u32),
+}
+
+impl From<u32> for GatCellType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => GatCellType::Walkable,
+            1 => GatCellType::Blocked,
+            2 => GatCellType::Water,
+            3 => GatCellType::WalkableWater,
+            5 => GatCellType::Snipeable,
+            other => GatCellType::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Gat {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<GatCellType>,
+}
+
+impl Gat {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < GAT_HEADER_SIZE || &data[..4] != GAT_MAGIC {
+            return Err(Error::Malformed("Not a GAT file".to_string()));
+        }
+        
+        let width = u32::from_le_bytes([data[6], data[7], data[8], data[9]]);
+        let height = u32::from_le_bytes([data[10], data[11], data[12], data[13]]);
+        if width == 0 || height == 0 || width > MAX_GAT_DIMENSION || height > MAX_GAT_DIMENSION {
+            return Err(Error::Malformed(format!("Invalid GAT dimensions {}x{}", width, height)));
+        }
+        
+        let expected = GAT_HEADER_SIZE + width as usize * height as usize * GAT_CELL_SIZE;
+        if data.len() < expected {
+            return Err(Error::Malformed(format!(
+                "GAT declares {}x{} cells but is only {} bytes",
+                width,
+                height,
+                data.len()
+            )));
+        }
+        
+        // Each cell is four corner heights (f32) followed by the cell type
+        let cells = data[GAT_HEADER_SIZE..expected]
+            .chunks_exact(GAT_CELL_SIZE)
+            .map(|cell| GatCellType::from(u32::from_le_bytes([cell[16], cell[17], cell[18], cell[19]])))
+            .collect();
+        
+        Ok(Gat { width, height, cells })
+    }
+    
+    // One pixel per cell, flipped so north is up (GAT rows start at the southern edge)
+    pub fn render_minimap(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        
+        for (index, cell) in self.cells.iter().enumerate() {
+            let x = index as u32 % self.width;
+            let y = self.height - 1 - index as u32 / self.width;
+            let color = match cell {
+                GatCellType::Walkable => [0xE8, 0xE0, 0xC8, 0xFF],
+                GatCellType::WalkableWater => [0x7F, 0xB2, 0xE5, 0xFF],
+                GatCellType::Water => [0x2A, 0x5C, 0xAA, 0xFF],
+                GatCellType::Snipeable => [0x80, 0x80, 0x80, 0xFF],
+                GatCellType::Blocked | GatCellType::Other(_) => [0x20, 0x20, 0x20, 0xFF],
+            };
+            image.put_pixel(x, y, color);
+        }
+        
+        image
+    }
+}
@@ -1,5 +1,6 @@
 use crate::grf::{Grf, GrfEntry};
 use crate::Result;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 pub struct Gpf {
@@ -7,11 +8,17 @@ pub struct Gpf {
 }
 
 impl Gpf {
+    #[cfg(feature = "fs")]
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let grf = Grf::open(path)?;
         Ok(Gpf { grf })
     }
     
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let grf = Grf::from_bytes(data)?;
+        Ok(Gpf { grf })
+    }
+    
     pub fn get_entry(&self, filename: &str) -> Option<&GrfEntry> {
         self.grf.get_entry(filename)
     }
@@ -1,10 +1,23 @@
+use crate::limits::{self, DecompressionBudget};
+use crate::progress::OperationControl;
+use crate::content_index::ContentHash;
+use crate::{bounds, crypto, des, gat, lua, pal, path, raster, text};
+#[cfg(feature = "fs")]
+use crate::table_cache;
 use crate::{Error, Result};
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
-use std::collections::HashMap;
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "fs")]
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
 
 const GRF_HEADER: &[u8; 15] = b"Master of Magic";
 const GRF_HEADER_SIZE: u32 = 46; // Full header size: magic(16) + key(14) + offset(4) + seed(4) + count(4) + version(4)
@@ -13,6 +26,17 @@ const GRF_VERSION_0X102: u32 = 0x102;
 const GRF_VERSION_0X103: u32 = 0x103;
 const GRF_VERSION_0X200: u32 = 0x200;
 const GRF_VERSION_0X300: u32 = 0x300;
+const SAVE_BUFFER_SIZE: usize = 1024 * 1024;
+const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+#[cfg(feature = "fs")]
+const JOURNAL_MAGIC: &[u8; 8] = b"BEAMJRNL";
+// magic(8) + original length(8) + table start(8) + header(46), followed by the old table region
+#[cfg(feature = "fs")]
+const JOURNAL_FIXED_SIZE: usize = 24 + GRF_HEADER_SIZE as usize;
+
+pub type GrfKey = [u8; 14];
+// Placeholder key written by tools that do not use the header key
+pub const DEFAULT_GRF_KEY: GrfKey = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
 
 #[derive(Debug, Clone)]
 pub struct GrfEntry {
@@ -24,36 +48,161 @@ pub struct GrfEntry {
     pub offset: u32,
 }
 
+// The flags byte written for patched entries. Bit 0x01 marks compressed data and the DES bits 0x02/0x04 are never
+// written, since entries are not DES-encrypted here; anything else is opaque to the client but checked by some
+// shields and tools.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrfFlagPolicy {
+    // 0x01 when compression pays off, otherwise 0x00
+    #[default]
+    Standard,
+    // A replaced entry keeps its flags, compressed or not as before, with the DES bits cleared. New entries are
+    // written as with `Standard`.
+    Preserve,
+    // Every written entry gets exactly these flags, compressed when 0x01 is set
+    Fixed(u8),
+}
+
+#[derive(Debug, Clone)]
+pub struct GrfCreateOptions {
+    // 0x200, or 0x300 when a GRF crypto plugin is registered
+    pub version: u32,
+    pub key: GrfKey,
+    pub seed: u32,
+    // Refuse a version the client's other GRFs in the same folder say it cannot read
+    pub check_siblings: bool,
+}
+
+impl Default for GrfCreateOptions {
+    fn default() -> Self {
+        GrfCreateOptions {
+            version: GRF_VERSION_0X200,
+            key: DEFAULT_GRF_KEY,
+            seed: 0,
+            check_siblings: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum GrfBackend {
+    #[cfg(feature = "fs")]
+    File(PathBuf),
+    Memory(Vec<u8>),
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
 #[derive(Debug)]
 pub struct Grf {
     pub version: u32,
+    key: GrfKey,
+    seed: u32,
     entries: HashMap<String, GrfEntry>,
-    file_path: PathBuf,
+    backend: GrfBackend,
     pending_patches: HashMap<String, Vec<u8>>,
+    // Stored names of entries the next save drops
+    pending_removals: HashSet<String>,
+    budget: DecompressionBudget,
+    alignment: u32,
+    flag_policy: GrfFlagPolicy,
+    // zlib level for newly written entries
+    compression_level: u32,
+    // `path::name_key` to the stored name, for lookups spelled differently from the table. Built on the first
+    // such lookup, since most never need it and a large client has hundreds of thousands of entries.
+    name_index: OnceLock<HashMap<String, String>>,
 }
 
 impl Grf {
+    #[cfg(feature = "fs")]
     pub fn create_new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::create_with(
+            path,
+            &GrfCreateOptions {
+                check_siblings: false,
+                ..GrfCreateOptions::default()
+            },
+        )
+    }
+    
+    #[cfg(feature = "fs")]
+    pub fn create_with<P: AsRef<Path>>(path: P, options: &GrfCreateOptions) -> Result<Self> {
         let path = path.as_ref();
+        
+        match options.version {
+            GRF_VERSION_0X200 => {}
+            GRF_VERSION_0X300 if crypto::has_grf_crypto() => {}
+            GRF_VERSION_0X300 => {
+                return Err(Error::Unsupported(
+                    "Creating a 0x300 GRF requires a registered GRF crypto plugin".to_string(),
+                ))
+            }
+            other => {
+                return Err(Error::Unsupported(format!(
+                    "New GRFs can only be created as 0x200 or 0x300, not {:#x}",
+                    other
+                )))
+            }
+        }
+        
+        if options.check_siblings {
+            Self::check_sibling_versions(path, options.version)?;
+        }
+        
         let mut file = std::fs::File::create(path)?;
+        Self::write_header(&mut file, options.version, &options.key, options.seed)?;
         
-        // Write GRF header (46 bytes): magic(16) + key(14) + offset(4) + seed(4) + count(4) + version(4)
-        file.write_all(GRF_HEADER)?;
-        file.write_all(&[0u8])?;
-        file.write_all(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14])?;
-        file.write_all(&0u32.to_le_bytes())?; // FileTableOffset
-        file.write_all(&0u32.to_le_bytes())?; // Seed
-        file.write_all(&0u32.to_le_bytes())?; // FilesCount
-        file.write_all(&GRF_VERSION_0X200.to_le_bytes())?; // Version
+        Ok(Grf {
+            version: options.version,
+            key: options.key,
+            seed: options.seed,
+            entries: HashMap::new(),
+            backend: GrfBackend::File(path.to_path_buf()),
+            pending_patches: HashMap::new(),
+            pending_removals: HashSet::new(),
+            budget: DecompressionBudget::default(),
+            alignment: 1,
+            flag_policy: GrfFlagPolicy::Standard,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            name_index: OnceLock::new(),
+        })
+    }
+    
+    pub fn create_in_memory() -> Result<Self> {
+        let mut buffer = Vec::with_capacity(GRF_HEADER_SIZE as usize);
+        Self::write_header(&mut buffer, GRF_VERSION_0X200, &DEFAULT_GRF_KEY, 0)?;
         
         Ok(Grf {
             version: GRF_VERSION_0X200,
+            key: DEFAULT_GRF_KEY,
+            seed: 0,
             entries: HashMap::new(),
-            file_path: path.to_path_buf(),
+            backend: GrfBackend::Memory(buffer),
             pending_patches: HashMap::new(),
+            pending_removals: HashSet::new(),
+            budget: DecompressionBudget::default(),
+            alignment: 1,
+            flag_policy: GrfFlagPolicy::Standard,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            name_index: OnceLock::new(),
         })
     }
     
+    fn write_header<W: Write>(writer: &mut W, version: u32, key: &GrfKey, seed: u32) -> Result<()> {
+        // Write GRF header (46 bytes): magic(16) + key(14) + offset(4) + seed(4) + count(4) + version(4)
+        writer.write_all(GRF_HEADER)?;
+        writer.write_all(&[0u8])?;
+        writer.write_all(key)?;
+        writer.write_all(&0u32.to_le_bytes())?; // FileTableOffset (will update later)
+        writer.write_all(&seed.to_le_bytes())?; // Seed
+        writer.write_all(&0u32.to_le_bytes())?; // FilesCount (will update later)
+        writer.write_all(&version.to_le_bytes())?; // Version
+        Ok(())
+    }
+    
     pub fn version_name(version: u32) -> &'static str {
         match version {
             GRF_VERSION_0X101 => "0x101 (Legacy)",
@@ -65,394 +214,1036 @@ impl Grf {
         }
     }
     
+    #[cfg(feature = "fs")]
     pub fn detect_version<P: AsRef<Path>>(path: P) -> Result<u32> {
         let path = path.as_ref();
         let mut file = std::fs::File::open(path)?;
-        
+        Self::read_version(&mut file)
+    }
+    
+    fn read_version<R: Read + Seek>(reader: &mut R) -> Result<u32> {
         let mut header = [0u8; 16];
-        file.read_exact(&mut header)?;
+        reader.read_exact(&mut header)?;
         
         if &header[..15] != GRF_HEADER {
             return Err(Error::InvalidGrfHeader);
         }
         
-        file.seek(SeekFrom::Start(42))?;
+        reader.seek(SeekFrom::Start(42))?;
         
         let mut version_buf = [0u8; 4];
-        file.read_exact(&mut version_buf)?;
+        reader.read_exact(&mut version_buf)?;
         let version = u32::from_le_bytes(version_buf);
         
         Ok(version)
     }
     
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    // Versions of the other GRFs next to `path`; unreadable files are skipped
+    #[cfg(feature = "fs")]
+    pub fn sibling_versions<P: AsRef<Path>>(path: P) -> Result<Vec<(PathBuf, u32)>> {
         let path = path.as_ref();
-        let mut file = std::fs::File::open(path)?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
         
-        let mut header = [0u8; 16];
-        file.read_exact(&mut header)?;
+        let mut versions = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let sibling = entry?.path();
+            let is_grf = sibling
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("grf"))
+                .unwrap_or(false);
+            if !is_grf || sibling.file_name() == path.file_name() {
+                continue;
+            }
+            
+            match Self::detect_version(&sibling) {
+                Ok(version) => versions.push((sibling, version)),
+                Err(e) => tracing::debug!("Skipping unreadable sibling GRF {:?}: {}", sibling, e),
+            }
+        }
         
-        if &header[..15] != GRF_HEADER {
-            return Err(Error::InvalidGrfHeader);
+        versions.sort();
+        Ok(versions)
+    }
+    
+    // Clients read every plain version up to 0x200, but a 0x300 client only loads archives encrypted for it and a
+    // stock client cannot load 0x300 at all, so the check is whether both sides agree on 0x300
+    #[cfg(feature = "fs")]
+    fn check_sibling_versions(path: &Path, version: u32) -> Result<()> {
+        let wants_encrypted = version == GRF_VERSION_0X300;
+        for (sibling, sibling_version) in Self::sibling_versions(path)? {
+            if (sibling_version == GRF_VERSION_0X300) != wants_encrypted {
+                return Err(Error::Unsupported(format!(
+                    "Cannot create a {} GRF: the client's {} is {}",
+                    Self::version_name(version),
+                    sibling.file_name().unwrap_or_default().to_string_lossy(),
+                    Self::version_name(sibling_version)
+                )));
+            }
         }
+        Ok(())
+    }
+    
+    // Cleans up after a save that was killed part-way. Returns true when the original archive had to be restored
+    // from the `.grf.bak` left by older releases, which renamed the archive away before rebuilding it.
+    #[cfg(feature = "fs")]
+    pub fn recover_interrupted_save<P: AsRef<Path>>(path: P) -> Result<bool> {
+        let path = path.as_ref();
         
-        let mut key = [0u8; 14];
-        file.read_exact(&mut key)?;
+        let temp_path = temp_save_path(path);
+        if temp_path.exists() {
+            tracing::warn!("Removing leftover partial GRF from an interrupted save: {:?}", temp_path);
+            std::fs::remove_file(&temp_path)?;
+        }
         
-        file.seek(SeekFrom::Start(42))?;
+        let journal_path = journal_path(path);
+        if journal_path.exists() {
+            return Self::roll_back_in_place_save(path, &journal_path);
+        }
         
-        let mut version_buf = [0u8; 4];
-        file.read_exact(&mut version_buf)?;
-        let version = u32::from_le_bytes(version_buf);
+        let backup_path = path.with_extension("grf.bak");
+        if !backup_path.exists() {
+            return Ok(false);
+        }
         
-        match version {
-            GRF_VERSION_0X101 | GRF_VERSION_0X102 | GRF_VERSION_0X103 | GRF_VERSION_0X200 | GRF_VERSION_0X300 => {},
-            _ => return Err(Error::InvalidGrfVersion(version)),
+        if path.exists() && Self::open(path).is_ok() {
+            std::fs::remove_file(&backup_path)?;
+            return Ok(false);
+        }
+        
+        tracing::warn!("Restoring {:?} from backup after an interrupted save", path);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        std::fs::rename(&backup_path, path)?;
+        Ok(true)
+    }
+    
+    // Undoes an in-place save that did not finish by putting back the header and the old table region
+    #[cfg(feature = "fs")]
+    fn roll_back_in_place_save(path: &Path, journal_path: &Path) -> Result<bool> {
+        let journal = std::fs::read(journal_path)?;
+        let complete = journal.len() >= JOURNAL_FIXED_SIZE
+            && journal.starts_with(JOURNAL_MAGIC)
+            && {
+                let original_len = u64::from_le_bytes(journal[8..16].try_into().unwrap());
+                let table_start = u64::from_le_bytes(journal[16..24].try_into().unwrap());
+                table_start <= original_len
+                    && journal.len() as u64 == JOURNAL_FIXED_SIZE as u64 + (original_len - table_start)
+            };
+        
+        // A journal cut short means the archive itself was never touched
+        if !complete {
+            tracing::warn!("Discarding incomplete in-place save journal: {:?}", journal_path);
+            std::fs::remove_file(journal_path)?;
+            return Ok(false);
         }
         
-        let entries = Self::read_file_table(&mut file, version)?;
+        tracing::warn!("Rolling back interrupted in-place save of {:?}", path);
+        let original_len = u64::from_le_bytes(journal[8..16].try_into().unwrap());
+        let table_start = u64::from_le_bytes(journal[16..24].try_into().unwrap());
+        
+        let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.write_all(&journal[24..24 + GRF_HEADER_SIZE as usize])?;
+        file.seek(SeekFrom::Start(table_start))?;
+        file.write_all(&journal[JOURNAL_FIXED_SIZE..])?;
+        file.set_len(original_len)?;
+        file.sync_all()?;
+        drop(file);
+        
+        std::fs::remove_file(journal_path)?;
+        Ok(true)
+    }
+    
+    #[cfg(feature = "fs")]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = std::fs::File::open(path)?;
+        let (version, key, seed, entries) = Self::read_archive(&mut file, Some(path))?;
+        
+        Ok(Grf {
+            version,
+            key,
+            seed,
+            entries,
+            backend: GrfBackend::File(path.to_path_buf()),
+            pending_patches: HashMap::new(),
+            pending_removals: HashSet::new(),
+            budget: DecompressionBudget::default(),
+            alignment: 1,
+            flag_policy: GrfFlagPolicy::Standard,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            name_index: OnceLock::new(),
+        })
+    }
+    
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut cursor = std::io::Cursor::new(&data[..]);
+        let (version, key, seed, entries) = Self::read_archive(&mut cursor, None)?;
         
         Ok(Grf {
             version,
+            key,
+            seed,
             entries,
-            file_path: path.to_path_buf(),
+            backend: GrfBackend::Memory(data),
             pending_patches: HashMap::new(),
+            pending_removals: HashSet::new(),
+            budget: DecompressionBudget::default(),
+            alignment: 1,
+            flag_policy: GrfFlagPolicy::Standard,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            name_index: OnceLock::new(),
         })
     }
     
-    fn read_file_table<R: Read + Seek>(reader: &mut R, version: u32) -> Result<HashMap<String, GrfEntry>> {
-        let mut entries = HashMap::new();
+    fn read_archive<R: Read + Seek>(reader: &mut R, source: Option<&Path>) -> Result<(u32, GrfKey, u32, HashMap<String, GrfEntry>)> {
+        let version = Self::read_version(reader)?;
         
         match version {
-            GRF_VERSION_0X300 => {
-                reader.seek(SeekFrom::Start(34))?;
-                
-                let mut file_count_buf = [0u8; 4];
-                reader.read_exact(&mut file_count_buf)?;
-                let _file_count = u32::from_le_bytes(file_count_buf);
-                
-                let mut seed_buf = [0u8; 4];
-                reader.read_exact(&mut seed_buf)?;
-                
-                let mut table_offset_buf = [0u8; 4];
-                reader.read_exact(&mut table_offset_buf)?;
-                let table_offset = u32::from_le_bytes(table_offset_buf);
-                
-                let mut table_size_buf = [0u8; 4];
-                reader.read_exact(&mut table_size_buf)?;
-                let table_size = u32::from_le_bytes(table_size_buf);
-                
-                let mut table_compressed_size_buf = [0u8; 4];
-                reader.read_exact(&mut table_compressed_size_buf)?;
-                let table_compressed_size = u32::from_le_bytes(table_compressed_size_buf);
-                
-                reader.seek(SeekFrom::Start((table_offset + GRF_HEADER_SIZE) as u64))?;
-                
-                let mut compressed_table = vec![0u8; table_compressed_size as usize];
-                reader.read_exact(&mut compressed_table)?;
-                
-                let table_data = Self::decrypt_grf_0x300_table(&compressed_table, table_size)?;
-                
-                let mut cursor = std::io::Cursor::new(table_data);
-                
-                while cursor.position() < cursor.get_ref().len() as u64 {
-                    // Read null-terminated filename
-                    let mut filename_bytes = Vec::new();
-                    loop {
-                        let mut byte = [0u8; 1];
-                        if cursor.read(&mut byte).unwrap_or(0) == 0 {
-                            break; // EOF
-                        }
-                        if byte[0] == 0 {
-                            break; // Null terminator
-                        }
-                        filename_bytes.push(byte[0]);
-                    }
-                    
-                    if filename_bytes.is_empty() {
-                        break; // No more entries
-                    }
-                    
-                    let filename = String::from_utf8_lossy(&filename_bytes).to_string();
-                    
-                    let mut compressed_size_buf = [0u8; 4];
-                    cursor.read_exact(&mut compressed_size_buf)?;
-                    let compressed_size = u32::from_le_bytes(compressed_size_buf);
-                    
-                    let mut compressed_size_aligned_buf = [0u8; 4];
-                    cursor.read_exact(&mut compressed_size_aligned_buf)?;
-                    let compressed_size_aligned = u32::from_le_bytes(compressed_size_aligned_buf);
-                    
-                    let mut uncompressed_size_buf = [0u8; 4];
-                    cursor.read_exact(&mut uncompressed_size_buf)?;
-                    let uncompressed_size = u32::from_le_bytes(uncompressed_size_buf);
-                    
-                    let mut flags_buf = [0u8; 1];
-                    cursor.read_exact(&mut flags_buf)?;
-                    let flags = flags_buf[0];
-                    
-                    let mut offset_buf = [0u8; 4];
-                    cursor.read_exact(&mut offset_buf)?;
-                    let offset = u32::from_le_bytes(offset_buf);
-                    
-                    entries.insert(
-                        filename.clone(),
-                        GrfEntry {
-                            filename,
-                            compressed_size,
-                            compressed_size_aligned,
-                            uncompressed_size,
-                            flags,
-                            offset,
-                        },
-                    );
-                }
-            },
+            GRF_VERSION_0X101 | GRF_VERSION_0X102 | GRF_VERSION_0X103 | GRF_VERSION_0X200 | GRF_VERSION_0X300 => {},
+            _ => return Err(Error::InvalidGrfVersion(version)),
+        }
+        
+        let mut key: GrfKey = [0; 14];
+        reader.seek(SeekFrom::Start(16))?;
+        reader.read_exact(&mut key)?;
+        
+        let mut seed_buf = [0u8; 4];
+        reader.seek(SeekFrom::Start(34))?;
+        reader.read_exact(&mut seed_buf)?;
+        
+        let entries = Self::read_file_table(reader, version, source)?;
+        Ok((version, key, u32::from_le_bytes(seed_buf), entries))
+    }
+    
+    pub fn key(&self) -> &GrfKey {
+        &self.key
+    }
+    
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+    
+    pub fn path(&self) -> Option<&Path> {
+        match &self.backend {
+            #[cfg(feature = "fs")]
+            GrfBackend::File(path) => Some(path),
+            GrfBackend::Memory(_) => None,
+        }
+    }
+    
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.backend {
+            #[cfg(feature = "fs")]
+            GrfBackend::File(_) => None,
+            GrfBackend::Memory(data) => Some(data),
+        }
+    }
+    
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        match self.backend {
+            #[cfg(feature = "fs")]
+            GrfBackend::File(path) => Ok(std::fs::read(path)?),
+            GrfBackend::Memory(data) => Ok(data),
+        }
+    }
+    
+    fn open_backend(&self) -> Result<Box<dyn ReadSeek + '_>> {
+        match &self.backend {
+            #[cfg(feature = "fs")]
+            GrfBackend::File(path) => Ok(Box::new(std::fs::File::open(path)?)),
+            GrfBackend::Memory(data) => Ok(Box::new(std::io::Cursor::new(&data[..]))),
+        }
+    }
+    
+    // `source` is the archive's path when it is opened from disk, which lets the table come from the cache
+    #[cfg_attr(not(feature = "fs"), allow(unused_variables))]
+    fn read_file_table<R: Read + Seek>(reader: &mut R, version: u32, source: Option<&Path>) -> Result<HashMap<String, GrfEntry>> {
+        let (table, table_size, file_count) = Self::read_raw_table(reader, version)?;
+        
+        // 0x300 tables are encrypted to keep them from being read, so they are never written out decrypted
+        #[cfg(feature = "fs")]
+        let cache_key = source
+            .filter(|_| version != GRF_VERSION_0X300)
+            .and_then(|path| table_cache::TableKey::new(path, &table));
+        #[cfg(feature = "fs")]
+        if let Some(entries) = cache_key.as_ref().and_then(table_cache::load) {
+            return Ok(entries);
+        }
+        
+        let entries = match version {
+            GRF_VERSION_0X300 => Self::parse_table_entries(Self::decrypt_grf_0x300_table(&table, table_size)?)?,
             GRF_VERSION_0X200 => {
-                // Read header fields
+                let table_data = limits::decompress_entry(
+                    ZlibDecoder::new(&table[..]),
+                    table.len() as u64,
+                    table_size as u64,
+                    "GRF file table",
+                )?;
+                Self::parse_table_entries(table_data)?
+            },
+            _ => Self::parse_legacy_table(&table, file_count)?,
+        };
+        
+        #[cfg(feature = "fs")]
+        if let Some(key) = &cache_key {
+            table_cache::store(key, &entries);
+        }
+        Ok(entries)
+    }
+    
+    // The table as stored: compressed (and for 0x300 encrypted) for 0x200 and up, with its uncompressed size, and
+    // the plain legacy table with its entry count for 0x101-0x103
+    fn read_raw_table<R: Read + Seek>(reader: &mut R, version: u32) -> Result<(Vec<u8>, u32, u32)> {
+        match version {
+            GRF_VERSION_0X200 | GRF_VERSION_0X300 => {
+                // Same header and table metadata layout; only 0x300 table bytes go through the crypto plugin
                 reader.seek(SeekFrom::Start(30))?;
-                let mut table_offset_buf = [0u8; 4];
-                reader.read_exact(&mut table_offset_buf)?;
-                let table_offset = u32::from_le_bytes(table_offset_buf);
-                
-                let mut seed_buf = [0u8; 4];
-                reader.read_exact(&mut seed_buf)?;
-                
-                let mut file_count_buf = [0u8; 4];
-                reader.read_exact(&mut file_count_buf)?;
-                let _file_count = u32::from_le_bytes(file_count_buf);
-                
-                // Seek to table metadata (at FileTableOffset + 46)
-                reader.seek(SeekFrom::Start((table_offset + GRF_HEADER_SIZE) as u64))?;
-                
-                // Read table metadata
-                let mut table_compressed_size_buf = [0u8; 4];
-                reader.read_exact(&mut table_compressed_size_buf)?;
-                let table_compressed_size = u32::from_le_bytes(table_compressed_size_buf);
+                let mut header = [0u8; 12];
+                reader.read_exact(&mut header)?;
+                let table_offset = u32::from_le_bytes(header[0..4].try_into().unwrap());
+                let file_count = u32::from_le_bytes(header[8..12].try_into().unwrap());
                 
-                let mut table_size_buf = [0u8; 4];
-                reader.read_exact(&mut table_size_buf)?;
-                let table_size = u32::from_le_bytes(table_size_buf);
+                // Table metadata sits at FileTableOffset + 46
+                reader.seek(SeekFrom::Start(table_offset as u64 + GRF_HEADER_SIZE as u64))?;
+                let mut metadata = [0u8; 8];
+                reader.read_exact(&mut metadata)?;
+                let table_compressed_size = u32::from_le_bytes(metadata[0..4].try_into().unwrap());
+                let table_size = u32::from_le_bytes(metadata[4..8].try_into().unwrap());
                 
-                tracing::info!("Reading GRF 0x200 - file_count: {}, table_offset: {}, table_size: {}, compressed_size: {}", 
-                    _file_count, table_offset, table_size, table_compressed_size);
+                tracing::info!("Reading GRF {:#x} - file_count: {}, table_offset: {}, table_size: {}, compressed_size: {}",
+                    version, file_count, table_offset, table_size, table_compressed_size);
                 
-                // Read compressed table data (already at correct position after reading metadata)
-                
-                let mut compressed_table = vec![0u8; table_compressed_size as usize];
-                reader.read_exact(&mut compressed_table)?;
-                
-                let mut decompressor = ZlibDecoder::new(&compressed_table[..]);
-                let mut table_data = Vec::with_capacity(table_size as usize);
-                decompressor.read_to_end(&mut table_data)?;
-                
-                let mut cursor = std::io::Cursor::new(table_data);
-                
-                while cursor.position() < cursor.get_ref().len() as u64 {
-                    // Read null-terminated filename
-                    let mut filename_bytes = Vec::new();
-                    loop {
-                        let mut byte = [0u8; 1];
-                        if cursor.read(&mut byte).unwrap_or(0) == 0 {
-                            break; // EOF
-                        }
-                        if byte[0] == 0 {
-                            break; // Null terminator
-                        }
-                        filename_bytes.push(byte[0]);
-                    }
-                    
-                    if filename_bytes.is_empty() {
-                        break; // No more entries
-                    }
-                    
-                    let filename = String::from_utf8_lossy(&filename_bytes).to_string();
-                    
-                    let mut compressed_size_buf = [0u8; 4];
-                    cursor.read_exact(&mut compressed_size_buf)?;
-                    let compressed_size = u32::from_le_bytes(compressed_size_buf);
-                    
-                    let mut compressed_size_aligned_buf = [0u8; 4];
-                    cursor.read_exact(&mut compressed_size_aligned_buf)?;
-                    let compressed_size_aligned = u32::from_le_bytes(compressed_size_aligned_buf);
-                    
-                    let mut uncompressed_size_buf = [0u8; 4];
-                    cursor.read_exact(&mut uncompressed_size_buf)?;
-                    let uncompressed_size = u32::from_le_bytes(uncompressed_size_buf);
-                    
-                    let mut flags_buf = [0u8; 1];
-                    cursor.read_exact(&mut flags_buf)?;
-                    let flags = flags_buf[0];
-                    
-                    let mut offset_buf = [0u8; 4];
-                    cursor.read_exact(&mut offset_buf)?;
-                    let offset = u32::from_le_bytes(offset_buf);
-                    
-                    entries.insert(
-                        filename.clone(),
-                        GrfEntry {
-                            filename,
-                            compressed_size,
-                            compressed_size_aligned,
-                            uncompressed_size,
-                            flags,
-                            offset,
-                        },
-                    );
-                }
+                let table = bounds::read_bytes(reader, table_compressed_size as u64, "GRF file table")?;
+                Ok((table, table_size, file_count))
             },
             _ => {
+                // 0x101-0x103: the count is stored offset by the seed and the table runs to the end of the archive
                 reader.seek(SeekFrom::Start(30))?;
+                let mut header = [0u8; 12];
+                reader.read_exact(&mut header)?;
+                let table_offset = u32::from_le_bytes(header[0..4].try_into().unwrap());
+                let seed = u32::from_le_bytes(header[4..8].try_into().unwrap());
+                let file_count = u32::from_le_bytes(header[8..12].try_into().unwrap()).wrapping_sub(seed).wrapping_sub(7);
                 
-                let mut file_count_buf = [0u8; 4];
-                reader.read_exact(&mut file_count_buf)?;
-                let file_count = u32::from_le_bytes(file_count_buf);
-                
-                reader.seek(SeekFrom::Start(GRF_HEADER_SIZE as u64))?;
-                
-                for _ in 0..file_count {
-                    let mut filename_len_buf = [0u8; 4];
-                    reader.read_exact(&mut filename_len_buf)?;
-                    let filename_len = u32::from_le_bytes(filename_len_buf);
-                    
-                    let mut filename_buf = vec![0u8; filename_len as usize];
-                    reader.read_exact(&mut filename_buf)?;
-                    let filename = String::from_utf8_lossy(&filename_buf).to_string();
-                    
-                    let mut compressed_size_buf = [0u8; 4];
-                    reader.read_exact(&mut compressed_size_buf)?;
-                    let compressed_size = u32::from_le_bytes(compressed_size_buf);
-                    
-                    let mut compressed_size_aligned_buf = [0u8; 4];
-                    reader.read_exact(&mut compressed_size_aligned_buf)?;
-                    let compressed_size_aligned = u32::from_le_bytes(compressed_size_aligned_buf);
-                    
-                    let mut uncompressed_size_buf = [0u8; 4];
-                    reader.read_exact(&mut uncompressed_size_buf)?;
-                    let uncompressed_size = u32::from_le_bytes(uncompressed_size_buf);
-                    
-                    let mut flags_buf = [0u8; 1];
-                    reader.read_exact(&mut flags_buf)?;
-                    let flags = flags_buf[0];
-                    
-                    let mut offset_buf = [0u8; 4];
-                    reader.read_exact(&mut offset_buf)?;
-                    let offset = u32::from_le_bytes(offset_buf);
-                    
-                    entries.insert(
-                        filename.clone(),
-                        GrfEntry {
-                            filename,
-                            compressed_size,
-                            compressed_size_aligned,
-                            uncompressed_size,
-                            flags,
-                            offset,
-                        },
-                    );
+                reader.seek(SeekFrom::Start(table_offset as u64 + GRF_HEADER_SIZE as u64))?;
+                let table_len = bounds::remaining(reader)?;
+                let table = bounds::read_bytes(reader, table_len, "GRF file table")?;
+                Ok((table, 0, file_count))
+            }
+        }
+    }
+    
+    fn parse_table_entries(table_data: Vec<u8>) -> Result<HashMap<String, GrfEntry>> {
+        let mut entries = HashMap::new();
+        let mut cursor = std::io::Cursor::new(table_data);
+        
+        while cursor.position() < cursor.get_ref().len() as u64 {
+            // Read null-terminated filename
+            let mut filename_bytes = Vec::new();
+            loop {
+                let mut byte = [0u8; 1];
+                if cursor.read(&mut byte).unwrap_or(0) == 0 {
+                    break; // EOF
+                }
+                if byte[0] == 0 {
+                    break; // Null terminator
                 }
+                filename_bytes.push(byte[0]);
             }
+            
+            if filename_bytes.is_empty() {
+                break; // No more entries
+            }
+            
+            let filename = path::decode_name(&filename_bytes);
+            
+            let mut compressed_size_buf = [0u8; 4];
+            cursor.read_exact(&mut compressed_size_buf)?;
+            let compressed_size = u32::from_le_bytes(compressed_size_buf);
+            
+            let mut compressed_size_aligned_buf = [0u8; 4];
+            cursor.read_exact(&mut compressed_size_aligned_buf)?;
+            let compressed_size_aligned = u32::from_le_bytes(compressed_size_aligned_buf);
+            
+            let mut uncompressed_size_buf = [0u8; 4];
+            cursor.read_exact(&mut uncompressed_size_buf)?;
+            let uncompressed_size = u32::from_le_bytes(uncompressed_size_buf);
+            
+            let mut flags_buf = [0u8; 1];
+            cursor.read_exact(&mut flags_buf)?;
+            let flags = flags_buf[0];
+            
+            let mut offset_buf = [0u8; 4];
+            cursor.read_exact(&mut offset_buf)?;
+            let offset = u32::from_le_bytes(offset_buf);
+            
+            entries.insert(
+                filename.clone(),
+                GrfEntry {
+                    filename,
+                    compressed_size,
+                    compressed_size_aligned,
+                    uncompressed_size,
+                    flags,
+                    offset,
+                },
+            );
         }
         
         Ok(entries)
     }
     
-    fn decrypt_grf_0x300_table(compressed_data: &[u8], expected_size: u32) -> Result<Vec<u8>> {
-        let mut decompressor = ZlibDecoder::new(compressed_data);
-        let mut table_data = Vec::with_capacity(expected_size as usize);
-        decompressor.read_to_end(&mut table_data)
-            .map_err(|e| Error::Decompression(e.to_string()))?;
+    // Names are obfuscated and sizes and offsets stored with fixed offsets added. Directory records are skipped;
+    // every file is DES-encrypted, only its start for the types the client reads in place.
+    fn parse_legacy_table(table: &[u8], file_count: u32) -> Result<HashMap<String, GrfEntry>> {
+        let read_u32 = |pos: usize| {
+            pos.checked_add(4)
+                .and_then(|end| table.get(pos..end))
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .ok_or_else(|| Error::Malformed(format!("GRF file table ends inside an entry at {}", pos)))
+        };
+        
+        let mut entries = HashMap::new();
+        let mut pos = 0usize;
+        for _ in 0..file_count {
+            let info = pos
+                .checked_add(read_u32(pos)? as usize)
+                .and_then(|info| info.checked_add(4))
+                .ok_or_else(|| Error::Malformed("GRF entry name length overflows".to_string()))?;
+            let flags = *table.get(info + 12).ok_or_else(|| {
+                Error::Malformed(format!("GRF file table ends inside an entry at {}", info))
+            })?;
+            
+            if flags & 0x01 != 0 {
+                let name_len = (table[pos] as usize).saturating_sub(6);
+                let mut name = table
+                    .get(pos + 6..pos + 6 + name_len)
+                    .ok_or_else(|| Error::Malformed(format!("GRF entry name at {} exceeds the file table", pos)))?
+                    .to_vec();
+                des::decrypt_name(&mut name);
+                if let Some(end) = name.iter().position(|&b| b == 0) {
+                    name.truncate(end);
+                }
+                let filename = path::decode_name(&name);
+                
+                let stored_size = read_u32(info)?;
+                let uncompressed_size = read_u32(info + 8)?;
+                let encryption = if des::is_fully_encrypted(&filename) { 0x02 } else { 0x04 };
+                entries.insert(
+                    filename.clone(),
+                    GrfEntry {
+                        compressed_size: stored_size.wrapping_sub(uncompressed_size).wrapping_sub(715),
+                        compressed_size_aligned: read_u32(info + 4)?.wrapping_sub(37579),
+                        uncompressed_size,
+                        flags: flags | encryption,
+                        offset: read_u32(info + 13)?,
+                        filename,
+                    },
+                );
+            }
+            pos = info + 17;
+        }
         
-        Ok(table_data)
+        Ok(entries)
+    }
+    
+    fn decrypt_grf_0x300_table(compressed_data: &[u8], expected_size: u32) -> Result<Vec<u8>> {
+        let crypto = crypto::grf_crypto();
+        tracing::debug!("Decrypting GRF 0x300 table with crypto plugin: {}", crypto.name());
+        crypto.decrypt_table(compressed_data, expected_size)
+    }
+    
+    // The name an entry or pending patch is stored under, for a name that may differ from it in case or separators.
+    // Names not in the archive come back in archive form.
+    fn resolve<'a>(&'a self, filename: &'a str) -> Cow<'a, str> {
+        if self.entries.contains_key(filename) || self.pending_patches.contains_key(filename) {
+            return Cow::Borrowed(filename);
+        }
+        let index = self.name_index.get_or_init(|| {
+            self.entries.keys()
+                .chain(self.pending_patches.keys())
+                .map(|name| (path::name_key(name), name.clone()))
+                .collect()
+        });
+        match index.get(&path::name_key(filename)) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(path::to_archive_name(filename)),
+        }
     }
     
+    // Entries waiting to be removed are already gone here, though the archive still holds them until the next save
     pub fn get_entry(&self, filename: &str) -> Option<&GrfEntry> {
-        self.entries.get(filename)
+        let filename = self.resolve(filename);
+        if self.pending_removals.contains(filename.as_ref()) {
+            return None;
+        }
+        self.entries.get(filename.as_ref())
     }
     
     pub fn extract_file(&self, filename: &str) -> Result<Vec<u8>> {
-        let entry = self.entries.get(filename)
+        let entry = self.get_entry(filename)
             .ok_or_else(|| Error::FileNotFound(filename.to_string()))?;
         
-        let mut file = std::fs::File::open(&self.file_path)?;
-        file.seek(SeekFrom::Start((entry.offset + GRF_HEADER_SIZE) as u64))?;
+        let mut reader = self.open_backend()?;
+        let data_offset = entry.offset as u64 + GRF_HEADER_SIZE as u64;
+        let archive_len = bounds::stream_len(&mut reader)?;
+        bounds::check_range(data_offset, entry.compressed_size_aligned as u64, archive_len, filename)?;
+        reader.seek(SeekFrom::Start(data_offset))?;
         
         let mut compressed_data = vec![0u8; entry.compressed_size_aligned as usize];
-        file.read_exact(&mut compressed_data)?;
+        reader.read_exact(&mut compressed_data)?;
+        
+        let data = Self::decode_entry(self.version, entry, compressed_data)?;
+        if entry.flags & 0x01 != 0 {
+            self.budget.consume(data.len() as u64, filename)?;
+        }
+        Ok(data)
+    }
+    
+    // Like `extract_file`, but decompresses while the caller reads, so an entry never has to fit in memory.
+    // The same decompression limits apply; exceeding one fails the read that crossed it.
+    pub fn open_entry_stream(&self, filename: &str) -> Result<GrfEntryReader<'_>> {
+        let entry = self.get_entry(filename)
+            .ok_or_else(|| Error::FileNotFound(filename.to_string()))?;
+        
+        let mut reader = self.open_backend()?;
+        let data_offset = entry.offset as u64 + GRF_HEADER_SIZE as u64;
+        let archive_len = bounds::stream_len(&mut reader)?;
+        bounds::check_range(data_offset, entry.compressed_size_aligned as u64, archive_len, filename)?;
+        reader.seek(SeekFrom::Start(data_offset))?;
+        
+        // The crypto plugin and DES work on whole buffers; the encrypted data is still only the compressed size
+        let raw: Box<dyn Read + '_> = if self.version == GRF_VERSION_0X300 || entry.flags & 0x06 != 0 {
+            let mut encrypted = vec![0u8; entry.compressed_size_aligned as usize];
+            reader.read_exact(&mut encrypted)?;
+            Box::new(std::io::Cursor::new(Self::decrypt_entry(self.version, entry, encrypted)?))
+        } else {
+            Box::new(BufReader::new(reader.take(entry.compressed_size_aligned as u64)))
+        };
+        
+        let compressed = entry.flags & 0x01 != 0;
+        let limits = limits::decompression_limits();
+        Ok(GrfEntryReader {
+            inner: if compressed {
+                Box::new(ZlibDecoder::new(raw))
+            } else {
+                // Stored entries are padded to the aligned size; the padding is not part of the file
+                Box::new(raw.take(entry.uncompressed_size as u64))
+            },
+            budget: compressed.then_some(&self.budget),
+            filename: filename.to_string(),
+            size: entry.uncompressed_size as u64,
+            compressed_len: entry.compressed_size_aligned as u64,
+            allowed: if compressed {
+                limits.allowed_output(entry.compressed_size_aligned as u64, limits.max_entry_size)
+            } else {
+                u64::MAX
+            },
+            produced: 0,
+        })
+    }
+    
+    fn decrypt_entry(version: u32, entry: &GrfEntry, mut data: Vec<u8>) -> Result<Vec<u8>> {
+        if version == GRF_VERSION_0X300 {
+            data = crypto::grf_crypto().decrypt_entry(entry, data)?;
+        }
+        if entry.flags & 0x06 != 0 {
+            des::decrypt_entry(&mut data, entry.flags, entry.compressed_size);
+        }
+        Ok(data)
+    }
+    
+    fn decode_entry(version: u32, entry: &GrfEntry, compressed_data: Vec<u8>) -> Result<Vec<u8>> {
+        let mut compressed_data = Self::decrypt_entry(version, entry, compressed_data)?;
         
         if entry.flags & 0x01 != 0 {
-            let mut decompressor = ZlibDecoder::new(&compressed_data[..]);
-            let mut decompressed = Vec::with_capacity(entry.uncompressed_size as usize);
-            decompressor.read_to_end(&mut decompressed)
-                .map_err(|e| Error::Decompression(e.to_string()))?;
-            Ok(decompressed)
+            limits::decompress_entry(
+                ZlibDecoder::new(&compressed_data[..]),
+                compressed_data.len() as u64,
+                entry.uncompressed_size as u64,
+                &entry.filename,
+            )
         } else {
+            // Stored entries are padded to the aligned size; drop the padding
+            compressed_data.truncate(entry.uncompressed_size as usize);
             Ok(compressed_data)
         }
     }
     
+    // Decoded text for the GRF browser; compiled lub goes through the registered decompiler, other binaries are refused
+    pub fn preview_text(&self, filename: &str, limit: usize) -> Result<text::TextPreview> {
+        let data = self.extract_file(filename)?;
+        
+        if lua::detect_lua_form(&data).is_compiled() {
+            let decompiler = lua::lub_decompiler().ok_or_else(|| {
+                Error::Unsupported(format!("{} is compiled Lua and no lub decompiler is registered", filename))
+            })?;
+            let source = decompiler.decompile(filename, &data)?;
+            let mut preview = text::decode_text(source.as_bytes(), limit);
+            preview.decompiled_by = Some(decompiler.name().to_string());
+            return Ok(preview);
+        }
+        
+        if text::looks_binary(&data) {
+            return Err(Error::Unsupported(format!("{} is not a text file", filename)));
+        }
+        Ok(text::decode_text(&data, limit))
+    }
+    
+    // Palettes render as colour swatches and maps (.gat, or .rsw via its sibling .gat) as a walkability minimap
+    pub fn preview_image(&self, filename: &str) -> Result<raster::RgbaImage> {
+        let (stem, extension) = filename.rsplit_once('.').unwrap_or((filename, ""));
+        
+        match extension.to_ascii_lowercase().as_str() {
+            "pal" => Ok(pal::Palette::parse(&self.extract_file(filename)?)?.render_swatches(8)),
+            "gat" => Ok(gat::Gat::parse(&self.extract_file(filename)?)?.render_minimap()),
+            "rsw" => {
+                Ok(gat::Gat::parse(&self.extract_file(&format!("{}.gat", stem))?)?.render_minimap())
+            }
+            _ => Err(Error::Unsupported(format!("No image preview for {}", filename))),
+        }
+    }
+    
+    pub fn lua_form(&self, filename: &str) -> Result<lua::LuaForm> {
+        Ok(lua::detect_lua_form(&self.extract_file(filename)?))
+    }
+    
+    // Replaces the entry the client would find under `filename`, keeping its stored spelling
     pub fn patch_file(&mut self, filename: &str, data: &[u8]) -> Result<()> {
         // Store uncompressed data in memory for later rebuild
         tracing::info!("patch_file() called for: {} ({} bytes)", filename, data.len());
-        self.pending_patches.insert(filename.to_string(), data.to_vec());
+        let filename = self.resolve(filename).into_owned();
+        if let Some(index) = self.name_index.get_mut() {
+            index.insert(path::name_key(&filename), filename.clone());
+        }
+        self.pending_removals.remove(&filename);
+        self.pending_patches.insert(filename, data.to_vec());
         tracing::debug!("Total pending patches now: {}", self.pending_patches.len());
         Ok(())
     }
     
+    // Drops the entry the client would find under `filename` on the next save, along with any pending patch for it.
+    // Returns false when there was nothing to remove.
+    pub fn remove_file(&mut self, filename: &str) -> Result<bool> {
+        let filename = self.resolve(filename).into_owned();
+        let patched = self.pending_patches.remove(&filename).is_some();
+        let stored = self.entries.contains_key(&filename) && self.pending_removals.insert(filename.clone());
+        if patched || stored {
+            tracing::debug!("Removing entry: {}", filename);
+        }
+        Ok(patched || stored)
+    }
+    
+    // Stored entries are streamed through the same hash `ContentIndex` uses, so a large entry is never held whole
+    pub fn content_matches(&self, filename: &str, data: &[u8]) -> Result<bool> {
+        let filename = self.resolve(filename);
+        let filename = filename.as_ref();
+        
+        if let Some(pending) = self.pending_patches.get(filename) {
+            return Ok(pending.as_slice() == data);
+        }
+        
+        let entry = match self.get_entry(filename) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+        
+        if entry.uncompressed_size as usize != data.len() {
+            return Ok(false);
+        }
+        
+        match ContentHash::of_entry(self, filename) {
+            Ok((existing, len)) => Ok(len == data.len() as u64 && existing == ContentHash::of(data)),
+            Err(e) => {
+                tracing::warn!("Existing entry {} unreadable, will overwrite: {}", filename, e);
+                Ok(false)
+            }
+        }
+    }
+    
+    // Returns false when the entry already holds identical content and nothing was queued
+    pub fn patch_file_if_changed(&mut self, filename: &str, data: &[u8]) -> Result<bool> {
+        if self.content_matches(filename, data)? {
+            tracing::debug!("Skipping unchanged entry: {}", filename);
+            return Ok(false);
+        }
+        
+        self.patch_file(filename, data)?;
+        Ok(true)
+    }
+    
+    // Data alignment for newly written entries; 1 (the default) matches standard GRF tools
+    pub fn set_alignment(&mut self, alignment: u32) -> Result<()> {
+        if !alignment.is_power_of_two() {
+            return Err(Error::Custom(format!("GRF alignment must be a power of two, got {}", alignment)));
+        }
+        self.alignment = alignment;
+        Ok(())
+    }
+    
+    pub fn alignment(&self) -> u32 {
+        self.alignment
+    }
+    
+    pub fn set_flag_policy(&mut self, policy: GrfFlagPolicy) -> Result<()> {
+        if let GrfFlagPolicy::Fixed(flags) = policy {
+            if flags & 0x06 != 0 {
+                return Err(Error::Custom(format!("GRF entry flags {:#04x} would mark entries as DES-encrypted", flags)));
+            }
+        }
+        self.flag_policy = policy;
+        Ok(())
+    }
+    
+    pub fn flag_policy(&self) -> GrfFlagPolicy {
+        self.flag_policy
+    }
+    
+    // zlib level (0-9) for newly written entries; 6 by default
+    pub fn set_compression_level(&mut self, level: u32) -> Result<()> {
+        if level > 9 {
+            return Err(Error::Custom(format!("GRF compression level must be 0 to 9, got {}", level)));
+        }
+        self.compression_level = level;
+        Ok(())
+    }
+    
     pub fn list_files(&self) -> Vec<&str> {
-        self.entries.keys().map(|s| s.as_str()).collect()
+        self.entries.keys()
+            .filter(|name| !self.pending_removals.contains(*name))
+            .map(|s| s.as_str())
+            .collect()
     }
     
     pub fn file_count(&self) -> usize {
-        self.entries.len()
+        self.entries.len() - self.pending_removals.len()
     }
     
     pub fn save(&mut self) -> Result<()> {
+        self.save_with(&OperationControl::default())
+    }
+    
+    pub fn save_with(&mut self, control: &OperationControl) -> Result<()> {
         if self.version != GRF_VERSION_0X200 && self.version != GRF_VERSION_0X300 {
-            return Err(Error::Unsupported("Only GRF 0x200 and 0x300 save is supported".to_string()));
+            return Err(Error::Unsupported("Only GRF 0x200 and 0x300 save is supported; convert older archives with convert_version first".to_string()));
+        }
+        
+        tracing::info!("GRF save() called - pending patches: {}, pending removals: {}, existing entries: {}", 
+            self.pending_patches.len(), self.pending_removals.len(), self.entries.len());
+        
+        if self.pending_patches.is_empty() && self.pending_removals.is_empty() {
+            tracing::info!("No pending patches, skipping save");
+            return Ok(());
         }
         
-        tracing::info!("GRF save() called - pending patches: {}, existing entries: {}", 
-            self.pending_patches.len(), self.entries.len());
+        self.rebuild(self.version, false, control)
+    }
+    
+    // Upper bound of the bytes the pending patches add to the archive
+    pub fn pending_size(&self) -> u64 {
+        self.pending_patches.values().map(|data| data.len() as u64).sum()
+    }
+    
+    #[cfg(feature = "fs")]
+    pub fn save_in_place(&mut self) -> Result<()> {
+        self.save_in_place_with(&OperationControl::default())
+    }
+    
+    // For disks without room for a rebuilt copy: new entries are appended where the old file table was and a fresh
+    // table is written after them. Replaced entries stay behind as dead space until the next full rebuild.
+    // The header and old table are journaled first, so `recover_interrupted_save` can roll an interrupted run back.
+    #[cfg(feature = "fs")]
+    pub fn save_in_place_with(&mut self, control: &OperationControl) -> Result<()> {
+        if self.version != GRF_VERSION_0X200 && self.version != GRF_VERSION_0X300 {
+            return Err(Error::Unsupported("Only GRF 0x200 and 0x300 save is supported; convert older archives with convert_version first".to_string()));
+        }
         
-        if self.pending_patches.is_empty() {
+        if self.pending_patches.is_empty() && self.pending_removals.is_empty() {
             tracing::info!("No pending patches, skipping save");
             return Ok(());
         }
         
-        tracing::info!("Starting GRF rebuild at: {:?}", self.file_path);
+        let file_path = match &self.backend {
+            GrfBackend::File(path) => path.clone(),
+            GrfBackend::Memory(_) => return self.save_with(control),
+        };
+        
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).open(&file_path)?;
+        let original_len = file.metadata()?.len();
+        let mut header = [0u8; GRF_HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        let table_offset = u32::from_le_bytes(header[30..34].try_into().unwrap());
+        // A freshly created archive has no table yet, only the header
+        let table_start = (table_offset as u64 + GRF_HEADER_SIZE as u64).min(original_len);
+        
+        let journal_path = journal_path(&file_path);
+        {
+            let mut journal = std::fs::File::create(&journal_path)?;
+            journal.write_all(JOURNAL_MAGIC)?;
+            journal.write_all(&original_len.to_le_bytes())?;
+            journal.write_all(&table_start.to_le_bytes())?;
+            journal.write_all(&header)?;
+            file.seek(SeekFrom::Start(table_start))?;
+            std::io::copy(&mut (&mut file).take(original_len - table_start), &mut journal)?;
+            journal.sync_all()?;
+        }
+        
+        let result = self.append_pending(&mut file, table_start, control);
+        drop(file);
+        
+        match result {
+            Ok(new_entries) => {
+                std::fs::remove_file(&journal_path)?;
+                self.entries = new_entries;
+                self.pending_patches.clear();
+                self.pending_removals.clear();
+                tracing::info!("In-place GRF save completed - total entries: {}", self.entries.len());
+                Ok(())
+            }
+            Err(e) => {
+                tracing::warn!("In-place GRF save aborted, rolling back: {}", e);
+                Self::roll_back_in_place_save(&file_path, &journal_path)?;
+                Err(e)
+            }
+        }
+    }
+    
+    #[cfg(feature = "fs")]
+    fn append_pending(
+        &self,
+        file: &mut std::fs::File,
+        table_start: u64,
+        control: &OperationControl,
+    ) -> Result<HashMap<String, GrfEntry>> {
+        let entry_crypto = (self.version == GRF_VERSION_0X300).then(crypto::grf_crypto);
+        // Removed entries stay behind as dead space, like replaced ones
+        let mut new_entries = self.entries.clone();
+        new_entries.retain(|filename, _| !self.pending_removals.contains(filename));
+        let mut current_offset = (table_start - GRF_HEADER_SIZE as u64) as u32;
+        
+        file.seek(SeekFrom::Start(table_start))?;
+        let mut writer = BufWriter::with_capacity(SAVE_BUFFER_SIZE, &mut *file);
+        
+        let aligned_offset = (current_offset + self.alignment - 1) & !(self.alignment - 1);
+        writer.write_all(&vec![0u8; (aligned_offset - current_offset) as usize])?;
+        current_offset = aligned_offset;
+        
+        let mut patches: Vec<(&String, &Vec<u8>)> = self.pending_patches.iter().collect();
+        patches.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        let total = patches.len() as u64;
+        for (done, (filename, data)) in patches.into_iter().enumerate() {
+            control.step(filename, done as u64, total)?;
+            let entry = self.write_entry(&mut writer, filename, data, current_offset, self.policy_flags(filename), entry_crypto.as_ref())?;
+            current_offset = next_offset(current_offset, entry.compressed_size_aligned)?;
+            new_entries.insert(filename.clone(), entry);
+        }
+        control.report("", total, total);
+        
+        let table_end = self.write_table(&mut writer, &new_entries, current_offset, entry_crypto.as_ref())?;
+        drop(writer);
+        
+        file.set_len(table_end)?;
+        file.sync_all()?;
+        Ok(new_entries)
+    }
+    
+    // Bytes of entries that in-place saves replaced but left in the archive
+    pub fn dead_space(&self) -> u64 {
+        let live: u64 = self.entries.values().map(|e| e.compressed_size_aligned as u64).sum();
+        let data_end = self.entries.values()
+            .map(|e| e.offset as u64 + e.compressed_size_aligned as u64)
+            .max()
+            .unwrap_or(0);
+        data_end.saturating_sub(live)
+    }
+    
+    pub fn defragment(&mut self) -> Result<()> {
+        self.defragment_with(&OperationControl::default())
+    }
+    
+    // Compacts an archive grown by in-place saves, applying any pending patches on the way. It is a full rebuild,
+    // so it needs room for a second copy of the archive.
+    pub fn defragment_with(&mut self, control: &OperationControl) -> Result<()> {
+        if self.version != GRF_VERSION_0X200 && self.version != GRF_VERSION_0X300 {
+            return Err(Error::Unsupported("Only GRF 0x200 and 0x300 save is supported; convert older archives with convert_version first".to_string()));
+        }
+        
+        let dead_space = self.dead_space();
+        self.rebuild(self.version, false, control)?;
+        tracing::info!("Defragmented GRF, reclaimed {} bytes", dead_space);
+        Ok(())
+    }
+    
+    pub fn repack(&mut self, level: Option<u32>) -> Result<u64> {
+        self.repack_with(level, &OperationControl::default())
+    }
+    
+    // Rewrites the archive with only its live entries like `defragment`, and with `level` also recompresses every
+    // entry at that zlib level. Returns how many bytes smaller the archive got.
+    pub fn repack_with(&mut self, level: Option<u32>, control: &OperationControl) -> Result<u64> {
+        if self.version != GRF_VERSION_0X200 && self.version != GRF_VERSION_0X300 {
+            return Err(Error::Unsupported("Only GRF 0x200 and 0x300 save is supported; convert older archives with convert_version first".to_string()));
+        }
+        if let Some(level) = level {
+            self.set_compression_level(level)?;
+        }
+        
+        let before = self.archive_size()?;
+        self.rebuild(self.version, level.is_some(), control)?;
+        let saved = before.saturating_sub(self.archive_size()?);
+        tracing::info!("Repacked GRF, {} bytes smaller", saved);
+        Ok(saved)
+    }
+    
+    fn archive_size(&self) -> Result<u64> {
+        bounds::stream_len(&mut self.open_backend()?)
+    }
+    
+    pub fn convert_version(&mut self, target: u32) -> Result<()> {
+        self.convert_version_with(target, &OperationControl::default())
+    }
+    
+    // Rewrites the archive as `target`, carrying over every entry and any pending patches. Old 0x101-0x103
+    // archives can be brought to 0x200; 0x300 is reachable in either direction while a crypto plugin is registered.
+    pub fn convert_version_with(&mut self, target: u32, control: &OperationControl) -> Result<()> {
+        match target {
+            GRF_VERSION_0X200 => {}
+            GRF_VERSION_0X300 if crypto::has_grf_crypto() => {}
+            GRF_VERSION_0X300 => {
+                return Err(Error::Unsupported(
+                    "Converting to GRF 0x300 requires a registered GRF crypto plugin".to_string(),
+                ))
+            }
+            other => {
+                return Err(Error::Unsupported(format!(
+                    "GRFs can only be converted to 0x200 or 0x300, not {:#x}",
+                    other
+                )))
+            }
+        }
+        
+        if self.version == target {
+            tracing::info!("GRF is already {}", Self::version_name(target));
+            return self.save_with(control);
+        }
+        
+        tracing::info!(
+            "Converting GRF from {} to {}",
+            Self::version_name(self.version),
+            Self::version_name(target)
+        );
+        self.rebuild(target, false, control)
+    }
+    
+    // With `recompress`, kept entries are decoded and written again like patches instead of copied as they are
+    fn rebuild(&mut self, target_version: u32, recompress: bool, control: &OperationControl) -> Result<()> {
+        let new_entries = match &self.backend {
+            #[cfg(feature = "fs")]
+            GrfBackend::File(file_path) => {
+                tracing::info!("Starting GRF rebuild at: {:?}", file_path);
+                
+                // Build the new archive next to the original and swap it in with a single rename,
+                // so an interrupted save (crash, kill, closed window) never leaves a half-written GRF
+                let temp_path = temp_save_path(file_path);
+                let result = (|| {
+                    let mut new_file = std::fs::File::create(&temp_path)?;
+                    let mut old_grf = std::fs::File::open(file_path)?;
+                    let new_entries = self.write_archive(&mut old_grf, &mut new_file, target_version, recompress, control)?;
+                    new_file.sync_all()?;
+                    Ok(new_entries)
+                })();
+                
+                match result {
+                    Ok(new_entries) => {
+                        std::fs::rename(&temp_path, file_path)?;
+                        new_entries
+                    }
+                    Err(e) => {
+                        tracing::warn!("GRF rebuild aborted, original archive left untouched: {}", e);
+                        let _ = std::fs::remove_file(&temp_path);
+                        return Err(e);
+                    }
+                }
+            }
+            GrfBackend::Memory(data) => {
+                tracing::info!("Starting in-memory GRF rebuild ({} bytes)", data.len());
+                
+                let mut old_grf = std::io::Cursor::new(&data[..]);
+                let mut new_grf = std::io::Cursor::new(Vec::with_capacity(data.len()));
+                let new_entries = self.write_archive(&mut old_grf, &mut new_grf, target_version, recompress, control)?;
+                
+                self.backend = GrfBackend::Memory(new_grf.into_inner());
+                new_entries
+            }
+        };
         
-        // Rebuild GRF with pending patches
-        let backup_path = self.file_path.with_extension("grf.bak");
-        tracing::info!("Creating backup: {:?}", backup_path);
-        std::fs::rename(&self.file_path, &backup_path)?;
+        // Update internal state
+        self.version = target_version;
+        self.entries = new_entries;
+        self.pending_patches.clear();
+        self.pending_removals.clear();
         
-        let mut new_file = std::fs::File::create(&self.file_path)?;
+        tracing::info!("GRF save completed successfully - total entries: {}", self.entries.len());
         
-        // Write header (46 bytes): magic(16) + key(14) + offset(4) + seed(4) + count(4) + version(4)
-        new_file.write_all(GRF_HEADER)?;
-        new_file.write_all(&[0u8])?;
-        new_file.write_all(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14])?;
-        new_file.write_all(&0u32.to_le_bytes())?; // FileTableOffset (will update later)
-        new_file.write_all(&0u32.to_le_bytes())?; // Seed
-        new_file.write_all(&0u32.to_le_bytes())?; // FilesCount (will update later)
-        new_file.write_all(&self.version.to_le_bytes())?; // Version
+        Ok(())
+    }
+    
+    fn write_archive<R: Read + Seek, W: Write + Seek>(
+        &self,
+        old_grf: &mut R,
+        new_file: &mut W,
+        target_version: u32,
+        recompress: bool,
+        control: &OperationControl,
+    ) -> Result<HashMap<String, GrfEntry>> {
+        let mut old_grf = BufReader::with_capacity(SAVE_BUFFER_SIZE, old_grf);
+        let mut new_file = BufWriter::with_capacity(SAVE_BUFFER_SIZE, new_file);
+        Self::write_header(&mut new_file, target_version, &self.key, self.seed)?;
+        let entry_crypto = (target_version == GRF_VERSION_0X300).then(crypto::grf_crypto);
+        // Converting between versions decodes every kept entry and writes it again like a patch
+        let reencode = recompress || target_version != self.version;
         
         // Start writing file data at offset 46 (after header)
         let mut current_offset: u32 = 0;
-        let mut new_entries = HashMap::new();
-        let mut old_grf = std::fs::File::open(&backup_path)?;
+        let mut new_entries = HashMap::with_capacity(self.entries.len() + self.pending_patches.len());
         
-        // Copy existing files that are not being patched
-        for (filename, entry) in &self.entries {
-            if self.pending_patches.contains_key(filename) {
-                continue; // Skip, will be replaced by patch
-            }
+        // Copy existing files in on-disk order so the old archive is read sequentially
+        let mut kept: Vec<&GrfEntry> = self.entries.iter()
+            .filter(|(filename, _)| !self.pending_patches.contains_key(*filename) && !self.pending_removals.contains(*filename))
+            .map(|(_, entry)| entry)
+            .collect();
+        kept.sort_unstable_by(|a, b| (a.offset, &a.filename).cmp(&(b.offset, &b.filename)));
+        
+        let total = (kept.len() + self.pending_patches.len()) as u64;
+        let mut done = 0;
+        
+        let mut read_position = None;
+        let mut file_data = Vec::new();
+        for entry in kept {
+            let filename = &entry.filename;
+            control.step(filename, done, total)?;
+            done += 1;
             
-            // Read old file data
-            old_grf.seek(SeekFrom::Start((entry.offset + GRF_HEADER_SIZE) as u64))?;
-            let mut file_data = vec![0u8; entry.compressed_size_aligned as usize];
+            // Read old file data, seeking only when entries are not contiguous
+            let data_offset = entry.offset as u64 + GRF_HEADER_SIZE as u64;
+            if read_position != Some(data_offset) {
+                old_grf.seek(SeekFrom::Start(data_offset))?;
+            }
+            file_data.resize(entry.compressed_size_aligned as usize, 0);
             old_grf.read_exact(&mut file_data)?;
+            read_position = Some(data_offset + entry.compressed_size_aligned as u64);
+            
+            if reencode {
+                let data = Self::decode_entry(self.version, entry, std::mem::take(&mut file_data))?;
+                let new_entry = self.write_entry(&mut new_file, filename, &data, current_offset, self.policy_flags(filename), entry_crypto.as_ref())?;
+                current_offset = next_offset(current_offset, new_entry.compressed_size_aligned)?;
+                new_entries.insert(filename.clone(), new_entry);
+                continue;
+            }
             
             // Write to new GRF
             new_file.write_all(&file_data)?;
@@ -469,60 +1260,48 @@ impl Grf {
                 },
             );
             
-            current_offset += entry.compressed_size_aligned;
+            current_offset = next_offset(current_offset, entry.compressed_size_aligned)?;
         }
         
         // Add patched files
         tracing::info!("Adding {} patched files to new GRF", self.pending_patches.len());
-        for (filename, data) in &self.pending_patches {
+        // Sorted so identical inputs always produce byte-identical archives
+        let mut patches: Vec<(&String, &Vec<u8>)> = self.pending_patches.iter().collect();
+        patches.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        for (filename, data) in patches {
+            control.step(filename, done, total)?;
+            done += 1;
             tracing::debug!("Adding patched file: {} ({} bytes uncompressed)", filename, data.len());
             
-            // Try compression for files > 1024 bytes
-            let (actual_data, flags): (Vec<u8>, u8) = if data.len() > 1024 {
-                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-                encoder.write_all(data)?;
-                let compressed_data = encoder.finish()?;
-                
-                // Use compression only if it reduces size
-                if compressed_data.len() < data.len() {
-                    (compressed_data, 0x01) // Compressed flag
-                } else {
-                    (data.clone(), 0x00) // No compression, no encryption
-                }
-            } else {
-                (data.clone(), 0x00) // Small files: no compression
-            };
-            
-            new_file.write_all(&actual_data)?;
-            
-            let compressed_size = actual_data.len() as u32;
-            let compressed_size_aligned = (compressed_size + 7) & !7;
-            
-            if compressed_size_aligned > compressed_size {
-                let padding = vec![0u8; (compressed_size_aligned - compressed_size) as usize];
-                new_file.write_all(&padding)?;
-            }
-            
-            new_entries.insert(
-                filename.clone(),
-                GrfEntry {
-                    filename: filename.clone(),
-                    compressed_size,
-                    compressed_size_aligned,
-                    uncompressed_size: data.len() as u32,
-                    flags,
-                    offset: current_offset,
-                },
-            );
-            
-            current_offset += compressed_size_aligned;
+            let new_entry = self.write_entry(&mut new_file, filename, data, current_offset, self.policy_flags(filename), entry_crypto.as_ref())?;
+            current_offset = next_offset(current_offset, new_entry.compressed_size_aligned)?;
+            new_entries.insert(filename.clone(), new_entry);
         }
         
+        control.report("", total, total);
+        
+        self.write_table(&mut new_file, &new_entries, current_offset, entry_crypto.as_ref())?;
+        
+        Ok(new_entries)
+    }
+    
+    // Writes the file table at the current position (`table_offset` past the header) and points the header at it;
+    // returns the end of the table, which is the end of the archive
+    fn write_table<W: Write + Seek>(
+        &self,
+        new_file: &mut W,
+        entries: &HashMap<String, GrfEntry>,
+        table_offset: u32,
+        entry_crypto: Option<&Arc<dyn crypto::GrfCrypto>>,
+    ) -> Result<u64> {
         // Build file table
-        let mut table_data = Vec::new();
-        for entry in new_entries.values() {
+        let table_size_hint: usize = entries.values().map(|e| e.filename.len() + 18).sum();
+        let mut table_data = Vec::with_capacity(table_size_hint);
+        let mut table_entries: Vec<&GrfEntry> = entries.values().collect();
+        table_entries.sort_unstable_by(|a, b| (a.offset, &a.filename).cmp(&(b.offset, &b.filename)));
+        for entry in table_entries {
             // Write null-terminated filename (variable length)
-            table_data.extend_from_slice(entry.filename.as_bytes());
+            table_data.extend_from_slice(&path::encode_name(&entry.filename));
             table_data.push(0); // Null terminator
             
             table_data.extend_from_slice(&entry.compressed_size.to_le_bytes());
@@ -532,38 +1311,440 @@ impl Grf {
             table_data.extend_from_slice(&entry.offset.to_le_bytes());
         }
         
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(&table_data)?;
-        let compressed_table = encoder.finish()?;
+        let compressed_table = match entry_crypto {
+            Some(crypto) => crypto.encrypt_table(&table_data)?,
+            None => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&table_data)?;
+                encoder.finish()?
+            }
+        };
         
         // Write file table metadata + compressed data
-        let table_offset = current_offset;
         new_file.write_all(&(compressed_table.len() as u32).to_le_bytes())?; // TableSizeCompressed
         new_file.write_all(&(table_data.len() as u32).to_le_bytes())?; // TableSize
         new_file.write_all(&compressed_table)?; // Compressed table data
+        let table_end = new_file.stream_position()?;
         
         // Update header with file table info
         tracing::info!("Writing header - file_count: {}, table_offset: {}, table_size: {}, compressed_size: {}", 
-            new_entries.len(), table_offset, table_data.len(), compressed_table.len());
+            entries.len(), table_offset, table_data.len(), compressed_table.len());
         new_file.seek(SeekFrom::Start(30))?;
         new_file.write_all(&table_offset.to_le_bytes())?; // FileTableOffset (offset 30)
-        new_file.write_all(&0u32.to_le_bytes())?; // Seed (offset 34)
-        new_file.write_all(&(new_entries.len() as u32).to_le_bytes())?; // FilesCount (offset 38)
-        // Version at offset 42 is already written in create_new(), don't overwrite
+        new_file.write_all(&self.seed.to_le_bytes())?; // Seed (offset 34)
+        new_file.write_all(&(entries.len() as u32).to_le_bytes())?; // FilesCount (offset 38)
+        // Version at offset 42 is already written by write_header(), don't overwrite
+        new_file.flush()?;
         
-        drop(new_file);
-        drop(old_grf);
+        Ok(table_end)
+    }
+    
+    // The flags `flag_policy` asks for when writing `filename`; None leaves them to `write_entry`
+    fn policy_flags(&self, filename: &str) -> Option<u8> {
+        match self.flag_policy {
+            GrfFlagPolicy::Standard => None,
+            GrfFlagPolicy::Preserve => self.entries.get(filename).map(|old| old.flags & !0x06),
+            GrfFlagPolicy::Fixed(flags) => Some(flags),
+        }
+    }
+    
+    // Compresses (when it pays off, or as `fixed` flags say), encrypts for 0x300 and pads one entry at `offset`
+    fn write_entry<W: Write>(
+        &self,
+        new_file: &mut W,
+        filename: &str,
+        data: &[u8],
+        offset: u32,
+        fixed: Option<u8>,
+        entry_crypto: Option<&Arc<dyn crypto::GrfCrypto>>,
+    ) -> Result<GrfEntry> {
+        let (actual_data, flags): (Cow<[u8]>, u8) = match fixed {
+            Some(flags) if flags & 0x01 != 0 => (Cow::Owned(self.compress(data)?), flags),
+            Some(flags) => (Cow::Borrowed(data), flags),
+            // Try compression for files > 1024 bytes
+            None if data.len() > 1024 => {
+                let compressed_data = self.compress(data)?;
+                
+                // Use compression only if it reduces size
+                if compressed_data.len() < data.len() {
+                    (Cow::Owned(compressed_data), 0x01) // Compressed flag
+                } else {
+                    (Cow::Borrowed(data), 0x00) // No compression, no encryption
+                }
+            }
+            None => (Cow::Borrowed(data), 0x00), // Small files: no compression
+        };
         
-        // Delete backup
-        tracing::info!("Deleting backup file: {:?}", backup_path);
-        std::fs::remove_file(&backup_path)?;
+        let actual_data = match entry_crypto {
+            Some(crypto) => {
+                let entry = GrfEntry {
+                    filename: filename.to_string(),
+                    compressed_size: actual_data.len() as u32,
+                    compressed_size_aligned: actual_data.len() as u32,
+                    uncompressed_size: data.len() as u32,
+                    flags,
+                    offset,
+                };
+                Cow::Owned(crypto.encrypt_entry(&entry, actual_data.into_owned())?)
+            }
+            None => actual_data,
+        };
         
-        // Update internal state
-        self.entries = new_entries;
-        self.pending_patches.clear();
+        new_file.write_all(&actual_data)?;
         
-        tracing::info!("GRF save completed successfully - total entries: {}", self.entries.len());
+        let compressed_size = actual_data.len() as u32;
+        let compressed_size_aligned = (compressed_size + self.alignment - 1) & !(self.alignment - 1);
+        
+        if compressed_size_aligned > compressed_size {
+            let padding = vec![0u8; (compressed_size_aligned - compressed_size) as usize];
+            new_file.write_all(&padding)?;
+        }
         
+        Ok(GrfEntry {
+            filename: filename.to_string(),
+            compressed_size,
+            compressed_size_aligned,
+            uncompressed_size: data.len() as u32,
+            flags,
+            offset,
+        })
+    }
+    
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(self.compression_level));
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+// How `GrfBuilder` stores an entry
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GrfCompression {
+    // zlib for entries over 1 KB when it makes them smaller, as `Grf::save` does
+    #[default]
+    Auto,
+    Always,
+    // For data that is already compressed, such as mp3 or jpg
+    Store,
+}
+
+#[cfg(feature = "fs")]
+impl GrfCompression {
+    fn flags(self) -> Option<u8> {
+        match self {
+            GrfCompression::Auto => None,
+            GrfCompression::Always => Some(0x01),
+            GrfCompression::Store => Some(0x00),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+enum GrfSource {
+    Data(Vec<u8>),
+    // Read only while the archive is written, so large trees need not fit in memory
+    File(PathBuf),
+}
+
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+struct GrfBuilderEntry {
+    name: String,
+    source: GrfSource,
+    compression: GrfCompression,
+}
+
+// Writes a new GRF from files and directory trees. Names are stored with backslashes; adding a name the
+// archive already has (ignoring case and separators) replaces that entry.
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub struct GrfBuilder {
+    options: GrfCreateOptions,
+    alignment: u32,
+    compression: GrfCompression,
+    // By `path::name_key`, which is also the order entries are written in
+    entries: BTreeMap<String, GrfBuilderEntry>,
+}
+
+#[cfg(feature = "fs")]
+impl Default for GrfBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl GrfBuilder {
+    pub fn new() -> Self {
+        GrfBuilder {
+            options: GrfCreateOptions {
+                check_siblings: false,
+                ..GrfCreateOptions::default()
+            },
+            alignment: 1,
+            compression: GrfCompression::Auto,
+            entries: BTreeMap::new(),
+        }
+    }
+    
+    pub fn with_options(mut self, options: GrfCreateOptions) -> Self {
+        self.options = options;
+        self
+    }
+    
+    pub fn with_alignment(mut self, alignment: u32) -> Result<Self> {
+        if !alignment.is_power_of_two() {
+            return Err(Error::Custom(format!("GRF alignment must be a power of two, got {}", alignment)));
+        }
+        self.alignment = alignment;
+        Ok(self)
+    }
+    
+    // For entries added from now on; `set_compression` changes single entries
+    pub fn with_compression(mut self, compression: GrfCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+    
+    pub fn add_file(&mut self, filename: &str, data: &[u8]) -> Result<()> {
+        self.insert(filename, GrfSource::Data(data.to_vec()))
+    }
+    
+    pub fn add_file_from_path<P: AsRef<Path>>(&mut self, file_path: P, archive_path: &str) -> Result<()> {
+        let file_path = file_path.as_ref();
+        if !file_path.is_file() {
+            return Err(Error::FileNotFound(file_path.display().to_string()));
+        }
+        self.insert(archive_path, GrfSource::File(file_path.to_path_buf()))
+    }
+    
+    // Adds every file below `dir` under `prefix` (e.g. a client's `data` folder with prefix "data"); returns the
+    // number of files added
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P, prefix: &str) -> Result<usize> {
+        let mut added = 0;
+        self.add_dir_entries(dir.as_ref(), prefix, &mut added)?;
+        Ok(added)
+    }
+    
+    fn add_dir_entries(&mut self, dir: &Path, prefix: &str, added: &mut usize) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = format!("{}/{}", prefix, os_name(&entry.file_name()));
+            if entry.file_type()?.is_dir() {
+                self.add_dir_entries(&entry.path(), &name, added)?;
+            } else {
+                self.insert(&name, GrfSource::File(entry.path()))?;
+                *added += 1;
+            }
+        }
         Ok(())
     }
+    
+    pub fn set_compression(&mut self, filename: &str, compression: GrfCompression) -> Result<()> {
+        match self.entries.get_mut(&path::name_key(filename)) {
+            Some(entry) => {
+                entry.compression = compression;
+                Ok(())
+            }
+            None => Err(Error::FileNotFound(filename.to_string())),
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    fn insert(&mut self, filename: &str, source: GrfSource) -> Result<()> {
+        path::sanitize_entry_path(filename)?;
+        let name = path::to_archive_name(filename);
+        self.entries.insert(
+            path::name_key(&name),
+            GrfBuilderEntry {
+                name,
+                source,
+                compression: self.compression,
+            },
+        );
+        Ok(())
+    }
+    
+    pub fn build<P: AsRef<Path>>(&self, path: P) -> Result<Grf> {
+        self.build_with(path, &OperationControl::default())
+    }
+    
+    // Written next to `path` and renamed over it at the end, so a failed build leaves an existing file alone
+    pub fn build_with<P: AsRef<Path>>(&self, path: P, control: &OperationControl) -> Result<Grf> {
+        let path = path.as_ref();
+        let temp_path = temp_save_path(path);
+        match self.write(&temp_path, control) {
+            Ok(()) => {
+                std::fs::rename(&temp_path, path)?;
+                tracing::info!("Built GRF {:?} with {} entries", path, self.entries.len());
+                Grf::open(path)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
+    }
+    
+    fn write(&self, path: &Path, control: &OperationControl) -> Result<()> {
+        let mut grf = Grf::create_with(path, &self.options)?;
+        grf.set_alignment(self.alignment)?;
+        let entry_crypto = (grf.version == GRF_VERSION_0X300).then(crypto::grf_crypto);
+        
+        let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.seek(SeekFrom::End(0))?;
+        let mut writer = BufWriter::with_capacity(SAVE_BUFFER_SIZE, file);
+        
+        let mut entries = HashMap::with_capacity(self.entries.len());
+        let mut current_offset = 0u32;
+        let total = self.entries.len() as u64;
+        for (done, entry) in self.entries.values().enumerate() {
+            control.step(&entry.name, done as u64, total)?;
+            let data = match &entry.source {
+                GrfSource::Data(data) => Cow::Borrowed(&data[..]),
+                GrfSource::File(file_path) => Cow::Owned(std::fs::read(file_path)?),
+            };
+            if u32::try_from(data.len()).is_err() {
+                return Err(Error::Unsupported(format!("{} is too large for a GRF entry", entry.name)));
+            }
+            
+            let written = grf.write_entry(
+                &mut writer,
+                &entry.name,
+                &data,
+                current_offset,
+                entry.compression.flags(),
+                entry_crypto.as_ref(),
+            )?;
+            current_offset = next_offset(current_offset, written.compressed_size_aligned)?;
+            entries.insert(entry.name.clone(), written);
+        }
+        control.report("", total, total);
+        
+        grf.write_table(&mut writer, &entries, current_offset, entry_crypto.as_ref())?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(())
+    }
+}
+
+// File names from disk; names that are not valid Unicode (CP949 bytes of Korean clients unpacked on Linux) are
+// decoded the way archive names are
+#[cfg(all(feature = "fs", unix))]
+fn os_name(name: &std::ffi::OsStr) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    
+    path::decode_name(name.as_bytes())
+}
+
+#[cfg(all(feature = "fs", not(unix)))]
+fn os_name(name: &std::ffi::OsStr) -> String {
+    name.to_string_lossy().to_string()
+}
+
+// Returned by `Grf::open_entry_stream`
+pub struct GrfEntryReader<'a> {
+    inner: Box<dyn Read + 'a>,
+    // Only compressed entries count against the archive's total, as with `extract_file`
+    budget: Option<&'a DecompressionBudget>,
+    filename: String,
+    size: u64,
+    compressed_len: u64,
+    allowed: u64,
+    produced: u64,
+}
+
+impl GrfEntryReader<'_> {
+    // Uncompressed size from the file table
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for GrfEntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.produced += read as u64;
+        if self.produced > self.allowed {
+            return Err(std::io::Error::other(Error::DecompressionLimitExceeded(format!(
+                "{} expands beyond {} bytes ({} bytes compressed)",
+                self.filename, self.allowed, self.compressed_len
+            ))));
+        }
+        if let Some(budget) = self.budget {
+            budget.consume(read as u64, &self.filename).map_err(std::io::Error::other)?;
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "fs")]
+fn temp_save_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(feature = "fs")]
+fn journal_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".journal");
+    path.with_file_name(name)
+}
+
+// Entry offsets are 32-bit, so an archive that would grow past 4 GB is refused instead of wrapping around
+fn next_offset(offset: u32, size: u32) -> Result<u32> {
+    offset
+        .checked_add(size)
+        .ok_or_else(|| Error::Unsupported("GRF would exceed 4 GB".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const FILES: [(&str, &[u8]); 3] = [
+        (r"data\texture\a.bmp", b"texture"),
+        (r"data\sprite\b.spr", b"sprite sprite sprite"),
+        (r"data\c.txt", b""),
+    ];
+    
+    fn build(order: &[usize]) -> Vec<u8> {
+        let mut grf = Grf::create_in_memory().unwrap();
+        for &index in order {
+            let (name, data) = FILES[index];
+            grf.patch_file(name, data).unwrap();
+        }
+        grf.save().unwrap();
+        grf.into_bytes().unwrap()
+    }
+    
+    #[test]
+    fn rebuilds_are_byte_identical() {
+        let first = build(&[0, 1, 2]);
+        assert_eq!(first, build(&[2, 0, 1]));
+        assert_eq!(first, build(&[1, 2, 0]));
+        
+        let grf = Grf::from_bytes(first).unwrap();
+        assert_eq!(grf.extract_file(r"data\sprite\b.spr").unwrap(), b"sprite sprite sprite");
+    }
+    
+    #[cfg(feature = "fs")]
+    #[test]
+    fn created_grf_keeps_its_seed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seeded.grf");
+        let options = GrfCreateOptions { seed: 0x1234, check_siblings: false, ..GrfCreateOptions::default() };
+        Grf::create_with(&path, &options).unwrap();
+        let header = std::fs::read(&path).unwrap();
+        assert_eq!(header[34..38], 0x1234u32.to_le_bytes());
+    }
 }
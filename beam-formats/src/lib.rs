@@ -3,6 +3,23 @@ pub mod thor;
 pub mod rgz;
 pub mod gpf;
 pub mod beam;
+pub mod beamd;
+pub mod crypto;
+pub mod limits;
+pub mod path;
+pub mod lua;
+pub mod progress;
+pub mod text;
+pub mod raster;
+pub mod pal;
+pub mod gat;
+pub mod pe;
+#[cfg(feature = "fs")]
+pub mod table_cache;
+pub mod content_index;
+pub mod vfs;
 pub mod error;
+mod bounds;
+mod des;
 
 pub use error::{Error, Result};
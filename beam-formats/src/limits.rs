@@ -0,0 +1,104 @@
+use crate::{bounds, Error, Result};
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+// Small outputs are exempt from the ratio cap; highly repetitive tiny files are normal
+const RATIO_EXEMPT_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressionLimits {
+    pub max_entry_size: u64,
+    pub max_total_size: u64,
+    pub max_ratio: u64,
+}
+
+impl Default for DecompressionLimits {
+    fn default() -> Self {
+        DecompressionLimits {
+            max_entry_size: 1024 * 1024 * 1024,
+            max_total_size: 16 * 1024 * 1024 * 1024,
+            max_ratio: 1100,
+        }
+    }
+}
+
+impl DecompressionLimits {
+    pub fn allowed_output(&self, compressed_len: u64, cap: u64) -> u64 {
+        let by_ratio = std::cmp::max(compressed_len.saturating_mul(self.max_ratio), RATIO_EXEMPT_BYTES);
+        std::cmp::min(cap, by_ratio)
+    }
+}
+
+static LIMITS: RwLock<Option<DecompressionLimits>> = RwLock::new(None);
+
+pub fn set_decompression_limits(limits: DecompressionLimits) {
+    tracing::debug!("Decompression limits set: {:?}", limits);
+    *LIMITS.write().unwrap_or_else(|e| e.into_inner()) = Some(limits);
+}
+
+pub fn decompression_limits() -> DecompressionLimits {
+    LIMITS.read().unwrap_or_else(|e| e.into_inner()).unwrap_or_default()
+}
+
+pub(crate) fn check_entry_size(size: u64, what: &str) -> Result<()> {
+    let max_entry_size = decompression_limits().max_entry_size;
+    if size > max_entry_size {
+        return Err(Error::DecompressionLimitExceeded(format!(
+            "{} is {} bytes, limit is {}",
+            what, size, max_entry_size
+        )));
+    }
+    Ok(())
+}
+
+// Decompresses a single entry, failing once the output grows past the per-entry or ratio limit
+pub(crate) fn decompress_entry<R: Read>(reader: R, compressed_len: u64, size_hint: u64, what: &str) -> Result<Vec<u8>> {
+    let limits = decompression_limits();
+    decompress_limited(reader, compressed_len, size_hint, limits.allowed_output(compressed_len, limits.max_entry_size), what)
+}
+
+// Decompresses a whole-archive stream (THOR/RGZ) against the per-archive total
+pub(crate) fn decompress_archive<R: Read>(reader: R, compressed_len: u64, what: &str) -> Result<Vec<u8>> {
+    let limits = decompression_limits();
+    decompress_limited(reader, compressed_len, 0, limits.allowed_output(compressed_len, limits.max_total_size), what)
+}
+
+fn decompress_limited<R: Read>(reader: R, compressed_len: u64, size_hint: u64, allowed: u64, what: &str) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(bounds::capacity_hint(std::cmp::min(size_hint, allowed)));
+    reader
+        .take(allowed.saturating_add(1))
+        .read_to_end(&mut output)
+        .map_err(|e| Error::Decompression(e.to_string()))?;
+    
+    if output.len() as u64 > allowed {
+        return Err(Error::DecompressionLimitExceeded(format!(
+            "{} expands beyond {} bytes ({} bytes compressed)",
+            what, allowed, compressed_len
+        )));
+    }
+    
+    Ok(output)
+}
+
+// Running per-archive total of decompressed bytes
+#[derive(Debug, Default)]
+pub(crate) struct DecompressionBudget {
+    used: AtomicU64,
+}
+
+impl DecompressionBudget {
+    pub(crate) fn consume(&self, bytes: u64, what: &str) -> Result<()> {
+        let max_total = decompression_limits().max_total_size;
+        let total = self.used.fetch_add(bytes, Ordering::Relaxed).saturating_add(bytes);
+        
+        if total > max_total {
+            return Err(Error::DecompressionLimitExceeded(format!(
+                "{} pushes archive total past {} bytes",
+                what, max_total
+            )));
+        }
+        
+        Ok(())
+    }
+}
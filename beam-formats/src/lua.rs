@@ -0,0 +1,89 @@
+use crate::{text, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+const LUA_SIGNATURE: &[u8] = b"\x1bLua";
+const LUAJIT_SIGNATURE: &[u8] = b"\x1bLJ";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LuaForm {
+    // Plain-text Lua, whatever the extension says
+    Source,
+    // Precompiled bytecode, e.g. "5.1" from luac or "LuaJIT 2"
+    Compiled { version: String },
+    NotLua,
+}
+
+impl LuaForm {
+    pub fn is_compiled(&self) -> bool {
+        matches!(self, LuaForm::Compiled { .. })
+    }
+}
+
+// Turns compiled lub bytecode back into readable Lua. None ships with Beam Patcher: wrap an external tool (unluac,
+// luadec, ...) and install it with `register_lub_decompiler` to preview compiled entries.
+pub trait LubDecompiler: Send + Sync {
+    fn name(&self) -> &str;
+    
+    fn decompile(&self, filename: &str, bytecode: &[u8]) -> Result<String>;
+}
+
+static LUB_DECOMPILER: RwLock<Option<Arc<dyn LubDecompiler>>> = RwLock::new(None);
+
+pub fn register_lub_decompiler(decompiler: Arc<dyn LubDecompiler>) {
+    tracing::info!("Registering lub decompiler: {}", decompiler.name());
+    *LUB_DECOMPILER.write().unwrap_or_else(|e| e.into_inner()) = Some(decompiler);
+}
+
+pub fn unregister_lub_decompiler() {
+    *LUB_DECOMPILER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn lub_decompiler() -> Option<Arc<dyn LubDecompiler>> {
+    LUB_DECOMPILER.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn is_lua_entry(name: &str) -> bool {
+    name.rsplit('.')
+        .next()
+        .map(|ext| ext.eq_ignore_ascii_case("lua") || ext.eq_ignore_ascii_case("lub"))
+        .unwrap_or(false)
+}
+
+// Servers ship both forms under either extension, so only the content is trusted
+pub fn detect_lua_form(data: &[u8]) -> LuaForm {
+    if let Some(rest) = data.strip_prefix(LUA_SIGNATURE) {
+        let version = match rest.first() {
+            Some(&v) => format!("{}.{}", v >> 4, v & 0x0F),
+            None => "unknown".to_string(),
+        };
+        return LuaForm::Compiled { version };
+    }
+    
+    if let Some(rest) = data.strip_prefix(LUAJIT_SIGNATURE) {
+        let version = rest.first().map(|v| format!("LuaJIT {}", v)).unwrap_or_else(|| "LuaJIT".to_string());
+        return LuaForm::Compiled { version };
+    }
+    
+    if text::looks_binary(data) {
+        LuaForm::NotLua
+    } else {
+        LuaForm::Source
+    }
+}
+
+// Readable source for either form; compiled entries need a registered decompiler
+pub fn decompile(filename: &str, data: &[u8]) -> Result<String> {
+    match detect_lua_form(data) {
+        LuaForm::Source => Ok(text::decode_text(data, usize::MAX).text),
+        LuaForm::Compiled { version } => match lub_decompiler() {
+            Some(decompiler) => decompiler.decompile(filename, data),
+            None => Err(Error::Unsupported(format!(
+                "{} is compiled Lua {} and no lub decompiler is registered",
+                filename, version
+            ))),
+        },
+        LuaForm::NotLua => Err(Error::Unsupported(format!("{} is not a Lua file", filename))),
+    }
+}
@@ -0,0 +1,44 @@
+use crate::raster::RgbaImage;
+use crate::{Error, Result};
+
+// Sprite palettes are 256 RGBX entries; the fourth byte is unused padding
+pub const PALETTE_SIZE: usize = 256 * 4;
+const SWATCH_COLUMNS: u32 = 16;
+
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < PALETTE_SIZE {
+            return Err(Error::Malformed(format!(
+                "Palette is {} bytes, expected {}",
+                data.len(),
+                PALETTE_SIZE
+            )));
+        }
+        
+        let colors = data[..PALETTE_SIZE]
+            .chunks_exact(4)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect();
+        Ok(Palette { colors })
+    }
+    
+    // 16x16 grid of swatches; index 0 is the sprite's transparent colour and keeps its real value
+    pub fn render_swatches(&self, cell_size: u32) -> RgbaImage {
+        let cell_size = cell_size.max(1);
+        let rows = (self.colors.len() as u32).div_ceil(SWATCH_COLUMNS);
+        let mut image = RgbaImage::new(SWATCH_COLUMNS * cell_size, rows * cell_size);
+        
+        for (index, color) in self.colors.iter().enumerate() {
+            let x = (index as u32 % SWATCH_COLUMNS) * cell_size;
+            let y = (index as u32 / SWATCH_COLUMNS) * cell_size;
+            image.fill_rect(x, y, cell_size, cell_size, [color[0], color[1], color[2], 0xFF]);
+        }
+        
+        image
+    }
+}
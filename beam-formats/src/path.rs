@@ -0,0 +1,133 @@
+use crate::{Error, Result};
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+// The parts of an entry name, whichever separators it was written with; empty and `.` parts are dropped
+fn parts(name: &str) -> impl Iterator<Item = &str> {
+    name.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".")
+}
+
+// The form GRF and THOR archives store entry names in, which the client looks files up by: `data\sprite\x.spr`
+pub fn to_archive_name(name: &str) -> String {
+    parts(name).collect::<Vec<_>>().join("\\")
+}
+
+// The form manifests, state files and URLs use for the same name: `data/sprite/x.spr`
+pub fn to_manifest_name(name: &str) -> String {
+    parts(name).collect::<Vec<_>>().join("/")
+}
+
+// Compares names from different sources the way the client does, ignoring case and separators. Only for lookups;
+// names are stored and written as they came.
+pub fn name_key(name: &str) -> String {
+    to_manifest_name(name).to_lowercase()
+}
+
+// Entry names in archives made by Korean tools are CP949 bytes. Valid UTF-8 is taken as-is, anything else is
+// decoded as CP949 instead of being replaced with U+FFFD, which would merge distinct names into one.
+pub fn decode_name(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(name) => name.to_string(),
+        Err(_) => encoding_rs::EUC_KR.decode_without_bom_handling(bytes).0.into_owned(),
+    }
+}
+
+// The bytes GRF and THOR names are written as: CP949, the client's code page, for anything it can hold, and UTF-8
+// for the rest
+pub fn encode_name(name: &str) -> Cow<'_, [u8]> {
+    if name.is_ascii() {
+        return Cow::Borrowed(name.as_bytes());
+    }
+    match encoding_rs::EUC_KR.encode(name) {
+        (bytes, _, false) => bytes,
+        _ => Cow::Borrowed(name.as_bytes()),
+    }
+}
+
+// Turns an archive entry name into a relative path, rejecting anything that could escape the target directory.
+// Entry names come from Windows tooling, so both separators are accepted regardless of host platform.
+pub fn sanitize_entry_path(name: &str) -> Result<PathBuf> {
+    let unsafe_path = |reason: &str| Error::UnsafePath(format!("{:?}: {}", name, reason));
+    
+    if name.contains('\0') {
+        return Err(unsafe_path("contains NUL byte"));
+    }
+    
+    if name.starts_with(['/', '\\']) {
+        return Err(unsafe_path("absolute path"));
+    }
+    
+    let mut relative = PathBuf::new();
+    for part in parts(name) {
+        match part {
+            ".." => return Err(unsafe_path("parent directory reference")),
+            _ if part.contains(':') => return Err(unsafe_path("drive prefix or stream name")),
+            _ => relative.push(part),
+        }
+    }
+    
+    // Catches anything the host platform still treats as rooted or prefixed
+    if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(unsafe_path("not a plain relative path"));
+    }
+    
+    if relative.as_os_str().is_empty() {
+        return Err(unsafe_path("empty path"));
+    }
+    
+    Ok(relative)
+}
+
+// Resolves an entry name under `base`, also following symlinks already on disk so they cannot redirect writes outside it
+pub fn confine_to(base: &Path, name: &str) -> Result<PathBuf> {
+    let relative = sanitize_entry_path(name)?;
+    let base = base.canonicalize()?;
+    let target = base.join(&relative);
+    
+    let mut existing = target.as_path();
+    while !existing.exists() {
+        existing = match existing.parent() {
+            Some(parent) => parent,
+            None => break,
+        };
+    }
+    
+    let resolved = existing.canonicalize()?;
+    if !resolved.starts_with(&base) {
+        return Err(Error::UnsafePath(format!(
+            "{:?} resolves outside {:?}",
+            name, base
+        )));
+    }
+    
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn rejects_escaping_names() {
+        for name in [r"..\..\windows\evil.dll", "/etc/passwd", r"C:\x", r"\\server\share\x", "data\0.txt"] {
+            assert!(sanitize_entry_path(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+    
+    #[test]
+    fn accepts_client_names() {
+        let path = sanitize_entry_path(r"data\texture\a.bmp").unwrap();
+        assert_eq!(path, Path::new("data").join("texture").join("a.bmp"));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn confine_to_refuses_symlinked_directory() {
+        let base = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), base.path().join("data")).unwrap();
+        
+        assert!(confine_to(base.path(), r"data\evil.dll").is_err());
+        assert!(confine_to(base.path(), r"other\a.bmp").is_ok());
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{Error, Result};
+
+const PE_SIGNATURE: &[u8; 4] = b"PE\0\0";
+const COFF_HEADER_SIZE: usize = 20;
+const SECTION_HEADER_SIZE: usize = 40;
+const IMPORT_DESCRIPTOR_SIZE: usize = 20;
+const DELAY_IMPORT_DESCRIPTOR_SIZE: usize = 32;
+const IMPORT_DIRECTORY: usize = 1;
+const DELAY_IMPORT_DIRECTORY: usize = 13;
+// Sanity limits; real executables are nowhere near these
+const MAX_SECTIONS: usize = 96;
+const MAX_IMPORTS: usize = 4096;
+const SECTION_CODE: u32 = 0x0000_0020;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Machine {
+    X86,
+    X64,
+    Arm64,
+    Other(u16),
+}
+
+impl From<u16> for Machine {
+    fn from(value: u16) -> Self {
+        match value {
+            0x014c => Machine::X86,
+            0x8664 => Machine::X64,
+            0xaa64 => Machine::Arm64,
+            other => Machine::Other(other),
+        }
+    }
+}
+
+impl std::fmt::Display for Machine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Machine::X86 => write!(f, "x86"),
+            Machine::X64 => write!(f, "x64"),
+            Machine::Arm64 => write!(f, "arm64"),
+            Machine::Other(value) => write!(f, "machine {:#06x}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub characteristics: u32,
+}
+
+impl Section {
+    pub fn is_code(&self) -> bool {
+        self.characteristics & SECTION_CODE != 0
+    }
+    
+    // Raw bytes of the section as stored in `file`
+    pub fn data<'a>(&self, file: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.raw_offset as usize;
+        file.get(start..start.checked_add(self.raw_size as usize)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub dll: String,
+    // Loaded on first use rather than at startup, so a missing one only breaks the feature using it
+    pub delay_loaded: bool,
+}
+
+// The parts of a Windows executable or DLL header the launcher needs to check a client before starting it
+#[derive(Debug, Clone)]
+pub struct PeImage {
+    pub machine: Machine,
+    // Link time as a Unix timestamp; reproducible builds put a content hash here instead
+    pub timestamp: u32,
+    pub image_base: u64,
+    pub sections: Vec<Section>,
+    pub imports: Vec<Import>,
+}
+
+impl PeImage {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let (mut image, directories) = Self::parse_headers_with_directories(data)?;
+        if let Some(rva) = directories.import {
+            image.read_imports(data, rva, IMPORT_DESCRIPTOR_SIZE, 12, false)?;
+        }
+        if let Some(rva) = directories.delay_import {
+            image.read_imports(data, rva, DELAY_IMPORT_DESCRIPTOR_SIZE, 4, true)?;
+        }
+        Ok(image)
+    }
+    
+    // Headers and section table only, which fit in the first few KB of the file; `imports` stays empty
+    pub fn parse_headers(data: &[u8]) -> Result<Self> {
+        Self::parse_headers_with_directories(data).map(|(image, _)| image)
+    }
+    
+    fn parse_headers_with_directories(data: &[u8]) -> Result<(Self, Directories)> {
+        if data.len() < 0x40 || &data[..2] != b"MZ" {
+            return Err(Error::Malformed("Not a Windows executable".to_string()));
+        }
+        let pe_offset = read_u32(data, 0x3c)? as usize;
+        if data.get(pe_offset..pe_offset + 4) != Some(PE_SIGNATURE) {
+            return Err(Error::Malformed("Missing PE signature".to_string()));
+        }
+        
+        let coff = pe_offset + 4;
+        let machine = Machine::from(read_u16(data, coff)?);
+        let section_count = read_u16(data, coff + 2)? as usize;
+        let timestamp = read_u32(data, coff + 4)?;
+        let optional_size = read_u16(data, coff + 16)? as usize;
+        if section_count > MAX_SECTIONS {
+            return Err(Error::Malformed(format!("Implausible section count {}", section_count)));
+        }
+        
+        let optional = coff + COFF_HEADER_SIZE;
+        let (image_base, directory_count_offset, directories_offset) = match read_u16(data, optional)? {
+            0x10b => (read_u32(data, optional + 28)? as u64, 92, 96),
+            0x20b => (read_u32(data, optional + 24)? as u64 | (read_u32(data, optional + 28)? as u64) << 32, 108, 112),
+            magic => return Err(Error::Malformed(format!("Unknown optional header magic {:#x}", magic))),
+        };
+        let directory_count = read_u32(data, optional + directory_count_offset)? as usize;
+        let directory = |index: usize| -> Result<Option<(u32, u32)>> {
+            if index >= directory_count || directories_offset + index * 8 + 8 > optional_size {
+                return Ok(None);
+            }
+            let entry = optional + directories_offset + index * 8;
+            let (rva, size) = (read_u32(data, entry)?, read_u32(data, entry + 4)?);
+            Ok((rva != 0 && size != 0).then_some((rva, size)))
+        };
+        
+        let sections_offset = optional + optional_size;
+        let sections = (0..section_count)
+            .map(|index| {
+                let header = sections_offset + index * SECTION_HEADER_SIZE;
+                let name = data
+                    .get(header..header + 8)
+                    .ok_or_else(|| truncated("section table"))?;
+                Ok(Section {
+                    name: String::from_utf8_lossy(name).trim_end_matches('\0').to_string(),
+                    virtual_size: read_u32(data, header + 8)?,
+                    virtual_address: read_u32(data, header + 12)?,
+                    raw_size: read_u32(data, header + 16)?,
+                    raw_offset: read_u32(data, header + 20)?,
+                    characteristics: read_u32(data, header + 36)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        let directories = Directories {
+            import: directory(IMPORT_DIRECTORY)?.map(|(rva, _)| rva),
+            delay_import: directory(DELAY_IMPORT_DIRECTORY)?.map(|(rva, _)| rva),
+        };
+        let image = PeImage {
+            machine,
+            timestamp,
+            image_base,
+            sections,
+            imports: Vec::new(),
+        };
+        Ok((image, directories))
+    }
+    
+    
+    // Descriptor tables end with an all-zero entry; only the DLL name field is needed from each
+    fn read_imports(&mut self, data: &[u8], rva: u32, descriptor_size: usize, name_field: usize, delay_loaded: bool) -> Result<()> {
+        let start = self.offset_of(rva).ok_or_else(|| truncated("import table"))?;
+        for index in 0..MAX_IMPORTS {
+            let descriptor = start + index * descriptor_size;
+            let entry = data
+                .get(descriptor..descriptor + descriptor_size)
+                .ok_or_else(|| truncated("import table"))?;
+            if entry.iter().all(|&b| b == 0) {
+                return Ok(());
+            }
+            
+            let mut name_rva = read_u32(data, descriptor + name_field)?;
+            // Delay-load tables from before Visual C++ 7 hold addresses instead of RVAs; bit 0 of the
+            // attributes marks the newer layout
+            if delay_loaded && read_u32(data, descriptor)? & 1 == 0 {
+                name_rva = (name_rva as u64).wrapping_sub(self.image_base) as u32;
+            }
+            let name = self
+                .offset_of(name_rva)
+                .and_then(|offset| data.get(offset..))
+                .and_then(|rest| rest.iter().position(|&b| b == 0).map(|end| &rest[..end]))
+                .ok_or_else(|| truncated("import name"))?;
+            let dll = String::from_utf8_lossy(name).to_string();
+            if !self.imports.iter().any(|import| import.dll.eq_ignore_ascii_case(&dll)) {
+                self.imports.push(Import { dll, delay_loaded });
+            }
+        }
+        Err(Error::Malformed("Import table is not terminated".to_string()))
+    }
+    
+    pub fn offset_of(&self, rva: u32) -> Option<usize> {
+        self.sections
+            .iter()
+            .find(|section| {
+                let size = section.virtual_size.max(section.raw_size);
+                rva >= section.virtual_address && rva - section.virtual_address < size
+            })
+            .map(|section| (rva - section.virtual_address) as usize + section.raw_offset as usize)
+    }
+}
+
+struct Directories {
+    import: Option<u32>,
+    delay_import: Option<u32>,
+}
+
+fn truncated(what: &str) -> Error {
+    Error::Malformed(format!("Executable is truncated in the {}", what))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| truncated("header"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| truncated("header"))
+}
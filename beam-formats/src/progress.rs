@@ -0,0 +1,68 @@
+use crate::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub type ProgressCallback = Arc<dyn Fn(&str, u64, u64) + Send + Sync>;
+
+// Progress reporting and cancellation for long-running archive operations.
+// The callback receives the current item name, items done and items total.
+#[derive(Clone, Default)]
+pub struct OperationControl {
+    progress: Option<ProgressCallback>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl OperationControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str, u64, u64) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+    
+    pub fn with_cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .map(|flag| flag.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+    
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+    
+    pub(crate) fn report(&self, item: &str, done: u64, total: u64) {
+        if let Some(callback) = &self.progress {
+            callback(item, done, total);
+        }
+    }
+    
+    // Reports progress and bails out if cancellation was requested; called once per item
+    pub(crate) fn step(&self, item: &str, done: u64, total: u64) -> Result<()> {
+        self.check_cancelled()?;
+        self.report(item, done, total);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for OperationControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OperationControl")
+            .field("progress", &self.progress.is_some())
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+// Plain RGBA8 pixel buffer produced by the preview renderers; encoding is left to the caller
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        RgbaImage {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+    
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x < self.width && y < self.height {
+            let index = (y as usize * self.width as usize + x as usize) * 4;
+            self.pixels[index..index + 4].copy_from_slice(&color);
+        }
+    }
+    
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 4]) {
+        for py in y..y.saturating_add(height).min(self.height) {
+            for px in x..x.saturating_add(width).min(self.width) {
+                self.put_pixel(px, py, color);
+            }
+        }
+    }
+}
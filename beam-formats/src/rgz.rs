@@ -1,6 +1,9 @@
-use crate::{Error, Result};
+#[cfg(feature = "fs")]
+use crate::progress::OperationControl;
+use crate::{bounds, limits, path, Error, Result};
 use flate2::read::GzDecoder;
 use std::io::Read;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 #[derive(Debug, Clone)]
@@ -20,16 +23,14 @@ pub struct Rgz {
 }
 
 impl Rgz {
+    #[cfg(feature = "fs")]
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let data = std::fs::read(path)?;
         Self::from_bytes(&data)
     }
     
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
-        let mut decoder = GzDecoder::new(data);
-        let mut decompressed = Vec::new();
-        decoder.read_to_end(&mut decompressed)
-            .map_err(|e| Error::Decompression(e.to_string()))?;
+        let decompressed = limits::decompress_archive(GzDecoder::new(data), data.len() as u64, "RGZ archive")?;
         
         let mut entries = Vec::new();
         let mut cursor = std::io::Cursor::new(decompressed);
@@ -49,14 +50,14 @@ impl Rgz {
                     
                     let mut name_buf = vec![0u8; name_len];
                     cursor.read_exact(&mut name_buf)?;
-                    let name = String::from_utf8_lossy(&name_buf).to_string();
+                    let name = path::decode_name(&name_buf);
                     
                     let mut size_buf = [0u8; 4];
                     cursor.read_exact(&mut size_buf)?;
                     let size = u32::from_le_bytes(size_buf);
                     
-                    let mut data = vec![0u8; size as usize];
-                    cursor.read_exact(&mut data)?;
+                    limits::check_entry_size(size as u64, "RGZ entry data")?;
+                    let data = bounds::read_bytes(&mut cursor, size as u64, "RGZ entry data")?;
                     
                     entries.push(RgzEntry::File { name, data });
                 },
@@ -67,7 +68,7 @@ impl Rgz {
                     
                     let mut name_buf = vec![0u8; name_len];
                     cursor.read_exact(&mut name_buf)?;
-                    let name = String::from_utf8_lossy(&name_buf).to_string();
+                    let name = path::decode_name(&name_buf);
                     
                     entries.push(RgzEntry::Directory { name });
                 },
@@ -82,4 +83,46 @@ impl Rgz {
     pub fn get_entries(&self) -> &[RgzEntry] {
         &self.entries
     }
+    
+    #[cfg(feature = "fs")]
+    pub fn extract_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<usize> {
+        self.extract_to_dir_with(dir, &OperationControl::default())
+    }
+    
+    #[cfg(feature = "fs")]
+    pub fn extract_to_dir_with<P: AsRef<Path>>(&self, dir: P, control: &OperationControl) -> Result<usize> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        
+        // Reject the whole archive before touching disk if any entry name is unsafe
+        for entry in &self.entries {
+            let (RgzEntry::File { name, .. } | RgzEntry::Directory { name }) = entry;
+            path::sanitize_entry_path(name)?;
+        }
+        
+        let total = self.entries.len() as u64;
+        let mut written = 0;
+        for (done, entry) in self.entries.iter().enumerate() {
+            let (RgzEntry::File { name, .. } | RgzEntry::Directory { name }) = entry;
+            control.step(name, done as u64, total)?;
+            
+            match entry {
+                RgzEntry::File { name, data } => {
+                    let target = path::confine_to(dir, name)?;
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&target, data)?;
+                    written += 1;
+                }
+                RgzEntry::Directory { name } => {
+                    let target = path::confine_to(dir, name)?;
+                    std::fs::create_dir_all(&target)?;
+                }
+            }
+        }
+        
+        control.report("", total, total);
+        Ok(written)
+    }
 }
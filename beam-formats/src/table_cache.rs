@@ -0,0 +1,132 @@
+use crate::grf::GrfEntry;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::UNIX_EPOCH;
+
+const CACHE_MAGIC: &[u8; 8] = b"BEAMGTC1";
+const CACHE_EXTENSION: &str = "table";
+
+static CACHE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+// Parsed GRF file tables are kept in this directory, one file per archive, so opening an unchanged archive
+// skips decompressing and decoding its table. None (the default) turns the cache off.
+pub fn set_table_cache_dir(dir: Option<PathBuf>) {
+    tracing::debug!("GRF table cache: {:?}", dir);
+    *CACHE_DIR.write().unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+pub fn table_cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// What a cached table must match: the archive's size and modification time, and a checksum of the table as
+// stored, which also catches archives rewritten within the timestamp resolution
+pub(crate) struct TableKey {
+    file: PathBuf,
+    fingerprint: [u8; 32],
+}
+
+impl TableKey {
+    // None while the cache is off or the archive cannot be inspected
+    pub(crate) fn new(archive: &Path, table: &[u8]) -> Option<Self> {
+        let dir = table_cache_dir()?;
+        let archive = std::fs::canonicalize(archive).ok()?;
+        let metadata = std::fs::metadata(&archive).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        
+        let mut fingerprint = [0u8; 32];
+        fingerprint[..8].copy_from_slice(&metadata.len().to_le_bytes());
+        fingerprint[8..16].copy_from_slice(&modified.as_nanos().to_le_bytes()[..8]);
+        fingerprint[16..].copy_from_slice(&md5::compute(table).0);
+        
+        let name = md5::compute(archive.as_os_str().as_encoded_bytes());
+        Some(TableKey {
+            file: dir.join(format!("{:x}.{}", name, CACHE_EXTENSION)),
+            fingerprint,
+        })
+    }
+}
+
+// A missing, stale or damaged cache file is a miss; the table is then parsed and stored again
+pub(crate) fn load(key: &TableKey) -> Option<HashMap<String, GrfEntry>> {
+    let data = std::fs::read(&key.file).ok()?;
+    let mut reader = Reader { data: &data, pos: 0 };
+    if reader.take(CACHE_MAGIC.len())? != CACHE_MAGIC || reader.take(key.fingerprint.len())? != key.fingerprint {
+        return None;
+    }
+    
+    let count = reader.u32()? as usize;
+    let mut entries = HashMap::with_capacity(count.min(data.len() / 21));
+    for _ in 0..count {
+        let name_len = reader.u32()? as usize;
+        let filename = std::str::from_utf8(reader.take(name_len)?).ok()?.to_string();
+        let entry = GrfEntry {
+            filename: filename.clone(),
+            compressed_size: reader.u32()?,
+            compressed_size_aligned: reader.u32()?,
+            uncompressed_size: reader.u32()?,
+            flags: reader.take(1)?[0],
+            offset: reader.u32()?,
+        };
+        entries.insert(filename, entry);
+    }
+    
+    tracing::debug!("Loaded {} GRF entries from {:?}", entries.len(), key.file);
+    (reader.pos == data.len()).then_some(entries)
+}
+
+// Failing to write the cache only costs the next open its speed
+pub(crate) fn store(key: &TableKey, entries: &HashMap<String, GrfEntry>) {
+    let mut data = Vec::with_capacity(44 + entries.len() * 64);
+    data.extend_from_slice(CACHE_MAGIC);
+    data.extend_from_slice(&key.fingerprint);
+    data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in entries.values() {
+        data.extend_from_slice(&(entry.filename.len() as u32).to_le_bytes());
+        data.extend_from_slice(entry.filename.as_bytes());
+        data.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        data.extend_from_slice(&entry.compressed_size_aligned.to_le_bytes());
+        data.extend_from_slice(&entry.uncompressed_size.to_le_bytes());
+        data.push(entry.flags);
+        data.extend_from_slice(&entry.offset.to_le_bytes());
+    }
+    
+    if let Err(e) = write_atomically(&key.file, &data) {
+        tracing::debug!("Could not cache GRF table in {:?}: {}", key.file, e);
+    }
+}
+
+// Launchers opening the same archive at once each write their own temporary file, and readers only ever see a
+// complete one
+fn write_atomically(file: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp = file.with_extension(format!("{}.{}", CACHE_EXTENSION, std::process::id()));
+    let result = std::fs::File::create(&temp)
+        .and_then(|mut out| out.write_all(data))
+        .and_then(|_| std::fs::rename(&temp, file));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+    
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
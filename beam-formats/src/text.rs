@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+// Entries worth offering a text preview for; lub is included because many servers ship it uncompiled
+pub const TEXT_EXTENSIONS: &[&str] = &["txt", "xml", "lua", "lub", "ini", "csv", "json", "conf", "yml", "yaml"];
+pub const DEFAULT_PREVIEW_LIMIT: usize = 1024 * 1024;
+const BINARY_SNIFF_LEN: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // Korean clients write CP949, the superset of EUC-KR that encoding_rs decodes under this label
+    EucKr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextPreview {
+    pub text: String,
+    pub encoding: TextEncoding,
+    pub had_errors: bool,
+    pub truncated: bool,
+    // Set when the text came from a registered lub decompiler rather than the raw entry
+    #[serde(default)]
+    pub decompiled_by: Option<String>,
+}
+
+pub fn is_text_entry(name: &str) -> bool {
+    name.rsplit('.')
+        .next()
+        .map(|ext| TEXT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// NUL bytes early in the data mean a binary file (compiled lub, sprites, ...) rather than text
+pub fn looks_binary(data: &[u8]) -> bool {
+    let sniff = &data[..data.len().min(BINARY_SNIFF_LEN)];
+    if sniff.starts_with(&[0xFF, 0xFE]) || sniff.starts_with(&[0xFE, 0xFF]) {
+        return false;
+    }
+    sniff.contains(&0)
+}
+
+// BOMs win; otherwise valid UTF-8 is taken as-is and anything else is decoded as EUC-KR
+pub fn decode_text(data: &[u8], limit: usize) -> TextPreview {
+    let truncated = data.len() > limit;
+    let data = &data[..data.len().min(limit)];
+    
+    let (encoding, bytes) = if let Some(rest) = data.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        (TextEncoding::Utf8, rest)
+    } else if let Some(rest) = data.strip_prefix(&[0xFF, 0xFE]) {
+        (TextEncoding::Utf16Le, rest)
+    } else if let Some(rest) = data.strip_prefix(&[0xFE, 0xFF]) {
+        (TextEncoding::Utf16Be, rest)
+    } else if valid_utf8_prefix(data, truncated) {
+        (TextEncoding::Utf8, data)
+    } else {
+        (TextEncoding::EucKr, data)
+    };
+    
+    let decoder = match encoding {
+        TextEncoding::Utf8 => encoding_rs::UTF_8,
+        TextEncoding::Utf16Le => encoding_rs::UTF_16LE,
+        TextEncoding::Utf16Be => encoding_rs::UTF_16BE,
+        TextEncoding::EucKr => encoding_rs::EUC_KR,
+    };
+    let (text, had_errors) = decoder.decode_without_bom_handling(bytes);
+    
+    TextPreview {
+        text: text.into_owned(),
+        encoding,
+        had_errors,
+        truncated,
+        decompiled_by: None,
+    }
+}
+
+// A preview cut can split a multi-byte character, which must not flip the guess to EUC-KR
+fn valid_utf8_prefix(data: &[u8], truncated: bool) -> bool {
+    match std::str::from_utf8(data) {
+        Ok(_) => true,
+        Err(e) => truncated && e.error_len().is_none(),
+    }
+}
@@ -1,9 +1,40 @@
-use crate::{Error, Result};
-use flate2::read::GzDecoder;
-use std::io::Read;
+use crate::limits::DecompressionBudget;
+use crate::path::{self, sanitize_entry_path};
+use crate::{bounds, limits, Error, Result};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Cursor, Read, Write};
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 const THOR_MAGIC: &[u8; 28] = b"ASSF (C) 2007 Aeomin DEV\x1A\x04\x0C\x00";
+const MODE_ADD: u8 = 0x01;
+const MODE_REMOVE: u8 = 0x02;
+// Names the GRF the patch goes into; readers that predate it skip the record like any unknown mode
+const MODE_TARGET: u8 = 0x03;
+// Extracts the patch into the game directory instead of a GRF
+const MODE_ROOT: u8 = 0x04;
+
+// Patches made with the Thor patch maker share the first 24 bytes of the magic, followed by their own header
+const ASSF_MAGIC: &[u8; 24] = b"ASSF (C) 2007 Aeomin DEV";
+const ASSF_SINGLE_FILE: u16 = 0x21;
+const ASSF_MULTIPLE_FILES: u16 = 0x30;
+const ASSF_REMOVED: u8 = 0x01;
+// Checksums the Thor patcher verifies its own downloads with; not a client file
+const ASSF_INTEGRITY_FILE: &str = "data.integrity";
+
+// Where a patch goes, as stated in its header
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ThorTarget {
+    // The patcher's `target_grf`
+    #[default]
+    DefaultGrf,
+    // A GRF in the game directory, by file name
+    Grf(String),
+    // Loose files in the game directory
+    Root,
+}
 
 #[derive(Debug, Clone)]
 pub enum ThorEntry {
@@ -19,31 +50,31 @@ pub enum ThorEntry {
 #[derive(Debug)]
 pub struct Thor {
     pub entries: Vec<ThorEntry>,
+    pub target: ThorTarget,
 }
 
 impl Thor {
+    #[cfg(feature = "fs")]
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let data = std::fs::read(path)?;
         Self::from_bytes(&data)
     }
     
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
-        if data.len() < 28 {
-            return Err(Error::InvalidThorHeader);
+        if data.starts_with(THOR_MAGIC) {
+            return Self::from_stream(&data[THOR_MAGIC.len()..]);
         }
-        
-        if &data[..28] != THOR_MAGIC {
-            return Err(Error::InvalidThorHeader);
+        if data.starts_with(ASSF_MAGIC) {
+            return Self::from_patch_maker(data);
         }
-        
-        let compressed_data = &data[28..];
-        
-        let mut decoder = GzDecoder::new(compressed_data);
-        let mut decompressed = Vec::new();
-        decoder.read_to_end(&mut decompressed)
-            .map_err(|e| Error::Decompression(e.to_string()))?;
+        Err(Error::InvalidThorHeader)
+    }
+    
+    fn from_stream(compressed_data: &[u8]) -> Result<Self> {
+        let decompressed = limits::decompress_archive(GzDecoder::new(compressed_data), compressed_data.len() as u64, "THOR patch")?;
         
         let mut entries = Vec::new();
+        let mut target = ThorTarget::DefaultGrf;
         let mut cursor = std::io::Cursor::new(decompressed);
         
         while cursor.position() < cursor.get_ref().len() as u64 {
@@ -59,30 +90,234 @@ impl Thor {
             
             let mut filename_buf = vec![0u8; filename_len];
             cursor.read_exact(&mut filename_buf)?;
-            let filename = String::from_utf8_lossy(&filename_buf).to_string();
+            let filename = path::decode_name(&filename_buf);
             
             match mode {
-                0x01 => {
+                MODE_ADD => {
                     let mut size_buf = [0u8; 4];
                     cursor.read_exact(&mut size_buf)?;
                     let size = u32::from_le_bytes(size_buf);
                     
-                    let mut data = vec![0u8; size as usize];
-                    cursor.read_exact(&mut data)?;
+                    limits::check_entry_size(size as u64, "THOR entry data")?;
+                    let data = bounds::read_bytes(&mut cursor, size as u64, "THOR entry data")?;
                     
                     entries.push(ThorEntry::Add { filename, data });
                 },
-                0x02 => {
+                MODE_REMOVE => {
                     entries.push(ThorEntry::Remove { filename });
                 },
+                MODE_TARGET => {
+                    target = ThorTarget::Grf(filename);
+                },
+                MODE_ROOT => {
+                    target = ThorTarget::Root;
+                },
                 _ => {},
             }
         }
         
-        Ok(Thor { entries })
+        Ok(Thor { entries, target })
+    }
+    
+    // Header: merge flag, file count, mode and target GRF name. Single-file patches follow it with one entry,
+    // multi-file patches with the location of a zlib-compressed file table; entry data is zlib-compressed.
+    fn from_patch_maker(data: &[u8]) -> Result<Self> {
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(ASSF_MAGIC.len() as u64);
+        
+        let merge = read_u8(&mut cursor)? != 0;
+        let _file_count = read_u32(&mut cursor)?;
+        let mode = u16::from_le_bytes(read_array(&mut cursor)?);
+        let grf = read_name(&mut cursor)?;
+        let target = match (merge, grf.is_empty()) {
+            (false, _) => ThorTarget::Root,
+            (true, true) => ThorTarget::DefaultGrf,
+            (true, false) => ThorTarget::Grf(grf),
+        };
+        
+        let budget = DecompressionBudget::default();
+        let mut entries = Vec::new();
+        match mode {
+            ASSF_SINGLE_FILE => {
+                let compressed_size = read_u32(&mut cursor)?;
+                let size = read_u32(&mut cursor)?;
+                let filename = read_name(&mut cursor)?;
+                let compressed = bounds::read_bytes(&mut cursor, compressed_size as u64, "THOR entry data")?;
+                let data = inflate(&compressed, size, &budget)?;
+                entries.push(ThorEntry::Add { filename, data });
+            }
+            ASSF_MULTIPLE_FILES => {
+                let table_size = read_u32(&mut cursor)? as u64;
+                let table_offset = read_u32(&mut cursor)? as u64;
+                bounds::check_range(table_offset, table_size, data.len() as u64, "THOR file table")?;
+                let table = &data[table_offset as usize..(table_offset + table_size) as usize];
+                let table = limits::decompress_archive(ZlibDecoder::new(table), table_size, "THOR file table")?;
+                
+                let mut table = Cursor::new(table);
+                while table.position() < table.get_ref().len() as u64 {
+                    let filename = read_name(&mut table)?;
+                    if read_u8(&mut table)? & ASSF_REMOVED != 0 {
+                        entries.push(ThorEntry::Remove { filename });
+                        continue;
+                    }
+                    
+                    let offset = read_u32(&mut table)? as u64;
+                    let compressed_size = read_u32(&mut table)? as u64;
+                    let size = read_u32(&mut table)?;
+                    bounds::check_range(offset, compressed_size, data.len() as u64, "THOR entry data")?;
+                    if filename.eq_ignore_ascii_case(ASSF_INTEGRITY_FILE) {
+                        continue;
+                    }
+                    let data = inflate(&data[offset as usize..(offset + compressed_size) as usize], size, &budget)?;
+                    entries.push(ThorEntry::Add { filename, data });
+                }
+            }
+            _ => return Err(Error::Malformed(format!("unknown THOR mode {:#x}", mode))),
+        }
+        
+        Ok(Thor { entries, target })
     }
     
     pub fn get_entries(&self) -> &[ThorEntry] {
         &self.entries
     }
 }
+
+// Writes patches in the format `Thor` reads. Entries keep the order they were added in, which is the order
+// they are applied.
+#[derive(Debug)]
+pub struct ThorBuilder {
+    entries: Vec<ThorEntry>,
+    target: ThorTarget,
+    compression: Compression,
+}
+
+impl Default for ThorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThorBuilder {
+    pub fn new() -> Self {
+        ThorBuilder {
+            entries: Vec::new(),
+            target: ThorTarget::DefaultGrf,
+            compression: Compression::default(),
+        }
+    }
+    
+    // Without a target the patch goes into the patcher's `target_grf`
+    pub fn with_target_grf(self, grf: &str) -> Result<Self> {
+        self.with_target(ThorTarget::Grf(grf.to_string()))
+    }
+    
+    pub fn with_target(mut self, target: ThorTarget) -> Result<Self> {
+        if let ThorTarget::Grf(grf) = &target {
+            check_name(grf)?;
+        }
+        self.target = target;
+        Ok(self)
+    }
+    
+    // gzip level, 0 (store) to 9 (smallest)
+    pub fn with_compression(mut self, level: u32) -> Self {
+        self.compression = Compression::new(level.min(9));
+        self
+    }
+    
+    pub fn add_file(&mut self, filename: &str, data: &[u8]) -> Result<()> {
+        if u32::try_from(data.len()).is_err() {
+            return Err(Error::Unsupported(format!("{} is too large for a THOR entry", filename)));
+        }
+        self.entries.push(ThorEntry::Add {
+            filename: check_name(filename)?.to_string(),
+            data: data.to_vec(),
+        });
+        Ok(())
+    }
+    
+    #[cfg(feature = "fs")]
+    pub fn add_file_from_path<P: AsRef<Path>>(&mut self, file_path: P, archive_path: &str) -> Result<()> {
+        let data = std::fs::read(file_path)?;
+        self.add_file(archive_path, &data)
+    }
+    
+    pub fn remove_file(&mut self, filename: &str) -> Result<()> {
+        self.entries.push(ThorEntry::Remove {
+            filename: check_name(filename)?.to_string(),
+        });
+        Ok(())
+    }
+    
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(THOR_MAGIC.to_vec(), self.compression);
+        match &self.target {
+            ThorTarget::DefaultGrf => {}
+            ThorTarget::Grf(grf) => write_record(&mut encoder, MODE_TARGET, grf)?,
+            ThorTarget::Root => write_record(&mut encoder, MODE_ROOT, "")?,
+        }
+        for entry in &self.entries {
+            match entry {
+                ThorEntry::Add { filename, data } => {
+                    write_record(&mut encoder, MODE_ADD, filename)?;
+                    encoder.write_all(&(data.len() as u32).to_le_bytes())?;
+                    encoder.write_all(data)?;
+                }
+                ThorEntry::Remove { filename } => write_record(&mut encoder, MODE_REMOVE, filename)?,
+            }
+        }
+        Ok(encoder.finish()?)
+    }
+    
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+}
+
+// Names are stored behind a single length byte, and a patch must not name anything outside the client
+fn check_name(name: &str) -> Result<&str> {
+    sanitize_entry_path(name)?;
+    if path::encode_name(name).len() > u8::MAX as usize {
+        return Err(Error::Unsupported(format!("{} is longer than the 255 bytes a THOR name can hold", name)));
+    }
+    Ok(name)
+}
+
+fn read_array<const N: usize>(reader: &mut Cursor<impl AsRef<[u8]>>) -> Result<[u8; N]> {
+    let mut buffer = [0u8; N];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn read_u8(reader: &mut Cursor<impl AsRef<[u8]>>) -> Result<u8> {
+    Ok(read_array::<1>(reader)?[0])
+}
+
+fn read_u32(reader: &mut Cursor<impl AsRef<[u8]>>) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_array(reader)?))
+}
+
+// Names are prefixed with a single length byte
+fn read_name(reader: &mut Cursor<impl AsRef<[u8]>>) -> Result<String> {
+    let len = read_u8(reader)? as usize;
+    let mut name = vec![0u8; len];
+    reader.read_exact(&mut name)?;
+    Ok(path::decode_name(&name))
+}
+
+fn inflate(compressed: &[u8], size: u32, budget: &DecompressionBudget) -> Result<Vec<u8>> {
+    limits::check_entry_size(size as u64, "THOR entry data")?;
+    let data = limits::decompress_entry(ZlibDecoder::new(compressed), compressed.len() as u64, size as u64, "THOR entry data")?;
+    budget.consume(data.len() as u64, "THOR entry data")?;
+    Ok(data)
+}
+
+fn write_record<W: Write>(writer: &mut W, mode: u8, name: &str) -> Result<()> {
+    let name = path::encode_name(name);
+    writer.write_all(&[mode, name.len() as u8])?;
+    writer.write_all(&name)?;
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use crate::beam::BeamArchive;
+use crate::gpf::Gpf;
+use crate::grf::Grf;
+use crate::{path, Error, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VfsMetadata {
+    // Uncompressed size; 0 for directories
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsDirEntry {
+    // Last component of the path, spelled as in the archive that provides it
+    pub name: String,
+    pub metadata: VfsMetadata,
+}
+
+// Read-only access to the files of one archive, for tools that view or convert client content without caring
+// which format it is stored in. Names are accepted in any case and with either separator, like the client does.
+pub trait Vfs: Send + Sync {
+    // Every file, as stored
+    fn list(&self) -> Vec<&str>;
+    
+    // None when there is no such file
+    fn stat(&self, name: &str) -> Option<VfsMetadata>;
+    
+    // Streams the uncompressed content where the format allows it
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>>;
+    
+    fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open(name)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+impl Vfs for Grf {
+    fn list(&self) -> Vec<&str> {
+        self.list_files()
+    }
+    
+    fn stat(&self, name: &str) -> Option<VfsMetadata> {
+        self.get_entry(name).map(|entry| VfsMetadata { size: entry.uncompressed_size as u64, is_dir: false })
+    }
+    
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(self.open_entry_stream(name)?))
+    }
+}
+
+impl Vfs for Gpf {
+    fn list(&self) -> Vec<&str> {
+        self.list_files()
+    }
+    
+    fn stat(&self, name: &str) -> Option<VfsMetadata> {
+        self.get_entry(name).map(|entry| VfsMetadata { size: entry.uncompressed_size as u64, is_dir: false })
+    }
+    
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(std::io::Cursor::new(self.extract_file(name)?)))
+    }
+}
+
+// BEAM entries are verified against their MD5 as a whole, so they are read completely before being handed out
+impl Vfs for BeamArchive {
+    fn list(&self) -> Vec<&str> {
+        self.list_files()
+    }
+    
+    fn stat(&self, name: &str) -> Option<VfsMetadata> {
+        let entry = self.get_entry(beam_name(self, name)?)?;
+        Some(VfsMetadata { size: entry.uncompressed_size as u64, is_dir: false })
+    }
+    
+    fn open(&self, name: &str) -> Result<Box<dyn Read + '_>> {
+        let stored = beam_name(self, name).ok_or_else(|| Error::FileNotFound(name.to_string()))?;
+        Ok(Box::new(std::io::Cursor::new(self.extract_file(stored)?)))
+    }
+}
+
+// BEAM lookups are exact, so other spellings are matched against the (small) list of entries
+fn beam_name<'a>(beam: &'a BeamArchive, name: &'a str) -> Option<&'a str> {
+    if beam.get_entry(name).is_some() {
+        return Some(name);
+    }
+    let key = path::name_key(name);
+    beam.list_files().into_iter().find(|stored| path::name_key(stored) == key)
+}
+
+// Several archives seen as one directory tree, the way the client layers its GRFs: a file in an earlier layer
+// hides the same file in later ones. Paths use `/` or `\`, without a leading separator; "" is the root.
+pub struct Mount {
+    layers: Vec<Box<dyn Vfs>>,
+    // `path::name_key` of every visible file: the layer it comes from and its stored name
+    files: HashMap<String, (usize, String)>,
+    // `path::name_key` of every directory: its children by `name_key` of the last component
+    dirs: BTreeMap<String, BTreeMap<String, VfsDirEntry>>,
+}
+
+impl Default for Mount {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mount {
+    pub fn new() -> Self {
+        Mount {
+            layers: Vec::new(),
+            files: HashMap::new(),
+            dirs: BTreeMap::from([(String::new(), BTreeMap::new())]),
+        }
+    }
+    
+    // Adds an archive below the ones already mounted
+    pub fn with_layer<V: Vfs + 'static>(mut self, layer: V) -> Self {
+        self.add_layer(Box::new(layer));
+        self
+    }
+    
+    pub fn add_layer(&mut self, layer: Box<dyn Vfs>) {
+        let index = self.layers.len();
+        for stored in layer.list() {
+            let key = path::name_key(stored);
+            if key.is_empty() || self.files.contains_key(&key) || self.dirs.contains_key(&key) {
+                continue;
+            }
+            let Some(metadata) = layer.stat(stored) else {
+                continue;
+            };
+            self.insert_parents(stored, metadata);
+            self.files.insert(key, (index, stored.to_string()));
+        }
+        self.layers.push(layer);
+    }
+    
+    // Links the file and any directories above it into their parents
+    fn insert_parents(&mut self, stored: &str, metadata: VfsMetadata) {
+        let parts: Vec<&str> = stored.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".").collect();
+        let mut parent = String::new();
+        for (depth, part) in parts.iter().enumerate() {
+            let is_file = depth == parts.len() - 1;
+            let child_key = part.to_lowercase();
+            let entry = VfsDirEntry {
+                name: part.to_string(),
+                metadata: if is_file { metadata } else { VfsMetadata { size: 0, is_dir: true } },
+            };
+            self.dirs.entry(parent.clone()).or_default().entry(child_key.clone()).or_insert(entry);
+            if !is_file {
+                parent = if parent.is_empty() { child_key } else { format!("{}/{}", parent, child_key) };
+                self.dirs.entry(parent.clone()).or_default();
+            }
+        }
+    }
+    
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+    
+    pub fn stat(&self, path: &str) -> Result<VfsMetadata> {
+        let key = path::name_key(path);
+        if self.dirs.contains_key(&key) {
+            return Ok(VfsMetadata { size: 0, is_dir: true });
+        }
+        let (layer, stored) = self.files.get(&key).ok_or_else(|| Error::FileNotFound(path.to_string()))?;
+        self.layers[*layer].stat(stored).ok_or_else(|| Error::FileNotFound(path.to_string()))
+    }
+    
+    // Immediate children of a directory, sorted by name
+    pub fn read_dir(&self, path: &str) -> Result<Vec<VfsDirEntry>> {
+        self.dirs
+            .get(&path::name_key(path))
+            .map(|children| children.values().cloned().collect())
+            .ok_or_else(|| Error::FileNotFound(path.to_string()))
+    }
+    
+    pub fn open(&self, path: &str) -> Result<Box<dyn Read + '_>> {
+        let (layer, stored) = self.files.get(&path::name_key(path)).ok_or_else(|| Error::FileNotFound(path.to_string()))?;
+        self.layers[*layer].open(stored)
+    }
+    
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let (layer, stored) = self.files.get(&path::name_key(path)).ok_or_else(|| Error::FileNotFound(path.to_string()))?;
+        self.layers[*layer].read(stored)
+    }
+}
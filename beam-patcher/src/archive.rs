@@ -0,0 +1,163 @@
+// Archive tools for server admins: GRF listing, extraction and updates, THOR inspection and BEAM creation
+use anyhow::Result;
+use beam_formats::beam::BeamArchive;
+use beam_formats::grf::Grf;
+use beam_formats::path;
+use beam_formats::thor::{Thor, ThorEntry, ThorTarget};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize)]
+pub struct GrfFile {
+    pub name: String,
+    pub size: u32,
+    pub compressed_size: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThorSummary {
+    // "default_grf" (the patcher's `target_grf`), "grf" (`target_grf` below) or "root"
+    pub target: &'static str,
+    pub target_grf: Option<String>,
+    // In the order they are applied
+    pub entries: Vec<ThorSummaryEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThorSummaryEntry {
+    pub name: String,
+    // "add" or "remove"
+    pub action: &'static str,
+    pub size: u64,
+}
+
+// Entries whose name contains `filter` (in any case and with either separator), sorted by name
+pub fn list_grf(grf: &Grf, filter: Option<&str>) -> Vec<GrfFile> {
+    let filter = filter.map(path::name_key);
+    let mut files: Vec<GrfFile> = grf
+        .list_files()
+        .into_iter()
+        .filter(|name| filter.as_ref().is_none_or(|filter| path::name_key(name).contains(filter.as_str())))
+        .filter_map(|name| grf.get_entry(name))
+        .map(|entry| GrfFile {
+            name: entry.filename.clone(),
+            size: entry.uncompressed_size,
+            compressed_size: entry.compressed_size,
+        })
+        .collect();
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    files
+}
+
+// Writes `names` below `out`, a folder name taking everything in it, or every entry when `names` is empty; returns
+// the number of files written and their total size
+pub fn extract_grf(grf: &Grf, names: &[String], out: &Path) -> Result<(usize, u64)> {
+    let mut selected: Vec<String> = Vec::new();
+    for name in names {
+        if grf.get_entry(name).is_some() {
+            selected.push(name.clone());
+            continue;
+        }
+        let folder = format!("{}/", path::name_key(name));
+        let before = selected.len();
+        let files = grf.list_files().into_iter().filter(|file| path::name_key(file).starts_with(&folder));
+        selected.extend(files.map(str::to_string));
+        if selected.len() == before {
+            anyhow::bail!("{} is not in the GRF", name);
+        }
+    }
+    if names.is_empty() {
+        selected = grf.list_files().into_iter().map(str::to_string).collect();
+    }
+    
+    std::fs::create_dir_all(out)?;
+    let mut bytes = 0;
+    for name in &selected {
+        let data = grf.extract_file(name)?;
+        let target = path::confine_to(out, name)?;
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&target, &data)?;
+        bytes += data.len() as u64;
+    }
+    Ok((selected.len(), bytes))
+}
+
+// Local files for `paths`, folders with everything below them, each named by its path relative to `root` the way
+// the client looks it up (`data\texture\a.bmp`)
+pub fn local_files(paths: &[String], root: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let root = root.canonicalize().map_err(|e| anyhow::anyhow!("Cannot open {}: {}", root.display(), e))?;
+    let mut files = Vec::new();
+    for local in paths {
+        let local = Path::new(local).canonicalize().map_err(|e| anyhow::anyhow!("Cannot open {}: {}", local, e))?;
+        collect_files(&local, &root, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn collect_files(local: &Path, root: &Path, files: &mut Vec<(PathBuf, String)>) -> Result<()> {
+    if local.is_dir() {
+        let mut entries = std::fs::read_dir(local)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<PathBuf>>>()?;
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, root, files)?;
+        }
+        return Ok(());
+    }
+    let relative = local
+        .strip_prefix(root)
+        .map_err(|_| anyhow::anyhow!("{} is not inside {}; pass --root", local.display(), root.display()))?;
+    files.push((local.to_path_buf(), path::to_archive_name(&relative.to_string_lossy())));
+    Ok(())
+}
+
+// Adds or replaces `files` in the GRF at `grf_path`, creating it when missing; returns the number of entries that
+// changed
+pub fn add_to_grf(grf_path: &Path, files: &[(PathBuf, String)]) -> Result<usize> {
+    Grf::recover_interrupted_save(grf_path)?;
+    let mut grf = if grf_path.exists() { Grf::open(grf_path)? } else { Grf::create_new(grf_path)? };
+    let mut changed = 0;
+    for (local, name) in files {
+        if grf.patch_file_if_changed(name, &std::fs::read(local)?)? {
+            changed += 1;
+        }
+    }
+    grf.save()?;
+    Ok(changed)
+}
+
+pub fn inspect_thor(patch: &Path) -> Result<ThorSummary> {
+    let thor = Thor::open(patch)?;
+    let (target, target_grf) = match &thor.target {
+        ThorTarget::DefaultGrf => ("default_grf", None),
+        ThorTarget::Grf(grf) => ("grf", Some(grf.clone())),
+        ThorTarget::Root => ("root", None),
+    };
+    let entries = thor
+        .entries
+        .iter()
+        .map(|entry| match entry {
+            ThorEntry::Add { filename, data } => {
+                ThorSummaryEntry { name: filename.clone(), action: "add", size: data.len() as u64 }
+            }
+            ThorEntry::Remove { filename } => ThorSummaryEntry { name: filename.clone(), action: "remove", size: 0 },
+        })
+        .collect();
+    Ok(ThorSummary { target, target_grf, entries })
+}
+
+// Writes a BEAM patch of `files` to `out`, signed with `secret_key` when given; returns the patch size
+pub fn create_beam(files: &[(PathBuf, String)], out: &Path, secret_key: Option<&[u8; 32]>) -> Result<u64> {
+    let mut archive = BeamArchive::new();
+    for (local, name) in files {
+        archive.add_file_from_path(local, name)?;
+    }
+    archive.save(out)?;
+    if let Some(secret_key) = secret_key {
+        BeamArchive::sign(out, secret_key)?;
+    }
+    Ok(std::fs::metadata(out)?.len())
+}
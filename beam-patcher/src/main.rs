@@ -1,11 +1,16 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use anyhow::Result;
-use beam_core::Config;
-use beam_ui;
-use clap::Parser;
+use beam_core::{Config, RunKind, RunReport};
+use clap::{CommandFactory, Parser, Subcommand};
+use output::{print_json, OutputFormat, Reported};
 use tracing_subscriber;
 
+mod archive;
+#[cfg(feature = "mount")]
+mod mount;
+mod output;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -15,19 +20,714 @@ struct Args {
     #[arg(long)]
     headless: bool,
     
+    /// Override app.game_directory from the config
+    #[arg(long, value_name = "DIR")]
+    game_dir: Option<String>,
+    
     #[arg(short, long)]
     manual_patch: Option<String>,
+    
+    /// Check the game files against the mirror's manifest.json instead of patching (headless)
+    #[arg(long)]
+    verify: bool,
+    
+    /// Check the patch list, every pending patch on the mirrors and the target GRFs without patching
+    #[arg(long)]
+    dry_run: bool,
+    
+    /// Write a JSON report of the headless run (versions, patches, corruption found, durations, errors)
+    #[arg(long, value_name = "FILE")]
+    output: Option<String>,
+    
+    /// Patch at below-normal CPU and I/O priority (overrides patcher.background_priority)
+    #[arg(long)]
+    background: bool,
+    
+    /// Lock settings, the game directory and manual patching, even if the config does not set app.kiosk
+    #[arg(long)]
+    kiosk: bool,
+    
+    /// Files changed on this computer that a patch would replace: ask (fail the patch and list them), keep-mine or
+    /// take-theirs (overrides patcher.conflict_policy)
+    #[arg(long, value_name = "POLICY", value_parser = parse_conflict_policy)]
+    conflicts: Option<beam_core::config::ConflictPolicy>,
+    
+    /// Hash every file in DIR (a clean official install) and write a stock checksum database
+    #[arg(long, value_name = "DIR")]
+    generate_checksum_db: Option<String>,
+    
+    #[arg(long, default_value = "stock_hashes.json")]
+    checksum_db_output: String,
+    
+    /// Let all local users patch the shared game directory (run once as administrator)
+    #[arg(long)]
+    grant_shared_access: bool,
+    
+    /// `json` prints one document with a versioned schema to stdout for scripts; logs go to stderr
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    format: OutputFormat,
+    
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    // `command` of the JSON output
+    fn command_name(&self) -> &'static str {
+        match &self.command {
+            Some(Command::Patch { .. }) | Some(Command::Verify { .. }) => "run",
+            Some(Command::Grf { command: GrfCommand::List { .. } }) => "grf-list",
+            Some(Command::Grf { command: GrfCommand::Extract { .. } }) => "grf-extract",
+            Some(Command::Grf { command: GrfCommand::Add { .. } }) => "grf-add",
+            Some(Command::Grf { command: GrfCommand::Repack { .. } }) => "grf-repack",
+            Some(Command::Thor { command: ThorCommand::Inspect { .. } }) => "thor-inspect",
+            Some(Command::Beam { command: BeamCommand::Create { .. } }) => "beam-create",
+            Some(Command::PackageClient { .. }) => "package-client",
+            Some(Command::ConvertGrf { .. }) => "convert-grf",
+            Some(Command::DefragmentGrf { .. }) => "defragment-grf",
+            Some(Command::DiffGrf { .. }) => "diff-grf",
+            Some(Command::MakeDelta { .. }) => "make-delta",
+            Some(Command::GenerateSigningKey { .. }) => "generate-signing-key",
+            Some(Command::SignPatch { .. }) => "sign-patch",
+            Some(Command::Simulate { .. }) => "simulate",
+            Some(Command::DiagnoseConnection) => "diagnose-connection",
+            Some(Command::InstallId { .. }) => "install-id",
+            Some(Command::Uninstall { .. }) => "uninstall",
+            Some(Command::ImportThorConfig { .. }) => "import-thor-config",
+            #[cfg(feature = "mount")]
+            Some(Command::Mount { .. }) => "mount",
+            Some(Command::Completions { .. }) => "completions",
+            Some(Command::ManPage { .. }) => "man-page",
+            None if self.generate_checksum_db.is_some() => "generate-checksum-db",
+            None if self.grant_shared_access => "grant-shared-access",
+            None if self.dry_run => "dry-run",
+            None => "run",
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Patch the game directory without the UI and exit, or apply PATCH from disk (like --headless)
+    Patch {
+        /// BEAM, BEAMD, THOR, RGZ, GPF or archive file to apply instead of the patch list
+        patch: Option<String>,
+        
+        /// Write a JSON report of the run
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Check the game files against the mirror's manifest.json (like --headless --verify)
+    Verify {
+        /// Write a JSON report of the run
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// List, extract or add GRF entries
+    Grf {
+        #[command(subcommand)]
+        command: GrfCommand,
+    },
+    /// Show what a THOR patch contains
+    Thor {
+        #[command(subcommand)]
+        command: ThorCommand,
+    },
+    /// Build BEAM patches
+    Beam {
+        #[command(subcommand)]
+        command: BeamCommand,
+    },
+    /// Zip the patched game directory into a full client download with manifest and checksums
+    PackageClient {
+        #[arg(long, default_value = "client.zip")]
+        out: String,
+        
+        /// Split the archive into OUT.001, OUT.002, ... volumes of at most this many MB
+        #[arg(long, value_name = "MB")]
+        split_size: Option<u64>,
+        
+        /// Extra exclude pattern (`*.ext`, `name`, or `dir/`), may be repeated
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        
+        /// Do not apply the built-in excludes (logs, screenshots, savedata, ...)
+        #[arg(long)]
+        no_default_excludes: bool,
+        
+        /// Also write OUT.torrent using the trackers and web seeds from the `packaging` config
+        #[arg(long)]
+        torrent: bool,
+    },
+    /// Rewrite a GRF as another version (0x200, or 0x300 with a crypto plugin), keeping every entry
+    ConvertGrf {
+        grf: String,
+        
+        #[arg(long, value_name = "VERSION", default_value = "0x200", value_parser = parse_grf_version)]
+        to: u32,
+    },
+    /// Rewrite a GRF without the dead space that appended updates leave behind
+    DefragmentGrf {
+        grf: String,
+    },
+    /// List the files added, removed and changed between two GRFs, compared by content
+    DiffGrf {
+        old: String,
+        
+        new: String,
+        
+        /// Also list files of NEW stored more than once with identical content
+        #[arg(long)]
+        duplicates: bool,
+    },
+    /// Write a BEAMD delta patch that turns OLD into NEW: binary diffs for changed files, whole files for new ones
+    MakeDelta {
+        old: String,
+        
+        new: String,
+        
+        #[arg(long, default_value = "patch.beamd")]
+        out: String,
+    },
+    /// Create an Ed25519 key pair for signing BEAM patches; prints the public key for patcher.patch_signing_keys
+    GenerateSigningKey {
+        /// File for the secret key; never overwritten
+        #[arg(long, value_name = "FILE")]
+        out: String,
+    },
+    /// Sign a BEAM patch in place with a key from generate-signing-key
+    SignPatch {
+        patch: String,
+        
+        #[arg(long, value_name = "FILE")]
+        key: String,
+    },
+    /// Apply a folder of patches to a client described by a manifest, without the client, and report the resulting files
+    Simulate {
+        /// manifest.json of the client the patches start from
+        #[arg(long, value_name = "FILE")]
+        baseline: String,
+        
+        /// Folder of .beam, .thor, .rgz and .gpf patches, applied in file name order (.beamd is not supported)
+        #[arg(long, value_name = "DIR")]
+        patches: String,
+        
+        /// Write the resulting file set as a manifest, usable as the next --baseline
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Test DNS, TCP, TLS and HTTP against every mirror and game server and print a report to paste into support channels
+    DiagnoseConnection,
+    /// Print the anonymous install ID used for staged rollouts and support reports
+    InstallId {
+        /// Replace it with a new random ID
+        #[arg(long)]
+        regenerate: bool,
+    },
+    /// Delete the game folder, the launcher's caches, registry values, shortcuts and URL handlers
+    Uninstall {
+        /// Leave the SaveData folder (hotkeys, chat and window settings) in place
+        #[arg(long)]
+        keep_savedata: bool,
+        
+        /// Leave the ScreenShot folder in place
+        #[arg(long)]
+        keep_screenshots: bool,
+        
+        /// Delete without asking; otherwise only lists what would be removed
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Convert a Thor (config.ini) or NeonCube (neoncube.ini) patcher configuration into the file given by --config
+    ImportThorConfig {
+        ini: String,
+        
+        /// Legacy skin folder to convert when the ini does not reference one
+        #[arg(long, value_name = "DIR")]
+        skin: Option<String>,
+        
+        /// Replace the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show the files of one or more archives as a read-only folder until Ctrl+C; earlier archives win
+    #[cfg(feature = "mount")]
+    Mount {
+        /// GRF, GPF or BEAM archives, in the order the client reads them
+        #[arg(required = true)]
+        archives: Vec<String>,
+        
+        /// Empty directory (Linux, macOS) or drive letter / empty folder (Windows) to mount at
+        mountpoint: String,
+    },
+    /// Print a completion script for SHELL (bash, zsh, fish, elvish or powershell)
+    Completions {
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page, or write one page per command into DIR
+    ManPage {
+        #[arg(long, value_name = "DIR")]
+        out: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum GrfCommand {
+    /// Print every entry with its size and stored size
+    List {
+        grf: String,
+        
+        /// Only entries whose name contains TEXT, in any case
+        #[arg(long, value_name = "TEXT")]
+        filter: Option<String>,
+    },
+    /// Write entries to a folder; a folder name extracts everything in it, no name the whole GRF
+    Extract {
+        grf: String,
+        
+        files: Vec<String>,
+        
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        out: String,
+    },
+    /// Add or replace local files and folders, named by their path below --root; creates the GRF if missing
+    Add {
+        grf: String,
+        
+        #[arg(required = true)]
+        files: Vec<String>,
+        
+        /// Folder the entry names start from, usually the one holding `data`
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        root: String,
+    },
+    /// Rewrite a GRF with only its live entries, optionally recompressing them
+    Repack {
+        grf: String,
+        
+        /// Recompress every entry at this zlib level, 0 (store) to 9 (smallest)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(0..=9))]
+        level: Option<u32>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ThorCommand {
+    /// Print the target and every entry of a THOR patch, in the order they are applied
+    Inspect {
+        patch: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BeamCommand {
+    /// Pack local files and folders into a BEAM patch, named by their path below --root
+    Create {
+        #[arg(required = true)]
+        files: Vec<String>,
+        
+        /// Folder the entry names start from, usually the one holding `data`
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        root: String,
+        
+        #[arg(long, value_name = "FILE", default_value = "patch.beam")]
+        out: String,
+        
+        /// Sign the patch with a secret key from generate-signing-key
+        #[arg(long, value_name = "FILE")]
+        key: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .init();
-    
     let args = Args::parse();
+    let format = args.format;
+    let command = args.command_name();
     
-    let config = if std::path::Path::new(&args.config).exists() {
+    // stdout carries only the JSON document in JSON mode
+    let logger = tracing_subscriber::fmt().with_max_level(tracing::Level::INFO);
+    if format == OutputFormat::Json {
+        logger.with_writer(std::io::stderr).init();
+    } else {
+        logger.init();
+    }
+    
+    match run(args).await {
+        Err(e) if e.is::<Reported>() => std::process::exit(1),
+        Err(e) if format == OutputFormat::Json => {
+            print_json::<()>(command, None, Some(&e.to_string()))?;
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn run(args: Args) -> Result<()> {
+    let json = args.format == OutputFormat::Json;
+    let command = args.command_name();
+    
+    if let Some(Command::Completions { shell }) = &args.command {
+        let mut cli = Args::command();
+        let name = cli.get_name().to_string();
+        let mut script = Vec::new();
+        clap_complete::generate(*shell, &mut cli, name, &mut script);
+        if json {
+            let result = serde_json::json!({ "shell": shell.to_string(), "script": String::from_utf8(script)? });
+            return print_json(command, Some(&result), None);
+        }
+        std::io::Write::write_all(&mut std::io::stdout(), &script)?;
+        return Ok(());
+    }
+    
+    if let Some(Command::ManPage { out }) = &args.command {
+        let cli = Args::command();
+        let Some(out) = out else {
+            let mut page = Vec::new();
+            clap_mangen::Man::new(cli).render(&mut page)?;
+            if json {
+                let result = serde_json::json!({ "page": String::from_utf8(page)? });
+                return print_json(command, Some(&result), None);
+            }
+            std::io::Write::write_all(&mut std::io::stdout(), &page)?;
+            return Ok(());
+        };
+        // beam-patcher.1 plus beam-patcher-<command>.1 for every subcommand
+        std::fs::create_dir_all(out)?;
+        clap_mangen::generate_to(cli, out)?;
+        let mut pages: Vec<String> = std::fs::read_dir(out)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("beam-patcher") && name.ends_with(".1"))
+            .collect();
+        pages.sort();
+        if json {
+            let result = serde_json::json!({ "out": out, "pages": pages });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Wrote {} man pages to {}", pages.len(), out);
+        return Ok(());
+    }
+    
+    if let Some(Command::Grf { command: GrfCommand::List { grf, filter } }) = &args.command {
+        let files = archive::list_grf(&beam_formats::grf::Grf::open(grf)?, filter.as_deref());
+        if json {
+            let result = serde_json::json!({ "grf": grf, "files": files });
+            return print_json(command, Some(&result), None);
+        }
+        for file in &files {
+            println!("{:>10} {:>10}  {}", file.size, file.compressed_size, file.name);
+        }
+        println!("{} files", files.len());
+        return Ok(());
+    }
+    
+    if let Some(Command::Grf { command: GrfCommand::Extract { grf, files, out } }) = &args.command {
+        let (extracted, bytes) = archive::extract_grf(&beam_formats::grf::Grf::open(grf)?, files, std::path::Path::new(out))?;
+        if json {
+            let result = serde_json::json!({ "grf": grf, "out": out, "files": extracted, "bytes": bytes });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Extracted {} files ({} KB) from {} to {}", extracted, bytes / 1024, grf, out);
+        return Ok(());
+    }
+    
+    if let Some(Command::Grf { command: GrfCommand::Add { grf, files, root } }) = &args.command {
+        let files = archive::local_files(files, std::path::Path::new(root))?;
+        let changed = archive::add_to_grf(std::path::Path::new(grf), &files)?;
+        if json {
+            let result = serde_json::json!({ "grf": grf, "files": files.len(), "changed": changed });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Added {} files to {} ({} unchanged)", changed, grf, files.len() - changed);
+        return Ok(());
+    }
+    
+    if let Some(Command::Grf { command: GrfCommand::Repack { grf, level } }) = &args.command {
+        beam_formats::grf::Grf::recover_interrupted_save(grf)?;
+        let saved = beam_formats::grf::Grf::open(grf)?.repack(*level)?;
+        if json {
+            let result = serde_json::json!({ "grf": grf, "level": level, "reclaimed_bytes": saved });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Repacked {}: {} MB smaller", grf, saved / (1024 * 1024));
+        return Ok(());
+    }
+    
+    if let Some(Command::Thor { command: ThorCommand::Inspect { patch } }) = &args.command {
+        let summary = archive::inspect_thor(std::path::Path::new(patch))?;
+        if json {
+            let result = serde_json::json!({
+                "patch": patch,
+                "target": summary.target,
+                "target_grf": summary.target_grf,
+                "entries": summary.entries,
+            });
+            return print_json(command, Some(&result), None);
+        }
+        match &summary.target_grf {
+            Some(grf) => println!("Target: {}", grf),
+            None if summary.target == "root" => println!("Target: game directory"),
+            None => println!("Target: the patcher's target_grf"),
+        }
+        for entry in &summary.entries {
+            let marker = if entry.action == "remove" { "-" } else { "+" };
+            println!("{} {:>10}  {}", marker, entry.size, entry.name);
+        }
+        return Ok(());
+    }
+    
+    if let Some(Command::Beam { command: BeamCommand::Create { files, root, out, key } }) = &args.command {
+        let files = archive::local_files(files, std::path::Path::new(root))?;
+        let secret_key = key.as_ref().map(|key| beam_formats::beam::decode_key(&std::fs::read_to_string(key)?)).transpose()?;
+        let bytes = archive::create_beam(&files, std::path::Path::new(out), secret_key.as_ref())?;
+        let public_key = secret_key.map(|key| beam_formats::beam::encode_key(&beam_formats::beam::public_key(&key)));
+        if json {
+            let result = serde_json::json!({ "out": out, "files": files.len(), "bytes": bytes, "public_key": public_key });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Wrote {} ({} KB, {} files){}", out, bytes / 1024, files.len(), if public_key.is_some() { ", signed" } else { "" });
+        return Ok(());
+    }
+    
+    if let Some(Command::ConvertGrf { grf, to }) = &args.command {
+        let mut archive = beam_formats::grf::Grf::open(grf)?;
+        let from = archive.version;
+        archive.convert_version(*to)?;
+        if json {
+            let result = serde_json::json!({
+                "grf": grf,
+                "from_version": format!("0x{:x}", from),
+                "to_version": format!("0x{:x}", to),
+                "entries": archive.file_count(),
+            });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!(
+            "Converted {} from {} to {} ({} entries)",
+            grf,
+            beam_formats::grf::Grf::version_name(from),
+            beam_formats::grf::Grf::version_name(*to),
+            archive.file_count()
+        );
+        return Ok(());
+    }
+    
+    if let Some(Command::DefragmentGrf { grf }) = &args.command {
+        beam_formats::grf::Grf::recover_interrupted_save(grf)?;
+        let mut archive = beam_formats::grf::Grf::open(grf)?;
+        let dead_space = archive.dead_space();
+        archive.defragment()?;
+        if json {
+            let result = serde_json::json!({ "grf": grf, "reclaimed_bytes": dead_space });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Defragmented {}: {} MB reclaimed", grf, dead_space / (1024 * 1024));
+        return Ok(());
+    }
+    
+    if let Some(Command::DiffGrf { old, new, duplicates }) = &args.command {
+        let old_index = beam_formats::content_index::ContentIndex::build(&beam_formats::grf::Grf::open(old)?)?;
+        let new_index = beam_formats::content_index::ContentIndex::build(&beam_formats::grf::Grf::open(new)?)?;
+        let diff = old_index.diff(&new_index);
+        let duplicate_groups = if *duplicates { new_index.duplicates() } else { Vec::new() };
+        if json {
+            let result = serde_json::json!({ "diff": diff, "duplicates": duplicate_groups });
+            print_json(command, Some(&result), None)?;
+        } else {
+            for (marker, names) in [("+", &diff.added), ("-", &diff.removed), ("~", &diff.changed)] {
+                for name in names {
+                    println!("{} {}", marker, name);
+                }
+            }
+            for group in &duplicate_groups {
+                println!("= {}", group.join(", "));
+            }
+            println!(
+                "{} added, {} removed, {} changed, {} unchanged",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len(),
+                diff.unchanged
+            );
+        }
+        return Ok(());
+    }
+    
+    if let Some(Command::MakeDelta { old, new, out }) = &args.command {
+        let old_grf = beam_formats::grf::Grf::open(old)?;
+        let new_grf = beam_formats::grf::Grf::open(new)?;
+        let diff = beam_formats::content_index::ContentIndex::build(&old_grf)?
+            .diff(&beam_formats::content_index::ContentIndex::build(&new_grf)?);
+        
+        // The index lowercases names; new files keep the spelling NEW has
+        let names: std::collections::HashMap<String, &str> = new_grf
+            .list_files()
+            .into_iter()
+            .map(|name| (beam_formats::path::name_key(name), name))
+            .collect();
+        let stored = |name: &String| {
+            names.get(name).map_or_else(|| beam_formats::path::to_archive_name(name), |name| name.to_string())
+        };
+        
+        let mut delta = beam_formats::beamd::DeltaBuilder::new();
+        for name in &diff.changed {
+            delta.add_diff(&stored(name), &old_grf.extract_file(name)?, &new_grf.extract_file(name)?)?;
+        }
+        for name in &diff.added {
+            delta.add_file(&stored(name), &new_grf.extract_file(name)?)?;
+        }
+        for name in &diff.removed {
+            delta.remove_file(&beam_formats::path::to_archive_name(name))?;
+        }
+        let data = delta.to_bytes()?;
+        std::fs::write(out, &data)?;
+        
+        let diffs = delta.entries().filter(|entry| entry.kind == beam_formats::beamd::DeltaKind::Diff).count();
+        if json {
+            let result = serde_json::json!({
+                "out": out,
+                "bytes": data.len(),
+                "diffs": diffs,
+                "full_files": delta.len() - diffs - diff.removed.len(),
+                "removed": diff.removed.len(),
+                "unchanged": diff.unchanged,
+            });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!(
+            "Wrote {} ({} KB): {} diffs, {} whole files, {} removals",
+            out,
+            data.len() / 1024,
+            diffs,
+            delta.len() - diffs - diff.removed.len(),
+            diff.removed.len()
+        );
+        return Ok(());
+    }
+    
+    if let Some(Command::GenerateSigningKey { out }) = &args.command {
+        let mut secret_key = [0u8; 32];
+        getrandom::getrandom(&mut secret_key)?;
+        let mut file = std::fs::OpenOptions::new();
+        file.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+        let mut file = file.open(out).map_err(|e| anyhow::anyhow!("Cannot create {}: {}", out, e))?;
+        std::io::Write::write_all(&mut file, format!("{}\n", beam_formats::beam::encode_key(&secret_key)).as_bytes())?;
+        
+        let public_key = beam_formats::beam::encode_key(&beam_formats::beam::public_key(&secret_key));
+        if json {
+            let result = serde_json::json!({ "out": out, "public_key": public_key });
+            return print_json(command, Some(&result), None);
+        }
+        println!("{}", public_key);
+        tracing::info!("Wrote the secret key to {}; add the public key above to patcher.patch_signing_keys", out);
+        return Ok(());
+    }
+    
+    if let Some(Command::SignPatch { patch, key }) = &args.command {
+        let secret_key = beam_formats::beam::decode_key(&std::fs::read_to_string(key)?)?;
+        beam_formats::beam::BeamArchive::sign(patch, &secret_key)?;
+        let public_key = beam_formats::beam::encode_key(&beam_formats::beam::public_key(&secret_key));
+        if json {
+            let result = serde_json::json!({ "patch": patch, "public_key": public_key });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Signed {} with {}", patch, public_key);
+        return Ok(());
+    }
+    
+    #[cfg(feature = "mount")]
+    if let Some(Command::Mount { archives, mountpoint }) = &args.command {
+        mount::run(archives, std::path::Path::new(mountpoint)).await?;
+        if json {
+            let result = serde_json::json!({ "archives": archives, "mountpoint": mountpoint });
+            print_json(command, Some(&result), None)?;
+        }
+        return Ok(());
+    }
+    
+    if let Some(Command::Simulate { baseline, patches, output }) = &args.command {
+        let baseline: beam_core::verifier::FileManifest = serde_json::from_str(&std::fs::read_to_string(baseline)?)?;
+        let patches = beam_core::simulate::patch_files(std::path::Path::new(patches))?;
+        let report = beam_core::simulate::simulate(&baseline, &patches)?;
+        if json {
+            print_json(command, Some(&report), None)?;
+        } else {
+            for patch in &report.patches {
+                println!(
+                    "{}: {} added, {} updated, {} unchanged, {} removed",
+                    patch.name,
+                    patch.added,
+                    patch.updated,
+                    patch.unchanged,
+                    patch.removed
+                );
+            }
+            println!(
+                "{} files after {} patches: {} added, {} changed, {} removed",
+                report.manifest.files.len(),
+                report.patches.len(),
+                report.added.len(),
+                report.changed.len(),
+                report.removed.len()
+            );
+        }
+        if let Some(output) = output {
+            std::fs::write(output, serde_json::to_string_pretty(&report.manifest)?)?;
+        }
+        return Ok(());
+    }
+    
+    if let Some(Command::ImportThorConfig { ini, skin, force }) = &args.command {
+        if std::path::Path::new(&args.config).exists() && !force {
+            anyhow::bail!("{} already exists; pass --force to replace it", args.config);
+        }
+        let mut import = beam_core::legacy_import::import_legacy_config(ini)?;
+        if let Some(dir) = skin {
+            let dir = std::path::Path::new(dir);
+            let skin = beam_core::legacy_skin::load_legacy_skin(dir, &beam_core::legacy_skin::asset_dir(dir))?;
+            skin.apply(&mut import.config.ui);
+            import.notes.extend(skin.notes);
+        }
+        import.config.validate()?;
+        import.config.save(&args.config)?;
+        if json {
+            let result = serde_json::json!({
+                "config": args.config,
+                "patcher": import.patcher,
+                "patch_list_url": import.config.patcher.patch_list_url,
+                "target_grf": import.config.patcher.target_grf,
+                "notes": import.notes,
+            });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!(
+            "Wrote {} from {:?} config (patch list: {}, target GRF: {})",
+            args.config,
+            import.patcher,
+            import.config.patcher.patch_list_url,
+            import.config.patcher.target_grf
+        );
+        for note in &import.notes {
+            tracing::warn!("{}", note);
+        }
+        return Ok(());
+    }
+    
+    if let Some(dir) = &args.generate_checksum_db {
+        let manifest = beam_core::ChecksumDatabase::generate(dir).await?;
+        std::fs::write(&args.checksum_db_output, serde_json::to_string_pretty(&manifest)?)?;
+        if json {
+            let result = serde_json::json!({ "output": args.checksum_db_output, "files": manifest.files.len() });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Wrote {} entries to {}", manifest.files.len(), args.checksum_db_output);
+        return Ok(());
+    }
+    
+    let mut config = if std::path::Path::new(&args.config).exists() {
         Config::load(&args.config)?
     } else {
         tracing::warn!("Config file not found, creating default config");
@@ -36,26 +736,313 @@ async fn main() -> Result<()> {
         config
     };
     
-    if args.headless {
-        run_headless(config, args.manual_patch).await?;
+    if let Some(game_dir) = &args.game_dir {
+        config.set_game_directory(game_dir)?;
+    }
+    
+    if args.kiosk {
+        config.app.kiosk = true;
+    }
+    
+    if args.background {
+        config.patcher.background_priority = Some(beam_core::config::BackgroundPriorityConfig::default());
+    }
+    
+    if let Some(policy) = args.conflicts {
+        config.patcher.conflict_policy = policy;
+    }
+    
+    if args.grant_shared_access {
+        let game_dir = config.app.game_directory.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Game directory not set in {}", args.config))?;
+        beam_core::multi_user::grant_shared_access(std::path::Path::new(game_dir))?;
+        if json {
+            let result = serde_json::json!({ "game_directory": game_dir });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!("Shared access granted on {}", game_dir);
+        return Ok(());
+    }
+    
+    if let Some(Command::InstallId { regenerate }) = args.command {
+        let id = if regenerate {
+            beam_core::install_id::regenerate(&config)?
+        } else {
+            beam_core::install_id::load_or_create(&config)?
+        };
+        if json {
+            let result = serde_json::json!({ "install_id": id, "regenerated": regenerate });
+            return print_json(command, Some(&result), None);
+        }
+        println!("{}", id);
+        return Ok(());
+    }
+    
+    if let Some(Command::DiagnoseConnection) = args.command {
+        let report = beam_core::connection_check::diagnose(&config).await?;
+        if !report.ok() {
+            if !json {
+                println!("{}", report.text);
+            }
+            return Err(output::fail(args.format, command, &report, "Some endpoints could not be reached".to_string()));
+        }
+        if json {
+            print_json(command, Some(&report), None)?;
+        } else {
+            println!("{}", report.text);
+        }
+        return Ok(());
+    }
+    
+    if args.dry_run {
+        let report = beam_core::Patcher::new(config)?.dry_run().await?;
+        if !json {
+            for patch in &report.patches {
+                let checksum = match patch.checksum_ok {
+                    Some(true) => ", checksum OK",
+                    Some(false) => ", checksum mismatch",
+                    None => "",
+                };
+                let mirrors = patch.mirrors.iter().filter(|mirror| mirror.ok).count();
+                println!("{}: on {}/{} mirrors{}", patch.id, mirrors, patch.mirrors.len(), checksum);
+            }
+            for grf in &report.grfs {
+                let state = match (grf.exists, grf.files) {
+                    (false, _) => "will be created".to_string(),
+                    (true, Some(files)) => format!("{} files", files),
+                    (true, None) => "unreadable".to_string(),
+                };
+                println!("{}: {}{}", grf.path, state, if grf.writable { "" } else { ", not writable" });
+            }
+        }
+        let problems = report.problems();
+        if !problems.is_empty() {
+            if !json {
+                for problem in &problems {
+                    println!("{}", problem);
+                }
+            }
+            return Err(output::fail(args.format, command, &report, format!("Dry run found {} problem(s)", problems.len())));
+        }
+        if json {
+            return print_json(command, Some(&report), None);
+        }
+        println!("{} pending patches can be applied", report.patches.len());
+        return Ok(());
+    }
+    
+    if let Some(Command::Uninstall { keep_savedata, keep_screenshots, yes }) = args.command {
+        let options = beam_core::UninstallOptions { keep_savedata, keep_screenshots };
+        let plan = beam_core::uninstall::plan(&config, &options)?;
+        if !json {
+            println!(
+                "{} files ({} MB) in {} will be deleted",
+                plan.files,
+                plan.bytes / (1024 * 1024),
+                plan.game_directory.display()
+            );
+            for kept in &plan.kept {
+                println!("Keeping {}", kept);
+            }
+        }
+        if !yes {
+            let result = serde_json::json!({ "plan": plan, "removed": null });
+            let message = "Nothing was deleted; run again with --yes to uninstall".to_string();
+            return Err(output::fail(args.format, command, &result, message));
+        }
+        
+        let report = beam_core::uninstall::uninstall(&mut config, std::path::Path::new(&args.config), &options)?;
+        if json {
+            let result = serde_json::json!({ "plan": plan, "removed": report });
+            return print_json(command, Some(&result), None);
+        }
+        tracing::info!(
+            "Removed {} files, {} registry values, {} shortcuts and {} URL handlers",
+            report.files_removed,
+            report.registry_values_removed,
+            report.shortcuts_removed,
+            report.protocol_handlers_removed
+        );
+        return Ok(());
+    }
+    
+    if let Some(Command::Patch { patch, output }) = &args.command {
+        return run_headless(config, patch.clone(), false, output.clone().or(args.output), args.format).await;
+    }
+    
+    if let Some(Command::Verify { output }) = &args.command {
+        return run_headless(config, None, true, output.clone().or(args.output), args.format).await;
+    }
+    
+    if let Some(Command::PackageClient { out, split_size, exclude, no_default_excludes, torrent }) = args.command {
+        let game_dir = config.app.game_directory.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Game directory not set in {}", args.config))?;
+        
+        let mut options = beam_core::PackageOptions::new(&out);
+        if no_default_excludes {
+            options.excludes.clear();
+        }
+        options.excludes.extend(exclude);
+        options.split_size = split_size.map(|mb| mb * 1024 * 1024);
+        if torrent {
+            options.torrent = Some(config.packaging.clone().unwrap_or_default().to_torrent_options());
+        }
+        
+        let report = beam_core::ClientPackager::new(game_dir, options).package()?;
+        if json {
+            return print_json(command, Some(&report), None);
+        }
+        tracing::info!(
+            "Packaged {} files ({} excluded) into {} part(s); manifest: {}, checksums: {}",
+            report.files_packaged,
+            report.files_excluded,
+            report.parts.len(),
+            report.manifest_path.display(),
+            report.checksums_path.display()
+        );
+        if let Some(torrent) = &report.torrent {
+            tracing::info!("Torrent: {} ({})", torrent.path.display(), torrent.magnet_link);
+        }
+        return Ok(());
+    }
+    
+    if args.headless || !cfg!(feature = "ui") {
+        run_headless(config, args.manual_patch, args.verify, args.output, args.format).await?;
     } else {
+        #[cfg(feature = "ui")]
         beam_ui::run_ui(config)?;
     }
     
     Ok(())
 }
 
-async fn run_headless(config: Config, manual_patch: Option<String>) -> Result<()> {
-    let patcher = beam_core::Patcher::new(config)?;
+fn parse_grf_version(value: &str) -> std::result::Result<u32, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).map_err(|_| format!("invalid GRF version: {}", value))
+}
+
+fn parse_conflict_policy(value: &str) -> std::result::Result<beam_core::config::ConflictPolicy, String> {
+    use beam_core::config::ConflictPolicy;
+    match value {
+        "ask" => Ok(ConflictPolicy::Ask),
+        "keep-mine" => Ok(ConflictPolicy::KeepMine),
+        "take-theirs" => Ok(ConflictPolicy::TakeTheirs),
+        _ => Err(format!("expected ask, keep-mine or take-theirs, got {}", value)),
+    }
+}
+
+async fn run_headless(
+    config: Config,
+    manual_patch: Option<String>,
+    verify: bool,
+    output: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let kind = match (&manual_patch, verify) {
+        (_, true) => RunKind::Verify,
+        (Some(_), false) => RunKind::ManualPatch,
+        (None, false) => RunKind::Patch,
+    };
+    let mut report = RunReport::start(kind, &config);
+    let patcher = beam_core::Patcher::new(config.clone())?;
     
-    if let Some(patch_path) = manual_patch {
-        tracing::info!("Applying manual patch: {}", patch_path);
-        patcher.manual_patch(std::path::Path::new(&patch_path)).await?;
-    } else {
-        tracing::info!("Starting full patch process");
-        patcher.run_full_patch().await?;
+    // The first signal cancels at the next safe point: GRF saves finish or roll back and the resume state is
+    // already on disk. A second one exits immediately.
+    let cancel = patcher.cancel_flag();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::warn!("Shutdown requested, stopping after the current step (signal again to force quit)");
+        cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        
+        shutdown_signal().await;
+        tracing::warn!("Forced shutdown");
+        std::process::exit(130);
+    });
+    
+    let result = match (kind, manual_patch) {
+        (RunKind::Verify, _) => {
+            tracing::info!("Verifying game files");
+            match beam_core::Verifier::from_config(config.clone()) {
+                Ok(verifier) => verifier.verify_game_files().await.and_then(|result| {
+                    beam_core::integrity::record_verification(&config, &result)?;
+                    report.verification = Some(result);
+                    Ok(())
+                }),
+                Err(e) => Err(e),
+            }
+        }
+        (_, Some(patch_path)) => {
+            tracing::info!("Applying manual patch: {}", patch_path);
+            patcher.manual_patch(std::path::Path::new(&patch_path)).await.map(|result| report.patch = Some(result))
+        }
+        (_, None) => {
+            tracing::info!("Starting full patch process");
+            let mut patch_report = beam_core::PatchReport::default();
+            let result = patcher.run_full_patch_into(&mut patch_report).await;
+            report.patch = Some(patch_report);
+            result
+        }
+    };
+    
+    if let Err(e) = &result {
+        report.record_error(e);
+    }
+    report.finish(&patcher.patch_history());
+    if let Some(path) = &output {
+        report.save(path)?;
+        tracing::info!("Wrote run report to {}", path);
     }
     
-    tracing::info!("Patching completed successfully");
+    if format == OutputFormat::Json {
+        let failure = match (&result, &report.verification) {
+            (Err(e), _) => Some(e.to_string()),
+            (Ok(()), Some(verification)) if !report.success => Some(format!(
+                "{} corrupted and {} missing files",
+                verification.corrupted.len(),
+                verification.missing.len()
+            )),
+            _ => None,
+        };
+        return match failure {
+            Some(message) => Err(output::fail(format, "run", &report, message)),
+            None => print_json("run", Some(&report), None),
+        };
+    }
+    result?;
+    
+    if let Some(verification) = &report.verification {
+        if !report.success {
+            anyhow::bail!(
+                "{} corrupted and {} missing files",
+                verification.corrupted.len(),
+                verification.missing.len()
+            );
+        }
+        tracing::info!("All {} files verified", verification.verified);
+    } else {
+        tracing::info!("Patching completed successfully");
+    }
     Ok(())
 }
+
+#[cfg(unix)]
+async fn shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = sigterm.recv() => {}
+            }
+        }
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,440 @@
+use anyhow::{Context, Result};
+use beam_formats::beam::BeamArchive;
+use beam_formats::gpf::Gpf;
+use beam_formats::grf::Grf;
+use beam_formats::limits::{self, DecompressionLimits};
+use beam_formats::vfs::{Mount, Vfs, VfsMetadata};
+use std::path::Path;
+
+// Archives are layered like the client reads them: the first one given wins over the ones after it.
+// Serves until Ctrl+C or SIGTERM, then unmounts.
+pub async fn run(archives: &[String], mountpoint: &Path) -> Result<()> {
+    let mut mount = Mount::new();
+    for archive in archives {
+        mount.add_layer(open_layer(archive).with_context(|| format!("Failed to open {}", archive))?);
+    }
+    
+    // The per-archive total guards a single extraction pass; a mount reads the same entries over and over for as
+    // long as it stays up, so only the per-entry and ratio limits apply
+    limits::set_decompression_limits(DecompressionLimits { max_total_size: u64::MAX, ..limits::decompression_limits() });
+    
+    tracing::info!("Mounting {} files at {} (read-only), Ctrl+C to unmount", mount.file_count(), mountpoint.display());
+    imp::run(mount, mountpoint).await?;
+    tracing::info!("Unmounted {}", mountpoint.display());
+    Ok(())
+}
+
+fn open_layer(archive: &str) -> Result<Box<dyn Vfs>> {
+    let extension = Path::new(archive)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+    Ok(match extension.as_str() {
+        "gpf" => Box::new(Gpf::open(archive)?),
+        "beam" => Box::new(BeamArchive::open(archive)?),
+        _ => Box::new(Grf::open(archive)?),
+    })
+}
+
+fn join(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::*;
+    use fuser::{
+        BackgroundSession, FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory,
+        ReplyEmpty, ReplyEntry, ReplyOpen, Request,
+    };
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+    use std::time::{Duration, SystemTime};
+    
+    // Nothing can change the archives through the mount, so the kernel may keep what it looked up
+    const TTL: Duration = Duration::from_secs(3600);
+    const ROOT_INODE: u64 = 1;
+    const BLOCK_SIZE: u32 = 4096;
+    
+    struct MountFs {
+        mount: Mount,
+        // Inode n is paths[n - 1]; every path gets its inode the first time it is looked up or listed
+        paths: Vec<String>,
+        inodes: HashMap<String, u64>,
+        // Compressed entries cannot be read from an offset, so a file is read whole when opened
+        handles: HashMap<u64, Vec<u8>>,
+        next_handle: u64,
+        mounted_at: SystemTime,
+        uid: u32,
+        gid: u32,
+    }
+    
+    impl MountFs {
+        fn new(mount: Mount) -> Self {
+            MountFs {
+                mount,
+                paths: vec![String::new()],
+                inodes: HashMap::from([(String::new(), ROOT_INODE)]),
+                handles: HashMap::new(),
+                next_handle: 1,
+                mounted_at: SystemTime::now(),
+                // SAFETY: getuid and getgid cannot fail and touch no memory
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
+            }
+        }
+        
+        fn path(&self, inode: u64) -> Option<&str> {
+            self.paths.get(inode.checked_sub(1)? as usize).map(String::as_str)
+        }
+        
+        // Names differing only in case are the same file to the client, so they share an inode
+        fn inode(&mut self, path: &str) -> u64 {
+            let key = beam_formats::path::name_key(path);
+            if let Some(inode) = self.inodes.get(&key) {
+                return *inode;
+            }
+            self.paths.push(path.to_string());
+            let inode = self.paths.len() as u64;
+            self.inodes.insert(key, inode);
+            inode
+        }
+        
+        fn attr(&self, inode: u64, metadata: VfsMetadata) -> FileAttr {
+            FileAttr {
+                ino: inode,
+                size: metadata.size,
+                blocks: metadata.size.div_ceil(512),
+                atime: self.mounted_at,
+                mtime: self.mounted_at,
+                ctime: self.mounted_at,
+                crtime: self.mounted_at,
+                kind: if metadata.is_dir { FileType::Directory } else { FileType::RegularFile },
+                perm: if metadata.is_dir { 0o555 } else { 0o444 },
+                nlink: if metadata.is_dir { 2 } else { 1 },
+                uid: self.uid,
+                gid: self.gid,
+                rdev: 0,
+                blksize: BLOCK_SIZE,
+                flags: 0,
+            }
+        }
+    }
+    
+    impl Filesystem for MountFs {
+        fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+            let (Some(parent), Some(name)) = (self.path(parent), name.to_str()) else {
+                return reply.error(libc::ENOENT);
+            };
+            let path = join(parent, name);
+            match self.mount.stat(&path) {
+                Ok(metadata) => {
+                    let inode = self.inode(&path);
+                    reply.entry(&TTL, &self.attr(inode, metadata), 0);
+                }
+                Err(_) => reply.error(libc::ENOENT),
+            }
+        }
+        
+        fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+            match self.path(ino).and_then(|path| self.mount.stat(path).ok()) {
+                Some(metadata) => reply.attr(&TTL, &self.attr(ino, metadata)),
+                None => reply.error(libc::ENOENT),
+            }
+        }
+        
+        fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+            if flags & libc::O_ACCMODE != libc::O_RDONLY {
+                return reply.error(libc::EROFS);
+            }
+            let Some(path) = self.path(ino) else {
+                return reply.error(libc::ENOENT);
+            };
+            match self.mount.read(path) {
+                Ok(data) => {
+                    let handle = self.next_handle;
+                    self.next_handle += 1;
+                    self.handles.insert(handle, data);
+                    reply.opened(handle, 0);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to read {}: {}", path, e);
+                    reply.error(libc::EIO);
+                }
+            }
+        }
+        
+        fn read(
+            &mut self,
+            _req: &Request<'_>,
+            _ino: u64,
+            fh: u64,
+            offset: i64,
+            size: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            reply: ReplyData,
+        ) {
+            let Some(data) = self.handles.get(&fh) else {
+                return reply.error(libc::EBADF);
+            };
+            let start = usize::try_from(offset).unwrap_or(0).min(data.len());
+            let end = start.saturating_add(size as usize).min(data.len());
+            reply.data(&data[start..end]);
+        }
+        
+        fn release(
+            &mut self,
+            _req: &Request<'_>,
+            _ino: u64,
+            fh: u64,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            _flush: bool,
+            reply: ReplyEmpty,
+        ) {
+            self.handles.remove(&fh);
+            reply.ok();
+        }
+        
+        fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+            let Some(path) = self.path(ino).map(str::to_string) else {
+                return reply.error(libc::ENOENT);
+            };
+            let Ok(children) = self.mount.read_dir(&path) else {
+                return reply.error(libc::ENOTDIR);
+            };
+            
+            let parent = path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
+            let mut entries = vec![
+                (ino, FileType::Directory, ".".to_string()),
+                (self.inode(parent), FileType::Directory, "..".to_string()),
+            ];
+            for child in children {
+                let kind = if child.metadata.is_dir { FileType::Directory } else { FileType::RegularFile };
+                entries.push((self.inode(&join(&path, &child.name)), kind, child.name));
+            }
+            
+            for (index, (inode, kind, name)) in entries.into_iter().enumerate().skip(offset.max(0) as usize) {
+                if reply.add(inode, index as i64 + 1, kind, name) {
+                    break;
+                }
+            }
+            reply.ok();
+        }
+    }
+    
+    pub(super) async fn run(mount: Mount, mountpoint: &Path) -> Result<()> {
+        let options = [MountOption::RO, MountOption::FSName("beam".to_string()), MountOption::DefaultPermissions];
+        let session: BackgroundSession = fuser::spawn_mount2(MountFs::new(mount), mountpoint, &options)
+            .with_context(|| format!("Failed to mount at {} (is FUSE installed?)", mountpoint.display()))?;
+        crate::shutdown_signal().await;
+        // Dropping the session unmounts
+        drop(session);
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+    use dokan::{
+        CreateFileInfo, FileInfo, FileSystemHandler, FileSystemMounter, FillDataError, FillDataResult, FindData,
+        MountFlags, MountOptions, OperationInfo, OperationResult, VolumeInfo, IO_SECURITY_CONTEXT,
+    };
+    use std::sync::OnceLock;
+    use std::time::SystemTime;
+    use widestring::{U16CStr, U16CString};
+    
+    // CreateDisposition values that only open what exists
+    const FILE_OPEN: u32 = 1;
+    const FILE_OPEN_IF: u32 = 3;
+    
+    // Any of these in the requested access is a write
+    const WRITE_ACCESS: u32 = 0x0000_0002 // FILE_WRITE_DATA
+        | 0x0000_0004 // FILE_APPEND_DATA
+        | 0x0000_0010 // FILE_WRITE_EA
+        | 0x0000_0100 // FILE_WRITE_ATTRIBUTES
+        | 0x0001_0000 // DELETE
+        | 0x1000_0000 // GENERIC_ALL
+        | 0x4000_0000; // GENERIC_WRITE
+    
+    const FILE_ATTRIBUTE_READONLY: u32 = 0x0000_0001;
+    const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x0000_0010;
+    const FILE_CASE_PRESERVED_NAMES: u32 = 0x0000_0002;
+    const FILE_UNICODE_ON_DISK: u32 = 0x0000_0004;
+    const FILE_READ_ONLY_VOLUME: u32 = 0x0008_0000;
+    
+    const STATUS_BUFFER_OVERFLOW: i32 = 0x8000_0005_u32 as i32;
+    const STATUS_OBJECT_NAME_NOT_FOUND: i32 = 0xC000_0034_u32 as i32;
+    const STATUS_MEDIA_WRITE_PROTECTED: i32 = 0xC000_00A2_u32 as i32;
+    const STATUS_NOT_A_DIRECTORY: i32 = 0xC000_0103_u32 as i32;
+    const STATUS_UNEXPECTED_IO_ERROR: i32 = 0xC000_00E9_u32 as i32;
+    
+    struct MountFs {
+        mount: Mount,
+        mounted_at: SystemTime,
+    }
+    
+    struct Handle {
+        path: String,
+        metadata: VfsMetadata,
+        // Compressed entries cannot be read from an offset, so a file is read whole on its first read
+        data: OnceLock<Option<Vec<u8>>>,
+    }
+    
+    // Dokan passes names as `\dir\file`; the root is `\`
+    fn vfs_path(file_name: &U16CStr) -> String {
+        file_name.to_string_lossy().trim_start_matches('\\').replace('\\', "/")
+    }
+    
+    fn attributes(metadata: VfsMetadata) -> u32 {
+        if metadata.is_dir {
+            FILE_ATTRIBUTE_DIRECTORY | FILE_ATTRIBUTE_READONLY
+        } else {
+            FILE_ATTRIBUTE_READONLY
+        }
+    }
+    
+    impl<'c, 'h: 'c> FileSystemHandler<'c, 'h> for MountFs {
+        type Context = Handle;
+        
+        fn create_file(
+            &'h self,
+            file_name: &U16CStr,
+            _security_context: &IO_SECURITY_CONTEXT,
+            desired_access: u32,
+            _file_attributes: u32,
+            _share_access: u32,
+            create_disposition: u32,
+            _create_options: u32,
+            _info: &mut OperationInfo<'c, 'h, Self>,
+        ) -> OperationResult<CreateFileInfo<Self::Context>> {
+            let path = vfs_path(file_name);
+            let metadata = self.mount.stat(&path).map_err(|_| STATUS_OBJECT_NAME_NOT_FOUND)?;
+            if desired_access & WRITE_ACCESS != 0 || (create_disposition != FILE_OPEN && create_disposition != FILE_OPEN_IF) {
+                return Err(STATUS_MEDIA_WRITE_PROTECTED);
+            }
+            Ok(CreateFileInfo {
+                context: Handle { path, metadata, data: OnceLock::new() },
+                is_dir: metadata.is_dir,
+                new_file_created: false,
+            })
+        }
+        
+        fn read_file(
+            &'h self,
+            _file_name: &U16CStr,
+            offset: i64,
+            buffer: &mut [u8],
+            _info: &OperationInfo<'c, 'h, Self>,
+            context: &'c Self::Context,
+        ) -> OperationResult<u32> {
+            let data = context.data.get_or_init(|| match self.mount.read(&context.path) {
+                Ok(data) => Some(data),
+                Err(e) => {
+                    tracing::warn!("Failed to read {}: {}", context.path, e);
+                    None
+                }
+            });
+            let data = data.as_deref().ok_or(STATUS_UNEXPECTED_IO_ERROR)?;
+            let start = usize::try_from(offset).unwrap_or(0).min(data.len());
+            let len = buffer.len().min(data.len() - start);
+            buffer[..len].copy_from_slice(&data[start..start + len]);
+            Ok(len as u32)
+        }
+        
+        fn get_file_information(
+            &'h self,
+            _file_name: &U16CStr,
+            _info: &OperationInfo<'c, 'h, Self>,
+            context: &'c Self::Context,
+        ) -> OperationResult<FileInfo> {
+            Ok(FileInfo {
+                attributes: attributes(context.metadata),
+                creation_time: self.mounted_at,
+                last_access_time: self.mounted_at,
+                last_write_time: self.mounted_at,
+                file_size: context.metadata.size,
+                number_of_links: 1,
+                file_index: 0,
+            })
+        }
+        
+        fn find_files(
+            &'h self,
+            _file_name: &U16CStr,
+            mut fill_find_data: impl FnMut(&FindData) -> FillDataResult,
+            _info: &OperationInfo<'c, 'h, Self>,
+            context: &'c Self::Context,
+        ) -> OperationResult<()> {
+            let children = self.mount.read_dir(&context.path).map_err(|_| STATUS_NOT_A_DIRECTORY)?;
+            for child in children {
+                let Ok(file_name) = U16CString::from_str(&child.name) else {
+                    continue;
+                };
+                let result = fill_find_data(&FindData {
+                    attributes: attributes(child.metadata),
+                    creation_time: self.mounted_at,
+                    last_access_time: self.mounted_at,
+                    last_write_time: self.mounted_at,
+                    file_size: child.metadata.size,
+                    file_name,
+                });
+                match result {
+                    Ok(()) => {}
+                    // One name Windows cannot show should not hide the rest of the directory
+                    Err(FillDataError::NameTooLong) => {}
+                    Err(FillDataError::BufferFull) => return Err(STATUS_BUFFER_OVERFLOW),
+                }
+            }
+            Ok(())
+        }
+        
+        fn get_volume_information(&'h self, _info: &OperationInfo<'c, 'h, Self>) -> OperationResult<VolumeInfo> {
+            Ok(VolumeInfo {
+                name: U16CString::from_str("Beam").unwrap(),
+                serial_number: 0,
+                max_component_length: 255,
+                fs_flags: FILE_CASE_PRESERVED_NAMES | FILE_UNICODE_ON_DISK | FILE_READ_ONLY_VOLUME,
+                fs_name: U16CString::from_str("BEAMFS").unwrap(),
+            })
+        }
+    }
+    
+    pub(super) async fn run(mount: Mount, mountpoint: &Path) -> Result<()> {
+        let mount_point = U16CString::from_os_str(mountpoint.as_os_str())
+            .with_context(|| format!("Invalid mount point {}", mountpoint.display()))?;
+        
+        // Dokan blocks in the drop of the mounted file system until something unmounts it
+        let (mounted_tx, mounted_rx) = tokio::sync::oneshot::channel();
+        let serve_point = mount_point.clone();
+        let server = tokio::task::spawn_blocking(move || {
+            let handler = MountFs { mount, mounted_at: SystemTime::now() };
+            let options = MountOptions { flags: MountFlags::WRITE_PROTECT, ..Default::default() };
+            dokan::init();
+            let mut mounter = FileSystemMounter::new(&handler, &serve_point, &options);
+            match mounter.mount() {
+                Ok(file_system) => {
+                    let _ = mounted_tx.send(Ok(()));
+                    drop(file_system);
+                }
+                Err(e) => {
+                    let _ = mounted_tx.send(Err(anyhow::anyhow!("Failed to mount (is Dokan installed?): {}", e)));
+                }
+            }
+            dokan::shutdown();
+        });
+        
+        mounted_rx.await??;
+        crate::shutdown_signal().await;
+        dokan::unmount(&mount_point);
+        server.await?;
+        Ok(())
+    }
+}
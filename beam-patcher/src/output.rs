@@ -0,0 +1,56 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+// Raised only when a field is removed or changes meaning; new fields can appear in any version
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+// The single document a command prints to stdout with `--format json`; logs go to stderr meanwhile
+#[derive(Serialize)]
+struct Document<'a, T> {
+    schema_version: u32,
+    command: &'a str,
+    ok: bool,
+    result: Option<&'a T>,
+    error: Option<&'a str>,
+}
+
+pub fn print_json<T: Serialize>(command: &str, result: Option<&T>, error: Option<&str>) -> anyhow::Result<()> {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        command,
+        ok: error.is_none(),
+        result,
+        error,
+    };
+    println!("{}", serde_json::to_string_pretty(&document)?);
+    Ok(())
+}
+
+// A failure already printed as a JSON document, so `main` only sets the exit code
+#[derive(Debug)]
+pub struct Reported;
+
+impl std::fmt::Display for Reported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("failure reported in the JSON output")
+    }
+}
+
+impl std::error::Error for Reported {}
+
+// A command that failed but still has a result to show, such as a diagnosis with unreachable endpoints
+pub fn fail<T: Serialize>(format: OutputFormat, command: &str, result: &T, message: String) -> anyhow::Error {
+    if format == OutputFormat::Text {
+        return anyhow::anyhow!(message);
+    }
+    match print_json(command, Some(result), Some(&message)) {
+        Ok(()) => anyhow::Error::new(Reported),
+        Err(e) => e,
+    }
+}
@@ -0,0 +1,269 @@
+// Python module `beam` for build pipelines that make patches: BEAM and THOR builders, GRF reading and diffing, and
+// patch manifest entries, all with the launcher's own code. Errors are raised as `beam.BeamError`.
+
+use beam_formats::beam::BeamArchive;
+use beam_formats::content_index::ContentIndex;
+use beam_formats::grf::Grf;
+use beam_formats::thor::{ThorBuilder, ThorTarget};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use sha2::{Digest, Sha256};
+use std::fmt::Display;
+use std::io::Read;
+use std::path::PathBuf;
+
+create_exception!(beam, BeamError, PyException);
+
+fn error(e: impl Display) -> PyErr {
+    BeamError::new_err(e.to_string())
+}
+
+/// A BEAM patch being built. Entries are written by `save`.
+#[pyclass(name = "BeamBuilder", module = "beam")]
+struct PyBeamBuilder {
+    archive: BeamArchive,
+}
+
+#[pymethods]
+impl PyBeamBuilder {
+    #[new]
+    fn new() -> Self {
+        PyBeamBuilder { archive: BeamArchive::new() }
+    }
+    
+    /// Adds or replaces `name` (e.g. `data\texture\a.bmp`) with `data`.
+    fn add_file(&mut self, name: &str, data: &[u8]) -> PyResult<()> {
+        self.archive.add_file(name, data).map_err(error)
+    }
+    
+    /// Adds the file at `path` as `name`.
+    fn add_path(&mut self, path: PathBuf, name: &str) -> PyResult<()> {
+        self.archive.add_file_from_path(path, name).map_err(error)
+    }
+    
+    /// Entry names, sorted.
+    fn files(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.archive.list_files().into_iter().map(str::to_string).collect();
+        names.sort_unstable();
+        names
+    }
+    
+    fn save(&mut self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+        let archive = &mut self.archive;
+        py.detach(|| archive.save(path)).map_err(error)
+    }
+    
+    fn __len__(&self) -> usize {
+        self.archive.list_files().len()
+    }
+}
+
+/// A THOR patch being built. Entries are applied in the order they are added.
+#[pyclass(name = "ThorBuilder", module = "beam")]
+struct PyThorBuilder {
+    builder: ThorBuilder,
+    len: usize,
+}
+
+#[pymethods]
+impl PyThorBuilder {
+    /// Without `target_grf` or `root` the patch goes into the patcher's `target_grf`; `root` extracts it into the
+    /// game directory. `compression` is the gzip level, 0 to 9.
+    #[new]
+    #[pyo3(signature = (*, target_grf=None, root=false, compression=6))]
+    fn new(target_grf: Option<&str>, root: bool, compression: u32) -> PyResult<Self> {
+        let target = match (target_grf, root) {
+            (Some(_), true) => return Err(error("A THOR patch has either a target GRF or root, not both")),
+            (Some(grf), false) => ThorTarget::Grf(grf.to_string()),
+            (None, true) => ThorTarget::Root,
+            (None, false) => ThorTarget::DefaultGrf,
+        };
+        let builder = ThorBuilder::new().with_target(target).map_err(error)?.with_compression(compression);
+        Ok(PyThorBuilder { builder, len: 0 })
+    }
+    
+    fn add_file(&mut self, name: &str, data: &[u8]) -> PyResult<()> {
+        self.builder.add_file(name, data).map_err(error)?;
+        self.len += 1;
+        Ok(())
+    }
+    
+    fn add_path(&mut self, path: PathBuf, name: &str) -> PyResult<()> {
+        self.builder.add_file_from_path(path, name).map_err(error)?;
+        self.len += 1;
+        Ok(())
+    }
+    
+    /// Deletes `name` from the target when the patch is applied.
+    fn remove_file(&mut self, name: &str) -> PyResult<()> {
+        self.builder.remove_file(name).map_err(error)?;
+        self.len += 1;
+        Ok(())
+    }
+    
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let data = py.detach(|| self.builder.to_bytes()).map_err(error)?;
+        Ok(PyBytes::new(py, &data))
+    }
+    
+    fn save(&self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+        py.detach(|| self.builder.save(path)).map_err(error)
+    }
+    
+    fn __len__(&self) -> usize {
+        self.len
+    }
+}
+
+/// A GRF or GPF opened for reading.
+#[pyclass(name = "Grf", module = "beam")]
+struct PyGrf {
+    grf: Grf,
+}
+
+#[pymethods]
+impl PyGrf {
+    #[new]
+    fn open(path: PathBuf) -> PyResult<Self> {
+        Ok(PyGrf { grf: Grf::open(path).map_err(error)? })
+    }
+    
+    /// Entry names as stored, sorted.
+    fn files(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.grf.list_files().into_iter().map(str::to_string).collect();
+        names.sort_unstable();
+        names
+    }
+    
+    /// Uncompressed content of `name`, matched in any case and with either separator.
+    fn read<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyBytes>> {
+        let data = py.detach(|| self.grf.extract_file(name)).map_err(error)?;
+        Ok(PyBytes::new(py, &data))
+    }
+    
+    #[getter]
+    fn version(&self) -> u32 {
+        self.grf.version
+    }
+    
+    fn __len__(&self) -> usize {
+        self.grf.file_count()
+    }
+}
+
+/// Files added, removed and changed from `old` to `new`, compared by content, as a dict of lists plus the number
+/// of unchanged files. Names are lowercase with `/`, as the client compares them.
+#[pyfunction]
+fn diff_grf<'py>(py: Python<'py>, old: PathBuf, new: PathBuf) -> PyResult<Bound<'py, PyDict>> {
+    let diff = py
+        .detach(|| -> beam_formats::Result<_> {
+            let old = ContentIndex::build(&Grf::open(old)?)?;
+            let new = ContentIndex::build(&Grf::open(new)?)?;
+            Ok(old.diff(&new))
+        })
+        .map_err(error)?;
+    
+    let dict = PyDict::new(py);
+    dict.set_item("added", diff.added)?;
+    dict.set_item("removed", diff.removed)?;
+    dict.set_item("changed", diff.changed)?;
+    dict.set_item("unchanged", diff.unchanged)?;
+    Ok(dict)
+}
+
+/// One patch of a JSON manifest, with the SHA-256 and size of the file at `path`. Only the given fields are set;
+/// `filename` defaults to the file's name.
+#[pyfunction]
+#[pyo3(signature = (
+    path, *, filename=None, index=None, channel=None, target=None, extract=false, optional=false,
+    description=None, min_launcher_version=None
+))]
+#[allow(clippy::too_many_arguments)]
+fn manifest_entry<'py>(
+    py: Python<'py>,
+    path: PathBuf,
+    filename: Option<String>,
+    index: Option<u64>,
+    channel: Option<String>,
+    target: Option<String>,
+    extract: bool,
+    optional: bool,
+    description: Option<String>,
+    min_launcher_version: Option<String>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let filename = match filename {
+        Some(filename) => filename,
+        None => path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| error(format!("{} has no file name", path.display())))?,
+    };
+    let (checksum, size) = py.detach(|| sha256_file(&path)).map_err(error)?;
+    
+    let entry = PyDict::new(py);
+    if let Some(index) = index {
+        entry.set_item("index", index)?;
+    }
+    entry.set_item("filename", filename)?;
+    entry.set_item("sha256", checksum)?;
+    entry.set_item("size", size)?;
+    for (key, value) in [
+        ("channel", channel),
+        ("target", target),
+        ("description", description),
+        ("min_launcher_version", min_launcher_version),
+    ] {
+        if let Some(value) = value {
+            entry.set_item(key, value)?;
+        }
+    }
+    if extract {
+        entry.set_item("extract", true)?;
+    }
+    if optional {
+        entry.set_item("optional", true)?;
+    }
+    Ok(entry)
+}
+
+fn sha256_file(path: &std::path::Path) -> std::io::Result<(String, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// Writes `{"patches": [...]}` to `output`, in application order, after checking it the way the launcher reads
+/// manifests.
+#[pyfunction]
+fn write_manifest(py: Python<'_>, patches: &Bound<'_, PyAny>, output: PathBuf) -> PyResult<()> {
+    let patches: String = py.import("json")?.call_method1("dumps", (patches,))?.extract()?;
+    let patches: serde_json::Value = serde_json::from_str(&patches).map_err(error)?;
+    let manifest = serde_json::to_string_pretty(&serde_json::json!({ "patches": patches })).map_err(error)?;
+    beam_core::downloader::parse_patch_manifest(&manifest)
+        .map_err(|e| error(format!("The launcher would reject this manifest: {}", e)))?;
+    std::fs::write(output, manifest + "\n").map_err(error)
+}
+
+#[pymodule]
+fn beam(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("BeamError", m.py().get_type::<BeamError>())?;
+    m.add_class::<PyBeamBuilder>()?;
+    m.add_class::<PyThorBuilder>()?;
+    m.add_class::<PyGrf>()?;
+    m.add_function(wrap_pyfunction!(diff_grf, m)?)?;
+    m.add_function(wrap_pyfunction!(manifest_entry, m)?)?;
+    m.add_function(wrap_pyfunction!(write_manifest, m)?)?;
+    Ok(())
+}
@@ -1,31 +1,103 @@
-use beam_core::{Config, Patcher, Verifier, VerificationResult, GameSettings, GameSettingsManager, ServerChecker, ServerStatusResult, ClientChecker, ClientStatusResult};
-use serde::{Deserialize, Serialize};
-use tauri::{State, AppHandle};
+use beam_core::{Config, Patcher, Verifier, VerificationResult, GameSettings, GameSettingsManager, ServerStatusResult, ClientChecker, ClientDiagnostics, ClientStatusResult, MigrationMode, MigrationReport, NewsItem, EventCalendar, EventNotice, EventStatus, PatchHistoryEntry, PatchInfo, PatchRunSnapshot, ProgressEvent, QueueItem, Character, CharacterSelection, LoginStep, MfaChallenge, SsoSession, PlayTimeStatus, SettingsWarning, SystemInfo, WrapperManager, WrapperStatus, DependencyReport, ClientInfo, ConnectionReport, Installer, PendingInstall, UninstallOptions, UninstallPlan, UninstallReport, StorageUsage, PopulationHistory, PopulationSample, ConflictResolution, FileConflict, IntegrityState, IntegrityStatus, PreflightReport};
+use beam_core::sso::{TokenResponse, EXPIRY_WARNING_SECS};
+use beam_core::{characters, client_info, dependencies, events, install_id, integrity, play_time};
+use beam_core::recovery::RecoverySuggestion;
+use beam_core::screenshots::Screenshot;
+use beam_formats::lua::LuaForm;
+use serde::Serialize;
+use tauri::{State, AppHandle, Manager};
 use crate::{AppState, PatchProgress};
+use crate::operations::Operation;
 use std::path::PathBuf;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NewsItem {
-    pub title: String,
-    pub date: String,
-    pub category: String,
-}
+use std::sync::Arc;
+use std::time::Duration;
 
 
 
 #[tauri::command]
-pub async fn start_patching(state: State<'_, AppState>) -> Result<(), String> {
-    let config = state.config.lock().unwrap().clone();
-    let patcher = Patcher::new(config).map_err(|e| e.to_string())?;
+pub async fn start_patching(window: tauri::Window, state: State<'_, AppState>) -> Result<(), String> {
+    let _operation = state.operations.begin(Operation::Patching)?;
+    let config = state.config();
+    let sso_token = state.sso_token();
+    let progress = state.progress.clone();
+    let event_window = window.clone();
+    let patcher = Patcher::new(config)
+        .map_err(coded_error)?
+        .with_progress_reporter(Arc::new(move |event: &ProgressEvent| {
+            progress.lock().unwrap().update(event);
+            let _ = event_window.emit("patch-progress", PatchProgressEvent { message: event.message(), event: event.clone() });
+        }))
+        .with_sso_token(sso_token);
+    *state.cancel_flag.lock().unwrap() = Some(patcher.cancel_flag());
     
-    patcher.run_full_patch().await.map_err(|e| e.to_string())?;
+    let result = patcher.run_full_patch().await;
+    *state.cancel_flag.lock().unwrap() = None;
+    state.progress.lock().unwrap().status = match &result {
+        Ok(_) => "Complete".to_string(),
+        Err(e) => e.to_string(),
+    };
+    
+    if state.close_requested.load(std::sync::atomic::Ordering::Relaxed) {
+        window.close().map_err(|e| e.to_string())?;
+    }
+    result.map_err(coded_error)?;
     
     Ok(())
 }
 
+// Payload of the `patch-progress` event: the core event plus the status line to show for it
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchProgressEvent {
+    pub message: String,
+    #[serde(flatten)]
+    pub event: ProgressEvent,
+}
+
+// Errors from long-running operations are prefixed with `[code]` so the frontend can call get_recovery_actions
+fn coded_error(e: beam_core::Error) -> String {
+    format!("[{}] {}", e.code(), e)
+}
+
+#[tauri::command]
+pub fn get_recovery_actions(error_code: String) -> Vec<RecoverySuggestion> {
+    beam_core::recovery::recovery_actions(&error_code)
+}
+
+#[tauri::command]
+pub fn switch_mirror(state: State<'_, AppState>) -> Result<String, String> {
+    state.update_config(|config| {
+        beam_core::recovery::switch_mirror(&mut config.patcher)
+            .ok_or_else(|| "No other mirror is configured".to_string())
+    })
+}
+
+// Session-only consent, given from the "Patch in place" recovery action
+#[tauri::command]
+pub fn allow_in_place_apply(state: State<'_, AppState>) {
+    state.config.send_modify(|config| config.patcher.allow_in_place_apply = true);
+}
+
+#[tauri::command]
+pub fn relaunch_as_admin(app: AppHandle) -> Result<(), String> {
+    beam_core::recovery::relaunch_as_admin().map_err(|e| e.to_string())?;
+    app.exit(0);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn cancel_patching(state: State<'_, AppState>) -> Result<bool, String> {
+    match state.cancel_flag.lock().unwrap().as_ref() {
+        Some(flag) => {
+            flag.store(true, std::sync::atomic::Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
 #[tauri::command]
 pub async fn check_updates(state: State<'_, AppState>) -> Result<Option<String>, String> {
-    let config = state.config.lock().unwrap().clone();
+    let config = state.config();
     let updater = beam_core::Updater::new(config).map_err(|e| e.to_string())?;
     
     let update_info = updater.check_for_updates().await.map_err(|e| e.to_string())?;
@@ -35,13 +107,14 @@ pub async fn check_updates(state: State<'_, AppState>) -> Result<Option<String>,
 
 #[tauri::command]
 pub async fn perform_update(state: State<'_, AppState>) -> Result<(), String> {
-    let config = state.config.lock().unwrap().clone();
-    let updater = beam_core::Updater::new(config).map_err(|e| e.to_string())?;
+    let _operation = state.operations.begin(Operation::Updating)?;
+    let config = state.config();
+    let updater = beam_core::Updater::new(config).map_err(coded_error)?;
     
-    let update_info = updater.check_for_updates().await.map_err(|e| e.to_string())?;
+    let update_info = updater.check_for_updates().await.map_err(coded_error)?;
     
     if let Some(info) = update_info {
-        updater.perform_update(&info).await.map_err(|e| e.to_string())?;
+        updater.perform_update(&info).await.map_err(coded_error)?;
     }
     
     Ok(())
@@ -49,7 +122,7 @@ pub async fn perform_update(state: State<'_, AppState>) -> Result<(), String> {
 
 #[tauri::command]
 pub async fn get_login_url(state: State<'_, AppState>) -> Result<String, String> {
-    let config = state.config.lock().unwrap().clone();
+    let config = state.config();
     let sso_client = beam_core::SsoClient::new(config).map_err(|e| e.to_string())?;
     
     sso_client.get_login_url().map_err(|e| e.to_string())
@@ -57,28 +130,150 @@ pub async fn get_login_url(state: State<'_, AppState>) -> Result<String, String>
 
 #[tauri::command]
 pub async fn exchange_sso_code(
+    app: AppHandle,
     state: State<'_, AppState>,
     code: String,
 ) -> Result<String, String> {
-    let config = state.config.lock().unwrap().clone();
+    let config = state.config();
     let sso_client = beam_core::SsoClient::new(config).map_err(|e| e.to_string())?;
     
-    let token_response = sso_client
+    let step = sso_client
         .exchange_code_for_token(&code)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(coded_error)?;
     
-    Ok(token_response.access_token)
+    match step {
+        LoginStep::Authenticated(token_response) => {
+            *state.pending_mfa.lock().unwrap() = None;
+            Ok(start_session(app, &state, token_response))
+        }
+        // Not a failure, but the frontend routes on the code just like it does for errors
+        LoginStep::OtpRequired(challenge) => {
+            *state.pending_mfa.lock().unwrap() = Some(challenge);
+            Err("[mfa_required] Enter the code from your authenticator app".to_string())
+        }
+    }
+}
+
+// Includes the authenticator secret to set up when the account has none yet
+#[tauri::command]
+pub fn get_mfa_challenge(state: State<'_, AppState>) -> Option<MfaChallenge> {
+    state.pending_mfa.lock().unwrap().clone()
+}
+
+#[tauri::command]
+pub async fn submit_otp(app: AppHandle, state: State<'_, AppState>, otp: String) -> Result<String, String> {
+    let config = state.config();
+    let challenge = state.pending_mfa.lock().unwrap().clone()
+        .ok_or_else(|| "No login is waiting for a code; log in again".to_string())?;
+    let sso_client = beam_core::SsoClient::new(config).map_err(|e| e.to_string())?;
+    
+    // A wrong code keeps the challenge, so the player can try the next one
+    let token_response = sso_client.submit_otp(&challenge, &otp).await.map_err(coded_error)?;
+    
+    *state.pending_mfa.lock().unwrap() = None;
+    Ok(start_session(app, &state, token_response))
+}
+
+// Payload of the `sso-session-expiring`, `sso-session-refreshed` and `sso-session-expired` events
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStatus {
+    pub seconds_remaining: i64,
+    // A refresh token exists, so the launcher renews the session by itself
+    pub refreshable: bool,
+}
+
+impl From<&SsoSession> for SessionStatus {
+    fn from(session: &SsoSession) -> Self {
+        SessionStatus {
+            seconds_remaining: session.seconds_remaining(),
+            refreshable: session.can_refresh(),
+        }
+    }
+}
+
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+fn start_session(app: AppHandle, state: &State<'_, AppState>, token_response: TokenResponse) -> String {
+    let session = SsoSession::new(token_response);
+    let access_token = session.access_token.clone();
+    *state.sso_session.lock().unwrap() = Some(session.clone());
+    tauri::async_runtime::spawn(keep_session_alive(app, session));
+    access_token
+}
+
+// Refreshes the session shortly before it runs out and counts the last minutes down for the UI, so a
+// launcher left open for hours does not start the game with a stale token. A newer login ends the loop.
+async fn keep_session_alive(app: AppHandle, session: SsoSession) {
+    let mut issued_at = session.issued_at;
+    loop {
+        tokio::time::sleep(SESSION_CHECK_INTERVAL).await;
+        let state = app.state::<AppState>();
+        let current = state.sso_session.lock().unwrap().clone().filter(|s| s.issued_at == issued_at);
+        let Some(session) = current else {
+            return;
+        };
+        
+        if session.can_refresh() && session.refresh_due() {
+            let config = state.config();
+            let refreshed = match beam_core::SsoClient::new(config) {
+                Ok(client) => client.refresh(&session).await,
+                Err(e) => Err(e),
+            };
+            match refreshed {
+                Ok(refreshed) => {
+                    let mut slot = state.sso_session.lock().unwrap();
+                    if slot.as_ref().map(|s| s.issued_at) != Some(issued_at) {
+                        return;
+                    }
+                    issued_at = refreshed.issued_at;
+                    let _ = app.emit_all("sso-session-refreshed", SessionStatus::from(&refreshed));
+                    *slot = Some(refreshed);
+                    continue;
+                }
+                // Tried again on the next check until the token runs out
+                Err(e) => tracing::warn!("Session refresh failed: {}", e),
+            }
+        }
+        
+        if session.is_expired() {
+            *state.sso_session.lock().unwrap() = None;
+            let _ = app.emit_all("sso-session-expired", SessionStatus::from(&session));
+            return;
+        }
+        if session.seconds_remaining() <= EXPIRY_WARNING_SECS {
+            let _ = app.emit_all("sso-session-expiring", SessionStatus::from(&session));
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_session_status(state: State<'_, AppState>) -> Option<SessionStatus> {
+    state.sso_session.lock().unwrap().as_ref().map(SessionStatus::from)
 }
 
 #[tauri::command]
 pub async fn launch_game(
     state: State<'_, AppState>,
     _token: String,
+    // Set once the player has confirmed the `event_warning` of a running event such as maintenance
+    ignore_event_warnings: Option<bool>,
 ) -> Result<(), String> {
-    let config = state.config.lock().unwrap().clone();
+    // Only until the client is started; patching while the game runs is left to the player
+    let _operation = state.operations.begin(Operation::Launching)?;
+    let config = state.config();
+    
+    if let Some(play_time) = &config.play_time {
+        let status = play_time::current_status(&config.http, play_time, state.sso_token().as_deref()).await.map_err(coded_error)?;
+        play_time::check_launch(&status).map_err(coded_error)?;
+    }
+    
+    if !ignore_event_warnings.unwrap_or(false) {
+        let notices = state.event_notices.lock().unwrap().clone();
+        events::check_launch(&notices).map_err(coded_error)?;
+    }
     
-    let game_dir = config.app.game_directory
+    let game_dir = config.app.game_directory.clone()
         .ok_or("Game directory not set. Please select game folder first.")?;
     
     let client_exe = PathBuf::from(&game_dir).join(&config.app.client_exe);
@@ -90,26 +285,133 @@ pub async fn launch_game(
         ));
     }
     
+    // An unreadable client is left for Windows to complain about; only a clear finding stops the launch
+    match dependencies::check_client(&config) {
+        Ok(report) => report.check_launch().map_err(coded_error)?,
+        Err(e) => tracing::warn!("Skipping dependency check: {}", e),
+    }
+    
+    integrity::check_before_launch(&config).await.map_err(coded_error)?;
+    
+    // A wrapper that cannot be updated right now keeps the copy already installed
+    if config.wrapper.is_some() {
+        if let Err(e) = WrapperManager::from_config(&config).map_err(coded_error)?.ensure().await {
+            tracing::warn!("Graphics wrapper not updated: {}", e);
+        }
+    }
+    
+    let settings_manager = GameSettingsManager::from_config(&config).map_err(|e| e.to_string())?;
+    let launch_arg = config.multi_user().and_then(|m| m.settings_launch_arg.as_deref());
+    let mut extra_args = settings_manager.prepare_launch(launch_arg).map_err(|e| e.to_string())?;
+    if let Some(launch) = &config.launch {
+        let selection = characters::selection_path(&config)
+            .ok()
+            .and_then(CharacterSelection::load)
+            .unwrap_or_default();
+        extra_args.extend(characters::launch_args(launch, &selection).into_iter().map(Into::into));
+    }
+    
     #[cfg(target_os = "windows")]
     {
         use std::process::Command;
-        Command::new(&client_exe)
+        let mut child = Command::new(&client_exe)
+            .args(&extra_args)
             .current_dir(&game_dir)
             .spawn()
             .map_err(|e| format!("Failed to launch game: {}", e))?;
+        
+        if let Err(e) = integrity::record_launch(&config) {
+            tracing::warn!("Failed to record the launch: {}", e);
+        }
+        // Exits are only seen while the launcher stays open
+        std::thread::spawn(move || {
+            if let Ok(status) = child.wait() {
+                if let Err(e) = integrity::record_exit(&config, status.success()) {
+                    tracing::warn!("Failed to record the game exit: {}", e);
+                }
+            }
+        });
     }
     
     #[cfg(not(target_os = "windows"))]
     {
+        let _ = extra_args;
         return Err("Game launch is only supported on Windows".to_string());
     }
     
     Ok(())
 }
 
+// Empty when the server offers no character list; the UI then asks for the name instead
+#[tauri::command]
+pub async fn get_characters(state: State<'_, AppState>) -> Result<Vec<Character>, String> {
+    let config = state.config();
+    let Some(launch) = config.launch.filter(|l| l.characters_url.is_some()) else {
+        return Ok(Vec::new());
+    };
+    let token = state.sso_token()
+        .ok_or_else(|| coded_error(beam_core::Error::AuthFailed("Log in to load your characters".to_string())))?;
+    
+    characters::fetch_characters(&config.http, &launch, &token).await.map_err(coded_error)
+}
+
+#[tauri::command]
+pub fn get_last_character(state: State<'_, AppState>) -> Result<Option<CharacterSelection>, String> {
+    let config = state.config();
+    let path = characters::selection_path(&config).map_err(|e| e.to_string())?;
+    
+    Ok(CharacterSelection::load(path).filter(|selection| selection.character.is_some()))
+}
+
+// Remembers the character the next launch continues as. With a character list configured the name
+// has to belong to the logged-in account, and its server and slot come from the list.
+#[tauri::command]
+pub async fn select_character(
+    state: State<'_, AppState>,
+    character: String,
+    server: Option<String>,
+) -> Result<CharacterSelection, String> {
+    let config = state.config();
+    let launch = config.launch.clone()
+        .ok_or_else(|| "Character selection is not configured for this server".to_string())?;
+    
+    let selection = match launch.characters_url {
+        Some(_) => {
+            let token = state.sso_token()
+                .ok_or_else(|| coded_error(beam_core::Error::AuthFailed("Log in to select a character".to_string())))?;
+            let account = characters::fetch_characters(&config.http, &launch, &token).await.map_err(coded_error)?;
+            let found = account
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(&character) && (server.is_none() || c.server == server))
+                .ok_or_else(|| format!("{} is not a character on this account", character))?;
+            CharacterSelection::from_character(found)
+        }
+        None => CharacterSelection {
+            character: Some(character),
+            server,
+            slot: None,
+        },
+    };
+    
+    let path = characters::selection_path(&config).map_err(|e| e.to_string())?;
+    selection.save(path).map_err(|e| e.to_string())?;
+    Ok(selection)
+}
+
+// None when no play-time rules are configured
+#[tauri::command]
+pub async fn get_play_time_status(state: State<'_, AppState>) -> Result<Option<PlayTimeStatus>, String> {
+    let config = state.config();
+    let Some(play_time) = &config.play_time else {
+        return Ok(None);
+    };
+    
+    play_time::current_status(&config.http, play_time, state.sso_token().as_deref()).await.map(Some).map_err(coded_error)
+}
+
 #[tauri::command]
 pub fn get_config(state: State<'_, AppState>) -> Result<Config, String> {
-    let config = state.config.lock().unwrap().clone();
+    let config = state.config();
     Ok(config)
 }
 
@@ -121,47 +423,156 @@ pub fn get_progress(state: State<'_, AppState>) -> Result<PatchProgress, String>
 
 #[tauri::command]
 pub async fn get_news(state: State<'_, AppState>) -> Result<Vec<NewsItem>, String> {
-    let config = state.config.lock().unwrap().clone();
+    let config = state.config();
     
     if let Some(news_url) = config.ui.news_feed_url {
-        let client = reqwest::Client::new();
-        let response = client.get(&news_url)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
-        
-        let news: Vec<NewsItem> = response.json()
-            .await
-            .map_err(|e| e.to_string())?;
-        
-        Ok(news)
+        beam_core::news::fetch_news(&config.http, &news_url).await.map_err(|e| e.to_string())
     } else {
         Ok(vec![])
     }
 }
 
 #[tauri::command]
-pub async fn get_server_status(state: State<'_, AppState>) -> Result<ServerStatusResult, String> {
-    let config = state.config.lock().unwrap().clone();
+pub async fn track_news_click(state: State<'_, AppState>, item: NewsItem) -> Result<(), String> {
+    let config = state.config();
+    
+    if let Some(tracking_url) = config.ui.news_click_tracking_url {
+        beam_core::news::track_click(&config.http, &tracking_url, &item).await.map_err(|e| e.to_string())?;
+    }
     
-    let checker = ServerChecker::new(config);
-    checker.check_servers().await.map_err(|e| e.to_string())
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn verify_game_files(state: State<'_, AppState>) -> Result<VerificationResult, String> {
-    let config = state.config.lock().unwrap().clone();
+pub fn get_patch_history(state: State<'_, AppState>, limit: Option<usize>) -> Result<Vec<PatchHistoryEntry>, String> {
+    let config = state.config();
+    let patcher = Patcher::new(config).map_err(|e| e.to_string())?;
     
-    let manifest_url = format!(
-        "{}/manifest.json",
-        config.patcher.mirrors.first()
-            .map(|m| m.url.as_str())
-            .unwrap_or("https://patch.example.com")
-    );
+    Ok(patcher.patch_history().recent(limit.unwrap_or(20)))
+}
+
+#[tauri::command]
+pub fn get_interrupted_run(state: State<'_, AppState>) -> Result<Option<PatchRunSnapshot>, String> {
+    let config = state.config();
+    let patcher = Patcher::new(config).map_err(|e| e.to_string())?;
     
-    let verifier = Verifier::new(config, manifest_url).map_err(|e| e.to_string())?;
+    Ok(patcher.interrupted_run())
+}
+
+fn queue_patcher(state: &State<'_, AppState>) -> Result<Patcher, String> {
+    let config = state.config();
+    let sso_token = state.sso_token();
+    Ok(Patcher::new(config).map_err(coded_error)?.with_sso_token(sso_token))
+}
+
+#[tauri::command]
+pub async fn get_queue(state: State<'_, AppState>) -> Result<Vec<QueueItem>, String> {
+    queue_patcher(&state)?.queue().await.map_err(coded_error)
+}
+
+// Takes effect on the next patch of a running update, or the next START
+#[tauri::command]
+pub fn retry_queue_item(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    queue_patcher(&state)?.retry_queue_item(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn skip_queue_item(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    queue_patcher(&state)?.skip_queue_item(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_channel_priority(state: State<'_, AppState>, channel: String, priority: i32) -> Result<(), String> {
+    queue_patcher(&state)?.set_channel_priority(&channel, priority).map_err(|e| e.to_string())
+}
+
+// Files the failed patch would have replaced, for the "Review changed files" dialog
+#[tauri::command]
+pub fn get_patch_conflicts(state: State<'_, AppState>) -> Result<Vec<FileConflict>, String> {
+    Ok(queue_patcher(&state)?.conflicts())
+}
+
+// Used when the patch is applied again by the next START
+#[tauri::command]
+pub fn resolve_patch_conflicts(state: State<'_, AppState>, resolutions: Vec<ConflictResolution>) -> Result<(), String> {
+    queue_patcher(&state)?.resolve_conflicts(&resolutions).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_pending_patches(state: State<'_, AppState>) -> Result<Vec<PatchInfo>, String> {
+    let config = state.config();
+    let sso_token = state.sso_token();
+    let patcher = Patcher::new(config).map_err(coded_error)?.with_sso_token(sso_token);
+    
+    patcher.pending_patches().await.map_err(coded_error)
+}
+
+// Checks the next update without applying it; see `Patcher::dry_run`
+#[tauri::command]
+pub async fn dry_run(state: State<'_, AppState>) -> Result<PreflightReport, String> {
+    let config = state.config();
+    let sso_token = state.sso_token();
+    let patcher = Patcher::new(config).map_err(coded_error)?.with_sso_token(sso_token);
+    
+    patcher.dry_run().await.map_err(coded_error)
+}
+
+#[tauri::command]
+pub async fn get_events(state: State<'_, AppState>) -> Result<Vec<EventStatus>, String> {
+    let config = state.config();
     
-    verifier.verify_game_files().await.map_err(|e| e.to_string())
+    if let Some(events_url) = config.ui.events_url {
+        let calendar = EventCalendar::fetch(&config.http, &events_url).await.map_err(|e| e.to_string())?;
+        Ok(calendar.upcoming())
+    } else {
+        Ok(vec![])
+    }
+}
+
+// Banners (with backend-computed countdowns) and launch warnings of the event calendar; changes arrive as
+// `event-notices-changed` events
+#[tauri::command]
+pub fn get_event_notices(state: State<'_, AppState>) -> Vec<EventNotice> {
+    state.event_notices.lock().unwrap().clone()
+}
+
+// What the background poller saw last; None until its first check has finished or without a `server` section.
+// Changes arrive as `server-status-changed` events.
+#[tauri::command]
+pub fn get_server_status(state: State<'_, AppState>) -> Option<ServerStatusResult> {
+    state.server_status.lock().unwrap().clone()
+}
+
+// Player counts sampled by the poller over the last `hours` (default and at most 24), oldest first, for the
+// status sparkline. Empty without `server.player_count_url`.
+#[tauri::command]
+pub fn get_population_history(state: State<'_, AppState>, hours: Option<u32>) -> Vec<PopulationSample> {
+    let config = state.config();
+    PopulationHistory::load(PopulationHistory::path(&config)).recent(hours.unwrap_or(24).clamp(1, 24))
+}
+
+#[tauri::command]
+pub async fn diagnose_connection(state: State<'_, AppState>) -> Result<ConnectionReport, String> {
+    let config = state.config();
+    beam_core::connection_check::diagnose(&config).await.map_err(coded_error)
+}
+
+#[tauri::command]
+pub async fn verify_game_files(state: State<'_, AppState>) -> Result<VerificationResult, String> {
+    let _operation = state.operations.begin(Operation::Verifying)?;
+    let config = state.config();
+    let verifier = Verifier::from_config(config.clone()).map_err(coded_error)?;
+    
+    let result = verifier.verify_game_files().await.map_err(coded_error)?;
+    integrity::record_verification(&config, &result).map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn get_integrity_status(state: State<'_, AppState>) -> Option<IntegrityStatus> {
+    let config = state.config();
+    let path = IntegrityState::path(&config)?;
+    Some(IntegrityState::load(path).status(config.integrity_check.as_ref()))
 }
 
 #[tauri::command]
@@ -169,55 +580,386 @@ pub async fn set_game_directory(
     state: State<'_, AppState>,
     directory: String,
 ) -> Result<(), String> {
-    let mut config = state.config.lock().unwrap();
-    config.app.game_directory = Some(directory.clone());
+    let _operation = state.operations.begin(Operation::ChangingGameDirectory)?;
+    state.update_config(|config| {
+        config.set_game_directory(&directory).map_err(coded_error)?;
+        config.save("config.yml").map_err(|e| e.to_string())
+    })
+}
+
+// None when no full client package is configured or nothing was interrupted
+#[tauri::command]
+pub fn get_pending_install(state: State<'_, AppState>) -> Result<Option<PendingInstall>, String> {
+    let config = state.config();
+    if config.installer.is_none() {
+        return Ok(None);
+    }
+    Ok(Installer::new(config).map_err(coded_error)?.pending())
+}
+
+// Without a directory, continues the install the launcher was closed during
+#[tauri::command]
+pub async fn install_full_client(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    directory: Option<String>,
+) -> Result<String, String> {
+    let _operation = state.operations.begin(Operation::Installing)?;
+    let config = state.config();
+    let installer = Installer::new(config)
+        .map_err(coded_error)?
+        .with_sso_token(state.sso_token())
+        .with_progress_reporter(Arc::new(move |event: &ProgressEvent| {
+            let _ = window.emit("patch-progress", PatchProgressEvent { message: event.message(), event: event.clone() });
+        }));
     
-    config.save("config.yml").map_err(|e| e.to_string())?;
+    let destination = match directory {
+        Some(directory) => directory,
+        None => installer.pending().map(|pending| pending.destination).ok_or_else(|| "There is no install to resume".to_string())?,
+    };
+    installer.install(std::path::Path::new(&destination)).await.map_err(coded_error)?;
     
-    Ok(())
+    state.update_config(|config| {
+        config.set_game_directory(&destination).map_err(coded_error)?;
+        config.save("config.yml").map_err(|e| e.to_string())
+    })?;
+    Ok(destination)
+}
+
+#[tauri::command]
+pub async fn migrate_game_directory(
+    state: State<'_, AppState>,
+    destination: String,
+    mode: MigrationMode,
+) -> Result<MigrationReport, String> {
+    let _operation = state.operations.begin(Operation::Migrating)?;
+    let mut config = state.config();
+    let progress = state.progress.clone();
+    
+    let report = beam_core::migration::migrate_game_directory(
+        &mut config,
+        std::path::Path::new("config.yml"),
+        std::path::Path::new(&destination),
+        mode,
+        |p| {
+            let mut progress = progress.lock().unwrap();
+            progress.current = p.files_done;
+            progress.total = p.files_total;
+            progress.filename = p.current_file.clone();
+            progress.bytes_downloaded = p.bytes_done;
+            progress.bytes_total = p.bytes_total;
+            progress.status = "Migrating".to_string();
+        },
+    )
+    .await
+    .map_err(coded_error)?;
+    
+    state.config.send_replace(config);
+    state.progress.lock().unwrap().status = "Idle".to_string();
+    
+    Ok(report)
+}
+
+// Shown in the confirmation dialog before `uninstall_game`
+#[tauri::command]
+pub fn get_uninstall_plan(state: State<'_, AppState>, options: UninstallOptions) -> Result<UninstallPlan, String> {
+    let config = state.config();
+    beam_core::uninstall::plan(&config, &options).map_err(coded_error)
+}
+
+#[tauri::command]
+pub fn uninstall_game(state: State<'_, AppState>, options: UninstallOptions) -> Result<UninstallReport, String> {
+    let _operation = state.operations.begin(Operation::Uninstalling)?;
+    let mut config = state.config();
+    let report = beam_core::uninstall::uninstall(&mut config, std::path::Path::new("config.yml"), &options);
+    // Whatever the uninstall got to, e.g. a cleared game directory, is kept even when a later step failed
+    state.config.send_replace(config);
+    report.map_err(coded_error)
+}
+
+#[tauri::command]
+pub fn get_storage_usage(state: State<'_, AppState>) -> Result<StorageUsage, String> {
+    let config = state.config();
+    beam_core::storage::usage(&config).map_err(coded_error)
+}
+
+// Both return the bytes freed. A running patch reads from the cache and may restore from a backup.
+#[tauri::command]
+pub fn clear_patch_cache(state: State<'_, AppState>) -> Result<u64, String> {
+    let _operation = state.operations.begin(Operation::CleaningUp)?;
+    let config = state.config();
+    beam_core::storage::clear_cache(&config).map_err(coded_error)
+}
+
+#[tauri::command]
+pub fn delete_backups(state: State<'_, AppState>) -> Result<u64, String> {
+    let _operation = state.operations.begin(Operation::CleaningUp)?;
+    let config = state.config();
+    beam_core::storage::delete_backups(&config).map_err(coded_error)
+}
+
+// Rewrites every GRF, which can take minutes for a full client
+#[tauri::command]
+pub async fn optimize_grfs(state: State<'_, AppState>, level: Option<u32>) -> Result<u64, String> {
+    let _operation = state.operations.begin(Operation::OptimizingGrfs)?;
+    let config = state.config();
+    tokio::task::spawn_blocking(move || beam_core::storage::optimize_grfs(&config, level))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(coded_error)
 }
 
 #[tauri::command]
 pub fn get_game_directory(state: State<'_, AppState>) -> Result<Option<String>, String> {
-    let config = state.config.lock().unwrap();
+    let config = state.config.borrow();
     Ok(config.app.game_directory.clone())
 }
 
+// Relative GRF names resolve against the game directory; defaults to the patch target
+fn grf_path(state: &State<'_, AppState>, grf: Option<String>) -> Result<PathBuf, String> {
+    let config = state.config.borrow();
+    let grf = PathBuf::from(grf.unwrap_or_else(|| config.patcher.target_grf.clone()));
+    match &config.app.game_directory {
+        Some(game_dir) if grf.is_relative() => Ok(PathBuf::from(game_dir).join(grf)),
+        _ => Ok(grf),
+    }
+}
+
+#[tauri::command]
+pub async fn preview_grf_text(
+    state: State<'_, AppState>,
+    entry: String,
+    grf: Option<String>,
+) -> Result<beam_formats::text::TextPreview, String> {
+    let path = grf_path(&state, grf)?;
+    
+    tokio::task::spawn_blocking(move || {
+        let grf = beam_formats::grf::Grf::open(&path)?;
+        grf.preview_text(&entry, beam_formats::text::DEFAULT_PREVIEW_LIMIT)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+// Returns a PNG data URL for palettes and map minimaps
+#[tauri::command]
+pub async fn preview_grf_image(
+    state: State<'_, AppState>,
+    entry: String,
+    grf: Option<String>,
+) -> Result<String, String> {
+    let path = grf_path(&state, grf)?;
+    
+    tokio::task::spawn_blocking(move || {
+        let grf = beam_formats::grf::Grf::open(&path)?;
+        let image = grf.preview_image(&entry)?;
+        beam_core::preview::png_data_url(&image)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrfEntryDetails {
+    pub name: String,
+    pub size: u32,
+    pub compressed_size: u32,
+    pub text: bool,
+    pub lua_form: Option<LuaForm>,
+}
+
+#[tauri::command]
+pub async fn inspect_grf_entry(
+    state: State<'_, AppState>,
+    entry: String,
+    grf: Option<String>,
+) -> Result<GrfEntryDetails, String> {
+    let path = grf_path(&state, grf)?;
+    
+    tokio::task::spawn_blocking(move || {
+        let grf = beam_formats::grf::Grf::open(&path)?;
+        let info = grf.get_entry(&entry)
+            .ok_or_else(|| beam_formats::Error::FileNotFound(entry.clone()))?;
+        
+        let lua_form = if beam_formats::lua::is_lua_entry(&entry) {
+            Some(grf.lua_form(&entry)?)
+        } else {
+            None
+        };
+        
+        Ok::<_, beam_formats::Error>(GrfEntryDetails {
+            text: beam_formats::text::is_text_entry(&entry) && !lua_form.as_ref().map(LuaForm::is_compiled).unwrap_or(false),
+            name: entry,
+            size: info.uncompressed_size,
+            compressed_size: info.compressed_size,
+            lua_form,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+fn screenshot_folder(state: &State<'_, AppState>) -> Result<PathBuf, String> {
+    let config = state.config.borrow();
+    let game_dir = config.app.game_directory.as_ref().ok_or("Game directory not set")?;
+    Ok(beam_core::screenshots::screenshot_dir(std::path::Path::new(game_dir)))
+}
+
+#[tauri::command]
+pub fn get_screenshot_folder(state: State<'_, AppState>) -> Result<String, String> {
+    Ok(screenshot_folder(&state)?.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn list_screenshots(state: State<'_, AppState>, limit: Option<usize>) -> Result<Vec<Screenshot>, String> {
+    let dir = screenshot_folder(&state)?;
+    
+    // Decoding full-size BMPs is slow, keep it off the async runtime
+    tokio::task::spawn_blocking(move || {
+        beam_core::screenshots::list_screenshots(
+            &dir,
+            limit.unwrap_or(24),
+            Some(beam_core::screenshots::DEFAULT_THUMBNAIL_SIZE),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn open_screenshot(state: State<'_, AppState>, name: String) -> Result<(), String> {
+    let path = beam_core::screenshots::resolve_screenshot(&screenshot_folder(&state)?, &name).map_err(|e| e.to_string())?;
+    beam_core::screenshots::open_path(&path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn reveal_screenshot(state: State<'_, AppState>, name: Option<String>) -> Result<(), String> {
+    let dir = screenshot_folder(&state)?;
+    match name {
+        Some(name) => {
+            let path = beam_core::screenshots::resolve_screenshot(&dir, &name).map_err(|e| e.to_string())?;
+            beam_core::screenshots::reveal_in_folder(&path).map_err(|e| e.to_string())
+        }
+        None => beam_core::screenshots::open_path(&dir).map_err(|e| e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn copy_screenshot(state: State<'_, AppState>, name: String, destination: String) -> Result<String, String> {
+    let path = beam_core::screenshots::resolve_screenshot(&screenshot_folder(&state)?, &name).map_err(|e| e.to_string())?;
+    let target = beam_core::screenshots::copy_screenshot(&path, std::path::Path::new(&destination))
+        .map_err(|e| e.to_string())?;
+    Ok(target.to_string_lossy().to_string())
+}
+
 #[tauri::command]
 pub async fn get_client_status(state: State<'_, AppState>) -> Result<ClientStatusResult, String> {
-    let config = state.config.lock().unwrap().clone();
+    let config = state.config();
     
     let checker = ClientChecker::new(config);
     checker.check_client_integrity().await.map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn diagnose_client(state: State<'_, AppState>) -> Result<ClientDiagnostics, String> {
+    let config = state.config();
+    
+    let checker = ClientChecker::new(config);
+    checker.diagnose_client().await.map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn apply_game_settings(
     state: State<'_, AppState>,
     settings: GameSettings,
 ) -> Result<(), String> {
-    let config = state.config.lock().unwrap();
+    let _operation = state.operations.begin(Operation::ApplyingSettings)?;
+    let config = state.config();
     
-    let game_dir = config.app.game_directory.as_ref()
-        .ok_or("Game directory not set")?;
-    
-    let manager = GameSettingsManager::new(game_dir);
-    manager.apply_settings(&settings).map_err(|e| e.to_string())?;
+    let manager = GameSettingsManager::from_config(&config).map_err(|e| e.to_string())?;
+    manager.apply_settings(&settings).map_err(coded_error)?;
     
     Ok(())
 }
 
 #[tauri::command]
 pub async fn load_game_settings(state: State<'_, AppState>) -> Result<GameSettings, String> {
-    let config = state.config.lock().unwrap();
-    
-    let game_dir = config.app.game_directory.as_ref()
-        .ok_or("Game directory not set")?;
+    let config = state.config();
     
-    let manager = GameSettingsManager::new(game_dir);
+    let manager = GameSettingsManager::from_config(&config).map_err(|e| e.to_string())?;
     manager.load_settings().map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_wrapper_status(state: State<'_, AppState>) -> Result<Option<WrapperStatus>, String> {
+    let config = state.config();
+    if config.wrapper.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(WrapperManager::from_config(&config).map_err(coded_error)?.status()))
+}
+
+#[tauri::command]
+pub async fn set_wrapper_enabled(state: State<'_, AppState>, enabled: bool) -> Result<WrapperStatus, String> {
+    let _operation = state.operations.begin(Operation::UpdatingWrapper)?;
+    let config = state.config();
+    let manager = WrapperManager::from_config(&config).map_err(coded_error)?;
+    manager.set_enabled(enabled).await.map_err(coded_error)
+}
+
+#[tauri::command]
+pub async fn get_client_info(state: State<'_, AppState>) -> Result<ClientInfo, String> {
+    let config = state.config();
+    tokio::task::spawn_blocking(move || client_info::inspect(&config))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(coded_error)
+}
+
+#[tauri::command]
+pub async fn check_client_dependencies(state: State<'_, AppState>) -> Result<DependencyReport, String> {
+    let config = state.config();
+    dependencies::check_client(&config).map_err(coded_error)
+}
+
+#[tauri::command]
+pub async fn install_redistributables(state: State<'_, AppState>) -> Result<DependencyReport, String> {
+    let _operation = state.operations.begin(Operation::InstallingRedistributables)?;
+    let config = state.config();
+    let report = dependencies::check_client(&config).map_err(coded_error)?;
+    dependencies::install_redistributables(&config, &report).await.map_err(coded_error)
+}
+
+#[tauri::command]
+pub fn get_install_id(state: State<'_, AppState>) -> Result<String, String> {
+    let config = state.config();
+    install_id::load_or_create(&config).map_err(coded_error)
+}
+
+#[tauri::command]
+pub fn regenerate_install_id(state: State<'_, AppState>) -> Result<String, String> {
+    let config = state.config();
+    install_id::regenerate(&config).map_err(coded_error)
+}
+
+#[tauri::command]
+pub async fn get_system_info() -> Result<SystemInfo, String> {
+    tokio::task::spawn_blocking(SystemInfo::probe)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn check_game_settings(settings: GameSettings) -> Result<Vec<SettingsWarning>, String> {
+    tokio::task::spawn_blocking(move || SystemInfo::probe().check(&settings))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn resolve_resource_path(app: AppHandle, path: String) -> Result<String, String> {
     let resource_path = app.path_resolver()
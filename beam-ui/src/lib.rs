@@ -1,10 +1,16 @@
 mod commands;
+mod operations;
 
 use anyhow::Result;
 use beam_core::Config;
 use beam_core::Patcher;
+use operations::Operations;
+use beam_core::{EventNotice, MfaChallenge, NoticeWatcher, ProgressEvent, ServerStatusResult, SsoSession, StatusPoller};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use tokio::sync::watch;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PatchProgress {
@@ -16,45 +22,248 @@ pub struct PatchProgress {
     pub status: String,
 }
 
+impl PatchProgress {
+    // Keeps the snapshot `get_progress` returns in step with the events sent as `patch-progress`
+    pub fn update(&mut self, event: &ProgressEvent) {
+        match event {
+            ProgressEvent::PatchStarted { patch, current, total } => {
+                self.current = *current;
+                self.total = *total;
+                self.filename = patch.clone();
+                self.bytes_downloaded = 0;
+                self.bytes_total = 0;
+            }
+            ProgressEvent::Resuming { downloaded_bytes, total_bytes, .. }
+            | ProgressEvent::Downloading { downloaded_bytes, total_bytes, .. } => {
+                self.bytes_downloaded = *downloaded_bytes;
+                self.bytes_total = *total_bytes;
+            }
+            ProgressEvent::Stalled { .. } | ProgressEvent::Extracting { .. } | ProgressEvent::Applying { .. } => {}
+        }
+        self.status = event.message();
+    }
+}
+
 pub struct AppState {
-    pub config: Arc<Mutex<Config>>,
+    // Commands take a snapshot with `config()`; background tasks subscribe to follow changes made while they run
+    pub config: watch::Sender<Config>,
     pub patcher: Arc<Mutex<Option<Patcher>>>,
     pub progress: Arc<Mutex<PatchProgress>>,
+    pub cancel_flag: Arc<Mutex<Option<Arc<AtomicBool>>>>,
+    // Patching, repairs, installs and other commands that change files run one at a time
+    pub operations: Operations,
+    // Set when the window was closed mid-patch; start_patching closes it once the run has stopped cleanly
+    pub close_requested: Arc<AtomicBool>,
+    // Last SSO login, used to request signed mirror URLs; kept fresh by the session keep-alive
+    pub sso_session: Arc<Mutex<Option<SsoSession>>>,
+    // Login waiting for a one-time code after the token endpoint answered `mfa_required`
+    pub pending_mfa: Arc<Mutex<Option<MfaChallenge>>>,
+    // Latest result of the background status poller, also sent as `server-status-changed`
+    pub server_status: Arc<Mutex<Option<ServerStatusResult>>>,
+    // Banners and launch warnings from the event calendar, also sent as `event-notices-changed`
+    pub event_notices: Arc<Mutex<Vec<EventNotice>>>,
+}
+
+impl AppState {
+    pub fn config(&self) -> Config {
+        self.config.borrow().clone()
+    }
+    
+    // Applies `change` to a copy and publishes it only when it succeeds, so a failed change leaves nothing behind
+    pub fn update_config<T>(&self, change: impl FnOnce(&mut Config) -> Result<T, String>) -> Result<T, String> {
+        let mut result = None;
+        self.config.send_if_modified(|config| {
+            let mut updated = config.clone();
+            let outcome = change(&mut updated);
+            let changed = outcome.is_ok();
+            if changed {
+                *config = updated;
+            }
+            result = Some(outcome);
+            changed
+        });
+        result.expect("send_if_modified always runs the change")
+    }
+    
+    // Expired tokens are withheld rather than sent and rejected
+    pub fn sso_token(&self) -> Option<String> {
+        self.sso_session
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|session| !session.is_expired())
+            .map(|session| session.access_token.clone())
+    }
+}
+
+// The event calendar and server status pollers, started again with the new config whenever it changes, e.g. after
+// the game directory moved
+async fn run_pollers(handle: tauri::AppHandle, mut updates: watch::Receiver<Config>) {
+    loop {
+        let config = updates.borrow_and_update().clone();
+        let mut pollers = Vec::new();
+        
+        if let Some(watcher) = NoticeWatcher::new(&config) {
+            let handle = handle.clone();
+            pollers.push(tauri::async_runtime::spawn(watcher.run(move |notices| {
+                *handle.state::<AppState>().event_notices.lock().unwrap() = notices.to_vec();
+                let _ = handle.emit_all("event-notices-changed", notices);
+            })));
+        }
+        
+        if let Ok(poller) = StatusPoller::new(config) {
+            let handle = handle.clone();
+            pollers.push(tauri::async_runtime::spawn(poller.run(move |status| {
+                *handle.state::<AppState>().server_status.lock().unwrap() = Some(status.clone());
+                let _ = handle.emit_all("server-status-changed", status);
+            })));
+        }
+        
+        let closed = updates.changed().await.is_err();
+        for poller in pollers {
+            poller.abort();
+        }
+        if closed {
+            return;
+        }
+    }
 }
 
 pub fn run_ui(config: Config) -> Result<()> {
-    let app_state = AppState {
-        config: Arc::new(Mutex::new(config.clone())),
-        patcher: Arc::new(Mutex::new(None)),
-        progress: Arc::new(Mutex::new(PatchProgress {
+    // A run the previous launcher never finished is shown instead of Idle until patching starts again
+    let interrupted = Patcher::new(config.clone()).ok().and_then(|patcher| patcher.interrupted_run());
+    let progress = match interrupted {
+        Some(snapshot) => PatchProgress {
+            current: snapshot.current,
+            total: snapshot.total,
+            filename: snapshot.patch.clone().unwrap_or_default(),
+            bytes_downloaded: 0,
+            bytes_total: 0,
+            status: snapshot.message(),
+        },
+        None => PatchProgress {
             current: 0,
             total: 0,
             filename: String::new(),
             bytes_downloaded: 0,
             bytes_total: 0,
             status: "Idle".to_string(),
-        })),
+        },
+    };
+    
+    let app_state = AppState {
+        config: watch::Sender::new(config.clone()),
+        patcher: Arc::new(Mutex::new(None)),
+        progress: Arc::new(Mutex::new(progress)),
+        cancel_flag: Arc::new(Mutex::new(None)),
+        operations: Operations::default(),
+        close_requested: Arc::new(AtomicBool::new(false)),
+        sso_session: Arc::new(Mutex::new(None)),
+        pending_mfa: Arc::new(Mutex::new(None)),
+        server_status: Arc::new(Mutex::new(None)),
+        event_notices: Arc::new(Mutex::new(Vec::new())),
     };
+    let layout = config.ui.layout.clone();
     
     tauri::Builder::default()
         .manage(app_state)
+        .setup(move |app| {
+            // Custom layouts come from pixel-positioned skins, so the window takes the skin's size
+            if layout.use_custom_layout {
+                if let Some(window) = app.get_window("main") {
+                    window.set_size(tauri::LogicalSize::new(layout.width as f64, layout.height as f64))?;
+                }
+            }
+            
+            let updates = app.state::<AppState>().config.subscribe();
+            tauri::async_runtime::spawn(run_pollers(app.handle(), updates));
+            Ok(())
+        })
+        .on_window_event(|event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event.event() {
+                let state = event.window().state::<AppState>();
+                let cancel_flag = state.cancel_flag.lock().unwrap().clone();
+                if let Some(flag) = cancel_flag {
+                    tracing::info!("Window closed during patching, finishing the current step first");
+                    api.prevent_close();
+                    state.close_requested.store(true, Ordering::Relaxed);
+                    flag.store(true, Ordering::Relaxed);
+                    let _ = event.window().emit("shutdown-pending", ());
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             commands::start_patching,
+            commands::cancel_patching,
+            commands::get_recovery_actions,
+            commands::switch_mirror,
+            commands::allow_in_place_apply,
+            commands::relaunch_as_admin,
             commands::check_updates,
             commands::perform_update,
             commands::get_login_url,
             commands::exchange_sso_code,
+            commands::get_mfa_challenge,
+            commands::submit_otp,
+            commands::get_session_status,
             commands::launch_game,
+            commands::get_characters,
+            commands::get_last_character,
+            commands::select_character,
+            commands::get_play_time_status,
             commands::get_config,
             commands::get_progress,
             commands::get_news,
+            commands::track_news_click,
+            commands::get_events,
+            commands::get_event_notices,
+            commands::get_patch_history,
+            commands::get_interrupted_run,
+            commands::get_pending_patches,
+            commands::dry_run,
+            commands::get_queue,
+            commands::retry_queue_item,
+            commands::skip_queue_item,
+            commands::set_channel_priority,
+            commands::get_patch_conflicts,
+            commands::resolve_patch_conflicts,
             commands::get_server_status,
+            commands::get_population_history,
+            commands::diagnose_connection,
+            commands::preview_grf_text,
+            commands::inspect_grf_entry,
+            commands::preview_grf_image,
+            commands::get_screenshot_folder,
+            commands::list_screenshots,
+            commands::open_screenshot,
+            commands::reveal_screenshot,
+            commands::copy_screenshot,
             commands::get_client_status,
+            commands::diagnose_client,
             commands::verify_game_files,
+            commands::get_integrity_status,
             commands::set_game_directory,
             commands::get_game_directory,
+            commands::migrate_game_directory,
+            commands::get_uninstall_plan,
+            commands::uninstall_game,
+            commands::get_storage_usage,
+            commands::clear_patch_cache,
+            commands::delete_backups,
+            commands::optimize_grfs,
+            commands::get_pending_install,
+            commands::install_full_client,
             commands::apply_game_settings,
             commands::load_game_settings,
+            commands::get_system_info,
+            commands::get_install_id,
+            commands::regenerate_install_id,
+            commands::check_game_settings,
+            commands::get_wrapper_status,
+            commands::set_wrapper_enabled,
+            commands::get_client_info,
+            commands::check_client_dependencies,
+            commands::install_redistributables,
             commands::resolve_resource_path,
         ])
         .run(tauri::generate_context!("tauri.conf.json"))
@@ -0,0 +1,77 @@
+// Commands that change the game files or the config take turns. Each holds the coordinator for its whole run and
+// a second one is refused with a `[busy]` error naming the one running, rather than waiting behind it while the
+// player clicks again.
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Patching,
+    Verifying,
+    Updating,
+    Launching,
+    ChangingGameDirectory,
+    Installing,
+    Migrating,
+    Uninstalling,
+    CleaningUp,
+    OptimizingGrfs,
+    ApplyingSettings,
+    UpdatingWrapper,
+    InstallingRedistributables,
+}
+
+impl Operation {
+    // Completes "Wait for ... to finish first"
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::Patching => "patching",
+            Operation::Verifying => "the file check",
+            Operation::Updating => "the launcher update",
+            Operation::Launching => "the game launch",
+            Operation::ChangingGameDirectory => "the game folder change",
+            Operation::Installing => "the client install",
+            Operation::Migrating => "the game folder move",
+            Operation::Uninstalling => "the uninstall",
+            Operation::CleaningUp => "the storage cleanup",
+            Operation::OptimizingGrfs => "the GRF optimization",
+            Operation::ApplyingSettings => "saving the game settings",
+            Operation::UpdatingWrapper => "the graphics wrapper update",
+            Operation::InstallingRedistributables => "the redistributable install",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Operations {
+    // A tokio lock so the guard can be held across the awaits of an async command
+    running: Arc<tokio::sync::Mutex<()>>,
+    current: Arc<Mutex<Option<Operation>>>,
+}
+
+// Ends the operation when dropped, including when the command fails or its future is dropped
+pub struct OperationGuard {
+    current: Arc<Mutex<Option<Operation>>>,
+    _running: OwnedMutexGuard<()>,
+}
+
+impl Operations {
+    pub fn begin(&self, operation: Operation) -> Result<OperationGuard, String> {
+        let running = self.running.clone().try_lock_owned().map_err(|_| {
+            let label = self.current().map_or("the running operation", |current| current.label());
+            format!("[busy] Wait for {} to finish first", label)
+        })?;
+        *self.current.lock().unwrap() = Some(operation);
+        Ok(OperationGuard { current: self.current.clone(), _running: running })
+    }
+    
+    pub fn current(&self) -> Option<Operation> {
+        *self.current.lock().unwrap()
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        *self.current.lock().unwrap() = None;
+    }
+}
@@ -0,0 +1,171 @@
+// Patch inspection for web pages, with the parsers the launcher uses. Everything works on bytes the page already
+// has (a file input or a fetch), so a server website can show what a THOR, BEAM, BEAMD, RGZ or GPF patch
+// contains before players download it.
+
+use beam_formats::beam::BeamArchive;
+use beam_formats::beamd::{DeltaKind, DeltaPatch};
+use beam_formats::gpf::Gpf;
+use beam_formats::rgz::{Rgz, RgzEntry};
+use beam_formats::thor::{Thor, ThorEntry, ThorTarget};
+use beam_formats::vfs::Vfs;
+use beam_formats::{path, Error};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum EntryAction {
+    Add,
+    Remove,
+    Directory,
+    // A BEAMD diff against the player's copy
+    Patch,
+}
+
+#[derive(Debug, Serialize)]
+struct PatchEntry {
+    name: String,
+    action: EntryAction,
+    // Uncompressed size (for diffs, of the resulting file); 0 for removals and directories
+    size: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct PatchSummary {
+    format: &'static str,
+    // Where a THOR patch goes: "default_grf" (the patcher's `target_grf`), "grf" (`target_grf` below) or "root"
+    target: &'static str,
+    target_grf: Option<String>,
+    // In the order they are applied; BEAM and GPF entries by name
+    entries: Vec<PatchEntry>,
+}
+
+enum Patch {
+    Thor(Thor),
+    Beam(BeamArchive),
+    Delta(DeltaPatch),
+    Rgz(Rgz),
+    Gpf(Gpf),
+}
+
+// Told apart by extension, as the patcher does
+fn open(file_name: &str, data: Vec<u8>) -> beam_formats::Result<Patch> {
+    let extension = file_name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "thor" => Ok(Patch::Thor(Thor::from_bytes(&data)?)),
+        "beam" => Ok(Patch::Beam(BeamArchive::from_bytes(data)?)),
+        "beamd" => Ok(Patch::Delta(DeltaPatch::from_bytes(data)?)),
+        "rgz" => Ok(Patch::Rgz(Rgz::from_bytes(&data)?)),
+        "gpf" | "grf" => Ok(Patch::Gpf(Gpf::from_bytes(data)?)),
+        _ => Err(Error::Unsupported(format!("{} is not a THOR, BEAM, BEAMD, RGZ or GPF patch", file_name))),
+    }
+}
+
+fn summarize(patch: &Patch) -> PatchSummary {
+    let mut summary = PatchSummary { format: "", target: "default_grf", target_grf: None, entries: Vec::new() };
+    match patch {
+        Patch::Thor(thor) => {
+            summary.format = "thor";
+            match &thor.target {
+                ThorTarget::DefaultGrf => {}
+                ThorTarget::Grf(grf) => {
+                    summary.target = "grf";
+                    summary.target_grf = Some(grf.clone());
+                }
+                ThorTarget::Root => summary.target = "root",
+            }
+            summary.entries = thor.entries.iter().map(|entry| match entry {
+                ThorEntry::Add { filename, data } => {
+                    PatchEntry { name: filename.clone(), action: EntryAction::Add, size: data.len() as u64 }
+                }
+                ThorEntry::Remove { filename } => {
+                    PatchEntry { name: filename.clone(), action: EntryAction::Remove, size: 0 }
+                }
+            }).collect();
+        }
+        Patch::Rgz(rgz) => {
+            summary.format = "rgz";
+            summary.entries = rgz.get_entries().iter().map(|entry| match entry {
+                RgzEntry::File { name, data } => {
+                    PatchEntry { name: name.clone(), action: EntryAction::Add, size: data.len() as u64 }
+                }
+                RgzEntry::Directory { name } => {
+                    PatchEntry { name: name.clone(), action: EntryAction::Directory, size: 0 }
+                }
+            }).collect();
+        }
+        Patch::Beam(beam) => {
+            summary.format = "beam";
+            summary.entries = archive_entries(beam);
+        }
+        Patch::Delta(delta) => {
+            summary.format = "beamd";
+            summary.entries = delta.entries().iter().map(|entry| {
+                let (action, size) = match entry.kind {
+                    DeltaKind::Full => (EntryAction::Add, entry.result_size as u64),
+                    DeltaKind::Diff => (EntryAction::Patch, entry.result_size as u64),
+                    DeltaKind::Remove => (EntryAction::Remove, 0),
+                };
+                PatchEntry { name: entry.filename.clone(), action, size }
+            }).collect();
+        }
+        Patch::Gpf(gpf) => {
+            summary.format = "gpf";
+            summary.entries = archive_entries(gpf);
+        }
+    }
+    summary
+}
+
+fn archive_entries(archive: &dyn Vfs) -> Vec<PatchEntry> {
+    let mut names = archive.list();
+    names.sort_unstable();
+    names
+        .into_iter()
+        .map(|name| PatchEntry {
+            name: name.to_string(),
+            action: EntryAction::Add,
+            size: archive.stat(name).map_or(0, |metadata| metadata.size),
+        })
+        .collect()
+}
+
+fn entry_data(patch: &Patch, entry: &str) -> beam_formats::Result<Vec<u8>> {
+    let key = path::name_key(entry);
+    let found = match patch {
+        Patch::Thor(thor) => thor.entries.iter().rev().find_map(|thor_entry| match thor_entry {
+            ThorEntry::Add { filename, data } if path::name_key(filename) == key => Some(data.clone()),
+            _ => None,
+        }),
+        Patch::Rgz(rgz) => rgz.get_entries().iter().find_map(|rgz_entry| match rgz_entry {
+            RgzEntry::File { name, data } if path::name_key(name) == key => Some(data.clone()),
+            _ => None,
+        }),
+        Patch::Beam(beam) => return beam.read(entry),
+        // A diff only yields the file together with the player's copy
+        Patch::Delta(delta) => match delta.entries().iter().rev().find(|delta_entry| path::name_key(&delta_entry.filename) == key) {
+            Some(delta_entry) if delta_entry.kind == DeltaKind::Full => delta.apply_to(delta_entry, None)?,
+            Some(delta_entry) if delta_entry.kind == DeltaKind::Diff => {
+                return Err(Error::Unsupported(format!("{} is a diff against the installed file", entry)))
+            }
+            _ => None,
+        },
+        Patch::Gpf(gpf) => return gpf.read(entry),
+    };
+    found.ok_or_else(|| Error::FileNotFound(entry.to_string()))
+}
+
+/// Parses a patch and returns its format, target and entries as JSON.
+#[wasm_bindgen(js_name = inspectPatch)]
+pub fn inspect_patch(file_name: &str, data: Vec<u8>) -> Result<String, JsError> {
+    let patch = open(file_name, data)?;
+    Ok(serde_json::to_string(&summarize(&patch))?)
+}
+
+/// The uncompressed content of one entry, for previews and downloads. Names match in any case and with either
+/// separator.
+#[wasm_bindgen(js_name = extractEntry)]
+pub fn extract_entry(file_name: &str, data: Vec<u8>, entry: &str) -> Result<Vec<u8>, JsError> {
+    let patch = open(file_name, data)?;
+    Ok(entry_data(&patch, entry)?)
+}